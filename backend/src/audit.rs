@@ -0,0 +1,163 @@
+//! Optional append-only record of every command line run, for deployments
+//! such as classrooms where someone has to review what users did.
+//!
+//! Each line of the file is one JSON [`AuditEntry`]. Entries are appended by a
+//! background task, so a slow disk never holds up a command; the file is
+//! opened in append mode and never rewritten.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+
+use crate::auth::Admin;
+use crate::fs_api::{api_error, ApiResult};
+use crate::AppState;
+
+/// Entries `GET /api/audit` returns when no `limit` is given.
+const DEFAULT_LIMIT: usize = 100;
+
+pub(crate) struct AuditLog {
+    path: PathBuf,
+    entries: mpsc::UnboundedSender<AuditEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct AuditEntry {
+    /// Milliseconds since the Unix epoch, by the host's clock.
+    pub(crate) timestamp: u64,
+    pub(crate) session: String,
+    /// Who owns the session, when authentication is on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) user: Option<String>,
+    /// The directory the command line started in.
+    pub(crate) cwd: String,
+    pub(crate) command: String,
+    pub(crate) exit_code: i32,
+    /// Bytes of output the command line produced.
+    pub(crate) output_bytes: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditQuery {
+    /// Only entries for this session, by its id or as the log shows it.
+    session: Option<String>,
+    /// Most recent entries to return.
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct AuditResponse {
+    /// Oldest first.
+    entries: Vec<AuditEntry>,
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new().route("/api/audit", get(audit))
+}
+
+impl AuditLog {
+    /// Starts appending entries to `path`, creating it if needed. Must be
+    /// called from within a Tokio runtime.
+    pub(crate) fn open(path: PathBuf) -> Arc<Self> {
+        let (entries, mut received) = mpsc::unbounded_channel::<AuditEntry>();
+        let file = path.clone();
+        tokio::spawn(async move {
+            while let Some(entry) = received.recv().await {
+                let mut batch = vec![entry];
+                while let Ok(entry) = received.try_recv() {
+                    batch.push(entry);
+                }
+                let target = file.clone();
+                let written = tokio::task::spawn_blocking(move || append(&target, &batch))
+                    .await
+                    .expect("audit writer panicked");
+                if let Err(error) = written {
+                    tracing::warn!("failed to write audit log {}: {}", file.display(), error);
+                }
+            }
+        });
+        Arc::new(AuditLog { path, entries })
+    }
+
+    pub(crate) fn record(&self, entry: AuditEntry) {
+        // The writer only stops with the runtime.
+        let _ = self.entries.send(entry);
+    }
+}
+
+fn append(path: &Path, entries: &[AuditEntry]) -> std::io::Result<()> {
+    let mut lines = String::new();
+    for entry in entries {
+        lines.push_str(&serde_json::to_string(entry)?);
+        lines.push('\n');
+    }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(lines.as_bytes())
+}
+
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// The most recent entries, for admins only. Session ids are shown
+/// redacted, as each one is all it takes to use its session.
+async fn audit(
+    State(state): State<AppState>,
+    _admin: Admin,
+    Query(query): Query<AuditQuery>,
+) -> ApiResult<AuditResponse> {
+    let Some(log) = state.audit else {
+        return Err(api_error(StatusCode::NOT_FOUND, "no audit log is configured"));
+    };
+    let path = log.path.clone();
+    let text = match tokio::task::spawn_blocking(move || std::fs::read_to_string(path))
+        .await
+        .expect("audit reader panicked")
+    {
+        Ok(text) => text,
+        // Nothing has been run yet.
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(error) => {
+            tracing::warn!("failed to read audit log {}: {}", log.path.display(), error);
+            return Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to read the audit log"));
+        }
+    };
+    let mut entries: Vec<AuditEntry> = text
+        .lines()
+        // A line cut short by a crash mid-write is skipped, not fatal.
+        .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok())
+        .filter_map(|mut entry| {
+            let redacted = redact_session(&entry.session);
+            let wanted = query
+                .session
+                .as_ref()
+                .is_none_or(|session| *session == entry.session || *session == redacted);
+            entry.session = redacted;
+            wanted.then_some(entry)
+        })
+        .collect();
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    entries.drain(..entries.len().saturating_sub(limit));
+    Ok(Json(AuditResponse { entries }))
+}
+
+/// A session id as the log shows it: enough of its SHA-256 to tell sessions
+/// apart, and nothing that would let anyone use one.
+fn redact_session(id: &str) -> String {
+    let digest = format!("{:x}", Sha256::digest(id.as_bytes()));
+    format!("sha256:{}", &digest[..16])
+}
@@ -0,0 +1,138 @@
+//! Optional token authentication, for deployments reachable by strangers.
+//!
+//! With it on, every request must carry a known token: as an
+//! `Authorization: Bearer <token>` header or, for clients such as
+//! `EventSource` and browser WebSockets that cannot set headers, as an
+//! `access_token` query parameter. Each token belongs to a user, and a
+//! session (with its filesystem) is only visible to the user who created it.
+
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query, Request, State},
+    http::{header, request::Parts, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::fs_api::{api_error, ApiError};
+
+/// The user everyone counts as when they share one token.
+const SHARED_USER: &str = "shared";
+
+/// Who may use the API.
+#[derive(Clone, Debug, Default)]
+pub struct AuthConfig {
+    /// User names keyed by token.
+    users: HashMap<String, String>,
+    /// Users who may read the audit log.
+    admins: HashSet<String>,
+}
+
+impl AuthConfig {
+    /// A single token for every client; they all see the same sessions.
+    pub fn shared_token(token: impl Into<String>) -> Self {
+        AuthConfig::default().user(SHARED_USER, token)
+    }
+
+    /// Adds a user who authenticates with `token`.
+    pub fn user(mut self, name: impl Into<String>, token: impl Into<String>) -> Self {
+        self.users.insert(token.into(), name.into());
+        self
+    }
+
+    /// Lets the user `name` read the audit log.
+    pub fn admin(mut self, name: impl Into<String>) -> Self {
+        self.admins.insert(name.into());
+        self
+    }
+
+    fn user_for(&self, token: &str) -> Option<&str> {
+        // Check every token in full, so response times say nothing about
+        // how close a guess came.
+        let mut found = None;
+        for (candidate, name) in &self.users {
+            if constant_time_eq(candidate.as_bytes(), token.as_bytes()) {
+                found = Some(name.as_str());
+            }
+        }
+        found
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The user a request authenticated as; `None` when authentication is off.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct User(pub(crate) Option<String>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for User {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<User>().cloned().unwrap_or_default())
+    }
+}
+
+/// Marks a request from an admin. Without authentication there are none,
+/// so routes that take this are closed to everyone.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Admin;
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Admin {
+    type Rejection = (StatusCode, Json<ApiError>);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<Admin>().copied().ok_or_else(|| {
+            api_error(StatusCode::FORBIDDEN, "only admins may use this, and only with authentication on")
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenQuery {
+    access_token: Option<String>,
+}
+
+/// Middleware rejecting requests without a valid token, and recording the
+/// [`User`] for those with one.
+pub(crate) async fn require_token(
+    State(config): State<Arc<AuthConfig>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let query = Query::<TokenQuery>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(query)| query.access_token);
+    let user = bearer
+        .or(query)
+        .and_then(|token| config.user_for(token.trim()).map(str::to_string));
+    let Some(user) = user else {
+        let mut response =
+            api_error(StatusCode::UNAUTHORIZED, "missing or invalid access token").into_response();
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return response;
+    };
+    if config.admins.contains(&user) {
+        request.extensions_mut().insert(Admin);
+    }
+    request.extensions_mut().insert(User(Some(user)));
+    next.run(request).await
+}
@@ -0,0 +1,377 @@
+//! Settings for the `backend` binary. Each comes from, in increasing order of
+//! precedence: built-in defaults, the TOML file given with `--config`, the
+//! `TERMWEB_*` environment variables, and command-line flags.
+//!
+//! ```toml
+//! port = 8080
+//! allow_origins = ["https://example.com"]
+//! state_file = "/var/lib/termweb/state.json"
+//! max_nodes = 10000
+//! log_level = "termweb=debug"
+//! audit_file = "/var/log/termweb/audit.jsonl"
+//! profile_file = "/etc/termweb/profile"
+//! seed_files = "/srv/termweb/tutorial"
+//!
+//! [auth]
+//! admins = ["alice"]
+//!
+//! [auth.users]
+//! alice = "secret-token"
+//!
+//! [sandbox]
+//! image = "alpine"
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::Parser;
+use serde::Deserialize;
+use termweb::termweb_core::Limits;
+use termweb::{sandbox::SandboxConfig, AuthConfig, Termweb, TermwebBuilder};
+
+/// Where `npm run build` leaves the web UI in a checkout of the repository.
+const FRONTEND_DIST: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../frontend/dist");
+
+/// Web terminal backed by a virtual shell.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub(crate) struct Args {
+    /// TOML file to read settings from.
+    #[arg(long, short, env = "TERMWEB_CONFIG")]
+    config: Option<PathBuf>,
+    /// Port to listen on [default: 3000].
+    #[arg(long, env = "TERMWEB_PORT")]
+    port: Option<u16>,
+    /// Address to listen on [default: 0.0.0.0].
+    #[arg(long, env = "TERMWEB_BIND")]
+    bind: Option<String>,
+    /// Origin browsers may call the API from; repeat for several. Every
+    /// origin is allowed when none is given.
+    #[arg(long = "allow-origin", env = "TERMWEB_ALLOW_ORIGIN", value_delimiter = ',')]
+    allow_origin: Vec<String>,
+    /// File to keep every session's virtual filesystem in across restarts.
+    #[arg(long, env = "TERMWEB_STATE_FILE")]
+    state_file: Option<PathBuf>,
+    /// Directory holding the built web UI to serve [default: ../frontend/dist,
+    /// if it has been built].
+    #[arg(long, env = "TERMWEB_STATIC_DIR")]
+    static_dir: Option<PathBuf>,
+    /// Directory, or JSON or TOML manifest, of files every session starts with.
+    #[arg(long = "seed", env = "TERMWEB_SEED_FILES")]
+    seed_files: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    pub(crate) bind: String,
+    pub(crate) port: u16,
+    allow_origins: Vec<String>,
+    /// A `tracing` filter; `RUST_LOG` takes precedence.
+    pub(crate) log_level: String,
+    state_file: Option<PathBuf>,
+    static_dir: Option<PathBuf>,
+    stats_file: Option<PathBuf>,
+    /// JSONL file every command line is recorded in.
+    audit_file: Option<PathBuf>,
+    /// Script every new session runs as `/etc/profile`.
+    profile_file: Option<PathBuf>,
+    /// Files every new session starts with; see `--seed`.
+    seed_files: Option<PathBuf>,
+    memory_limit: Option<usize>,
+    /// Bytes all sessions together may hold in memory.
+    total_memory_limit: Option<usize>,
+    /// Bytes in any one file.
+    max_file_size: Option<usize>,
+    /// Files, directories and symlinks per session.
+    max_nodes: Option<usize>,
+    max_path_depth: Option<usize>,
+    max_name_length: Option<usize>,
+    rate_limit: Option<u32>,
+    command_timeout_secs: Option<u64>,
+    scripts_dir: Option<PathBuf>,
+    plugins_dir: Option<PathBuf>,
+    wasi_dir: Option<PathBuf>,
+    pty_shell: Option<String>,
+    /// Seed for deterministic mode, which also freezes the clock at `fixed_time`.
+    seed: Option<u64>,
+    /// Seconds since the epoch.
+    fixed_time: u64,
+    auth: AuthSettings,
+    sandbox: Option<SandboxSettings>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AuthSettings {
+    /// One token every client shares.
+    token: Option<String>,
+    /// Tokens keyed by user name.
+    users: BTreeMap<String, String>,
+    /// Users who may read the audit log.
+    admins: Vec<String>,
+    /// Positions, from 1, of `TERMWEB_AUTH_USERS` entries without a token,
+    /// warned about once logging is set up.
+    #[serde(skip)]
+    ignored_users: Vec<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SandboxSettings {
+    image: String,
+    runtime: Option<String>,
+    idle_secs: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind: "0.0.0.0".to_string(),
+            port: 3000,
+            allow_origins: Vec::new(),
+            log_level: "termweb=info,backend=info".to_string(),
+            state_file: None,
+            static_dir: None,
+            stats_file: None,
+            audit_file: None,
+            profile_file: None,
+            seed_files: None,
+            memory_limit: None,
+            total_memory_limit: None,
+            max_file_size: None,
+            max_nodes: None,
+            max_path_depth: None,
+            max_name_length: None,
+            rate_limit: None,
+            command_timeout_secs: None,
+            scripts_dir: None,
+            plugins_dir: None,
+            wasi_dir: None,
+            pty_shell: None,
+            seed: None,
+            fixed_time: 0,
+            auth: AuthSettings::default(),
+            sandbox: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the settings for this run from the command line and everything
+    /// it points at.
+    pub(crate) fn load() -> Result<Self, String> {
+        let args = Args::parse();
+        let mut config = match &args.config {
+            Some(path) => Config::read(path)?,
+            None => Config::default(),
+        };
+        config.apply_env();
+        config.apply_args(args);
+        Ok(config)
+    }
+
+    fn read(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("failed to read {}: {}", path.display(), error))?;
+        toml::from_str(&text).map_err(|error| format!("invalid config file {}: {}", path.display(), error))
+    }
+
+    fn apply_env(&mut self) {
+        fn var(name: &str) -> Option<String> {
+            std::env::var(name).ok()
+        }
+        fn parsed<T: std::str::FromStr>(name: &str) -> Option<T> {
+            var(name).and_then(|value| value.parse().ok())
+        }
+
+        self.scripts_dir = var("TERMWEB_SCRIPTS_DIR").map(PathBuf::from).or(self.scripts_dir.take());
+        self.plugins_dir = var("TERMWEB_PLUGINS_DIR").map(PathBuf::from).or(self.plugins_dir.take());
+        self.wasi_dir = var("TERMWEB_WASI_DIR").map(PathBuf::from).or(self.wasi_dir.take());
+        if let Some(image) = var("TERMWEB_SANDBOX_IMAGE") {
+            let sandbox = self.sandbox.get_or_insert(SandboxSettings {
+                image: String::new(),
+                runtime: None,
+                idle_secs: None,
+            });
+            sandbox.image = image;
+        }
+        if let Some(sandbox) = &mut self.sandbox {
+            sandbox.runtime = var("TERMWEB_SANDBOX_RUNTIME").or(sandbox.runtime.take());
+            sandbox.idle_secs = parsed("TERMWEB_SANDBOX_IDLE_SECS").or(sandbox.idle_secs);
+        }
+        self.memory_limit = parsed("TERMWEB_MEMORY_LIMIT").or(self.memory_limit);
+        self.total_memory_limit = parsed("TERMWEB_TOTAL_MEMORY_LIMIT").or(self.total_memory_limit);
+        self.max_file_size = parsed("TERMWEB_MAX_FILE_SIZE").or(self.max_file_size);
+        self.max_nodes = parsed("TERMWEB_MAX_NODES").or(self.max_nodes);
+        self.max_path_depth = parsed("TERMWEB_MAX_PATH_DEPTH").or(self.max_path_depth);
+        self.max_name_length = parsed("TERMWEB_MAX_NAME_LENGTH").or(self.max_name_length);
+        self.seed = parsed("TERMWEB_SEED").or(self.seed);
+        self.fixed_time = parsed("TERMWEB_FIXED_TIME").unwrap_or(self.fixed_time);
+        self.stats_file = var("TERMWEB_STATS_FILE").map(PathBuf::from).or(self.stats_file.take());
+        self.audit_file = var("TERMWEB_AUDIT_FILE").map(PathBuf::from).or(self.audit_file.take());
+        self.profile_file = var("TERMWEB_PROFILE_FILE").map(PathBuf::from).or(self.profile_file.take());
+        self.auth.token = var("TERMWEB_AUTH_TOKEN").or(self.auth.token.take());
+        // `name:token` pairs separated by commas.
+        if let Some(users) = var("TERMWEB_AUTH_USERS") {
+            let entries = users.split(',').enumerate().filter(|(_, entry)| !entry.trim().is_empty());
+            for (index, entry) in entries {
+                match entry.split_once(':') {
+                    Some((name, token)) => {
+                        self.auth.users.insert(name.trim().to_string(), token.trim().to_string());
+                    }
+                    // It may be a token on its own, so it is not repeated in the log.
+                    None => self.auth.ignored_users.push(index + 1),
+                }
+            }
+        }
+        if let Some(admins) = var("TERMWEB_AUTH_ADMINS") {
+            self.auth.admins = admins
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        self.rate_limit = parsed("TERMWEB_RATE_LIMIT").or(self.rate_limit);
+        self.command_timeout_secs = parsed("TERMWEB_COMMAND_TIMEOUT_SECS").or(self.command_timeout_secs);
+        self.pty_shell = var("TERMWEB_PTY_SHELL").or(self.pty_shell.take());
+    }
+
+    /// Flags win over everything else; clap has already folded in their
+    /// environment variables.
+    fn apply_args(&mut self, args: Args) {
+        if let Some(port) = args.port {
+            self.port = port;
+        }
+        if let Some(bind) = args.bind {
+            self.bind = bind;
+        }
+        if !args.allow_origin.is_empty() {
+            self.allow_origins = args.allow_origin;
+        }
+        if args.state_file.is_some() {
+            self.state_file = args.state_file;
+        }
+        if args.static_dir.is_some() {
+            self.static_dir = args.static_dir;
+        }
+        if args.seed_files.is_some() {
+            self.seed_files = args.seed_files;
+        }
+    }
+
+    /// The app these settings describe.
+    pub(crate) fn builder(self) -> TermwebBuilder {
+        let mut builder = Termweb::builder();
+        #[cfg(feature = "scripting")]
+        if let Some(dir) = self.scripts_dir {
+            builder = builder.scripts_dir(dir);
+        }
+        #[cfg(not(feature = "scripting"))]
+        if self.scripts_dir.is_some() {
+            unsupported("scripts_dir", "scripting");
+        }
+        #[cfg(feature = "wasm-plugins")]
+        if let Some(dir) = self.plugins_dir {
+            builder = builder.plugins_dir(dir);
+        }
+        #[cfg(not(feature = "wasm-plugins"))]
+        if self.plugins_dir.is_some() {
+            unsupported("plugins_dir", "wasm-plugins");
+        }
+        #[cfg(feature = "wasi-sandbox")]
+        if let Some(dir) = self.wasi_dir {
+            builder = builder.wasi_dir(dir);
+        }
+        #[cfg(not(feature = "wasi-sandbox"))]
+        if self.wasi_dir.is_some() {
+            unsupported("wasi_dir", "wasi-sandbox");
+        }
+        #[cfg(feature = "pty")]
+        if let Some(shell) = self.pty_shell {
+            builder = builder.pty_shell(shell);
+        }
+        #[cfg(not(feature = "pty"))]
+        if self.pty_shell.is_some() {
+            unsupported("pty_shell", "pty");
+        }
+        if let Some(sandbox) = self.sandbox {
+            let mut config = SandboxConfig::new(sandbox.image);
+            if let Some(runtime) = sandbox.runtime {
+                config.runtime = runtime;
+            }
+            if let Some(secs) = sandbox.idle_secs {
+                config.idle_timeout = Duration::from_secs(secs);
+            }
+            builder = builder.sandbox(config);
+        }
+        if let Some(bytes) = self.memory_limit {
+            builder = builder.memory_limit(bytes);
+        }
+        if let Some(bytes) = self.total_memory_limit {
+            builder = builder.total_memory_limit(bytes);
+        }
+        builder = builder.limits(Limits {
+            max_file_size: self.max_file_size,
+            max_nodes: self.max_nodes,
+            max_depth: self.max_path_depth,
+            max_name_len: self.max_name_length,
+        });
+        if let Some(seed) = self.seed {
+            builder = builder.deterministic(seed, self.fixed_time.saturating_mul(1000));
+        }
+        if let Some(path) = self.state_file {
+            builder = builder.state_file(path);
+        }
+        if let Some(path) = self.stats_file {
+            builder = builder.stats_file(path);
+        }
+        if let Some(path) = self.audit_file {
+            builder = builder.audit_file(path);
+        }
+        if let Some(path) = self.profile_file {
+            match std::fs::read_to_string(&path) {
+                Ok(script) => builder = builder.profile(script),
+                Err(error) => tracing::warn!("ignoring profile_file {}: {}", path.display(), error),
+            }
+        }
+        if let Some(path) = self.seed_files {
+            builder = builder.seed(path);
+        }
+        for position in self.auth.ignored_users {
+            tracing::warn!("ignoring TERMWEB_AUTH_USERS entry {}: not a `name:token` pair", position);
+        }
+        let mut auth = self.auth.token.map(AuthConfig::shared_token);
+        for (name, token) in self.auth.users {
+            auth = Some(auth.unwrap_or_default().user(name, token));
+        }
+        let admins = self.auth.admins;
+        if let Some(config) = auth.map(|config| admins.into_iter().fold(config, AuthConfig::admin)) {
+            builder = builder.auth(config);
+        }
+        if let Some(per_minute) = self.rate_limit {
+            builder = builder.rate_limit(per_minute);
+        }
+        if let Some(secs) = self.command_timeout_secs {
+            builder = builder.command_timeout(Duration::from_secs(secs));
+        }
+        for origin in self.allow_origins {
+            builder = builder.allow_origin(origin);
+        }
+        let built_frontend = Path::new(FRONTEND_DIST);
+        let static_dir = self.static_dir.or_else(|| built_frontend.is_dir().then(|| built_frontend.into()));
+        if let Some(dir) = static_dir {
+            builder = builder.static_dir(dir);
+        }
+        builder
+    }
+}
+
+/// Warns about a setting this build has no feature for.
+#[cfg(not(all(feature = "scripting", feature = "wasi-sandbox", feature = "pty")))]
+fn unsupported(setting: &str, feature: &str) {
+    tracing::warn!("ignoring {}: built without the {} feature", setting, feature);
+}
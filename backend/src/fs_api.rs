@@ -0,0 +1,662 @@
+//! JSON endpoints for working with files directly, outside the shell.
+//!
+//! `/api/fs/*path` is a REST view of the session's filesystem: `GET` downloads
+//! a file (or with `?list=true` describes it and, for a directory, its
+//! entries), `PUT` uploads one and `DELETE` removes it.
+//!
+//! `/api/export.tar` (or `.tar.gz`) downloads the whole filesystem as an
+//! archive, and `/api/import` unpacks one, gzipped or not, into a directory.
+
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use std::io::Read;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use termweb_core::archive::{self, ArchiveError};
+use termweb_core::fs::{is_binary, FileSystem, READ};
+use termweb_core::path::{path_string, resolve_path};
+use termweb_core::journal::JournalEntry;
+use termweb_core::{content_revision, FsError, Node};
+
+use crate::sessions::{Session, SessionId};
+use crate::uploads::MAX_UPLOAD_BYTES;
+use crate::AppState;
+
+pub(crate) type ApiResult<T> = Result<Json<T>, (StatusCode, Json<ApiError>)>;
+
+#[derive(Debug, Serialize)]
+pub struct ApiError {
+    error: String,
+    /// Set for filesystem failures; see [`FsError::code`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+}
+
+pub(crate) fn api_error(status: StatusCode, message: impl Into<String>) -> (StatusCode, Json<ApiError>) {
+    (
+        status,
+        Json(ApiError {
+            error: message.into(),
+            code: None,
+        }),
+    )
+}
+
+/// A filesystem failure, with the status that best matches its cause.
+pub(crate) fn fs_error(error: FsError) -> (StatusCode, Json<ApiError>) {
+    let status = match error {
+        FsError::NotFound => StatusCode::NOT_FOUND,
+        FsError::PermissionDenied => StatusCode::FORBIDDEN,
+        FsError::AlreadyExists | FsError::DirectoryNotEmpty => StatusCode::CONFLICT,
+        FsError::NoSpace | FsError::QuotaExceeded => StatusCode::INSUFFICIENT_STORAGE,
+        FsError::FileTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    let (status, Json(mut body)) = api_error(status, error.to_string());
+    body.code = Some(error.code());
+    (status, Json(body))
+}
+
+/// Resolves the session a request names, failing in the API's error shape.
+pub(crate) async fn session(state: &AppState, id: SessionId) -> Result<Session, (StatusCode, Json<ApiError>)> {
+    state
+        .sessions
+        .resolve(&id)
+        .await
+        .map_err(|(status, message)| api_error(status, message))
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenRequest {
+    path: String,
+}
+
+/// How file content is spelled in JSON: as is, or base64 for binary files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Encoding {
+    #[default]
+    Text,
+    Base64,
+}
+
+#[derive(Debug, Serialize)]
+struct OpenResponse {
+    path: String,
+    content: String,
+    encoding: Encoding,
+    revision: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SaveRequest {
+    path: String,
+    content: String,
+    #[serde(default)]
+    encoding: Encoding,
+    /// The revision the edit was based on; `None` when creating a new file.
+    revision: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SaveResponse {
+    path: String,
+    revision: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReadQuery {
+    /// Describe the node as JSON instead of sending its content.
+    #[serde(default)]
+    list: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeleteQuery {
+    /// Remove a directory along with everything in it.
+    #[serde(default)]
+    recursive: bool,
+}
+
+/// A node as `?list=true` describes it.
+#[derive(Debug, Serialize)]
+struct FsEntry {
+    name: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    /// Bytes of content; for a directory, everything beneath it.
+    size: u64,
+    /// Milliseconds since the Unix epoch.
+    mtime: u64,
+    /// The `ls -l` style permissions, e.g. `-rw-r--r--`.
+    mode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    /// A directory's entries, in name order. Only given for the node asked about.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    children: Option<Vec<FsEntry>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportQuery {
+    /// Directory to unpack into; created if missing.
+    #[serde(default = "root_dir")]
+    path: String,
+}
+
+fn root_dir() -> String {
+    "/".to_string()
+}
+
+#[derive(Debug, Serialize)]
+struct ImportResponse {
+    path: String,
+    /// The archive members written, in archive order.
+    entries: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JournalQuery {
+    /// Last sequence number the client has seen.
+    #[serde(default)]
+    since: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct JournalResponse {
+    entries: Vec<JournalEntry>,
+    /// Sequence number to pass as `since` on the next request.
+    last_seq: u64,
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/fs/open", post(open_file))
+        .route("/api/fs/save", post(save_file))
+        .route("/api/fs/journal", get(journal))
+        .route("/api/fs/", get(list_root))
+        .route(
+            "/api/fs/*path",
+            get(download_file)
+                .put(upload_file)
+                .delete(delete_file)
+                .layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES)),
+        )
+        .route("/api/export.tar", get(export_tar))
+        .route("/api/export.tar.gz", get(export_tar_gz))
+        .route(
+            "/api/import",
+            post(import_archive).layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES)),
+        )
+}
+
+async fn open_file(
+    State(state): State<AppState>,
+    session_id: SessionId,
+    Json(request): Json<OpenRequest>,
+) -> ApiResult<OpenResponse> {
+    let session = session(&state, session_id).await?;
+    let terminal = session.terminal.lock().await;
+    let path = resolve_path(&terminal.cwd, &request.path);
+    let content = terminal.fs.read_bytes(&path).map_err(fs_error)?;
+    let bytes = content.bytes();
+    let (content_text, encoding) = if is_binary(&bytes) {
+        (STANDARD.encode(&bytes), Encoding::Base64)
+    } else {
+        (String::from_utf8_lossy(&bytes).into_owned(), Encoding::Text)
+    };
+    Ok(Json(OpenResponse {
+        path: path_string(&path),
+        revision: content_revision(content.chunks()),
+        content: content_text,
+        encoding,
+    }))
+}
+
+async fn save_file(
+    State(state): State<AppState>,
+    session_id: SessionId,
+    Json(request): Json<SaveRequest>,
+) -> ApiResult<SaveResponse> {
+    let session = session(&state, session_id).await?;
+    let mut terminal = session.terminal.lock().await;
+    let path = resolve_path(&terminal.cwd, &request.path);
+    let current = current_revision(&terminal.fs, &path)?;
+    if current != request.revision {
+        return Err(api_error(
+            StatusCode::CONFLICT,
+            "file was modified since it was opened",
+        ));
+    }
+    let content = match request.encoding {
+        Encoding::Text => request.content.into_bytes(),
+        Encoding::Base64 => STANDARD
+            .decode(request.content)
+            .map_err(|_| api_error(StatusCode::BAD_REQUEST, "content is not valid base64"))?,
+    };
+    let revision = content_revision([content.as_slice()]);
+    terminal
+        .fs
+        .write_bytes(&path, content, false)
+        .map_err(fs_error)?;
+    drop(terminal);
+    state.sessions.enforce_memory_limit(&session.id).await;
+    Ok(Json(SaveResponse {
+        path: path_string(&path),
+        revision,
+    }))
+}
+
+async fn journal(
+    State(state): State<AppState>,
+    session_id: SessionId,
+    Query(query): Query<JournalQuery>,
+) -> ApiResult<JournalResponse> {
+    let session = session(&state, session_id).await?;
+    let terminal = session.terminal.lock().await;
+    let journal = terminal.fs.journal();
+    Ok(Json(JournalResponse {
+        entries: journal.since(query.since).cloned().collect(),
+        last_seq: journal.last_seq(),
+    }))
+}
+
+/// The revision of the file at `path`, or `None` if there is none. Comparing
+/// revisions tells what the file holds, so it needs read permission.
+fn current_revision(fs: &FileSystem, path: &[String]) -> Result<Option<String>, (StatusCode, Json<ApiError>)> {
+    match fs.read_bytes(path) {
+        Ok(content) => Ok(Some(content_revision(content.chunks()))),
+        Err(FsError::NotFound) => Ok(None),
+        Err(error) => Err(fs_error(error)),
+    }
+}
+
+/// Describes the root directory; `/api/fs/*path` needs a non-empty path.
+async fn list_root(State(state): State<AppState>, session_id: SessionId) -> ApiResult<FsEntry> {
+    let session = session(&state, session_id).await?;
+    let terminal = session.terminal.lock().await;
+    describe(&terminal.fs, &[]).map(Json)
+}
+
+fn describe(fs: &FileSystem, path: &[String]) -> Result<FsEntry, (StatusCode, Json<ApiError>)> {
+    let resolved = fs.resolve_links(path, true).map_err(fs_error)?;
+    let node = fs.get_node(&resolved).ok_or(fs_error(FsError::NotFound))?;
+    // As with `ls -l`, describing an entry needs the directories above it to be searchable.
+    if !fs.permitted(&resolved, 0) {
+        return Err(fs_error(FsError::PermissionDenied));
+    }
+    let name = path.last().map_or("/", String::as_str);
+    let mut entry = fs_entry(name, node);
+    if let Node::Dir { children, .. } = node {
+        if !fs.permitted(&resolved, READ) {
+            return Err(fs_error(FsError::PermissionDenied));
+        }
+        entry.children = Some(children.iter().map(|(name, child)| fs_entry(name, child)).collect());
+    }
+    Ok(entry)
+}
+
+fn fs_entry(name: &str, node: &Node) -> FsEntry {
+    let (kind, target) = match node {
+        Node::Dir { .. } => ("dir", None),
+        Node::File { .. } => ("file", None),
+        Node::Symlink { target, .. } => ("symlink", Some(target.clone())),
+    };
+    FsEntry {
+        name: name.to_string(),
+        kind,
+        size: node.size(),
+        mtime: node.meta().modified,
+        mode: node.mode_string(),
+        target,
+        children: None,
+    }
+}
+
+/// Serves a file's raw content, honouring `If-None-Match` against its ETag and
+/// single `Range` requests so clients can skip or resume large downloads.
+async fn download_file(
+    State(state): State<AppState>,
+    session_id: SessionId,
+    Path(path): Path<String>,
+    Query(query): Query<ReadQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let session = match session(&state, session_id).await {
+        Ok(session) => session,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let terminal = session.terminal.lock().await;
+    let path = resolve_path(&[], &path);
+    if query.list {
+        return describe(&terminal.fs, &path).map(Json).into_response();
+    }
+    let content = match terminal.fs.read_bytes(&path) {
+        Ok(content) => content,
+        Err(error) => return fs_error(error).into_response(),
+    };
+    let etag = format!("\"{}\"", content_revision(content.chunks()));
+    let header_str = |name: header::HeaderName| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if header_str(header::IF_NONE_MATCH).is_some_and(|tags| etag_matches(tags, &etag)) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+
+    let total = content.len();
+    // A stale If-Range means the client's partial copy is outdated: send everything.
+    let range = header_str(header::RANGE)
+        .filter(|_| header_str(header::IF_RANGE).is_none_or(|tag| tag == etag));
+    // A header that is not a single byte range is ignored, as if it were absent.
+    let range = range.and_then(parse_range).map(|range| range.within(total));
+    let (status, body, content_range) = match range {
+        None => (StatusCode::OK, content.to_vec(), None),
+        Some(Some((start, end))) => (
+            StatusCode::PARTIAL_CONTENT,
+            content.range(start, end + 1 - start),
+            Some(format!("bytes {}-{}/{}", start, end, total)),
+        ),
+        Some(None) => {
+            return (
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", total))],
+            )
+                .into_response();
+        }
+    };
+
+    let mut response = (status, body).into_response();
+    let response_headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&content_type(&path, &content.bytes())) {
+        response_headers.insert(header::CONTENT_TYPE, value);
+    }
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response_headers.insert(header::ETAG, value);
+    }
+    if let Some(value) = content_range.and_then(|range| HeaderValue::from_str(&range).ok()) {
+        response_headers.insert(header::CONTENT_RANGE, value);
+    }
+    response
+}
+
+/// Writes the request body to a file, creating or replacing it. With
+/// `If-Match`, the write only happens if the file still has that ETag.
+async fn upload_file(
+    State(state): State<AppState>,
+    session_id: SessionId,
+    Path(path): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let session = match session(&state, session_id).await {
+        Ok(session) => session,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let mut terminal = session.terminal.lock().await;
+    let path = resolve_path(&[], &path);
+    let exists = match terminal.fs.get_node(&path) {
+        Some(Node::File { .. }) => true,
+        Some(_) => return fs_error(FsError::IsADirectory).into_response(),
+        None => false,
+    };
+    let if_match = headers.get(header::IF_MATCH).and_then(|value| value.to_str().ok());
+    if let Some(tags) = if_match {
+        let current = match current_revision(&terminal.fs, &path) {
+            Ok(current) => current.map(|revision| format!("\"{}\"", revision)),
+            Err(rejection) => return rejection.into_response(),
+        };
+        if !current.as_ref().is_some_and(|etag| etag_matches(tags, etag)) {
+            let message = "file was modified since it was read";
+            return api_error(StatusCode::PRECONDITION_FAILED, message).into_response();
+        }
+    }
+    let content = body.to_vec();
+    let revision = content_revision([content.as_slice()]);
+    if let Err(error) = terminal.fs.write_bytes(&path, content, false) {
+        return fs_error(error).into_response();
+    }
+    drop(terminal);
+    state.sessions.enforce_memory_limit(&session.id).await;
+    let status = if exists {
+        StatusCode::OK
+    } else {
+        StatusCode::CREATED
+    };
+    let etag = format!("\"{}\"", revision);
+    let body = SaveResponse {
+        path: path_string(&path),
+        revision,
+    };
+    (status, [(header::ETAG, etag)], Json(body)).into_response()
+}
+
+/// Removes a file, symlink or empty directory; `?recursive=true` also
+/// removes directories with entries.
+async fn delete_file(
+    State(state): State<AppState>,
+    session_id: SessionId,
+    Path(path): Path<String>,
+    Query(query): Query<DeleteQuery>,
+) -> Result<StatusCode, (StatusCode, Json<ApiError>)> {
+    let session = session(&state, session_id).await?;
+    let mut terminal = session.terminal.lock().await;
+    let path = resolve_path(&[], &path);
+    let removed = match terminal.fs.get_node_nofollow(&path) {
+        Some(Node::Dir { .. }) if !query.recursive => terminal.fs.remove_dir(&path),
+        _ => terminal.fs.remove(&path, query.recursive),
+    };
+    removed.map_err(fs_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn export_tar(State(state): State<AppState>, session_id: SessionId) -> Response {
+    match export(&state, session_id).await {
+        Ok(tar) => archive_response(tar, "application/x-tar", "termweb.tar"),
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+async fn export_tar_gz(State(state): State<AppState>, session_id: SessionId) -> Response {
+    let tar = match export(&state, session_id).await {
+        Ok(tar) => tar,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    // Writing into a Vec cannot fail.
+    std::io::Write::write_all(&mut encoder, &tar).expect("gzip into memory");
+    let gzipped = encoder.finish().expect("gzip into memory");
+    archive_response(gzipped, "application/gzip", "termweb.tar.gz")
+}
+
+/// The session's whole filesystem as a tar, members named relative to `/`.
+async fn export(state: &AppState, session_id: SessionId) -> Result<Vec<u8>, (StatusCode, Json<ApiError>)> {
+    let session = session(state, session_id).await?;
+    let terminal = session.terminal.lock().await;
+    archive::pack(&terminal.fs, &[(String::new(), Vec::new())]).map_err(fs_error)
+}
+
+fn archive_response(body: Vec<u8>, content_type: &'static str, file_name: &str) -> Response {
+    let disposition = format!("attachment; filename=\"{}\"", file_name);
+    (
+        [(header::CONTENT_TYPE, content_type.to_string()), (header::CONTENT_DISPOSITION, disposition)],
+        body,
+    )
+        .into_response()
+}
+
+/// Unpacks a tar, or gzipped tar, from the request body into `?path=`,
+/// replacing files already there.
+async fn import_archive(
+    State(state): State<AppState>,
+    session_id: SessionId,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> ApiResult<ImportResponse> {
+    let session = session(&state, session_id).await?;
+    let tar = if body.starts_with(&GZIP_MAGIC) {
+        let mut tar = Vec::new();
+        // Bound what a small upload may inflate to.
+        GzDecoder::new(body.as_ref())
+            .take(MAX_UPLOAD_BYTES as u64 * 4 + 1)
+            .read_to_end(&mut tar)
+            .map_err(|error| api_error(StatusCode::BAD_REQUEST, format!("invalid gzip data: {}", error)))?;
+        if tar.len() > MAX_UPLOAD_BYTES * 4 {
+            return Err(api_error(StatusCode::PAYLOAD_TOO_LARGE, "archive is too large once decompressed"));
+        }
+        tar
+    } else {
+        body.to_vec()
+    };
+    let mut terminal = session.terminal.lock().await;
+    let dest = resolve_path(&[], &query.path);
+    let entries = archive::unpack(&mut terminal.fs, &tar, &dest).map_err(|error| match error {
+        ArchiveError::Fs(_, fs) => {
+            let (status, Json(mut body)) = fs_error(fs);
+            body.error = error.to_string();
+            (status, Json(body))
+        }
+        ArchiveError::Invalid(_) | ArchiveError::UnsafePath(_) => {
+            let (status, Json(mut body)) = api_error(StatusCode::BAD_REQUEST, error.to_string());
+            body.code = Some("invalid_archive");
+            (status, Json(body))
+        }
+    })?;
+    drop(terminal);
+    state.sessions.enforce_memory_limit(&session.id).await;
+    Ok(Json(ImportResponse {
+        path: path_string(&dest),
+        entries,
+    }))
+}
+
+/// The first two bytes of every gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// What a download is served as: guessed from the file name, or else from
+/// whether the content looks like text.
+fn content_type(path: &[String], content: &[u8]) -> String {
+    let name = path.last().map(String::as_str).unwrap_or_default();
+    match mime_guess::from_path(name).first() {
+        Some(mime) if mime.type_() == "text" => format!("{}; charset=utf-8", mime),
+        Some(mime) => mime.to_string(),
+        None if is_binary(content) => "application/octet-stream".to_string(),
+        None => "text/plain; charset=utf-8".to_string(),
+    }
+}
+
+fn etag_matches(header: &str, etag: &str) -> bool {
+    header
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/// The one byte range a `Range` header asks for.
+enum ByteRange {
+    /// `bytes=start-` or `bytes=start-end`, both inclusive.
+    From(usize, Option<usize>),
+    /// `bytes=-length`, the last `length` bytes.
+    Suffix(usize),
+}
+
+impl ByteRange {
+    /// The inclusive bounds of the part of `total` bytes the range covers, or
+    /// `None` when it covers none of them.
+    fn within(self, total: usize) -> Option<(usize, usize)> {
+        let last = total.checked_sub(1)?;
+        match self {
+            ByteRange::From(start, end) => (start <= last).then(|| (start, end.unwrap_or(last).min(last))),
+            ByteRange::Suffix(0) => None,
+            ByteRange::Suffix(length) => Some((total.saturating_sub(length), last)),
+        }
+    }
+}
+
+/// Reads a `Range` header, or gives `None` when it is malformed or asks for
+/// several ranges at once.
+fn parse_range(header: &str) -> Option<ByteRange> {
+    let spec = header.strip_prefix("bytes=")?.trim();
+    let (start, end) = spec.split_once('-')?;
+    let number = |text: &str| match text.trim() {
+        text if !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_digit()) => text.parse().ok(),
+        _ => None,
+    };
+    match (start.trim(), end.trim()) {
+        ("", length) => Some(ByteRange::Suffix(number(length)?)),
+        (start, "") => Some(ByteRange::From(number(start)?, None)),
+        (start, end) => {
+            let (start, end) = (number(start)?, number(end)?);
+            (start <= end).then_some(ByteRange::From(start, Some(end)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{etag_matches, parse_range};
+
+    /// The inclusive bounds `header` asks for out of `total` bytes.
+    fn bounds(header: &str, total: usize) -> Option<(usize, usize)> {
+        parse_range(header)?.within(total)
+    }
+
+    #[test]
+    fn reads_closed_and_open_ended_ranges() {
+        assert_eq!(bounds("bytes=0-4", 10), Some((0, 4)));
+        assert_eq!(bounds("bytes= 2 - 3 ", 10), Some((2, 3)));
+        assert_eq!(bounds("bytes=5-", 10), Some((5, 9)));
+        // An end past the content stops at its last byte.
+        assert_eq!(bounds("bytes=8-100", 10), Some((8, 9)));
+    }
+
+    #[test]
+    fn reads_suffix_ranges() {
+        assert_eq!(bounds("bytes=-3", 10), Some((7, 9)));
+        assert_eq!(bounds("bytes=-30", 10), Some((0, 9)));
+    }
+
+    #[test]
+    fn unsatisfiable_ranges_cover_nothing() {
+        assert!(parse_range("bytes=10-").is_some());
+        assert_eq!(bounds("bytes=10-", 10), None);
+        assert_eq!(bounds("bytes=-0", 10), None);
+        assert_eq!(bounds("bytes=0-", 0), None);
+    }
+
+    #[test]
+    fn malformed_ranges_are_ignored() {
+        let headers = [
+            "bytes=",
+            "bytes=-",
+            "bytes=a-b",
+            "bytes=+1-2",
+            "bytes=4-2",
+            "bytes=0-1,4-5",
+            "items=0-1",
+            "0-1",
+        ];
+        for header in headers {
+            assert!(parse_range(header).is_none(), "{}", header);
+        }
+    }
+
+    #[test]
+    fn matches_etags_in_if_none_match_lists() {
+        let etag = "\"abc\"";
+        assert!(etag_matches("\"abc\"", etag));
+        assert!(etag_matches("\"xyz\", \"abc\"", etag));
+        assert!(etag_matches("W/\"abc\"", etag));
+        assert!(etag_matches("*", etag));
+        assert!(!etag_matches("\"abd\"", etag));
+        assert!(!etag_matches("abc", etag));
+        assert!(!etag_matches("", etag));
+    }
+}
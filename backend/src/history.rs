@@ -0,0 +1,26 @@
+//! A session's command history, so clients can recall the lines the server
+//! actually ran (with `!!` references already expanded) instead of keeping
+//! their own list.
+
+use axum::{extract::State, routing::get, Json, Router};
+use serde::Serialize;
+
+use crate::fs_api::{session, ApiResult};
+use crate::sessions::SessionId;
+use crate::AppState;
+
+#[derive(Debug, Serialize)]
+struct HistoryResponse {
+    /// Oldest first; entry `i` is what `!{i + 1}` recalls.
+    entries: Vec<String>,
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new().route("/api/history", get(history))
+}
+
+async fn history(State(state): State<AppState>, session_id: SessionId) -> ApiResult<HistoryResponse> {
+    let session = session(&state, session_id).await?;
+    let entries = session.terminal.lock().await.history.clone();
+    Ok(Json(HistoryResponse { entries }))
+}
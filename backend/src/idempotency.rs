@@ -0,0 +1,63 @@
+//! Deduplication of retried command submissions.
+//!
+//! Clients on flaky connections may resend a command whose response they never
+//! saw. When the request carries an idempotency key, the first submission runs
+//! and every retry with the same key gets that same response, even if it
+//! arrives while the first is still executing.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use termweb_core::CommandResponse;
+use tokio::sync::{Mutex, OnceCell};
+
+pub(crate) const HEADER: &str = "idempotency-key";
+
+/// How many keys are remembered before the oldest are forgotten.
+const MAX_KEYS: usize = 1024;
+
+struct Entry {
+    command: String,
+    response: Arc<OnceCell<CommandResponse>>,
+}
+
+#[derive(Default)]
+pub(crate) struct IdempotencyCache {
+    entries: Mutex<(HashMap<String, Entry>, VecDeque<String>)>,
+}
+
+pub(crate) enum Lookup {
+    /// Run (or wait for) the command through this cell.
+    Cell(Arc<OnceCell<CommandResponse>>),
+    /// The key was already used for a different command.
+    Mismatch,
+}
+
+impl IdempotencyCache {
+    pub(crate) async fn lookup(&self, key: &str, command: &str) -> Lookup {
+        let mut guard = self.entries.lock().await;
+        let (entries, order) = &mut *guard;
+        if let Some(entry) = entries.get(key) {
+            if entry.command != command {
+                return Lookup::Mismatch;
+            }
+            return Lookup::Cell(entry.response.clone());
+        }
+
+        let response = Arc::new(OnceCell::new());
+        entries.insert(
+            key.to_string(),
+            Entry {
+                command: command.to_string(),
+                response: response.clone(),
+            },
+        );
+        order.push_back(key.to_string());
+        while order.len() > MAX_KEYS {
+            if let Some(expired) = order.pop_front() {
+                entries.remove(&expired);
+            }
+        }
+        Lookup::Cell(response)
+    }
+}
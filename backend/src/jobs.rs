@@ -0,0 +1,272 @@
+//! Fire-and-poll command execution for HTTP clients that cannot hold a
+//! request open for the whole run of a long command. Jobs started in a
+//! shell with `&` are run here too, and can be polled by their id. Ids are
+//! only unique, not secret, so a job is polled through the session that
+//! started it. Either kind can be polled for its output so far while it
+//! runs.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use termweb_core::jobs::{next_pid, Job as ShellJob};
+use termweb_core::shell::{step_job, JobStep};
+use termweb_core::{CommandResponse, OutputChunk};
+use tokio::sync::{mpsc, Mutex};
+
+use crate::sessions::{Session, SessionId};
+use crate::{dispatch_streaming, negotiate, AppState, CommandRequest};
+
+/// How many finished jobs are kept around for polling before the oldest go.
+const MAX_FINISHED_JOBS: usize = 256;
+
+/// Jobs submitted over HTTP that may be unfinished in one session at once.
+/// They take turns at the terminal, so more would only queue up.
+const MAX_RUNNING_JOBS: usize = 8;
+
+/// How often a sleeping job looks to see whether it has been killed.
+const KILL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+pub(crate) struct JobStore {
+    jobs: Mutex<BTreeMap<u64, Job>>,
+    finished: Mutex<VecDeque<u64>>,
+}
+
+struct Job {
+    /// The id of the session it runs in, the only one it can be polled through.
+    session: String,
+    command: String,
+    result: Option<CommandResponse>,
+    /// The output so far of a job submitted over HTTP.
+    output: String,
+    /// A job started with `&`, whose output can be read while it runs.
+    shell: Option<Arc<ShellJob>>,
+}
+
+#[derive(Debug, Serialize)]
+struct JobCreated {
+    id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct PollQuery {
+    /// Byte offset into the output the client has already seen.
+    #[serde(default)]
+    offset: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct JobStatus<'a> {
+    id: u64,
+    command: &'a str,
+    status: &'static str,
+    /// Output from `offset` onwards.
+    output: &'a str,
+    /// Offset to pass on the next poll.
+    offset: usize,
+    result: Option<&'a CommandResponse>,
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/jobs", post(submit_job))
+        .route("/api/jobs/:id", get(poll_job))
+}
+
+async fn submit_job(
+    State(state): State<AppState>,
+    session_id: SessionId,
+    Json(payload): Json<CommandRequest>,
+) -> Result<(StatusCode, Json<JobCreated>), (StatusCode, String)> {
+    let session = state
+        .sessions
+        .resolve(&session_id.or(payload.session))
+        .await?;
+    session.ensure_idle()?;
+    let id = next_pid();
+    let command = payload.command.trim().to_string();
+    {
+        let mut jobs = state.jobs.jobs.lock().await;
+        let running = jobs
+            .values()
+            .filter(|job| job.session == session.id && job.shell.is_none() && job.result.is_none())
+            .count();
+        if running >= MAX_RUNNING_JOBS {
+            let message = format!("{} jobs are already running in this session", running);
+            return Err((StatusCode::TOO_MANY_REQUESTS, message));
+        }
+        jobs.insert(
+            id,
+            Job {
+                session: session.id.clone(),
+                command: command.clone(),
+                result: None,
+                output: String::new(),
+                shell: None,
+            },
+        );
+    }
+
+    // Everything that waits for the terminal happens in the job, so an
+    // earlier job still holding it never keeps the client waiting.
+    tokio::spawn(async move {
+        negotiate(&session, payload.color, payload.columns).await;
+        let (output, mut chunks) = mpsc::unbounded_channel();
+        let collect = async {
+            while let Some(chunk) = chunks.recv().await {
+                state.jobs.append(id, chunk).await;
+            }
+        };
+        let (response, ()) = tokio::join!(dispatch_streaming(&state, &session, &command, output), collect);
+        state.jobs.finish(id, response).await;
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(JobCreated { id })))
+}
+
+/// Takes over the jobs a command line in `session` started with `&`, each
+/// after its first step: those still sleeping carry on in a task of their
+/// own, taking the terminal only between sleeps.
+pub(crate) async fn launch(state: &AppState, session: &Session, started: Vec<(Arc<ShellJob>, JobStep)>) {
+    for (job, step) in started {
+        let id = job.id;
+        state.jobs.jobs.lock().await.insert(
+            id,
+            Job {
+                session: session.id.clone(),
+                command: job.command.clone(),
+                result: None,
+                output: String::new(),
+                shell: Some(job.clone()),
+            },
+        );
+        let millis = match step {
+            JobStep::Sleep(millis) => millis,
+            JobStep::Finished(response) => {
+                state.jobs.finish(id, *response).await;
+                continue;
+            }
+        };
+        let state = state.clone();
+        let terminal = session.terminal.clone();
+        tokio::spawn(async move {
+            let mut millis = millis;
+            loop {
+                sleep(&job, Duration::from_millis(millis)).await;
+                let (commands, terminal, stepped) = (state.commands.clone(), terminal.clone(), job.clone());
+                let step = tokio::task::spawn_blocking(move || {
+                    step_job(&commands, &mut terminal.blocking_lock(), &stepped)
+                })
+                .await
+                .expect("background job panicked");
+                match step {
+                    JobStep::Sleep(next) => millis = next,
+                    JobStep::Finished(response) => {
+                        state.jobs.finish(id, *response).await;
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Waits out a job's `sleep` without holding its terminal, stopping early
+/// if the job is killed.
+async fn sleep(job: &ShellJob, duration: Duration) {
+    let deadline = Instant::now() + duration;
+    while !job.killed() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        tokio::time::sleep(left.min(KILL_CHECK_INTERVAL)).await;
+    }
+}
+
+impl JobStore {
+    /// Adds a chunk of output to job `id`'s, joined as
+    /// [`termweb_core::shell::join_output`] joins a finished command line's.
+    async fn append(&self, id: u64, chunk: OutputChunk) {
+        let mut jobs = self.jobs.lock().await;
+        let Some(job) = jobs.get_mut(&id) else {
+            return;
+        };
+        match chunk {
+            OutputChunk::Text(text) => {
+                if !job.output.is_empty() {
+                    job.output.push('\n');
+                }
+                job.output.push_str(&text);
+            }
+            OutputChunk::Clear => job.output.clear(),
+        }
+    }
+
+    /// Records job `id`'s result, dropping the oldest finished jobs once
+    /// there are too many. A job submitted over HTTP gets the output
+    /// collected as it ran.
+    async fn finish(&self, id: u64, mut response: CommandResponse) {
+        if let Some(job) = self.jobs.lock().await.get_mut(&id) {
+            if job.shell.is_none() {
+                response.output = std::mem::take(&mut job.output);
+            }
+            job.result = Some(response);
+        }
+        let mut finished = self.finished.lock().await;
+        finished.push_back(id);
+        while finished.len() > MAX_FINISHED_JOBS {
+            if let Some(expired) = finished.pop_front() {
+                self.jobs.lock().await.remove(&expired);
+            }
+        }
+    }
+}
+
+async fn poll_job(
+    State(state): State<AppState>,
+    session_id: SessionId,
+    Path(id): Path<u64>,
+    Query(query): Query<PollQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let session = state.sessions.resolve(&session_id).await?;
+    let jobs = state.jobs.jobs.lock().await;
+    let job = jobs
+        .get(&id)
+        .filter(|job| job.session == session.id)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("unknown job {}", id)))?;
+    // Output is there to read before the job finishes.
+    let output = match (&job.result, &job.shell) {
+        (Some(result), _) => result.output.clone(),
+        (None, Some(shell)) => shell.output(),
+        (None, None) => job.output.clone(),
+    };
+    let start = floor_char_boundary(&output, query.offset);
+    let status = JobStatus {
+        id,
+        command: &job.command,
+        status: if job.result.is_some() { "completed" } else { "running" },
+        output: &output[start..],
+        offset: output.len(),
+        result: job.result.as_ref(),
+    };
+    // Serialise while the lock is held; the status borrows from the job.
+    Ok(Json(status).into_response())
+}
+
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
@@ -0,0 +1,834 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware,
+    routing::post,
+    Json, Router,
+};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use termweb_core::editor::EditorOp;
+use termweb_core::plugin::CommandPlugin;
+use termweb_core::shell::{join_output, step_job};
+use termweb_core::archive::make_dirs;
+use termweb_core::path::resolve_path;
+use termweb_core::startup::{init_session, PROFILE};
+use termweb_core::{
+    execute_command_streaming, Command, CommandRegistry, CommandResponse, Limits, Node, Notification,
+    OutputChunk, TerminalState,
+};
+use tokio::sync::mpsc;
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::services::{ServeDir, ServeFile};
+
+pub use termweb_core;
+
+mod audit;
+mod auth;
+mod fs_api;
+mod history;
+mod idempotency;
+mod jobs;
+mod output;
+mod persistence;
+mod presence;
+#[cfg(feature = "pty")]
+mod pty;
+mod rate_limit;
+mod replay;
+pub mod sandbox;
+mod seed;
+mod sessions;
+mod stats;
+mod stream;
+mod terminal_ws;
+mod transcript;
+mod uploads;
+
+pub use auth::AuthConfig;
+use sandbox::{SandboxConfig, SandboxManager};
+use sessions::{Session, SessionId};
+
+#[derive(Clone)]
+struct AppState {
+    sessions: Arc<sessions::SessionManager>,
+    commands: Arc<CommandRegistry>,
+    sandbox: Option<Arc<SandboxManager>>,
+    jobs: Arc<jobs::JobStore>,
+    outputs: Arc<output::OutputStore>,
+    uploads: Arc<uploads::UploadStore>,
+    idempotency: Arc<idempotency::IdempotencyCache>,
+    stats: Arc<stats::CommandStats>,
+    audit: Option<Arc<audit::AuditLog>>,
+    session_options: SessionOptions,
+    command_timeout: Option<Duration>,
+}
+
+/// Settings every new virtual shell session starts with.
+#[derive(Clone, Default)]
+struct SessionOptions {
+    memory_limit: Option<usize>,
+    limits: Limits,
+    deterministic: Option<(u64, u64)>,
+    /// What goes in `/etc/profile`.
+    profile: Option<Arc<str>>,
+    /// The tree a session starts with, when not an empty home directory.
+    seed: Option<Arc<Node>>,
+}
+
+impl SessionOptions {
+    /// The `(seed, epoch_millis)` for a deterministic session, taking what a
+    /// request leaves out from the server's own deterministic settings, or 0.
+    fn deterministic_with(&self, seed: Option<u64>, fixed_time: Option<u64>) -> (u64, u64) {
+        let (default_seed, epoch_millis) = self.deterministic.unwrap_or_default();
+        (
+            seed.unwrap_or(default_seed),
+            fixed_time.map_or(epoch_millis, |secs| secs.saturating_mul(1000)),
+        )
+    }
+
+    fn new_terminal(&self) -> TerminalState {
+        let mut terminal = TerminalState::default();
+        if let Some(seed) = &self.seed {
+            terminal.fs.replace_root(Node::clone(seed));
+        }
+        terminal.fs.set_capacity(self.memory_limit);
+        terminal.fs.set_limits(self.limits);
+        if let Some((seed, epoch_millis)) = self.deterministic {
+            terminal.make_deterministic(seed, epoch_millis);
+        }
+        if let Some(profile) = &self.profile {
+            let path = resolve_path(&[], PROFILE);
+            let written = make_dirs(&mut terminal.fs, &path[..path.len() - 1])
+                .and_then(|()| terminal.fs.write_file(&path, profile.to_string(), false));
+            if let Err(error) = written {
+                tracing::warn!("failed to write {}: {}", PROFILE, error);
+            }
+        }
+        terminal
+    }
+}
+
+impl AppState {
+    /// A new session's terminal once its startup files have run, with what
+    /// they printed, made deterministic with a `(seed, epoch_millis)` of its
+    /// own if one is given.
+    fn new_terminal_with(&self, deterministic: Option<(u64, u64)>) -> (TerminalState, String) {
+        let mut terminal = self.session_options.new_terminal();
+        if let Some((seed, epoch_millis)) = deterministic {
+            terminal.make_deterministic(seed, epoch_millis);
+        }
+        let greeting = init_session(&self.commands, &mut terminal).output;
+        (terminal, greeting)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CommandRequest {
+    /// May be left out when `editor` is given.
+    #[serde(default)]
+    command: String,
+    /// Alternative to the `X-Session-Id` header.
+    #[serde(default)]
+    session: Option<String>,
+    /// Alternative to the `Idempotency-Key` header for clients that cannot set headers.
+    #[serde(default)]
+    idempotency_key: Option<String>,
+    /// Whether the client renders ANSI colours. Sticks to the session, so a
+    /// client only needs to say so once.
+    #[serde(default)]
+    color: Option<bool>,
+    /// The client's terminal width in characters, which `ls` fills. Also sticks.
+    #[serde(default)]
+    columns: Option<usize>,
+    /// A change to the file open in `edit`, sent instead of a command line.
+    #[serde(default)]
+    editor: Option<EditorOp>,
+}
+
+/// Builds the termweb HTTP app, letting embedders add their own commands
+/// alongside the built-ins before the router is created.
+///
+/// ```no_run
+/// # use termweb::termweb_core::{Command, CommandContext, CommandResult};
+/// struct Hello;
+///
+/// impl Command for Hello {
+///     fn name(&self) -> &'static str { "hello" }
+///     fn help(&self) -> &'static str { "hello" }
+///     fn run(&self, _ctx: &mut CommandContext<'_>, _args: &[String]) -> CommandResult {
+///         CommandResult::ok("hi there")
+///     }
+/// }
+///
+/// let app = termweb::Termweb::builder().command(Hello).router();
+/// ```
+pub struct Termweb;
+
+impl Termweb {
+    pub fn builder() -> TermwebBuilder {
+        TermwebBuilder::default()
+    }
+}
+
+pub struct TermwebBuilder {
+    commands: CommandRegistry,
+    /// Whether to add the built-ins that reach other sessions; see [`presence`].
+    presence: bool,
+    sandbox: Option<SandboxConfig>,
+    stats_file: Option<std::path::PathBuf>,
+    audit_file: Option<std::path::PathBuf>,
+    state_file: Option<std::path::PathBuf>,
+    memory_limit: Option<usize>,
+    total_memory_limit: Option<usize>,
+    limits: Limits,
+    deterministic: Option<(u64, u64)>,
+    profile: Option<String>,
+    seed: Option<std::path::PathBuf>,
+    auth: Option<AuthConfig>,
+    rate_limit: Option<u32>,
+    command_timeout: Option<Duration>,
+    allowed_origins: Vec<HeaderValue>,
+    static_dir: Option<std::path::PathBuf>,
+    #[cfg(feature = "pty")]
+    pty_shell: Option<String>,
+}
+
+impl Default for TermwebBuilder {
+    fn default() -> Self {
+        TermwebBuilder {
+            commands: CommandRegistry::with_builtins(),
+            presence: true,
+            sandbox: None,
+            stats_file: None,
+            audit_file: None,
+            state_file: None,
+            memory_limit: None,
+            total_memory_limit: None,
+            limits: Limits::default(),
+            deterministic: None,
+            profile: None,
+            seed: None,
+            auth: None,
+            rate_limit: None,
+            command_timeout: None,
+            allowed_origins: Vec::new(),
+            static_dir: None,
+            #[cfg(feature = "pty")]
+            pty_shell: None,
+        }
+    }
+}
+
+impl TermwebBuilder {
+    /// Registers a command; a command named like a built-in replaces it.
+    pub fn command(mut self, command: impl Command + 'static) -> Self {
+        self.commands.register(command);
+        self
+    }
+
+    /// Installs a bundle of commands from another crate; see
+    /// [`termweb_core::plugin`].
+    pub fn plugin(mut self, plugin: impl CommandPlugin) -> Self {
+        tracing::info!("installing command plugin {}", plugin.name());
+        self.commands.install(&plugin);
+        self
+    }
+
+    /// Starts from an empty registry instead of the built-in command set.
+    pub fn without_builtins(mut self) -> Self {
+        self.commands = CommandRegistry::new();
+        self.presence = false;
+        self
+    }
+
+    /// Loads every `*.rhai` script in `dir` as a command. Failures are logged
+    /// and leave the already-registered commands untouched.
+    #[cfg(feature = "scripting")]
+    pub fn scripts_dir(mut self, dir: impl AsRef<std::path::Path>) -> Self {
+        let dir = dir.as_ref();
+        match termweb_core::scripting::load_dir(&mut self.commands, dir) {
+            Ok(count) => tracing::info!("loaded {} script command(s) from {}", count, dir.display()),
+            Err(message) => tracing::warn!("failed to load scripts: {}", message),
+        }
+        self
+    }
+
+    /// Loads every `*.wasm` / `*.wat` plugin in `dir` as a command. Failures are
+    /// logged and leave the already-registered commands untouched.
+    #[cfg(feature = "wasm-plugins")]
+    pub fn plugins_dir(mut self, dir: impl AsRef<std::path::Path>) -> Self {
+        let dir = dir.as_ref();
+        match termweb_core::wasm::load_dir(&mut self.commands, dir) {
+            Ok(count) => tracing::info!("loaded {} wasm plugin(s) from {}", count, dir.display()),
+            Err(message) => tracing::warn!("failed to load wasm plugins: {}", message),
+        }
+        self
+    }
+
+    /// Loads every `*.wasm` WASI program in `dir` as a command that runs
+    /// against a copy of the virtual filesystem.
+    #[cfg(feature = "wasi-sandbox")]
+    pub fn wasi_dir(mut self, dir: impl AsRef<std::path::Path>) -> Self {
+        let dir = dir.as_ref();
+        match termweb_core::wasi::load_dir(&mut self.commands, dir) {
+            Ok(count) => tracing::info!("loaded {} wasi program(s) from {}", count, dir.display()),
+            Err(message) => tracing::warn!("failed to load wasi programs: {}", message),
+        }
+        self
+    }
+
+    /// Exposes `/ws/pty`, bridging WebSocket clients to a real `shell` on the
+    /// host. Only for trusted deployments: the virtual shell stays the default.
+    /// Without [`auth`](Self::auth), only pages from this server or an origin
+    /// given to [`allow_origin`](Self::allow_origin) may open it.
+    #[cfg(feature = "pty")]
+    pub fn pty_shell(mut self, shell: impl Into<String>) -> Self {
+        self.pty_shell = Some(shell.into());
+        self
+    }
+
+    /// Runs commands in per-session containers instead of the virtual shell.
+    pub fn sandbox(mut self, config: SandboxConfig) -> Self {
+        self.sandbox = Some(config);
+        self
+    }
+
+    /// Keeps command usage statistics in `path` across restarts.
+    pub fn stats_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.stats_file = Some(path.into());
+        self
+    }
+
+    /// Appends a JSON line to `path` for every command line run, readable
+    /// by admins through `GET /api/audit`, which needs authentication on.
+    pub fn audit_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.audit_file = Some(path.into());
+        self
+    }
+
+    /// Saves every session's virtual filesystem to `path` shortly after it
+    /// changes, and restores the saved sessions when the router is built.
+    /// Sessions parked to make room for others wait in a directory beside it.
+    pub fn state_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+
+    /// Caps the bytes of file content a session's virtual filesystem may hold;
+    /// writes past the cap fail with "no space left on device".
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Caps the bytes all sessions together may hold in memory: their files,
+    /// history and transcripts. Past it, the least recently used idle
+    /// sessions are parked next to the [state file](Self::state_file), or,
+    /// without one, new sessions are refused.
+    pub fn total_memory_limit(mut self, bytes: usize) -> Self {
+        self.total_memory_limit = Some(bytes);
+        self
+    }
+
+    /// Bounds each session's file sizes, node count, path depth and name
+    /// length; see [`Limits`].
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Makes runs reproducible: the clock starts at `epoch_millis` and only
+    /// moves when `sleep` skips ahead instead of waiting, and random values
+    /// come from a generator seeded with `seed`.
+    pub fn deterministic(mut self, seed: u64, epoch_millis: u64) -> Self {
+        self.deterministic = Some((seed, epoch_millis));
+        self
+    }
+
+    /// Runs `script` as `/etc/profile` at the start of every new session,
+    /// before the user's own dotfiles: a place for aliases, exports and a
+    /// greeting shared by everyone.
+    pub fn profile(mut self, script: impl Into<String>) -> Self {
+        self.profile = Some(script.into());
+        self
+    }
+
+    /// Starts every session with the files at `path`: a directory copied as
+    /// the root, or a JSON or TOML manifest. Read once, when the router is
+    /// built; failures are logged and leave sessions empty.
+    pub fn seed(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.seed = Some(path.into());
+        self
+    }
+
+    /// Requires a token on every request, and keeps each user's sessions
+    /// private to them.
+    pub fn auth(mut self, config: AuthConfig) -> Self {
+        self.auth = Some(config);
+        self
+    }
+
+    /// Allows each client IP address at most `per_minute` requests a minute,
+    /// answering the rest with `429 Too Many Requests`. Only effective when
+    /// the router is served with `into_make_service_with_connect_info`.
+    pub fn rate_limit(mut self, per_minute: u32) -> Self {
+        self.rate_limit = Some(per_minute);
+        self
+    }
+
+    /// Gives up waiting for a command line after `timeout`, answering with a
+    /// `timeout` status, and asks it to stop. Commands see that between the
+    /// steps of a command list and while they sleep; until it has stopped,
+    /// the session answers new command lines with `409 Conflict`.
+    pub fn command_timeout(mut self, timeout: Duration) -> Self {
+        self.command_timeout = Some(timeout);
+        self
+    }
+
+    /// Lets browser pages from `origin` (e.g. `https://example.com`) call the
+    /// API. With no origins given, or `*`, every origin may.
+    pub fn allow_origin(mut self, origin: impl AsRef<str>) -> Self {
+        let origin = origin.as_ref();
+        match HeaderValue::from_str(origin) {
+            Ok(value) => self.allowed_origins.push(value),
+            Err(_) => tracing::warn!("ignoring invalid CORS origin {:?}", origin),
+        }
+        self
+    }
+
+    /// Serves the built web UI from `dir` for every path the API does not
+    /// claim, so one server is all a browser needs.
+    pub fn static_dir(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.static_dir = Some(dir.into());
+        self
+    }
+
+    /// Builds the router. With a sandbox, stats file or state file configured this spawns
+    /// background tasks, so it must be called from within a Tokio runtime.
+    pub fn router(self) -> Router {
+        let sandbox = self.sandbox.map(|config| {
+            tracing::info!("sandbox mode: running commands in {} containers", config.image);
+            let manager = SandboxManager::new(config, self.command_timeout);
+            manager.spawn_reaper();
+            manager
+        });
+        let mut session_options = SessionOptions {
+            memory_limit: self.memory_limit,
+            limits: self.limits,
+            deterministic: self.deterministic,
+            profile: None,
+            seed: None,
+        };
+        if let Some(path) = &self.seed {
+            // Seeded under the sessions' own limits and clock.
+            let mut fs = session_options.new_terminal().fs;
+            match seed::load(path, &mut fs) {
+                Ok(count) => {
+                    tracing::info!("seeding sessions with {} entries from {}", count, path.display());
+                    session_options.seed = Some(Arc::new(fs.root().clone()));
+                }
+                Err(error) => tracing::warn!("failed to seed sessions from {}: {}", path.display(), error),
+            }
+        }
+        session_options.profile = self.profile.map(Arc::from);
+        if let Some((seed, epoch_millis)) = session_options.deterministic {
+            tracing::info!("deterministic mode: seed {}, clock fixed at {}ms", seed, epoch_millis);
+        }
+        let mut sessions = match &self.state_file {
+            Some(path) => persistence::load(path, &session_options),
+            None => sessions::SessionManager::default(),
+        };
+        sessions.limit_memory(self.total_memory_limit);
+        let sessions = Arc::new(sessions);
+        let mut commands = self.commands;
+        if self.presence {
+            presence::register(&mut commands, &sessions);
+        }
+        let state = AppState {
+            sessions,
+            commands: Arc::new(commands),
+            sandbox,
+            jobs: Arc::default(),
+            outputs: Arc::default(),
+            uploads: Arc::default(),
+            idempotency: Arc::default(),
+            stats: Arc::default(),
+            audit: self.audit_file.map(|path| {
+                tracing::info!("recording commands in {}", path.display());
+                audit::AuditLog::open(path)
+            }),
+            session_options,
+            command_timeout: self.command_timeout,
+        };
+        if let Some(path) = self.stats_file {
+            state.stats.persist_to(path);
+        }
+        if let Some(path) = self.state_file {
+            persistence::persist_to(state.sessions.clone(), path);
+        }
+
+        let mut router = Router::new()
+            .route("/api/command", post(run_command))
+            .merge(stream::router())
+            .merge(sessions::router())
+            .merge(fs_api::router())
+            .merge(history::router())
+            .merge(jobs::router())
+            .merge(output::router())
+            .merge(replay::router())
+            .merge(uploads::router())
+            .merge(stats::router())
+            .merge(audit::router())
+            .merge(terminal_ws::router())
+            .with_state(state);
+
+        #[cfg(feature = "pty")]
+        if let Some(shell) = self.pty_shell {
+            tracing::warn!("pty passthrough enabled: /ws/pty spawns {} on the host", shell);
+            router = router.merge(pty::router(shell, self.allowed_origins.clone()));
+        }
+
+        if let Some(config) = self.auth {
+            tracing::info!("token authentication enabled");
+            router = router.layer(middleware::from_fn_with_state(Arc::new(config), auth::require_token));
+        }
+
+        // After authentication, so the page loads before the user has a token.
+        if let Some(dir) = self.static_dir {
+            tracing::info!("serving the web UI from {}", dir.display());
+            let index = ServeFile::new(dir.join("index.html"));
+            router = router.fallback_service(ServeDir::new(dir).fallback(index));
+        }
+
+        // Outside authentication, so token guessing is throttled too.
+        if let Some(per_minute) = self.rate_limit {
+            tracing::info!("rate limiting to {} request(s) per minute per client", per_minute);
+            let limiter = Arc::new(rate_limit::RateLimiter::new(per_minute));
+            router = router.layer(middleware::from_fn_with_state(limiter, rate_limit::limit));
+        }
+
+        let any_origin = self.allowed_origins.iter().any(|origin| origin == "*");
+        let origins = if self.allowed_origins.is_empty() || any_origin {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(self.allowed_origins)
+        };
+        // Compression is negotiated via Accept-Encoding; the default predicate
+        // skips tiny bodies and streaming content types such as SSE.
+        router
+            .layer(CompressionLayer::new())
+            .layer(
+                CorsLayer::new()
+                    .allow_origin(origins)
+                    .allow_methods(Any)
+                    .allow_headers(Any),
+            )
+    }
+}
+
+async fn run_command(
+    State(state): State<AppState>,
+    session_id: SessionId,
+    headers: HeaderMap,
+    Json(payload): Json<CommandRequest>,
+) -> Result<Json<CommandResponse>, (StatusCode, String)> {
+    let session = state
+        .sessions
+        .resolve(&session_id.or(payload.session))
+        .await?;
+    session.ensure_idle()?;
+    negotiate(&session, payload.color, payload.columns).await;
+    if let Some(op) = payload.editor {
+        return Ok(Json(edit(&session, op).await));
+    }
+    let command = payload.command.trim();
+    let key = headers
+        .get(idempotency::HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .or(payload.idempotency_key);
+    let Some(key) = key else {
+        return Ok(Json(dispatch_paged(&state, &session, command).await));
+    };
+
+    // Keys are only unique per client, so scope them to the session.
+    let key = format!("{}:{}", session.id, key);
+    match state.idempotency.lookup(&key, command).await {
+        idempotency::Lookup::Cell(cell) => {
+            // Detached, so that a client hanging up leaves the command to finish
+            // and fill the cell for its retry rather than run it again.
+            let command = command.to_string();
+            let filled = tokio::spawn(async move {
+                cell.get_or_init(|| dispatch_paged(&state, &session, &command)).await.clone()
+            });
+            Ok(Json(filled.await.expect("command task panicked")))
+        }
+        idempotency::Lookup::Mismatch => Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "idempotency key was already used for a different command".to_string(),
+        )),
+    }
+}
+
+/// Records what the session's client said it can display: whether it
+/// renders colours, and how wide it is.
+async fn negotiate(session: &Session, color: Option<bool>, columns: Option<usize>) {
+    let mut terminal = session.terminal.lock().await;
+    if let Some(color) = color {
+        terminal.color = color;
+    }
+    if let Some(columns) = columns.filter(|&columns| columns > 0) {
+        terminal.columns = Some(columns);
+    }
+}
+
+/// Applies an editor operation to the file the session has open in `edit`.
+async fn edit(session: &Session, op: EditorOp) -> CommandResponse {
+    let mut terminal = session.terminal.lock().await;
+    termweb_core::editor::apply(&mut terminal, op)
+}
+
+/// Exit code reported for a command line that timed out, as `timeout(1)` uses.
+const EXIT_TIMEOUT: i32 = 124;
+
+/// Runs one command line against the session's sandbox container or virtual
+/// shell, counting it in the usage statistics.
+async fn dispatch(state: &AppState, session: &Session, input: &str) -> CommandResponse {
+    let (output, mut chunks) = mpsc::unbounded_channel();
+    let mut response = dispatch_streaming(state, session, input, output).await;
+    let mut collected = Vec::new();
+    while let Ok(chunk) = chunks.try_recv() {
+        collected.push(chunk);
+    }
+    response.output = join_output(collected);
+    response
+}
+
+/// Like [`dispatch`], but sends only the first page of a large output,
+/// keeping the rest for `GET /api/output/{token}`.
+async fn dispatch_paged(state: &AppState, session: &Session, input: &str) -> CommandResponse {
+    let mut response = dispatch(state, session, input).await;
+    state.outputs.paginate(&mut response, session.owner.clone()).await;
+    response
+}
+
+/// Like [`dispatch`], but sends the output to `output` as it is produced.
+/// The channel closes when the command line finishes; the returned response
+/// carries everything but the output.
+async fn dispatch_streaming(
+    state: &AppState,
+    session: &Session,
+    input: &str,
+    output: mpsc::UnboundedSender<OutputChunk>,
+) -> CommandResponse {
+    let started = Instant::now();
+    state.sessions.touch(&session.id).await;
+    session.transcript().input(input);
+    // Record the output on its way through.
+    let (sink, mut chunks) = mpsc::unbounded_channel();
+    let forward = async {
+        while let Some(chunk) = chunks.recv().await {
+            session.transcript().output(&chunk);
+            let _ = output.send(chunk);
+        }
+    };
+    let running = async {
+        match &state.audit {
+            Some(audit) => run_audited(state, audit, session, input, sink).await,
+            None => run(state, session, input, sink).await,
+        }
+    };
+    let (mut response, ()) = tokio::join!(running, forward);
+    // Hand over what `write` and `wall` left meanwhile.
+    let messages = session.inbox.take().into_iter().map(|text| Notification::Message { text });
+    response.notifications.extend(messages);
+    session.transcript().finish(&response);
+    state
+        .stats
+        .record(&state.commands, input, response.status == "ok", started.elapsed())
+        .await;
+    state.sessions.enforce_memory_limit(&session.id).await;
+    response
+}
+
+/// Like [`run`], recording the command line in the audit log.
+async fn run_audited(
+    state: &AppState,
+    audit: &audit::AuditLog,
+    session: &Session,
+    input: &str,
+    output: mpsc::UnboundedSender<OutputChunk>,
+) -> CommandResponse {
+    let timestamp = audit::now_millis();
+    // A command still running from before holds the terminal; don't wait on it.
+    let cwd = session
+        .terminal
+        .try_lock()
+        .map(|terminal| terminal.cwd_string())
+        .unwrap_or_default();
+    // Count the output on its way through.
+    let (sink, mut chunks) = mpsc::unbounded_channel();
+    let forward = async {
+        let mut bytes = 0;
+        while let Some(chunk) = chunks.recv().await {
+            if let OutputChunk::Text(text) = &chunk {
+                bytes += text.len();
+            }
+            let _ = output.send(chunk);
+        }
+        bytes
+    };
+    let (response, output_bytes) = tokio::join!(run(state, session, input, sink), forward);
+    audit.record(audit::AuditEntry {
+        timestamp,
+        session: session.id.clone(),
+        user: session.owner.clone(),
+        cwd,
+        command: input.to_string(),
+        exit_code: response.exit_code,
+        output_bytes,
+    });
+    response
+}
+
+/// Runs a command line, within the command timeout if there is one.
+async fn run(
+    state: &AppState,
+    session: &Session,
+    input: &str,
+    output: mpsc::UnboundedSender<OutputChunk>,
+) -> CommandResponse {
+    let stop = Arc::new(AtomicBool::new(false));
+    match state.command_timeout {
+        Some(limit) => execute_within(state, session, input, output, stop, limit).await,
+        None => execute(state, session, input, output, stop).await,
+    }
+}
+
+/// Like [`execute`], but gives up after `limit`, setting `stop` and marking
+/// the session busy until the command line has stopped. The output goes
+/// through a channel of its own so that `output` still closes on time when
+/// the command outlives its budget.
+async fn execute_within(
+    state: &AppState,
+    session: &Session,
+    input: &str,
+    output: mpsc::UnboundedSender<OutputChunk>,
+    stop: Arc<AtomicBool>,
+    limit: Duration,
+) -> CommandResponse {
+    let (sink, mut chunks) = mpsc::unbounded_channel();
+    let running = execute(state, session, input, sink, stop.clone());
+    tokio::pin!(running);
+    let deadline = tokio::time::sleep(limit);
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            response = &mut running => {
+                while let Some(chunk) = chunks.recv().await {
+                    let _ = output.send(chunk);
+                }
+                return response;
+            }
+            Some(chunk) = chunks.recv() => {
+                let _ = output.send(chunk);
+            }
+            () = &mut deadline => {
+                stop.store(true, Ordering::Relaxed);
+                session.stopping.store(true, Ordering::Relaxed);
+                return timed_out(session, limit, output);
+            }
+        }
+    }
+}
+
+/// The response for a command line that ran past its budget.
+fn timed_out(
+    session: &Session,
+    limit: Duration,
+    output: mpsc::UnboundedSender<OutputChunk>,
+) -> CommandResponse {
+    let message = format!("command timed out after {}s", limit.as_secs_f64());
+    let _ = output.send(OutputChunk::Text(message));
+    refused(session, "timeout", EXIT_TIMEOUT)
+}
+
+/// The response for a command line that did not run to its end, with
+/// `status` and `exit_code` saying why.
+fn refused(session: &Session, status: &str, exit_code: i32) -> CommandResponse {
+    CommandResponse {
+        output: String::new(),
+        // The command may still hold the terminal, in which case the
+        // directory it ends up in is not known yet.
+        cwd: session
+            .terminal
+            .try_lock()
+            .map(|terminal| terminal.cwd_string())
+            .unwrap_or_default(),
+        status: status.to_string(),
+        exit_code,
+        error_code: None,
+        clear: false,
+        notifications: Vec::new(),
+        mode: None,
+        editor: None,
+        truncated: false,
+        continuation: None,
+    }
+}
+
+/// Runs a command line in the session's sandbox container or virtual shell,
+/// where setting `stop` asks it to stop early.
+async fn execute(
+    state: &AppState,
+    session: &Session,
+    input: &str,
+    output: mpsc::UnboundedSender<OutputChunk>,
+    stop: Arc<AtomicBool>,
+) -> CommandResponse {
+    if let Some(sandbox) = &state.sandbox {
+        // The container's output arrives in one piece.
+        let mut response = sandbox.execute(&session.id, input).await;
+        let _ = output.send(OutputChunk::Text(std::mem::take(&mut response.output)));
+        return response;
+    }
+    if session.busy() {
+        let _ = output.send(OutputChunk::Text(sessions::BUSY_MESSAGE.to_string()));
+        return refused(session, "error", 1);
+    }
+    let terminal = session.terminal.clone();
+    let stopping = session.stopping.clone();
+    let commands = state.commands.clone();
+    let input = input.to_string();
+    // Built-ins run synchronously; keep long ones off the async workers.
+    let (response, started) = tokio::task::spawn_blocking(move || {
+        let mut terminal = terminal.blocking_lock();
+        // Whatever timed out before has let go of the terminal by now.
+        stopping.store(false, Ordering::Relaxed);
+        terminal.interrupt = stop;
+        // A client that went away just stops receiving; the command still finishes.
+        let response = execute_command_streaming(&commands, &mut terminal, &input, &mut |chunk| {
+            let _ = output.send(chunk);
+        });
+        // Jobs get going straight away, so those that never sleep are over
+        // before the next command, as they are in a deterministic session.
+        let started: Vec<_> = terminal
+            .jobs
+            .take_pending()
+            .into_iter()
+            .map(|job| {
+                let step = step_job(&commands, &mut terminal, &job);
+                (job, step)
+            })
+            .collect();
+        (response, started)
+    })
+    .await
+    .expect("command execution panicked");
+    jobs::launch(state, session, started).await;
+    response
+}
@@ -0,0 +1,122 @@
+//! Paging for outputs too large to send at once. `POST /api/command` sends
+//! the first page and keeps the whole output here; the response's
+//! `continuation` token fetches the next page from
+//! `GET /api/output/{token}`, whose own token fetches the one after.
+//!
+//! Pages end between lines where they can, dropping the newline there, as
+//! clients show each response's output on lines of its own.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use termweb_core::CommandResponse;
+use tokio::sync::Mutex;
+
+use crate::auth::User;
+use crate::AppState;
+
+/// Bytes of output in one response, or one page.
+const PAGE_BYTES: usize = 256 * 1024;
+/// Outputs kept for paging before the oldest are dropped.
+const MAX_OUTPUTS: usize = 64;
+
+#[derive(Default)]
+pub(crate) struct OutputStore {
+    outputs: Mutex<(HashMap<String, Stored>, VecDeque<String>)>,
+}
+
+struct Stored {
+    /// Only this user may page through it, when authentication is on.
+    owner: Option<String>,
+    output: Arc<str>,
+}
+
+#[derive(Debug, Serialize)]
+struct Page {
+    output: String,
+    truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    continuation: Option<String>,
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new().route("/api/output/:token", get(next_page))
+}
+
+impl OutputStore {
+    /// Cuts `response` down to its first page, keeping the whole output to
+    /// page through if there is more.
+    pub(crate) async fn paginate(&self, response: &mut CommandResponse, owner: Option<String>) {
+        if response.output.len() <= PAGE_BYTES {
+            return;
+        }
+        let output: Arc<str> = Arc::from(std::mem::take(&mut response.output));
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let (page, next) = page(&output, 0);
+        response.output = page.to_string();
+        response.truncated = true;
+        response.continuation = next.map(|offset| continuation(&id, offset));
+
+        let mut outputs = self.outputs.lock().await;
+        let (stored, order) = &mut *outputs;
+        order.push_back(id.clone());
+        while order.len() > MAX_OUTPUTS {
+            if let Some(expired) = order.pop_front() {
+                stored.remove(&expired);
+            }
+        }
+        stored.insert(id, Stored { owner, output });
+    }
+}
+
+/// The page of `output` starting at `offset`, and where the next one
+/// starts if there is more.
+fn page(output: &str, offset: usize) -> (&str, Option<usize>) {
+    let rest = &output[offset..];
+    if rest.len() <= PAGE_BYTES {
+        return (rest, None);
+    }
+    let mut end = PAGE_BYTES;
+    while !rest.is_char_boundary(end) {
+        end -= 1;
+    }
+    match rest[..end].rfind('\n') {
+        Some(newline) if newline > 0 => (&rest[..newline], Some(offset + newline + 1)),
+        _ => (&rest[..end], Some(offset + end)),
+    }
+}
+
+fn continuation(id: &str, offset: usize) -> String {
+    format!("{}-{}", id, offset)
+}
+
+async fn next_page(
+    State(state): State<AppState>,
+    user: User,
+    Path(token): Path<String>,
+) -> Result<Json<Page>, StatusCode> {
+    let (id, offset) = token
+        .rsplit_once('-')
+        .and_then(|(id, offset)| Some((id, offset.parse::<usize>().ok()?)))
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let outputs = state.outputs.outputs.lock().await;
+    let stored = outputs
+        .0
+        .get(id)
+        .filter(|stored| stored.owner == user.0)
+        .filter(|stored| offset <= stored.output.len() && stored.output.is_char_boundary(offset))
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let (output, next) = page(&stored.output, offset);
+    Ok(Json(Page {
+        output: output.to_string(),
+        truncated: next.is_some(),
+        continuation: next.map(|offset| continuation(id, offset)),
+    }))
+}
\ No newline at end of file
@@ -0,0 +1,227 @@
+//! Optional on-disk copy of every session's virtual filesystem, so files
+//! survive a server restart.
+//!
+//! The snapshot is JSON keyed by session id. Sessions come back under their
+//! old ids, so clients holding one carry on where they left off.
+//!
+//! The snapshot also keeps the sessions started and ended, for `last`, and
+//! the names users gave sessions.
+//!
+//! Sessions pushed out of memory to make room for others are [parked](Store)
+//! beside it, one file each, and come back when they are next used.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use termweb_core::{Node, TerminalState};
+
+use crate::sessions::{Login, SessionManager, SessionName};
+use crate::SessionOptions;
+
+/// What a save depends on: the filesystem's last mutation, the cwd, the
+/// variables and the aliases.
+type Version = (u64, Vec<String>, BTreeMap<String, String>, BTreeMap<String, String>);
+
+/// How long changes may sit in memory before they are written out.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Default, Serialize, Deserialize)]
+struct Snapshot {
+    sessions: BTreeMap<String, SavedSession>,
+    #[serde(default)]
+    logins: Vec<Login>,
+    #[serde(default)]
+    names: Vec<SessionName>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SavedSession {
+    /// The user it belongs to, when authentication is on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner: Option<String>,
+    cwd: Vec<String>,
+    root: Node,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
+}
+
+impl SavedSession {
+    pub(crate) fn of(owner: Option<String>, terminal: &TerminalState) -> Self {
+        SavedSession {
+            owner,
+            cwd: terminal.cwd.clone(),
+            root: terminal.fs.root().clone(),
+            env: terminal.env.clone(),
+            aliases: terminal.aliases.clone(),
+        }
+    }
+
+    /// A terminal holding what was saved, set up like a new session's.
+    fn restore(self, options: &SessionOptions) -> TerminalState {
+        let mut terminal = options.new_terminal();
+        terminal.fs.replace_root(self.root);
+        // The saved tree may predate the home directory a new session starts in.
+        terminal.cwd = match terminal.fs.get_node(&self.cwd) {
+            Some(Node::Dir { .. }) => self.cwd,
+            _ => Vec::new(),
+        };
+        terminal.env = self.env;
+        terminal.aliases = self.aliases;
+        terminal
+    }
+}
+
+/// Sessions out of memory, kept in a directory beside the state file.
+pub(crate) struct Store {
+    dir: PathBuf,
+    options: SessionOptions,
+}
+
+impl Store {
+    pub(crate) fn new(state_file: &Path, options: SessionOptions) -> Self {
+        Store {
+            dir: state_file.with_extension("parked"),
+            options,
+        }
+    }
+
+    /// Where session `id` is parked. Ids are only ever letters and digits;
+    /// anything else names no file.
+    fn file(&self, id: &str) -> Option<PathBuf> {
+        let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric());
+        valid.then(|| self.dir.join(id).with_extension("json"))
+    }
+
+    /// Writes `saved` out as session `id`, so it can leave memory.
+    pub(crate) async fn park(&self, id: &str, saved: SavedSession) -> std::io::Result<()> {
+        let path = self.file(id).ok_or_else(|| std::io::Error::other("invalid session id"))?;
+        let dir = self.dir.clone();
+        tokio::task::spawn_blocking(move || {
+            let json = serde_json::to_string(&saved).map_err(std::io::Error::other)?;
+            std::fs::create_dir_all(&dir)?;
+            let temp = path.with_extension("tmp");
+            std::fs::write(&temp, json).and_then(|()| std::fs::rename(&temp, &path))
+        })
+        .await
+        .expect("session writer panicked")
+    }
+
+    /// The terminal of parked session `id` if it belongs to `owner`. It stays
+    /// parked until [discarded](Store::discard).
+    pub(crate) async fn unpark(&self, id: &str, owner: &Option<String>) -> Option<TerminalState> {
+        let path = self.file(id)?;
+        let saved = tokio::task::spawn_blocking(move || std::fs::read_to_string(path))
+            .await
+            .expect("session reader panicked");
+        let saved: SavedSession = match saved {
+            Ok(saved) => serde_json::from_str(&saved)
+                .inspect_err(|error| tracing::warn!("ignoring unreadable parked session {}: {}", id, error))
+                .ok()?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(error) => {
+                tracing::warn!("failed to read parked session {}: {}", id, error);
+                return None;
+            }
+        };
+        (saved.owner == *owner).then(|| saved.restore(&self.options))
+    }
+
+    /// Forgets parked session `id`, once it is back in memory or deleted.
+    pub(crate) async fn discard(&self, id: &str) {
+        let Some(path) = self.file(id) else {
+            return;
+        };
+        let removed = tokio::task::spawn_blocking(move || std::fs::remove_file(path))
+            .await
+            .expect("session remover panicked");
+        match removed {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                tracing::warn!("failed to remove parked session {}: {}", id, error);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads the sessions saved in `path`. A missing file is an empty snapshot;
+/// an unreadable one is logged and ignored rather than stopping the server.
+pub(crate) fn load(path: &Path, options: &SessionOptions) -> SessionManager {
+    let snapshot: Snapshot = match std::fs::read_to_string(path) {
+        Ok(saved) => serde_json::from_str(&saved).unwrap_or_else(|error| {
+            tracing::warn!("ignoring unreadable state file {}: {}", path.display(), error);
+            Snapshot::default()
+        }),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Snapshot::default(),
+        Err(error) => {
+            tracing::warn!("failed to read state file {}: {}", path.display(), error);
+            Snapshot::default()
+        }
+    };
+    tracing::info!("restored {} session(s) from {}", snapshot.sessions.len(), path.display());
+    let sessions = snapshot.sessions.into_iter().map(|(id, saved)| {
+        let owner = saved.owner.clone();
+        (id, owner, saved.restore(options))
+    });
+    SessionManager::restore(sessions, snapshot.logins, snapshot.names, Store::new(path, options.clone()))
+}
+
+/// Rewrites `path` whenever a session's filesystem, directory, variables or aliases change,
+/// or a session starts, ends or is named, at most once per [`FLUSH_INTERVAL`].
+pub(crate) fn persist_to(sessions: Arc<SessionManager>, path: PathBuf) {
+    tokio::spawn(async move {
+        let mut saved: BTreeMap<String, Version> = BTreeMap::new();
+        let mut saved_records = (Vec::new(), Vec::new());
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let live = sessions.all().await;
+            let mut versions = BTreeMap::new();
+            for session in &live {
+                let terminal = session.terminal.lock().await;
+                versions.insert(session.id.clone(), version(&terminal));
+            }
+            let records = (sessions.logins(), sessions.names());
+            if versions == saved && records == saved_records {
+                continue;
+            }
+            let mut snapshot = Snapshot {
+                logins: records.0.clone(),
+                names: records.1.clone(),
+                ..Snapshot::default()
+            };
+            for session in live {
+                let terminal = session.terminal.lock().await;
+                // Record what was actually copied, in case it moved on since.
+                versions.insert(session.id.clone(), version(&terminal));
+                snapshot.sessions.insert(session.id, SavedSession::of(session.owner.clone(), &terminal));
+            }
+            let path = path.clone();
+            let written = tokio::task::spawn_blocking(move || {
+                let json = serde_json::to_string(&snapshot).map_err(std::io::Error::other)?;
+                let temp = path.with_extension("tmp");
+                std::fs::write(&temp, json).and_then(|()| std::fs::rename(&temp, &path))
+            })
+            .await
+            .expect("state writer panicked");
+            match written {
+                Ok(()) => (saved, saved_records) = (versions, records),
+                Err(error) => tracing::warn!("failed to write state file: {}", error),
+            }
+        }
+    });
+}
+
+fn version(terminal: &TerminalState) -> Version {
+    (
+        terminal.fs.journal().last_seq(),
+        terminal.cwd.clone(),
+        terminal.env.clone(),
+        terminal.aliases.clone(),
+    )
+}
@@ -0,0 +1,399 @@
+//! Commands that reach the server's other sessions: `who` lists them, `last`
+//! the ones started before, `write` and `wall` leave a message in their
+//! [inboxes](crate::sessions::Inbox), `mesg` turns them away, and `attach`
+//! names the session to come back to from elsewhere. They go in the registry
+//! the server builds, since the virtual shell on its own knows only its own
+//! session.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use termweb_core::clock::format_timestamp;
+use termweb_core::command::EXIT_USAGE;
+use termweb_core::{Command, CommandContext, CommandRegistry, CommandResult, Manual};
+
+use crate::sessions::{valid_name, Presence, SessionManager};
+
+/// Bytes of a message kept; the rest is cut off.
+const MAX_MESSAGE_BYTES: usize = 4096;
+
+/// Adds the commands to `commands`, reaching the sessions in `sessions`,
+/// leaving alone any of the same name already there.
+pub(crate) fn register(commands: &mut CommandRegistry, sessions: &Arc<SessionManager>) {
+    register_unclaimed(commands, Who(sessions.clone()));
+    register_unclaimed(commands, Last(sessions.clone()));
+    register_unclaimed(commands, Write(sessions.clone()));
+    register_unclaimed(commands, Wall(sessions.clone()));
+    register_unclaimed(commands, Mesg(sessions.clone()));
+    register_unclaimed(commands, Attach(sessions.clone()));
+}
+
+fn register_unclaimed(commands: &mut CommandRegistry, command: impl Command + 'static) {
+    if commands.get(command.name()).is_none() {
+        commands.register(command);
+    }
+}
+
+fn usage_error(message: String) -> CommandResult {
+    CommandResult::error(message).with_exit_code(EXIT_USAGE)
+}
+
+/// The session running the command, or `None` outside a registered
+/// session, e.g. in a startup file.
+fn own_session<'a>(ctx: &CommandContext<'_>, roster: &'a [Presence]) -> Option<&'a Presence> {
+    let (_, session) = ctx.state.fs.actor();
+    roster.iter().find(|presence| Some(presence.id.as_str()) == session)
+}
+
+/// Who is at `presence`, as messages name them.
+fn name(presence: &Presence) -> &str {
+    presence.owner.as_deref().unwrap_or("anonymous")
+}
+
+/// The message given as `args`, or else read from standard input.
+fn message(ctx: &mut CommandContext<'_>, args: &[String]) -> Option<String> {
+    let mut message = if args.is_empty() {
+        ctx.stdin.take()?.trim_end().to_string()
+    } else {
+        args.join(" ")
+    };
+    if message.len() > MAX_MESSAGE_BYTES {
+        let mut end = MAX_MESSAGE_BYTES;
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        message.truncate(end);
+    }
+    (!message.is_empty()).then_some(message)
+}
+
+/// How long `presence` has gone without a command line: `.` for under a
+/// minute, `old` for over a day, and `HH:MM` in between, as `who -u` shows it.
+fn idle(presence: &Presence) -> String {
+    let minutes = Instant::now().saturating_duration_since(presence.last_used).as_secs() / 60;
+    match minutes {
+        0 => ".".to_string(),
+        1..1440 => format!("{:02}:{:02}", minutes / 60, minutes % 60),
+        _ => "old".to_string(),
+    }
+}
+
+pub struct Who(Arc<SessionManager>);
+
+impl Command for Who {
+    fn name(&self) -> &'static str {
+        "who"
+    }
+
+    fn help(&self) -> &'static str {
+        "who [-m | am i]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "show who is logged in",
+            description: "Lists the sessions on the server, oldest first: who is at each, its terminal name, \
+                when it started and how long it has been idle. `-m`, or `am i`, lists only this session.\n\n\
+                Without sign-in everybody is `anonymous`, and other sessions are only counted, not listed.",
+            examples: &[("who", "See who else is around."), ("who am i", "Show this session's terminal name.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let only_mine = match args {
+            [] => false,
+            [flag] if flag == "-m" => true,
+            [am, i] if am == "am" && i.eq_ignore_ascii_case("i") => true,
+            _ => return usage_error(format!("who: invalid arguments '{}'", args.join(" "))),
+        };
+        let mut roster = self.0.roster();
+        roster.sort_by_key(|presence| presence.started);
+        let Some(own) = own_session(ctx, &roster) else {
+            return CommandResult::error("who: not in a session");
+        };
+        // Anonymous sessions are only told apart by their ids, which are not to be shared.
+        let anonymous = own.owner.is_none();
+        let (listed, hidden): (Vec<&Presence>, Vec<&Presence>) = roster
+            .iter()
+            .partition(|presence| presence.id == own.id || !(only_mine || anonymous));
+        let mut lines: Vec<String> = listed
+            .iter()
+            .map(|presence| {
+                let started = format_timestamp(presence.started);
+                format!("{:<12} {:<8} {} {:>5}", name(presence), presence.tty, &started[..16], idle(presence))
+            })
+            .collect();
+        if anonymous && !only_mine && !hidden.is_empty() {
+            lines.push(format!("and {} other anonymous session(s)", hidden.len()));
+        }
+        CommandResult::ok(lines.join("\n"))
+    }
+}
+
+/// `HH:MM` for a span of `millis`, with the days ahead as `D+HH:MM` when
+/// there are any, as `last` shows how long a session lasted.
+fn span(millis: u64) -> String {
+    let minutes = millis / 60_000;
+    let (days, hours) = (minutes / 1440, minutes / 60 % 24);
+    match days {
+        0 => format!("{:02}:{:02}", hours, minutes % 60),
+        _ => format!("{}+{:02}:{:02}", days, hours, minutes % 60),
+    }
+}
+
+pub struct Last(Arc<SessionManager>);
+
+impl Command for Last {
+    fn name(&self) -> &'static str {
+        "last"
+    }
+
+    fn help(&self) -> &'static str {
+        "last [-n count] [user]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "show the sessions started before",
+            description: "Lists the sessions started on the server, newest first: who started each, its \
+                terminal name, when it started and ended, and how long it lasted. With a state file this \
+                goes back past restarts.\n\nGiven users, lists only theirs; `-n` lists at most `count`. \
+                Without sign-in, only this session is listed.",
+            examples: &[
+                ("last", "See who has been using the server."),
+                ("last -n 5 al", "Show al's last five."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut limit = usize::MAX;
+        let mut users = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "-n" {
+                match args.next().and_then(|count| count.parse().ok()) {
+                    Some(count) => limit = count,
+                    None => return usage_error("last: -n needs a count".to_string()),
+                }
+            } else if arg.starts_with('-') {
+                return usage_error(format!("last: invalid option '{}'", arg));
+            } else {
+                users.push(arg.as_str());
+            }
+        }
+        let roster = self.0.roster();
+        let Some(own) = own_session(ctx, &roster) else {
+            return CommandResult::error("last: not in a session");
+        };
+        let lines: Vec<String> = self
+            .0
+            .logins()
+            .iter()
+            .rev()
+            // As with `who`, anonymous sessions keep to themselves.
+            .filter(|login| own.owner.is_some() || login.session == own.id)
+            .filter(|login| users.is_empty() || login.user.as_deref().is_some_and(|user| users.contains(&user)))
+            .take(limit)
+            .map(|login| {
+                let start = format_timestamp(login.start);
+                let until = match login.end {
+                    Some(end) => {
+                        let end_time = format_timestamp(end);
+                        format!("- {}  ({})", &end_time[11..16], span(end.saturating_sub(login.start)))
+                    }
+                    None => "still logged in".to_string(),
+                };
+                let user = login.user.as_deref().unwrap_or("anonymous");
+                format!("{:<12} {:<8} {} {}", user, login.tty, &start[..16], until)
+            })
+            .collect();
+        CommandResult::ok(lines.join("\n"))
+    }
+}
+
+pub struct Write(Arc<SessionManager>);
+
+impl Command for Write {
+    fn name(&self) -> &'static str {
+        "write"
+    }
+
+    fn help(&self) -> &'static str {
+        "write <user|terminal> [message]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "send a message to another user",
+            description: "Shows `message`, or else what is piped in, as a notification in the terminal of \
+                `user`, in the session they used last of those that accept messages. A terminal name such \
+                as `pts/3`, as `who` lists them, instead of a user sends to that session.\n\nA session that \
+                ran `mesg n` gets no messages.",
+            examples: &[
+                ("write al lunch in five minutes?", "Send al a message."),
+                ("cat notes.txt | write al", "Send al what notes.txt says."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let Some((target, words)) = args.split_first() else {
+            return usage_error("write: missing user".to_string());
+        };
+        let roster = self.0.roster();
+        let Some(sender) = own_session(ctx, &roster) else {
+            return CommandResult::error("write: not in a session");
+        };
+        let Some(message) = message(ctx, words) else {
+            return usage_error("write: missing message".to_string());
+        };
+        let by_tty = roster.iter().find(|presence| presence.tty == *target);
+        let mut candidates: Vec<&Presence> = match by_tty {
+            Some(presence) => vec![presence],
+            None => roster
+                .iter()
+                .filter(|presence| presence.owner.as_deref() == Some(target) && presence.id != sender.id)
+                .collect(),
+        };
+        if candidates.is_empty() {
+            return CommandResult::error(format!("write: {} is not logged in", target));
+        }
+        candidates.retain(|presence| presence.inbox.accepts());
+        let Some(recipient) = candidates.into_iter().max_by_key(|presence| presence.last_used) else {
+            return CommandResult::error(format!("write: {} has messages disabled", target));
+        };
+        recipient
+            .inbox
+            .deliver(format!("Message from {} on {}: {}", name(sender), sender.tty, message));
+        CommandResult::empty()
+    }
+}
+
+pub struct Wall(Arc<SessionManager>);
+
+impl Command for Wall {
+    fn name(&self) -> &'static str {
+        "wall"
+    }
+
+    fn help(&self) -> &'static str {
+        "wall [message]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "send a message to everybody",
+            description: "Shows `message`, or else what is piped in, as a notification in every session on \
+                the server that accepts messages, this one included.",
+            examples: &[("wall the server restarts at noon", "Warn everyone of a restart.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let roster = self.0.roster();
+        let Some(sender) = own_session(ctx, &roster) else {
+            return CommandResult::error("wall: not in a session");
+        };
+        let Some(message) = message(ctx, args) else {
+            return usage_error("wall: missing message".to_string());
+        };
+        let text = format!("Broadcast message from {} on {}: {}", name(sender), sender.tty, message);
+        for presence in &roster {
+            presence.inbox.deliver(text.clone());
+        }
+        CommandResult::empty()
+    }
+}
+
+pub struct Mesg(Arc<SessionManager>);
+
+impl Command for Mesg {
+    fn name(&self) -> &'static str {
+        "mesg"
+    }
+
+    fn help(&self) -> &'static str {
+        "mesg [y|n]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "accept or refuse messages",
+            description: "With `n`, this session gets no more messages from `write` and `wall`; with `y`, \
+                it does again. Without an argument, prints whether it does, exiting with status 1 if not.",
+            examples: &[
+                ("mesg n", "Stop messages while you work."),
+                ("mesg", "Check whether messages get through."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let accepting = match args {
+            [] => None,
+            [answer] if answer == "y" => Some(true),
+            [answer] if answer == "n" => Some(false),
+            _ => return usage_error(format!("mesg: invalid argument '{}'", args.join(" "))),
+        };
+        let roster = self.0.roster();
+        let Some(presence) = own_session(ctx, &roster) else {
+            return CommandResult::error("mesg: not in a session");
+        };
+        match accepting {
+            Some(accepting) => {
+                presence.inbox.set_accepting(accepting);
+                CommandResult::empty()
+            }
+            None if presence.inbox.accepts() => CommandResult::ok("is y"),
+            None => CommandResult::ok("is n").with_exit_code(1),
+        }
+    }
+}
+
+pub struct Attach(Arc<SessionManager>);
+
+impl Command for Attach {
+    fn name(&self) -> &'static str {
+        "attach"
+    }
+
+    fn help(&self) -> &'static str {
+        "attach [name]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "name this session to come back to",
+            description: "Names this session `name`, so that you can get back to it later from another \
+                browser or device, with its directory, variables, jobs and what it showed so far: open the \
+                terminal with `?name=` and the name, or post `{\"name\": ...}` to `/api/session`. A name \
+                not in use there starts a new session under it.\n\nNames are letters, digits, `.`, `_` and \
+                `-`, and need authentication on. Without `name`, prints this session's name.",
+            examples: &[("attach work", "Name this session work."), ("attach", "Show this session's name.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let roster = self.0.roster();
+        let Some(Presence { id: session, owner, .. }) = own_session(ctx, &roster) else {
+            return CommandResult::error("attach: not in a session");
+        };
+        match args {
+            [] => match self.0.name_of(session) {
+                Some(name) => CommandResult::ok(name),
+                None => CommandResult::error("attach: this session has no name"),
+            },
+            [name] => {
+                let Some(owner) = owner else {
+                    return CommandResult::error("attach: naming sessions needs authentication");
+                };
+                if !valid_name(name) {
+                    return usage_error(format!("attach: invalid session name '{}'", name));
+                }
+                self.0.name(session, owner, name).map_err(|error| format!("attach: {}", error)).into()
+            }
+            _ => usage_error(format!("usage: {}", self.help())),
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Opt-in bridge from a WebSocket to a real shell on the host.
+//!
+//! This hands the client a genuine shell with the server's privileges, so it
+//! is only compiled with the `pty` feature and only routed when a shell is
+//! explicitly configured. Binary and plain text frames are forwarded as
+//! keyboard input; a text frame of the form `{"resize":{"cols":C,"rows":R}}`
+//! resizes the terminal. Output is sent back as binary frames.
+//!
+//! With authentication on, it takes a token like every other route. Without
+//! it, a page must come from the server itself or an allowed origin.
+
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+    routing::get,
+    Router,
+};
+use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::auth::User;
+
+#[derive(Deserialize)]
+struct ControlMessage {
+    resize: TerminalSize,
+}
+
+#[derive(Deserialize)]
+struct TerminalSize {
+    cols: u16,
+    rows: u16,
+}
+
+/// What the bridge needs: the shell to spawn, and the origins whose pages
+/// may open it without a token.
+struct Pty {
+    shell: String,
+    origins: Vec<HeaderValue>,
+}
+
+impl Pty {
+    /// Whether a handshake with these headers comes from a page allowed to
+    /// drive the shell: one served by this server or from a listed origin.
+    /// Browsers always send `Origin` here, so clients that leave it out are
+    /// not web pages.
+    fn trusts(&self, headers: &HeaderMap) -> bool {
+        let Some(origin) = headers.get(header::ORIGIN) else {
+            return true;
+        };
+        if self.origins.contains(origin) {
+            return true;
+        }
+        let host = headers.get(header::HOST).and_then(|host| host.to_str().ok());
+        let origin_host = origin
+            .to_str()
+            .ok()
+            .and_then(|origin| origin.split_once("://"))
+            .map(|(_, host)| host);
+        host.is_some() && origin_host == host
+    }
+}
+
+/// Routes `/ws/pty`. Without authentication, only pages from `origins`
+/// (`*` aside) or the server itself may open it, so a site the operator
+/// happens to visit cannot reach the host's shell.
+pub fn router(shell: String, origins: Vec<HeaderValue>) -> Router {
+    let origins = origins.into_iter().filter(|origin| origin != "*").collect();
+    Router::new()
+        .route("/ws/pty", get(pty_socket))
+        .with_state(Arc::new(Pty { shell, origins }))
+}
+
+async fn pty_socket(
+    ws: WebSocketUpgrade,
+    State(pty): State<Arc<Pty>>,
+    user: User,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    if user.0.is_none() && !pty.trusts(&headers) {
+        return Err((StatusCode::FORBIDDEN, "origin not allowed to open a host shell".to_string()));
+    }
+    Ok(ws.on_upgrade(move |socket| async move {
+        if let Err(message) = bridge(socket, &pty.shell).await {
+            tracing::warn!("pty session ended with error: {}", message);
+        }
+    }))
+}
+
+async fn bridge(mut socket: WebSocket, shell: &str) -> Result<(), String> {
+    let pair = native_pty_system()
+        .openpty(PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+        .map_err(|err| err.to_string())?;
+    let mut child = pair
+        .slave
+        .spawn_command(CommandBuilder::new(shell))
+        .map_err(|err| err.to_string())?;
+    drop(pair.slave);
+
+    let result = forward(&mut socket, pair.master).await;
+    // Reap the shell, off the async workers since waiting blocks.
+    tokio::task::spawn_blocking(move || {
+        let _ = child.kill();
+        let _ = child.wait();
+    });
+    result
+}
+
+/// Passes bytes between the socket and the pty until either side closes.
+async fn forward(socket: &mut WebSocket, master: Box<dyn MasterPty + Send>) -> Result<(), String> {
+    let mut reader = master.try_clone_reader().map_err(|err| err.to_string())?;
+    let mut writer = master.take_writer().map_err(|err| err.to_string())?;
+
+    // The pty reader and writer block, so each runs on its own thread behind a channel.
+    let (tx, mut rx) = mpsc::channel::<Vec<u8>>(64);
+    tokio::task::spawn_blocking(move || {
+        let mut buffer = [0u8; 4096];
+        while let Ok(read) = reader.read(&mut buffer) {
+            if read == 0 || tx.blocking_send(buffer[..read].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    let (keys, mut typed) = mpsc::channel::<Vec<u8>>(64);
+    tokio::task::spawn_blocking(move || {
+        while let Some(bytes) = typed.blocking_recv() {
+            if writer.write_all(&bytes).is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        let input = tokio::select! {
+            chunk = rx.recv() => match chunk {
+                Some(bytes) => {
+                    if socket.send(Message::Binary(bytes)).await.is_err() {
+                        return Ok(());
+                    }
+                    continue;
+                }
+                None => return Ok(()),
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Binary(bytes))) => bytes,
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<ControlMessage>(&text) {
+                    Ok(control) => {
+                        master
+                            .resize(PtySize {
+                                rows: control.resize.rows,
+                                cols: control.resize.cols,
+                                pixel_width: 0,
+                                pixel_height: 0,
+                            })
+                            .map_err(|err| err.to_string())?;
+                        continue;
+                    }
+                    Err(_) => text.into_bytes(),
+                },
+                Some(Ok(Message::Close(_))) | None | Some(Err(_)) => return Ok(()),
+                Some(Ok(_)) => continue,
+            },
+        };
+        if keys.send(input).await.is_err() {
+            return Err("pty closed for writing".to_string());
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Per-client request throttling, so one noisy client cannot starve the rest.
+//!
+//! Each IP address gets a token bucket holding a minute's worth of requests,
+//! refilled continuously. The address comes from axum's `ConnectInfo`, so the
+//! router must be served with `into_make_service_with_connect_info`; requests
+//! without one are not limited.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::sync::Mutex;
+
+use crate::fs_api::api_error;
+
+/// Tracked clients past which idle ones are forgotten.
+const MAX_TRACKED_CLIENTS: usize = 4096;
+
+pub(crate) struct RateLimiter {
+    per_minute: u32,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(per_minute: u32) -> Self {
+        RateLimiter {
+            per_minute: per_minute.max(1),
+            buckets: Mutex::default(),
+        }
+    }
+
+    /// Takes a token for `client`, or says how many seconds until one is free.
+    async fn acquire(&self, client: IpAddr) -> Result<(), u64> {
+        let capacity = f64::from(self.per_minute);
+        let per_second = capacity / 60.0;
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().await;
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            // A full bucket is the same as no bucket at all.
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.refilled).as_secs_f64() * per_second < capacity
+            });
+        }
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: capacity,
+            refilled: now,
+        });
+        bucket.tokens =
+            (bucket.tokens + now.duration_since(bucket.refilled).as_secs_f64() * per_second).min(capacity);
+        bucket.refilled = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / per_second).ceil() as u64)
+        }
+    }
+}
+
+/// Middleware answering `429 Too Many Requests` once a client has used up
+/// its allowance.
+pub(crate) async fn limit(State(limiter): State<Arc<RateLimiter>>, request: Request, next: Next) -> Response {
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip());
+    if let Some(client) = client
+        && let Err(retry_after) = limiter.acquire(client).await
+    {
+        let mut response = api_error(StatusCode::TOO_MANY_REQUESTS, "too many requests").into_response();
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after.max(1)));
+        return response;
+    }
+    next.run(request).await
+}
@@ -0,0 +1,78 @@
+//! Replays a recorded transcript of command lines in a fresh deterministic
+//! session and returns every response, so the same transcript always gives
+//! the same output: what auto-grading an exercise needs. Each command line
+//! goes the way one sent to `/api/command` does, so it is audited, counted
+//! in the usage statistics and held to the command timeout.
+
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use serde::{Deserialize, Serialize};
+use termweb_core::CommandResponse;
+
+use crate::auth::User;
+use crate::sessions::SessionManager;
+use crate::{dispatch, AppState};
+
+/// Command lines one replay may hold.
+const MAX_REPLAY_COMMANDS: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+struct ReplayRequest {
+    commands: Vec<String>,
+    /// Seeds the session's random values; by default the server's
+    /// deterministic seed, or 0.
+    #[serde(default)]
+    seed: Option<u64>,
+    /// Seconds since the epoch the session's clock starts at; by default the
+    /// server's fixed time, or 0.
+    #[serde(default)]
+    fixed_time: Option<u64>,
+    #[serde(default)]
+    color: bool,
+    #[serde(default)]
+    columns: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct ReplayResponse {
+    /// What the session's startup files printed.
+    greeting: String,
+    /// One for each command line, in order.
+    responses: Vec<CommandResponse>,
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new().route("/api/replay", post(replay))
+}
+
+async fn replay(
+    State(mut state): State<AppState>,
+    user: User,
+    Json(request): Json<ReplayRequest>,
+) -> Result<Json<ReplayResponse>, (StatusCode, String)> {
+    if request.commands.len() > MAX_REPLAY_COMMANDS {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("a replay may hold at most {} commands", MAX_REPLAY_COMMANDS),
+        ));
+    }
+    let deterministic = state.session_options.deterministic_with(request.seed, request.fixed_time);
+    // Startup files run synchronously; keep them off the async workers.
+    let starting = state.clone();
+    let (mut terminal, greeting) =
+        tokio::task::spawn_blocking(move || starting.new_terminal_with(Some(deterministic)))
+            .await
+            .expect("replay panicked");
+    terminal.color = request.color;
+    terminal.columns = request.columns.filter(|&columns| columns > 0);
+    let session = SessionManager::detached(terminal, &user);
+    // What a replay gives is graded against the virtual shell, even where
+    // other commands run in a container.
+    state.sandbox = None;
+    // Jobs never wait on a deterministic clock, so each has finished by the
+    // time the next command line runs.
+    let mut responses = Vec::with_capacity(request.commands.len());
+    for command in &request.commands {
+        responses.push(dispatch(&state, &session, command).await);
+    }
+    Ok(Json(ReplayResponse { greeting, responses }))
+}
@@ -0,0 +1,248 @@
+//! Container-backed sandboxes: instead of the virtual shell, each session's
+//! commands run inside a short-lived Docker/Podman container.
+//!
+//! Containers are started lazily on a session's first command, run with no
+//! network and capped memory/pids, and are removed by a reaper task once they
+//! have been idle for longer than the configured timeout.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use termweb_core::shell::strip_bells;
+use termweb_core::{CommandResponse, Notification};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+const CWD_MARKER: &str = "__TERMWEB_CWD__";
+/// How long a command may run when the server has no command timeout of its own.
+const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Runs the user's input with `eval`, then reports the resulting directory on a
+// marker line so the next command can start from it.
+const EXEC_SCRIPT: &str = r#"eval "$1"; status=$?; printf '\n%s%s' __TERMWEB_CWD__ "$(pwd)"; exit $status"#;
+
+#[derive(Debug, Clone)]
+pub struct SandboxConfig {
+    /// `docker` or `podman` (or anything with the same CLI).
+    pub runtime: String,
+    pub image: String,
+    pub idle_timeout: Duration,
+    pub memory_limit: String,
+}
+
+impl SandboxConfig {
+    pub fn new(image: impl Into<String>) -> Self {
+        SandboxConfig {
+            runtime: "docker".to_string(),
+            image: image.into(),
+            idle_timeout: Duration::from_secs(600),
+            memory_limit: "256m".to_string(),
+        }
+    }
+}
+
+struct Sandbox {
+    container: String,
+    cwd: String,
+    last_used: Instant,
+}
+
+pub struct SandboxManager {
+    config: SandboxConfig,
+    command_timeout: Duration,
+    /// Only held to look sandboxes up or change them, never while a
+    /// container starts or a command runs, so sessions don't wait on each other.
+    sandboxes: Mutex<HashMap<String, Sandbox>>,
+}
+
+impl SandboxManager {
+    /// Commands are killed after `command_timeout`, or 30 seconds if `None`.
+    pub fn new(config: SandboxConfig, command_timeout: Option<Duration>) -> Arc<Self> {
+        Arc::new(SandboxManager {
+            config,
+            command_timeout: command_timeout.unwrap_or(DEFAULT_COMMAND_TIMEOUT),
+            sandboxes: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Periodically removes containers that have been idle past the timeout.
+    pub fn spawn_reaper(self: &Arc<Self>) {
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(30));
+            loop {
+                interval.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                manager.reap_idle().await;
+            }
+        });
+    }
+
+    async fn reap_idle(&self) {
+        let expired: Vec<String> = {
+            let mut sandboxes = self.sandboxes.lock().await;
+            let timeout = self.config.idle_timeout;
+            let idle: Vec<String> = sandboxes
+                .iter()
+                .filter(|(_, sandbox)| sandbox.last_used.elapsed() > timeout)
+                .map(|(session, _)| session.clone())
+                .collect();
+            idle.iter()
+                .filter_map(|session| sandboxes.remove(session))
+                .map(|sandbox| sandbox.container)
+                .collect()
+        };
+        for container in expired {
+            tracing::info!("removing idle sandbox {}", container);
+            self.remove_container(&container).await;
+        }
+    }
+
+    pub async fn execute(&self, session: &str, input: &str) -> CommandResponse {
+        let (container, cwd) = match self.sandbox_for(session).await {
+            Ok(sandbox) => sandbox,
+            Err(message) => return response(message, "/".to_string(), 1),
+        };
+
+        if input.is_empty() {
+            return response(String::new(), cwd, 0);
+        }
+        if input == "clear" {
+            let mut cleared = response(String::new(), cwd, 0);
+            cleared.clear = true;
+            return cleared;
+        }
+
+        let mut exec = Command::new(&self.config.runtime);
+        exec.args(["exec", "-w", &cwd, &container, "sh", "-c", EXEC_SCRIPT, "sh", input])
+            .kill_on_drop(true);
+        let output = match tokio::time::timeout(self.command_timeout, exec.output()).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(err)) => return response(format!("sandbox: {}", err), cwd, 1),
+            // 124, as timeout(1) reports it.
+            Err(_) => return response("sandbox: command timed out".to_string(), cwd, 124),
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (text, cwd) = match stdout.rsplit_once(CWD_MARKER) {
+            Some((text, new_cwd)) if !new_cwd.trim().is_empty() => {
+                (text.strip_suffix('\n').unwrap_or(text), new_cwd.trim().to_string())
+            }
+            Some((text, _)) => (text.strip_suffix('\n').unwrap_or(text), cwd),
+            None => (stdout.as_ref(), cwd),
+        };
+        if let Some(sandbox) = self.sandboxes.lock().await.get_mut(session) {
+            sandbox.cwd = cwd.clone();
+        }
+        let mut text = text.to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.is_empty() {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str(&stderr);
+        }
+        // A shell killed by a signal has no code of its own.
+        let exit_code = output.status.code().unwrap_or(1);
+        response(text.trim_end_matches('\n').to_string(), cwd, exit_code)
+    }
+
+    /// The container and directory of `session`'s sandbox, starting one
+    /// if it has none yet.
+    async fn sandbox_for(&self, session: &str) -> Result<(String, String), String> {
+        if let Some(sandbox) = self.sandboxes.lock().await.get_mut(session) {
+            sandbox.last_used = Instant::now();
+            return Ok((sandbox.container.clone(), sandbox.cwd.clone()));
+        }
+        let container = self.create_container().await?;
+        let mut sandboxes = self.sandboxes.lock().await;
+        // Another command may have started one meanwhile; keep whichever came first.
+        if let Some(sandbox) = sandboxes.get(session) {
+            let kept = (sandbox.container.clone(), sandbox.cwd.clone());
+            drop(sandboxes);
+            self.remove_container(&container).await;
+            return Ok(kept);
+        }
+        let cwd = "/".to_string();
+        sandboxes.insert(
+            session.to_string(),
+            Sandbox {
+                container: container.clone(),
+                cwd: cwd.clone(),
+                last_used: Instant::now(),
+            },
+        );
+        Ok((container, cwd))
+    }
+
+    async fn create_container(&self) -> Result<String, String> {
+        let name = format!(
+            "termweb-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos())
+                .unwrap_or_default()
+        );
+        let output = Command::new(&self.config.runtime)
+            .args([
+                "run",
+                "-d",
+                "--rm",
+                "--network",
+                "none",
+                "--memory",
+                &self.config.memory_limit,
+                "--pids-limit",
+                "128",
+                "--name",
+                &name,
+                &self.config.image,
+                "sleep",
+                "infinity",
+            ])
+            .output()
+            .await
+            .map_err(|err| format!("sandbox: failed to start {}: {}", self.config.runtime, err))?;
+        if !output.status.success() {
+            return Err(format!(
+                "sandbox: failed to create container: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        tracing::info!("created sandbox {}", name);
+        Ok(name)
+    }
+
+    async fn remove_container(&self, container: &str) {
+        let result = Command::new(&self.config.runtime)
+            .args(["rm", "-f", container])
+            .output()
+            .await;
+        if let Err(err) = result {
+            tracing::warn!("failed to remove sandbox {}: {}", container, err);
+        }
+    }
+}
+
+fn response(mut output: String, cwd: String, exit_code: i32) -> CommandResponse {
+    let mut notifications = Vec::new();
+    if strip_bells(&mut output) {
+        notifications.push(Notification::Bell);
+    }
+    CommandResponse {
+        output,
+        cwd,
+        status: if exit_code == 0 { "ok" } else { "error" }.to_string(),
+        exit_code,
+        error_code: None,
+        clear: false,
+        notifications,
+        mode: None,
+        editor: None,
+        truncated: false,
+        continuation: None,
+    }
+}
@@ -0,0 +1,155 @@
+//! Files every new session starts with, read once at startup so demos and
+//! tutorials need no setup.
+//!
+//! A directory is copied as if it were the root: `seed/home/user/notes.txt`
+//! becomes `/home/user/notes.txt`. A manifest is a JSON or TOML file listing
+//! entries, with relative paths taken from the home directory:
+//!
+//! ```toml
+//! [[files]]
+//! path = "notes.txt"
+//! content = "Welcome!\n"
+//!
+//! [[files]]
+//! path = "bin/hello"
+//! content = "echo hello\n"
+//! mode = "755"
+//!
+//! # Neither content nor target: a directory.
+//! [[files]]
+//! path = "/srv/data"
+//!
+//! [[files]]
+//! path = "latest"
+//! target = "notes.txt"
+//! ```
+
+use std::path::Path;
+
+use serde::Deserialize;
+use termweb_core::archive::make_dirs;
+use termweb_core::path::resolve_path;
+use termweb_core::state::HOME_DIR;
+use termweb_core::{FileSystem, FsError, Node};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default)]
+    files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestEntry {
+    path: String,
+    /// A file's text.
+    content: Option<String>,
+    /// Where a symlink points.
+    target: Option<String>,
+    /// Octal permissions, e.g. `"755"`.
+    mode: Option<String>,
+}
+
+/// Adds the files at `source`, a directory or a `.json` or `.toml` manifest,
+/// to `fs`. Returns how many entries were added.
+pub(crate) fn load(source: &Path, fs: &mut FileSystem) -> Result<usize, String> {
+    if source.is_dir() {
+        return copy_dir(source, &[], fs);
+    }
+    let text = std::fs::read_to_string(source).map_err(|error| error.to_string())?;
+    let manifest: Manifest = match source.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => serde_json::from_str(&text).map_err(|error| error.to_string())?,
+        Some("toml") => toml::from_str(&text).map_err(|error| error.to_string())?,
+        _ => return Err("expected a directory, or a manifest ending in .json or .toml".to_string()),
+    };
+    let home = resolve_path(&[], HOME_DIR);
+    for entry in &manifest.files {
+        add_entry(fs, &home, entry).map_err(|error| format!("{}: {}", entry.path, error))?;
+    }
+    Ok(manifest.files.len())
+}
+
+fn add_entry(fs: &mut FileSystem, home: &[String], entry: &ManifestEntry) -> Result<(), String> {
+    let path = resolve_path(home, &entry.path);
+    if path.is_empty() {
+        return Err("cannot replace the root directory".to_string());
+    }
+    let mode = entry
+        .mode
+        .as_deref()
+        .map(|mode| u32::from_str_radix(mode, 8).map_err(|_| format!("invalid mode '{}'", mode)))
+        .transpose()?;
+    match (&entry.content, &entry.target) {
+        (Some(_), Some(_)) => return Err("has both content and a target".to_string()),
+        (Some(content), None) => {
+            make_dirs(fs, &path[..path.len() - 1]).map_err(fs_error)?;
+            fs.write_file(&path, content.clone(), false).map_err(fs_error)?;
+        }
+        (None, Some(target)) => {
+            make_dirs(fs, &path[..path.len() - 1]).map_err(fs_error)?;
+            fs.symlink(target, &path).map_err(fs_error)?;
+        }
+        (None, None) => make_dirs(fs, &path).map_err(fs_error)?,
+    }
+    if let Some(mode) = mode {
+        fs.set_mode(&path, mode & 0o777).map_err(fs_error)?;
+    }
+    Ok(())
+}
+
+/// Copies the real directory `dir` to `dest`, returning how many entries
+/// it held.
+fn copy_dir(dir: &Path, dest: &[String], fs: &mut FileSystem) -> Result<usize, String> {
+    let read_error = |path: &Path, error: std::io::Error| format!("{}: {}", path.display(), error);
+    let mut entries = std::fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+        .map_err(|error| read_error(dir, error))?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut count = 0;
+    for entry in entries {
+        let source = entry.path();
+        let mut path = dest.to_vec();
+        path.push(entry.file_name().to_string_lossy().into_owned());
+        let metadata = std::fs::symlink_metadata(&source).map_err(|error| read_error(&source, error))?;
+        let fail = |error: FsError| format!("{}: {}", source.display(), error);
+        if metadata.is_symlink() {
+            let target = std::fs::read_link(&source).map_err(|error| read_error(&source, error))?;
+            if fs.get_node_nofollow(&path).is_some() {
+                fs.remove(&path, true).map_err(fail)?;
+            }
+            fs.symlink(&target.to_string_lossy(), &path).map_err(fail)?;
+        } else if metadata.is_dir() {
+            // The home directory and others a session starts with already exist.
+            if !matches!(fs.get_node(&path), Some(Node::Dir { .. })) {
+                fs.mkdir(&path).map_err(fail)?;
+            }
+            count += copy_dir(&source, &path, fs)?;
+        } else {
+            let content = std::fs::read(&source).map_err(|error| read_error(&source, error))?;
+            fs.write_bytes(&path, content, false).map_err(fail)?;
+        }
+        // Symlinks keep the usual `rwxrwxrwx`.
+        if let Some(mode) = unix_mode(&metadata).filter(|_| !metadata.is_symlink()) {
+            fs.set_mode(&path, mode).map_err(fail)?;
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(unix)]
+fn unix_mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn unix_mode(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+fn fs_error(error: FsError) -> String {
+    error.to_string()
+}
@@ -0,0 +1,803 @@
+//! Named virtual shell sessions, so separate clients (or browser tabs) keep
+//! separate working directories and filesystems.
+//!
+//! A client creates a session with `POST /api/session` and names it on later
+//! requests with the `X-Session-Id` header, a `session` query parameter, or,
+//! for command submissions, a `session` body field. With authentication on,
+//! a session belongs to the user who created it and is unknown to others.
+//!
+//! Each user may keep [`MAX_SESSIONS_PER_USER`] sessions, and everyone
+//! together [`MAX_SESSIONS`]. Past that, with a state file configured, a new
+//! session makes room by parking the caller's own least recently used idle
+//! one on disk, from where it comes back when next named; without one, it
+//! is refused.
+//!
+//! With a total memory limit, the least recently used idle sessions are
+//! parked, whoever they belong to, whenever all sessions together hold more
+//! than that; without a state file, new sessions are refused instead.
+//!
+//! Every session started and ended is kept as a [`Login`], for `last`, in
+//! the state file when there is one.
+//!
+//! With authentication on, a user may name a session with `attach <name>`
+//! and get back to it from anywhere by posting `{"name": ...}` to
+//! `/api/session`, or connecting to `/ws/terminal?name=...`, which start one
+//! under that name if there is none. What it showed so far comes back as
+//! its scrollback.
+//!
+//! Each session has a terminal name, `pts/N`, that names it to other users
+//! without giving away its id, and an [`Inbox`] that `write` and `wall`
+//! leave messages in for its client, which gets them as notifications.
+//!
+//! `GET /api/session/{id}/cast` returns what the session has run so far as
+//! an asciinema recording.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path, Query, State},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use termweb_core::TerminalState;
+use tokio::sync::{Mutex, Notify};
+
+use crate::auth::User;
+use crate::persistence::{SavedSession, Store};
+use crate::transcript::Transcript;
+use crate::AppState;
+
+pub(crate) const HEADER: &str = "x-session-id";
+/// Sessions kept in memory at once, and the most anonymous users, who are
+/// all one owner, may keep between them.
+const MAX_SESSIONS: usize = 1024;
+/// Sessions one signed-in user may keep in memory.
+const MAX_SESSIONS_PER_USER: usize = 64;
+/// Columns a recording claims when the client never said how wide it is.
+const CAST_WIDTH: usize = 80;
+/// Why a command line was turned away from a [busy](Session::busy) session.
+pub(crate) const BUSY_MESSAGE: &str = "session is busy stopping a command that timed out";
+/// Longest session name; see [`valid_name`].
+const MAX_NAME_LEN: usize = 64;
+/// [`Login`]s kept, the oldest dropped past it.
+const MAX_LOGINS: usize = 1000;
+/// Messages an [`Inbox`] keeps for a client that is not picking them up;
+/// past that the oldest are dropped.
+const MAX_PENDING_MESSAGES: usize = 64;
+
+/// A handle on one live session.
+#[derive(Clone)]
+pub(crate) struct Session {
+    pub(crate) id: String,
+    /// The user who created it, when authentication is on.
+    pub(crate) owner: Option<String>,
+    pub(crate) terminal: Arc<Mutex<TerminalState>>,
+    pub(crate) transcript: Arc<std::sync::Mutex<Transcript>>,
+    /// Set when a command line ran past the command timeout and was asked to
+    /// stop; see [`Session::busy`].
+    pub(crate) stopping: Arc<AtomicBool>,
+    pub(crate) inbox: Arc<Inbox>,
+    /// Its terminal name, e.g. `pts/3`.
+    pub(crate) tty: String,
+}
+
+impl Session {
+    /// Whether a command line that timed out still holds the terminal. New
+    /// ones are turned away meanwhile, rather than left waiting behind it.
+    pub(crate) fn busy(&self) -> bool {
+        self.stopping.load(Ordering::Relaxed) && self.terminal.try_lock().is_err()
+    }
+
+    /// Fails with `409 Conflict` while the session is [busy](Session::busy).
+    pub(crate) fn ensure_idle(&self) -> Result<(), (StatusCode, String)> {
+        if self.busy() {
+            Err((StatusCode::CONFLICT, BUSY_MESSAGE.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// What the session has run so far, to record more in.
+    pub(crate) fn transcript(&self) -> std::sync::MutexGuard<'_, Transcript> {
+        self.transcript.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Messages left for a session by `write` and `wall`, until its client
+/// picks them up.
+#[derive(Default)]
+pub(crate) struct Inbox {
+    pending: std::sync::Mutex<Vec<String>>,
+    arrived: Notify,
+    /// Set by `mesg n`.
+    refusing: AtomicBool,
+}
+
+impl Inbox {
+    pub(crate) fn accepts(&self) -> bool {
+        !self.refusing.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_accepting(&self, accepting: bool) {
+        self.refusing.store(!accepting, Ordering::Relaxed);
+    }
+
+    /// Leaves `message`, unless the session refuses messages. Whether it did.
+    pub(crate) fn deliver(&self, message: String) -> bool {
+        if !self.accepts() {
+            return false;
+        }
+        let mut pending = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if pending.len() >= MAX_PENDING_MESSAGES {
+            pending.remove(0);
+        }
+        pending.push(message);
+        drop(pending);
+        self.arrived.notify_one();
+        true
+    }
+
+    /// The messages left since last taken, oldest first.
+    pub(crate) fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+
+    /// Waits until a message is left, or returns at once if one was left
+    /// since the last wait.
+    pub(crate) async fn wait(&self) {
+        self.arrived.notified().await;
+    }
+}
+
+/// A session as `write`, `wall` and `who` see it; see [`SessionManager::roster`].
+pub(crate) struct Presence {
+    pub(crate) id: String,
+    pub(crate) owner: Option<String>,
+    /// Its terminal name, e.g. `pts/3`.
+    pub(crate) tty: String,
+    /// When it was created or last brought back, in milliseconds since the epoch.
+    pub(crate) started: u64,
+    pub(crate) last_used: Instant,
+    pub(crate) inbox: Arc<Inbox>,
+}
+
+/// One session's span, as `last` lists it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Login {
+    /// Only to find the record again when the session ends; never shown.
+    pub(crate) session: String,
+    pub(crate) user: Option<String>,
+    pub(crate) tty: String,
+    /// Milliseconds since the epoch.
+    pub(crate) start: u64,
+    /// When it was deleted, or `None` while it lasts.
+    #[serde(default)]
+    pub(crate) end: Option<u64>,
+}
+
+/// A session a user named, to [attach](SessionManager::attach) to by name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SessionName {
+    pub(crate) owner: String,
+    pub(crate) name: String,
+    pub(crate) session: String,
+}
+
+/// Whether `name` may name a session: letters, digits, `.`, `_` and `-`.
+pub(crate) fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+#[derive(Default)]
+pub(crate) struct SessionManager {
+    sessions: Mutex<HashMap<String, Entry>>,
+    /// Where sessions go to make room, when there is a state file.
+    store: Option<Store>,
+    /// Bytes all sessions in memory may hold together; see [`Entry::footprint`].
+    memory_limit: Option<usize>,
+    /// The number in the next admitted session's terminal name.
+    next_tty: AtomicUsize,
+    /// Sessions started, oldest first.
+    logins: std::sync::Mutex<VecDeque<Login>>,
+    /// Session ids by owner and name, for sessions in memory or parked.
+    names: std::sync::Mutex<HashMap<(String, String), String>>,
+}
+
+struct Entry {
+    owner: Option<String>,
+    terminal: Arc<Mutex<TerminalState>>,
+    transcript: Arc<std::sync::Mutex<Transcript>>,
+    stopping: Arc<AtomicBool>,
+    inbox: Arc<Inbox>,
+    /// The number in its terminal name, given when it is admitted.
+    tty: usize,
+    started: u64,
+    last_used: Instant,
+    /// Bytes the session held when last measured.
+    footprint: usize,
+}
+
+impl Entry {
+    fn new(id: &str, owner: Option<String>, mut terminal: TerminalState) -> Self {
+        terminal.fs.set_actor(owner.clone(), Some(id.to_string()));
+        Entry {
+            owner,
+            transcript: Arc::new(std::sync::Mutex::new(Transcript::new(terminal.cwd_string()))),
+            terminal: Arc::new(Mutex::new(terminal)),
+            stopping: Arc::default(),
+            inbox: Arc::default(),
+            tty: 0,
+            started: crate::audit::now_millis(),
+            last_used: Instant::now(),
+            footprint: 0,
+        }
+    }
+
+    /// Roughly the bytes the session holds: its terminal's files and history
+    /// and its transcript. A command running in it has the terminal, so then
+    /// it is as last measured.
+    fn footprint(&mut self) -> usize {
+        if let Ok(terminal) = self.terminal.try_lock() {
+            let transcript = self.transcript.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).bytes();
+            self.footprint = terminal.memory_usage() + transcript;
+        }
+        self.footprint
+    }
+
+    fn session(&self, id: &str) -> Session {
+        Session {
+            id: id.to_string(),
+            owner: self.owner.clone(),
+            terminal: self.terminal.clone(),
+            transcript: self.transcript.clone(),
+            stopping: self.stopping.clone(),
+            inbox: self.inbox.clone(),
+            tty: self.tty_name(),
+        }
+    }
+
+    fn tty_name(&self) -> String {
+        format!("pts/{}", self.tty)
+    }
+}
+
+/// How many sessions `owner` may keep in memory.
+fn owner_cap(owner: &Option<String>) -> usize {
+    match owner {
+        Some(_) => MAX_SESSIONS_PER_USER,
+        None => MAX_SESSIONS,
+    }
+}
+
+/// The session id a request names, if any, and who is asking; see
+/// [`SessionManager::resolve`].
+pub(crate) struct SessionId {
+    pub(crate) id: Option<String>,
+    pub(crate) user: User,
+}
+
+impl SessionId {
+    /// Falls back to `id`, e.g. one given in the request body.
+    pub(crate) fn or(mut self, id: Option<String>) -> Self {
+        self.id = self.id.or(id);
+        self
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionQuery {
+    session: Option<String>,
+}
+
+/// Options for a new session, all optional.
+#[derive(Debug, Deserialize)]
+struct NewSession {
+    /// Makes the session deterministic, its random values seeded with this;
+    /// see `TerminalState::make_deterministic`.
+    seed: Option<u64>,
+    /// Makes the session deterministic, its clock starting at these seconds
+    /// since the epoch.
+    fixed_time: Option<u64>,
+    /// Reattaches to the caller's session of this name, or starts one under
+    /// it; see [`open`].
+    name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SessionCreated {
+    id: String,
+    cwd: String,
+    /// What the session's startup files printed.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    greeting: String,
+    /// What a named session showed before its client reattached.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    scrollback: String,
+}
+
+/// A session a client [opened](open), and what to show first.
+pub(crate) struct Opened {
+    pub(crate) session: Session,
+    /// What a new session's startup files printed.
+    pub(crate) greeting: String,
+    /// What a named session showed so far, when it was there already.
+    pub(crate) scrollback: Option<String>,
+}
+
+/// The session `user` named `name`, brought back if parked, or else a new
+/// one, made deterministic with `(seed, epoch_millis)` if given and named
+/// `name` if given.
+pub(crate) async fn open(
+    state: &AppState,
+    user: &User,
+    name: Option<&str>,
+    deterministic: Option<(u64, u64)>,
+) -> Result<Opened, (StatusCode, String)> {
+    if let Some(name) = name
+        && let Some(session) = state.sessions.find_named(user, name).await?
+    {
+        let scrollback = session.transcript().scrollback();
+        return Ok(Opened {
+            session,
+            greeting: String::new(),
+            scrollback: Some(scrollback),
+        });
+    }
+    // Startup files run synchronously; keep them off the async workers.
+    let starting = state.clone();
+    let (terminal, greeting) = tokio::task::spawn_blocking(move || starting.new_terminal_with(deterministic))
+        .await
+        .expect("session startup panicked");
+    let session = state.sessions.create(terminal, user).await?;
+    if let (Some(name), Some(owner)) = (name, &user.0)
+        && let Err(error) = state.sessions.name(&session.id, owner, name)
+    {
+        // Someone else started one under the name meanwhile.
+        state.sessions.delete(&session.id, user).await;
+        return Err((StatusCode::CONFLICT, error));
+    }
+    Ok(Opened {
+        session,
+        greeting,
+        scrollback: None,
+    })
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/session", post(create_session))
+        .route("/api/session/:id", delete(delete_session))
+        .route("/api/session/:id/cast", get(cast))
+}
+
+impl SessionManager {
+    /// Starts out holding previously saved sessions under their old ids and
+    /// owners, and the logins and names saved with them, parking sessions in
+    /// `store` to make room.
+    pub(crate) fn restore(
+        sessions: impl IntoIterator<Item = (String, Option<String>, TerminalState)>,
+        logins: Vec<Login>,
+        names: Vec<SessionName>,
+        store: Store,
+    ) -> Self {
+        let sessions: HashMap<_, _> = sessions
+            .into_iter()
+            .enumerate()
+            .map(|(tty, (id, owner, terminal))| {
+                let entry = Entry {
+                    tty,
+                    ..Entry::new(&id, owner, terminal)
+                };
+                (id, entry)
+            })
+            .collect();
+        SessionManager {
+            next_tty: AtomicUsize::new(sessions.len()),
+            sessions: Mutex::new(sessions),
+            store: Some(store),
+            memory_limit: None,
+            logins: std::sync::Mutex::new(logins.into()),
+            names: std::sync::Mutex::new(
+                names
+                    .into_iter()
+                    .map(|named| ((named.owner, named.name), named.session))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Caps the bytes all sessions in memory may hold together.
+    pub(crate) fn limit_memory(&mut self, bytes: Option<usize>) {
+        self.memory_limit = bytes;
+    }
+
+    pub(crate) fn memory_limit(&self) -> Option<usize> {
+        self.memory_limit
+    }
+
+    /// Roughly the bytes all sessions in memory hold together.
+    pub(crate) async fn memory_usage(&self) -> usize {
+        self.sessions.lock().await.values_mut().map(Entry::footprint).sum()
+    }
+
+    /// A session for `terminal` that is never registered, so no request can
+    /// name it: for running commands through the usual path, as a replay does.
+    pub(crate) fn detached(terminal: TerminalState, owner: &User) -> Session {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        Entry::new(&id, owner.0.clone(), terminal).session(&id)
+    }
+
+    pub(crate) async fn create(
+        &self,
+        terminal: TerminalState,
+        owner: &User,
+    ) -> Result<Session, (StatusCode, String)> {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let entry = Entry::new(&id, owner.0.clone(), terminal);
+        let session = self.admit(id, entry).await?;
+        self.record_login(&session);
+        self.enforce_memory_limit(&session.id).await;
+        Ok(session)
+    }
+
+    /// Adds `entry` as session `id`, unless it is there already, first
+    /// parking its owner's coldest idle sessions while they are at a cap.
+    async fn admit(&self, id: String, mut entry: Entry) -> Result<Session, (StatusCode, String)> {
+        loop {
+            let mut sessions = self.sessions.lock().await;
+            if let Some(existing) = sessions.get(&id) {
+                return Ok(existing.session(&id));
+            }
+            let owned = sessions.values().filter(|other| other.owner == entry.owner).count();
+            let refusal = if owned >= owner_cap(&entry.owner) {
+                let message = "too many sessions; delete one with DELETE /api/session/{id}";
+                (StatusCode::TOO_MANY_REQUESTS, message.to_string())
+            } else if sessions.len() >= MAX_SESSIONS {
+                (StatusCode::SERVICE_UNAVAILABLE, "no room for more sessions".to_string())
+            } else if self.store.is_none()
+                && self
+                    .memory_limit
+                    .is_some_and(|limit| sessions.values_mut().map(Entry::footprint).sum::<usize>() > limit)
+            {
+                (StatusCode::SERVICE_UNAVAILABLE, "out of memory for more sessions".to_string())
+            } else {
+                entry.tty = self.next_tty.fetch_add(1, Ordering::Relaxed);
+                let session = entry.session(&id);
+                sessions.insert(id, entry);
+                return Ok(session);
+            };
+            // Only the owner's own sessions make way, and only once they are saved.
+            let Some(store) = &self.store else {
+                return Err(refusal);
+            };
+            let coldest = sessions
+                .iter()
+                .filter(|(_, other)| other.owner == entry.owner && other.terminal.try_lock().is_ok())
+                .min_by_key(|(_, other)| other.last_used)
+                .map(|(id, _)| id.clone());
+            let Some((victim, parked)) = coldest.and_then(|id| sessions.remove_entry(&id)) else {
+                return Err(refusal);
+            };
+            drop(sessions);
+            if !self.park(store, victim, parked).await {
+                return Err(refusal);
+            }
+        }
+    }
+
+    /// Parks the least recently used idle sessions other than `keep` while
+    /// all sessions together hold more than the memory limit.
+    pub(crate) async fn enforce_memory_limit(&self, keep: &str) {
+        let (Some(limit), Some(store)) = (self.memory_limit, &self.store) else {
+            return;
+        };
+        loop {
+            let mut sessions = self.sessions.lock().await;
+            if sessions.values_mut().map(Entry::footprint).sum::<usize>() <= limit {
+                return;
+            }
+            let coldest = sessions
+                .iter()
+                .filter(|(id, entry)| *id != keep && entry.terminal.try_lock().is_ok())
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(id, _)| id.clone());
+            let Some((victim, parked)) = coldest.and_then(|id| sessions.remove_entry(&id)) else {
+                return;
+            };
+            drop(sessions);
+            if !self.park(store, victim, parked).await {
+                return;
+            }
+        }
+    }
+
+    /// Saves session `id`, already taken out of memory, to `store`, putting
+    /// it back if that fails. Whether it was parked.
+    async fn park(&self, store: &Store, id: String, entry: Entry) -> bool {
+        let saved = SavedSession::of(entry.owner.clone(), &*entry.terminal.lock().await);
+        if let Err(error) = store.park(&id, saved).await {
+            tracing::warn!("failed to park session {}: {}", id, error);
+            self.sessions.lock().await.insert(id, entry);
+            return false;
+        }
+        tracing::info!("parked idle session {}", id);
+        true
+    }
+
+    /// Looks up the session named by a request, marking it as recently used
+    /// and bringing it back if it was parked. Another user's session is
+    /// reported as unknown.
+    pub(crate) async fn resolve(&self, request: &SessionId) -> Result<Session, (StatusCode, String)> {
+        let Some(id) = request.id.as_deref() else {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "missing session id; create one with POST /api/session".to_string(),
+            ));
+        };
+        let unknown = || (StatusCode::NOT_FOUND, format!("unknown session '{}'", id));
+        {
+            let mut sessions = self.sessions.lock().await;
+            if let Some(entry) = sessions.get_mut(id) {
+                if entry.owner != request.user.0 {
+                    return Err(unknown());
+                }
+                entry.last_used = Instant::now();
+                return Ok(entry.session(id));
+            }
+        }
+        let store = self.store.as_ref().ok_or_else(unknown)?;
+        let terminal = store.unpark(id, &request.user.0).await.ok_or_else(unknown)?;
+        let session = self.admit(id.to_string(), Entry::new(id, request.user.0.clone(), terminal)).await?;
+        store.discard(id).await;
+        self.enforce_memory_limit(id).await;
+        Ok(session)
+    }
+
+    /// Ends session `id` if it belongs to `user`, wherever it is kept.
+    async fn delete(&self, id: &str, user: &User) -> bool {
+        let deleted = 'deleted: {
+            {
+                let mut sessions = self.sessions.lock().await;
+                if let Some(entry) = sessions.get(id) {
+                    let owned = entry.owner == user.0;
+                    if owned {
+                        sessions.remove(id);
+                    }
+                    break 'deleted owned;
+                }
+            }
+            let Some(store) = &self.store else {
+                return false;
+            };
+            let parked = store.unpark(id, &user.0).await.is_some();
+            if parked {
+                store.discard(id).await;
+            }
+            parked
+        };
+        if deleted {
+            let mut logins = self.logins.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if let Some(login) = logins.iter_mut().rev().find(|login| login.session == id) {
+                login.end.get_or_insert_with(crate::audit::now_millis);
+            }
+            drop(logins);
+            self.forget_name(id);
+        }
+        deleted
+    }
+
+    /// Names session `id`, which belongs to `owner`, dropping any name it
+    /// had. Fails if another of the owner's sessions has the name.
+    pub(crate) fn name(&self, id: &str, owner: &str, name: &str) -> Result<(), String> {
+        let mut names = self.names.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let key = (owner.to_string(), name.to_string());
+        match names.get(&key) {
+            Some(other) if other != id => return Err(format!("'{}' names another session", name)),
+            _ => {}
+        }
+        names.retain(|_, session| session != id);
+        names.insert(key, id.to_string());
+        Ok(())
+    }
+
+    /// What session `id` is named, if anything.
+    pub(crate) fn name_of(&self, id: &str) -> Option<String> {
+        let names = self.names.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        names.iter().find(|(_, session)| *session == id).map(|((_, name), _)| name.clone())
+    }
+
+    fn forget_name(&self, id: &str) {
+        let mut names = self.names.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        names.retain(|_, session| session != id);
+    }
+
+    /// Every session name, for persistence.
+    pub(crate) fn names(&self) -> Vec<SessionName> {
+        let names = self.names.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut names: Vec<SessionName> = names
+            .iter()
+            .map(|((owner, name), session)| SessionName {
+                owner: owner.clone(),
+                name: name.clone(),
+                session: session.clone(),
+            })
+            .collect();
+        names.sort_by(|a, b| (&a.owner, &a.name).cmp(&(&b.owner, &b.name)));
+        names
+    }
+
+    /// The session `owner` named `name`, brought back if parked, if there
+    /// is one. Only signed-in users name sessions.
+    pub(crate) async fn find_named(
+        &self,
+        owner: &User,
+        name: &str,
+    ) -> Result<Option<Session>, (StatusCode, String)> {
+        let Some(user) = owner.0.as_deref() else {
+            // Anyone could claim a name when everybody is anonymous.
+            return Err((StatusCode::BAD_REQUEST, "naming sessions needs authentication".to_string()));
+        };
+        if !valid_name(name) {
+            return Err((StatusCode::BAD_REQUEST, format!("invalid session name '{}'", name)));
+        }
+        let named = {
+            let names = self.names.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            names.get(&(user.to_string(), name.to_string())).cloned()
+        };
+        let Some(id) = named else {
+            return Ok(None);
+        };
+        let request = SessionId {
+            id: Some(id.clone()),
+            user: owner.clone(),
+        };
+        match self.resolve(&request).await {
+            Ok(session) => Ok(Some(session)),
+            // Lost, e.g. with the parked copy removed; it can be started afresh.
+            Err((StatusCode::NOT_FOUND, _)) => {
+                self.forget_name(&id);
+                Ok(None)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    fn record_login(&self, session: &Session) {
+        let mut logins = self.logins.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if logins.len() >= MAX_LOGINS {
+            logins.pop_front();
+        }
+        logins.push_back(Login {
+            session: session.id.clone(),
+            user: session.owner.clone(),
+            tty: session.tty.clone(),
+            start: crate::audit::now_millis(),
+            end: None,
+        });
+    }
+
+    /// Every session started, oldest first.
+    pub(crate) fn logins(&self) -> Vec<Login> {
+        let logins = self.logins.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        logins.iter().cloned().collect()
+    }
+
+    /// Marks session `id` as just used, as a command line sent over a
+    /// connection that named it once does.
+    pub(crate) async fn touch(&self, id: &str) {
+        if let Some(entry) = self.sessions.lock().await.get_mut(id) {
+            entry.last_used = Instant::now();
+        }
+    }
+
+    /// Every session in memory, for commands that reach other sessions.
+    /// Blocks, so only call it from a blocking task, as commands run in.
+    pub(crate) fn roster(&self) -> Vec<Presence> {
+        let sessions = self.sessions.blocking_lock();
+        sessions
+            .iter()
+            .map(|(id, entry)| Presence {
+                id: id.clone(),
+                owner: entry.owner.clone(),
+                tty: entry.tty_name(),
+                started: entry.started,
+                last_used: entry.last_used,
+                inbox: entry.inbox.clone(),
+            })
+            .collect()
+    }
+
+    /// Every live session, for aggregate reporting and persistence.
+    pub(crate) async fn all(&self) -> Vec<Session> {
+        let sessions = self.sessions.lock().await;
+        sessions.iter().map(|(id, entry)| entry.session(id)).collect()
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for SessionId {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let header = parts
+            .headers
+            .get(HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let query = Query::<SessionQuery>::try_from_uri(&parts.uri)
+            .ok()
+            .and_then(|Query(query)| query.session);
+        let Ok(user) = User::from_request_parts(parts, state).await;
+        Ok(SessionId {
+            id: header.or(query),
+            user,
+        })
+    }
+}
+
+async fn create_session(
+    State(state): State<AppState>,
+    user: User,
+    request: Option<Json<NewSession>>,
+) -> Result<(StatusCode, Json<SessionCreated>), (StatusCode, String)> {
+    let request = request.map(|Json(request)| request);
+    let deterministic = request
+        .as_ref()
+        .filter(|request| request.seed.is_some() || request.fixed_time.is_some())
+        .map(|request| state.session_options.deterministic_with(request.seed, request.fixed_time));
+    let name = request.and_then(|request| request.name);
+    let opened = open(&state, &user, name.as_deref(), deterministic).await?;
+    let cwd = opened.session.terminal.lock().await.cwd_string();
+    let status = match opened.scrollback {
+        Some(_) => StatusCode::OK,
+        None => StatusCode::CREATED,
+    };
+    Ok((
+        status,
+        Json(SessionCreated {
+            id: opened.session.id,
+            cwd,
+            greeting: opened.greeting,
+            scrollback: opened.scrollback.unwrap_or_default(),
+        }),
+    ))
+}
+
+async fn delete_session(State(state): State<AppState>, user: User, Path(id): Path<String>) -> StatusCode {
+    if state.sessions.delete(&id, &user).await {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// The session's transcript as an asciinema v2 recording, for `asciinema
+/// play` or the asciinema web player.
+async fn cast(
+    State(state): State<AppState>,
+    user: User,
+    Path(id): Path<String>,
+) -> Result<Response, StatusCode> {
+    let (terminal, transcript) = {
+        let sessions = state.sessions.sessions.lock().await;
+        let entry = sessions.get(&id).filter(|entry| entry.owner == user.0).ok_or(StatusCode::NOT_FOUND)?;
+        (entry.terminal.clone(), entry.transcript.clone())
+    };
+    // A command still running holds the terminal; don't wait on it for the width.
+    let width = terminal
+        .try_lock()
+        .ok()
+        .and_then(|terminal| terminal.columns)
+        .unwrap_or(CAST_WIDTH);
+    let cast = transcript.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).cast(width);
+    Ok(([(header::CONTENT_TYPE, "application/x-asciicast")], cast).into_response())
+}
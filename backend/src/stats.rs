@@ -0,0 +1,179 @@
+//! Per-command usage counters, so maintainers can see which commands get used
+//! and which fail most.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use axum::{extract::State, routing::get, Json, Router};
+use serde::{Deserialize, Serialize};
+use termweb_core::CommandRegistry;
+use tokio::sync::Mutex;
+
+use crate::auth::Admin;
+use crate::AppState;
+
+/// Latency samples kept per command for the percentile estimates.
+const LATENCY_SAMPLES: usize = 512;
+/// How often the counters are written out when a stats file is configured.
+const PERSIST_INTERVAL: Duration = Duration::from_secs(60);
+/// Most commands counted separately; any more are counted as [`UNKNOWN_COMMAND`].
+const MAX_COMMANDS: usize = 512;
+/// What command lines are counted under when they do not start with a known command.
+const UNKNOWN_COMMAND: &str = "<unknown>";
+
+#[derive(Default)]
+pub(crate) struct CommandStats {
+    commands: Mutex<BTreeMap<String, Usage>>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Usage {
+    invocations: u64,
+    errors: u64,
+    /// Most recent latencies in microseconds, oldest first.
+    latencies: VecDeque<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct StatsResponse {
+    /// Most-used commands first.
+    commands: Vec<CommandSummary>,
+    memory: MemoryUsage,
+}
+
+#[derive(Debug, Serialize)]
+struct MemoryUsage {
+    sessions: usize,
+    /// Bytes held across all sessions: their files, history and transcripts.
+    used: usize,
+    /// Per-session cap on file content, if one is configured.
+    capacity: Option<usize>,
+    /// Cap on `used`, if one is configured.
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct CommandSummary {
+    name: String,
+    invocations: u64,
+    errors: u64,
+    error_rate: f64,
+    p50_ms: f64,
+    p90_ms: f64,
+    p99_ms: f64,
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new().route("/api/admin/stats", get(stats))
+}
+
+impl CommandStats {
+    /// Counts one run of the command line `input`, keyed by its first word
+    /// if `registry` has a command by that name. Anything else, which may be
+    /// a typo or something private, is only counted as [`UNKNOWN_COMMAND`].
+    pub(crate) async fn record(
+        &self,
+        registry: &CommandRegistry,
+        input: &str,
+        success: bool,
+        elapsed: Duration,
+    ) {
+        let Some(word) = input.split_whitespace().next() else {
+            return;
+        };
+        let mut commands = self.commands.lock().await;
+        let name = match registry.get(word) {
+            Some(_) if commands.contains_key(word) || commands.len() < MAX_COMMANDS => word,
+            _ => UNKNOWN_COMMAND,
+        };
+        let usage = commands.entry(name.to_string()).or_default();
+        usage.invocations += 1;
+        if !success {
+            usage.errors += 1;
+        }
+        usage.latencies.push_back(elapsed.as_micros() as u64);
+        while usage.latencies.len() > LATENCY_SAMPLES {
+            usage.latencies.pop_front();
+        }
+    }
+
+    /// Seeds the counters from `path`, then rewrites it every
+    /// [`PERSIST_INTERVAL`] so the numbers survive restarts.
+    pub(crate) fn persist_to(self: &std::sync::Arc<Self>, path: PathBuf) {
+        match std::fs::read_to_string(&path) {
+            Ok(saved) => match serde_json::from_str(&saved) {
+                Ok(saved) => *self.commands.try_lock().expect("stats are not shared yet") = saved,
+                Err(error) => tracing::warn!("ignoring unreadable stats file {}: {}", path.display(), error),
+            },
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => tracing::warn!("failed to read stats file {}: {}", path.display(), error),
+        }
+
+        let stats = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PERSIST_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let json = match serde_json::to_string(&*stats.commands.lock().await) {
+                    Ok(json) => json,
+                    Err(error) => {
+                        tracing::warn!("failed to serialise stats: {}", error);
+                        continue;
+                    }
+                };
+                let path = path.clone();
+                let written = tokio::task::spawn_blocking(move || {
+                    let temp = path.with_extension("tmp");
+                    std::fs::write(&temp, json).and_then(|()| std::fs::rename(&temp, &path))
+                })
+                .await
+                .expect("stats writer panicked");
+                if let Err(error) = written {
+                    tracing::warn!("failed to write stats file: {}", error);
+                }
+            }
+        });
+    }
+}
+
+async fn stats(_admin: Admin, State(state): State<AppState>) -> Json<StatsResponse> {
+    let commands = state.stats.commands.lock().await;
+    let mut summaries: Vec<CommandSummary> = commands
+        .iter()
+        .map(|(name, usage)| {
+            let mut latencies: Vec<u64> = usage.latencies.iter().copied().collect();
+            latencies.sort_unstable();
+            CommandSummary {
+                name: name.clone(),
+                invocations: usage.invocations,
+                errors: usage.errors,
+                error_rate: usage.errors as f64 / usage.invocations.max(1) as f64,
+                p50_ms: percentile(&latencies, 50),
+                p90_ms: percentile(&latencies, 90),
+                p99_ms: percentile(&latencies, 99),
+            }
+        })
+        .collect();
+    summaries.sort_by(|a, b| b.invocations.cmp(&a.invocations).then_with(|| a.name.cmp(&b.name)));
+    drop(commands);
+    Json(StatsResponse {
+        commands: summaries,
+        memory: MemoryUsage {
+            sessions: state.sessions.all().await.len(),
+            used: state.sessions.memory_usage().await,
+            capacity: state.session_options.memory_limit,
+            limit: state.sessions.memory_limit(),
+        },
+    })
+}
+
+/// Nearest-rank percentile of sorted microsecond samples, in milliseconds.
+fn percentile(sorted: &[u64], percent: usize) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1] as f64 / 1000.0
+}
@@ -0,0 +1,78 @@
+//! Command output as Server-Sent Events, so a client sees each command of a
+//! long command line as soon as it finishes instead of waiting for the lot.
+//!
+//! `POST /api/command/stream` takes the same body as `/api/command` and
+//! answers with `output` events (`{"text": ...}`), a `clear` event wherever
+//! earlier output should be discarded, and finally one `status` event holding
+//! the command response without its output.
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::post,
+    Json, Router,
+};
+use futures_util::{stream, Stream, StreamExt};
+use serde::Serialize;
+use termweb_core::OutputChunk;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::sessions::SessionId;
+use crate::{dispatch_streaming, edit, negotiate, AppState, CommandRequest};
+
+#[derive(Debug, Serialize)]
+struct OutputEvent {
+    text: String,
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new().route("/api/command/stream", post(stream_command))
+}
+
+async fn stream_command(
+    State(state): State<AppState>,
+    session_id: SessionId,
+    Json(payload): Json<CommandRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, (StatusCode, String)> {
+    let session = state
+        .sessions
+        .resolve(&session_id.or(payload.session))
+        .await?;
+    session.ensure_idle()?;
+    negotiate(&session, payload.color, payload.columns).await;
+    let (output, chunks) = mpsc::unbounded_channel();
+    let (finished, response) = oneshot::channel();
+    tokio::spawn(async move {
+        let response = match payload.editor {
+            Some(op) => {
+                let mut response = edit(&session, op).await;
+                let text = std::mem::take(&mut response.output);
+                if !text.is_empty() {
+                    let _ = output.send(OutputChunk::Text(text));
+                }
+                response
+            }
+            None => dispatch_streaming(&state, &session, payload.command.trim(), output).await,
+        };
+        let _ = finished.send(response);
+    });
+
+    // The output channel closes once the command line is done, so every chunk
+    // is sent before the status.
+    let output = stream::unfold(chunks, |mut chunks| async move {
+        let chunk = chunks.recv().await?;
+        let event = match chunk {
+            OutputChunk::Text(text) => Event::default().event("output").json_data(OutputEvent { text }),
+            OutputChunk::Clear => Ok(Event::default().event("clear").data("")),
+        };
+        Some((event, chunks))
+    });
+    let status = stream::once(async move {
+        match response.await {
+            Ok(response) => Event::default().event("status").json_data(response),
+            Err(_) => Ok(Event::default().event("error").data("command execution failed")),
+        }
+    });
+    Ok(Sse::new(output.chain(status)).keep_alive(KeepAlive::default()))
+}
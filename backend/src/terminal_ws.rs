@@ -0,0 +1,156 @@
+//! Interactive sessions over a WebSocket. A connection attaches to the
+//! session named by `?session=`, or by `?name=` as a user named it (see
+//! [`open`]), or creates a fresh one, whose id the `ready` frame reports so
+//! HTTP endpoints can reach the same shell.
+//!
+//! Clients send text frames `{"command": "...", "id": ...}`, or
+//! `{"editor": {...}}` while a file is open in `edit`; `id` is optional and
+//! echoed back. The server answers with JSON frames tagged by `type`:
+//! `ready` once on connect, `result` for each command, `error` for frames it
+//! could not parse, and `notifications` whenever `write` or `wall` leaves the
+//! session a message while no command is running.
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::StatusCode,
+    response::Response,
+    routing::get,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use termweb_core::editor::EditorOp;
+use termweb_core::{CommandResponse, Notification};
+
+use crate::sessions::{open, Session, SessionId};
+use crate::{dispatch, edit, negotiate, AppState};
+
+#[derive(Debug, Deserialize)]
+struct ClientFrame {
+    #[serde(default)]
+    command: String,
+    #[serde(default)]
+    id: Option<serde_json::Value>,
+    /// What the client can display; see `CommandRequest`.
+    #[serde(default)]
+    color: Option<bool>,
+    #[serde(default)]
+    columns: Option<usize>,
+    #[serde(default)]
+    editor: Option<EditorOp>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AttachQuery {
+    name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ServerFrame {
+    Ready {
+        session: String,
+        cwd: String,
+        /// What a new session's startup files printed.
+        #[serde(skip_serializing_if = "String::is_empty")]
+        greeting: String,
+        /// What a named session showed before this connection attached.
+        #[serde(skip_serializing_if = "String::is_empty")]
+        scrollback: String,
+    },
+    Result {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<serde_json::Value>,
+        #[serde(flatten)]
+        response: CommandResponse,
+    },
+    Error {
+        message: String,
+    },
+    Notifications {
+        notifications: Vec<Notification>,
+    },
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new().route("/ws/terminal", get(terminal_socket))
+}
+
+async fn terminal_socket(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    session_id: SessionId,
+    Query(query): Query<AttachQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let (session, greeting, scrollback) = match session_id.id {
+        Some(_) => (state.sessions.resolve(&session_id).await?, String::new(), String::new()),
+        None => {
+            let opened = open(&state, &session_id.user, query.name.as_deref(), None).await?;
+            (opened.session, opened.greeting, opened.scrollback.unwrap_or_default())
+        }
+    };
+    Ok(ws.on_upgrade(move |socket| serve(socket, state, session, greeting, scrollback)))
+}
+
+async fn serve(mut socket: WebSocket, state: AppState, session: Session, greeting: String, scrollback: String) {
+    let ready = ServerFrame::Ready {
+        session: session.id.clone(),
+        cwd: session.terminal.lock().await.cwd_string(),
+        greeting,
+        scrollback,
+    };
+    if send(&mut socket, &ready).await.is_err() {
+        return;
+    }
+
+    loop {
+        let message = tokio::select! {
+            message = socket.recv() => message,
+            () = session.inbox.wait() => {
+                let notifications: Vec<_> =
+                    session.inbox.take().into_iter().map(|text| Notification::Message { text }).collect();
+                if !notifications.is_empty() {
+                    let frame = ServerFrame::Notifications { notifications };
+                    if send(&mut socket, &frame).await.is_err() {
+                        break;
+                    }
+                }
+                continue;
+            }
+        };
+        let Some(Ok(message)) = message else {
+            break;
+        };
+        let frame = match message {
+            Message::Text(text) => match serde_json::from_str::<ClientFrame>(&text) {
+                Ok(request) => {
+                    negotiate(&session, request.color, request.columns).await;
+                    let response = match request.editor {
+                        Some(op) => edit(&session, op).await,
+                        None => dispatch(&state, &session, request.command.trim()).await,
+                    };
+                    ServerFrame::Result {
+                        id: request.id,
+                        response,
+                    }
+                }
+                Err(err) => ServerFrame::Error {
+                    message: format!("invalid frame: {}", err),
+                },
+            },
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        if send(&mut socket, &frame).await.is_err() {
+            break;
+        }
+    }
+    tracing::debug!("terminal socket for session {} closed", session.id);
+}
+
+async fn send(socket: &mut WebSocket, frame: &ServerFrame) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(frame).expect("frames always serialise");
+    socket.send(Message::Text(text)).await
+}
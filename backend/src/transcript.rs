@@ -0,0 +1,147 @@
+//! What each session typed and saw, with timings, so it can be played back
+//! as an asciinema v2 recording from `GET /api/session/{id}/cast`, and shown
+//! again as scrollback when a client reattaches to it.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use serde::Serialize;
+use termweb_core::state::HOME_DIR;
+use termweb_core::{CommandResponse, OutputChunk};
+
+/// Event data a transcript holds before the oldest events are dropped.
+const MAX_TRANSCRIPT_BYTES: usize = 1 << 20;
+
+/// Most of the screen a reattaching client gets back.
+const MAX_SCROLLBACK_BYTES: usize = 64 << 10;
+
+/// Rows the recording claims; the width is the client's.
+const CAST_HEIGHT: usize = 24;
+
+/// The colour the web UI shows its prompt in, and the reset after it.
+const PROMPT_COLOR: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// Clears the screen and homes the cursor, standing in for `clear`.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+pub(crate) struct Transcript {
+    started: Instant,
+    /// Milliseconds since the epoch when recording began.
+    timestamp: u64,
+    /// Where the next prompt is, as the last response left it.
+    cwd: String,
+    events: VecDeque<Event>,
+    bytes: usize,
+}
+
+struct Event {
+    /// Seconds since recording began.
+    time: f64,
+    /// `"i"` for typed input, `"o"` for what the terminal showed.
+    kind: &'static str,
+    data: String,
+}
+
+/// The first line of a `.cast` file.
+#[derive(Serialize)]
+struct CastHeader {
+    version: u8,
+    width: usize,
+    height: usize,
+    timestamp: u64,
+}
+
+impl Transcript {
+    pub(crate) fn new(cwd: String) -> Self {
+        Transcript {
+            started: Instant::now(),
+            timestamp: crate::audit::now_millis() / 1000,
+            cwd,
+            events: VecDeque::new(),
+            bytes: 0,
+        }
+    }
+
+    /// Records a command line as typed at the prompt the web UI shows.
+    pub(crate) fn input(&mut self, line: &str) {
+        let shown = match self.cwd.strip_prefix(HOME_DIR) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{}", rest),
+            _ => self.cwd.clone(),
+        };
+        self.push("o", format!("{}user@termweb:{}${} ", PROMPT_COLOR, shown, RESET));
+        self.push("i", format!("{}\r", line));
+        self.push("o", format!("{}\r\n", line));
+    }
+
+    pub(crate) fn output(&mut self, chunk: &OutputChunk) {
+        match chunk {
+            OutputChunk::Text(text) => self.push("o", format!("{}\r\n", text.replace('\n', "\r\n"))),
+            OutputChunk::Clear => self.push("o", CLEAR_SCREEN.to_string()),
+        }
+    }
+
+    /// Notes where the command line left the session, for the next prompt.
+    pub(crate) fn finish(&mut self, response: &CommandResponse) {
+        if !response.cwd.is_empty() {
+            self.cwd = response.cwd.clone();
+        }
+    }
+
+    /// Bytes of event data held.
+    pub(crate) fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    fn push(&mut self, kind: &'static str, data: String) {
+        self.bytes += data.len();
+        self.events.push_back(Event {
+            time: self.started.elapsed().as_secs_f64(),
+            kind,
+            data,
+        });
+        while self.bytes > MAX_TRANSCRIPT_BYTES {
+            match self.events.pop_front() {
+                Some(event) => self.bytes -= event.data.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// What the terminal shows now: everything since it was last cleared,
+    /// up to the last [`MAX_SCROLLBACK_BYTES`], with plain line breaks and
+    /// prompts.
+    pub(crate) fn scrollback(&self) -> String {
+        let mut shown = Vec::new();
+        let mut bytes = 0;
+        for event in self.events.iter().rev().filter(|event| event.kind == "o") {
+            if event.data == CLEAR_SCREEN || bytes + event.data.len() > MAX_SCROLLBACK_BYTES {
+                break;
+            }
+            bytes += event.data.len();
+            shown.push(event.data.as_str());
+        }
+        shown.reverse();
+        let shown = shown.concat().replace("\r\n", "\n").replace(PROMPT_COLOR, "").replace(RESET, "");
+        shown.trim_end_matches('\n').to_string()
+    }
+
+    /// The recording in asciinema's v2 format: a header line, then one JSON
+    /// array per event.
+    pub(crate) fn cast(&self, width: usize) -> String {
+        let header = CastHeader {
+            version: 2,
+            width,
+            height: CAST_HEIGHT,
+            timestamp: self.timestamp,
+        };
+        let mut lines = vec![serde_json::to_string(&header).expect("header serialises")];
+        for event in &self.events {
+            let time = (event.time * 1e6).round() / 1e6;
+            let line = serde_json::to_string(&(time, event.kind, &event.data)).expect("event serialises");
+            lines.push(line);
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,220 @@
+//! Resumable uploads: a client opens a session, appends chunks at explicit
+//! offsets, and finalizes with a checksum. A dropped connection only costs
+//! the chunk in flight; the client asks for the session's offset and carries on.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use termweb_core::content_revision;
+use termweb_core::path::{path_string, resolve_path};
+use termweb_core::{FsError, Node, TerminalState};
+use tokio::sync::Mutex;
+
+use crate::fs_api::{api_error, fs_error, session, ApiError, ApiResult};
+use crate::auth::User;
+use crate::sessions::SessionId;
+use crate::AppState;
+
+/// Largest file an upload session will assemble.
+pub(crate) const MAX_UPLOAD_BYTES: usize = 16 * 1024 * 1024;
+/// Sessions left open beyond this many are abandoned, oldest first.
+const MAX_SESSIONS: usize = 64;
+
+#[derive(Default)]
+pub(crate) struct UploadStore {
+    next_id: AtomicU64,
+    sessions: Mutex<(BTreeMap<u64, Upload>, VecDeque<u64>)>,
+}
+
+struct Upload {
+    /// Only this user may continue it, when authentication is on.
+    owner: Option<String>,
+    /// The shell session the file is written into on finalize.
+    terminal: Arc<Mutex<TerminalState>>,
+    path: Vec<String>,
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InitRequest {
+    path: String,
+}
+
+#[derive(Debug, Serialize)]
+struct UploadStatus {
+    id: u64,
+    path: String,
+    /// Bytes received so far; the next chunk must start here.
+    offset: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkQuery {
+    offset: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct FinalizeRequest {
+    /// Hex-encoded SHA-256 of the whole file.
+    sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+struct FinalizeResponse {
+    path: String,
+    revision: String,
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/uploads", post(init_upload))
+        .route("/api/uploads/:id", get(upload_status).put(append_chunk))
+        .route("/api/uploads/:id/finalize", post(finalize_upload))
+}
+
+async fn init_upload(
+    State(state): State<AppState>,
+    session_id: SessionId,
+    Json(request): Json<InitRequest>,
+) -> ApiResult<UploadStatus> {
+    let session = session(&state, session_id).await?;
+    let terminal = session.terminal;
+    let path = resolve_path(&terminal.lock().await.cwd, &request.path);
+    if path.is_empty() {
+        return Err(api_error(StatusCode::BAD_REQUEST, "cannot upload to /"));
+    }
+    let id = state.uploads.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    let mut sessions = state.uploads.sessions.lock().await;
+    let (uploads, order) = &mut *sessions;
+    order.push_back(id);
+    while order.len() > MAX_SESSIONS {
+        if let Some(expired) = order.pop_front() {
+            uploads.remove(&expired);
+        }
+    }
+    let status = UploadStatus {
+        id,
+        path: path_string(&path),
+        offset: 0,
+    };
+    uploads.insert(
+        id,
+        Upload {
+            owner: session.owner,
+            terminal,
+            path,
+            data: Vec::new(),
+        },
+    );
+    Ok(Json(status))
+}
+
+async fn upload_status(
+    State(state): State<AppState>,
+    user: User,
+    Path(id): Path<u64>,
+) -> ApiResult<UploadStatus> {
+    let sessions = state.uploads.sessions.lock().await;
+    let upload = sessions
+        .0
+        .get(&id)
+        .filter(|upload| upload.owner == user.0)
+        .ok_or_else(unknown_session)?;
+    Ok(Json(UploadStatus {
+        id,
+        path: path_string(&upload.path),
+        offset: upload.data.len(),
+    }))
+}
+
+/// Appends a chunk at `?offset=`. A chunk that starts before the current end
+/// is a retry of data already received and only its new tail is kept; one that
+/// starts past the end would leave a gap and is rejected.
+async fn append_chunk(
+    State(state): State<AppState>,
+    user: User,
+    Path(id): Path<u64>,
+    Query(query): Query<ChunkQuery>,
+    chunk: Bytes,
+) -> ApiResult<UploadStatus> {
+    let mut sessions = state.uploads.sessions.lock().await;
+    let upload = sessions
+        .0
+        .get_mut(&id)
+        .filter(|upload| upload.owner == user.0)
+        .ok_or_else(unknown_session)?;
+    let received = upload.data.len();
+    if query.offset > received {
+        return Err(api_error(
+            StatusCode::CONFLICT,
+            format!("expected offset {}, got {}", received, query.offset),
+        ));
+    }
+    let end = query.offset + chunk.len();
+    if end > MAX_UPLOAD_BYTES {
+        return Err(api_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("uploads are limited to {} bytes", MAX_UPLOAD_BYTES),
+        ));
+    }
+    if end > received {
+        upload.data.extend_from_slice(&chunk[received - query.offset..]);
+    }
+    Ok(Json(UploadStatus {
+        id,
+        path: path_string(&upload.path),
+        offset: upload.data.len(),
+    }))
+}
+
+async fn finalize_upload(
+    State(state): State<AppState>,
+    user: User,
+    Path(id): Path<u64>,
+    Json(request): Json<FinalizeRequest>,
+) -> ApiResult<FinalizeResponse> {
+    let mut sessions = state.uploads.sessions.lock().await;
+    let upload = sessions
+        .0
+        .get(&id)
+        .filter(|upload| upload.owner == user.0)
+        .ok_or_else(unknown_session)?;
+    let digest = format!("{:x}", Sha256::digest(&upload.data));
+    if !digest.eq_ignore_ascii_case(request.sha256.trim()) {
+        // The session stays open so the client can inspect the offset and retry.
+        return Err(api_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("checksum mismatch: received data hashes to {}", digest),
+        ));
+    }
+    let mut terminal = upload.terminal.lock().await;
+    if let Some(Node::Dir { .. }) = terminal.fs.get_node(&upload.path) {
+        return Err(fs_error(FsError::IsADirectory));
+    }
+    let revision = content_revision([upload.data.as_slice()]);
+    terminal
+        .fs
+        .write_bytes(&upload.path, upload.data.clone(), false)
+        .map_err(fs_error)?;
+    let path = path_string(&upload.path);
+    drop(terminal);
+
+    let (uploads, order) = &mut *sessions;
+    uploads.remove(&id);
+    order.retain(|&session| session != id);
+    Ok(Json(FinalizeResponse { path, revision }))
+}
+
+fn unknown_session() -> (StatusCode, Json<ApiError>) {
+    api_error(StatusCode::NOT_FOUND, "no such upload session")
+}
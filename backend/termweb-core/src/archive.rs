@@ -0,0 +1,197 @@
+//! ustar archives of the virtual filesystem, shared by the `tar` command and
+//! the export and import endpoints.
+
+use std::fmt;
+use std::io::Read;
+
+use crate::fs::{FileSystem, FsError, Node, READ};
+use crate::path::resolve_path;
+
+/// Why an archive could not be unpacked.
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// The bytes are not a tar archive, or it is cut short.
+    Invalid(String),
+    /// A member names a path outside the destination, e.g. with `..`.
+    UnsafePath(String),
+    /// Writing a member into the filesystem failed.
+    Fs(String, FsError),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Invalid(reason) => write!(f, "invalid archive: {}", reason),
+            ArchiveError::UnsafePath(name) => write!(f, "{}: member is outside the destination", name),
+            ArchiveError::Fs(name, error) => write!(f, "{}: {}", name, error),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+/// Packs each `(name, path)` pair, and for directories everything beneath
+/// them, into a tar archive with members named after `name`. Symlinks are
+/// stored as links, not followed.
+pub fn pack(fs: &FileSystem, entries: &[(String, Vec<String>)]) -> Result<Vec<u8>, FsError> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, path) in entries {
+        let node = fs.get_node_nofollow(path).ok_or(FsError::NotFound)?;
+        append(&mut builder, fs, name.trim_end_matches('/'), path, node)?;
+    }
+    // Writing into a Vec cannot fail.
+    Ok(builder.into_inner().expect("tar into memory"))
+}
+
+fn append(
+    builder: &mut tar::Builder<Vec<u8>>,
+    fs: &FileSystem,
+    name: &str,
+    path: &[String],
+    node: &Node,
+) -> Result<(), FsError> {
+    let meta = node.meta();
+    let mut header = tar::Header::new_ustar();
+    header.set_mode(meta.mode);
+    header.set_mtime(meta.modified / 1000);
+    let written = match node {
+        Node::File { content, .. } => {
+            if !fs.permitted(path, READ) {
+                return Err(FsError::PermissionDenied);
+            }
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_size(content.len() as u64);
+            builder.append_data(&mut header, name, &*content.bytes())
+        }
+        Node::Symlink { target, .. } => {
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, name, target)
+        }
+        Node::Dir { children, .. } => {
+            if !fs.permitted(path, READ) {
+                return Err(FsError::PermissionDenied);
+            }
+            if !name.is_empty() {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                builder
+                    .append_data(&mut header, format!("{}/", name), std::io::empty())
+                    .expect("tar into memory");
+            }
+            for (child_name, child) in children {
+                let child_path: Vec<String> = path.iter().cloned().chain([child_name.clone()]).collect();
+                let member = match name {
+                    "" => child_name.clone(),
+                    _ => format!("{}/{}", name, child_name),
+                };
+                append(builder, fs, &member, &child_path, child)?;
+            }
+            return Ok(());
+        }
+    };
+    written.map_err(|_| FsError::InvalidPath("file name too long for the archive"))
+}
+
+/// The member names in `archive`, in order, directories with a trailing `/`.
+pub fn list(archive: &[u8]) -> Result<Vec<String>, ArchiveError> {
+    let mut names = Vec::new();
+    for_each_entry(archive, |name, _| {
+        names.push(name);
+        Ok(())
+    })?;
+    Ok(names)
+}
+
+/// Unpacks `archive` beneath `dest`, creating directories as needed and
+/// replacing files that are already there. Returns the member names.
+pub fn unpack(fs: &mut FileSystem, archive: &[u8], dest: &[String]) -> Result<Vec<String>, ArchiveError> {
+    let mut names = Vec::new();
+    // Modes are applied last, so a read-only directory can still be filled.
+    let mut dir_modes = Vec::new();
+    for_each_entry(archive, |name, mut entry| {
+        // As GNU tar does, treat absolute member names as relative.
+        let path = resolve_path(dest, name.trim_start_matches('/'));
+        if !path.starts_with(dest) {
+            return Err(ArchiveError::UnsafePath(name));
+        }
+        if path.len() == dest.len() {
+            // `./` itself.
+            return Ok(());
+        }
+        let fail = |error| ArchiveError::Fs(name.clone(), error);
+        let mode = entry.header().mode().map_err(invalid)? & 0o777;
+        make_dirs(fs, &path[..path.len() - 1]).map_err(fail)?;
+        let entry_type = entry.header().entry_type();
+        // A link left in place would redirect the write.
+        if !entry_type.is_dir() && matches!(fs.get_node_nofollow(&path), Some(Node::Symlink { .. })) {
+            fs.remove(&path, false).map_err(fail)?;
+        }
+        match entry_type {
+            tar::EntryType::Directory => {
+                make_dirs(fs, &path).map_err(fail)?;
+                dir_modes.push((path, mode, name.clone()));
+            }
+            tar::EntryType::Symlink => {
+                let target = entry
+                    .link_name()
+                    .map_err(invalid)?
+                    .ok_or_else(|| ArchiveError::Invalid(format!("{}: symlink without a target", name)))?
+                    .to_string_lossy()
+                    .into_owned();
+                if fs.get_node_nofollow(&path).is_some() {
+                    fs.remove(&path, false).map_err(fail)?;
+                }
+                fs.symlink(&target, &path).map_err(fail)?;
+            }
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content).map_err(invalid)?;
+                fs.write_bytes(&path, content, false).map_err(fail)?;
+                fs.set_mode(&path, mode).map_err(fail)?;
+            }
+            // Hard links, devices and the like have no equivalent here.
+            _ => return Ok(()),
+        }
+        names.push(name);
+        Ok(())
+    })?;
+    for (path, mode, name) in dir_modes.into_iter().rev() {
+        fs.set_mode(&path, mode).map_err(|error| ArchiveError::Fs(name, error))?;
+    }
+    Ok(names)
+}
+
+/// Calls `visit` with each member of `archive` and its name.
+fn for_each_entry(
+    archive: &[u8],
+    mut visit: impl FnMut(String, tar::Entry<'_, &[u8]>) -> Result<(), ArchiveError>,
+) -> Result<(), ArchiveError> {
+    // An empty or cut-short archive would otherwise read as having no members.
+    if archive.len() < 512 || !archive.len().is_multiple_of(512) {
+        return Err(ArchiveError::Invalid("not a tar file".to_string()));
+    }
+    let mut archive = tar::Archive::new(archive);
+    for entry in archive.entries().map_err(invalid)? {
+        let entry = entry.map_err(invalid)?;
+        let name = entry.path().map_err(invalid)?.to_string_lossy().into_owned();
+        visit(name, entry)?;
+    }
+    Ok(())
+}
+
+fn invalid(error: std::io::Error) -> ArchiveError {
+    ArchiveError::Invalid(error.to_string())
+}
+
+/// Creates `path` and any missing parents, like `mkdir -p`.
+pub fn make_dirs(fs: &mut FileSystem, path: &[String]) -> Result<(), FsError> {
+    for end in 1..=path.len() {
+        match fs.get_node(&path[..end]) {
+            Some(Node::Dir { .. }) => {}
+            Some(_) => return Err(FsError::NotADirectory),
+            None => fs.mkdir(&path[..end])?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! Brace expansion, the first of a word's expansions: `a{b,c}d` becomes
+//! `abd acd`, and `{1..5}`, `{01..10..3}` or `{a..e}` a sequence. Only
+//! unquoted braces and commas count, and a brace with neither a comma nor
+//! a sequence in it is left as written.
+
+use crate::tokenizer::{Word, WordPart};
+
+/// Words one word may expand to; a word that would give more is left as
+/// written rather than flood the command.
+const MAX_WORDS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+enum Unit<'a> {
+    /// An unquoted character, which may be brace syntax.
+    Plain(char),
+    Quoted(char),
+    Variable(&'a WordPart),
+}
+
+/// The words `word` expands to, in order; just `word` if it has no braces
+/// to expand.
+pub fn expand(word: &Word) -> Vec<Word> {
+    let mut units = Vec::new();
+    for part in &word.parts {
+        match part {
+            WordPart::Literal(text) => units.extend(text.chars().map(Unit::Plain)),
+            WordPart::Quoted(text) => units.extend(text.chars().map(Unit::Quoted)),
+            WordPart::Variable { .. } => units.push(Unit::Variable(part)),
+        }
+    }
+    if !units.iter().any(|unit| matches!(unit, Unit::Plain('{'))) {
+        return vec![word.clone()];
+    }
+    let mut expanded = Vec::new();
+    if !expand_units(units, &mut expanded) {
+        return vec![word.clone()];
+    }
+    expanded
+        .into_iter()
+        .map(|units| {
+            let mut expanded = Word {
+                parts: Vec::new(),
+                quoted: word.quoted,
+            };
+            for unit in units {
+                match unit {
+                    Unit::Plain(ch) => expanded.push(ch, false),
+                    Unit::Quoted(ch) => expanded.push(ch, true),
+                    Unit::Variable(part) => expanded.parts.push(part.clone()),
+                }
+            }
+            expanded
+        })
+        .collect()
+}
+
+/// Adds what `units` expands to onto `expanded`, the first brace first and
+/// the rest within each result; false once there would be too many.
+fn expand_units<'a>(units: Vec<Unit<'a>>, expanded: &mut Vec<Vec<Unit<'a>>>) -> bool {
+    for open in 0..units.len() {
+        if !matches!(units[open], Unit::Plain('{')) {
+            continue;
+        }
+        let Some((close, commas)) = closing_brace(&units, open) else {
+            continue;
+        };
+        let inner = &units[open + 1..close];
+        let alternatives: Vec<Vec<Unit>> = if commas.is_empty() {
+            match sequence(inner) {
+                Some(items) => items.iter().map(|item| item.chars().map(Unit::Plain).collect()).collect(),
+                None => continue,
+            }
+        } else {
+            let mut bounds = vec![open];
+            bounds.extend(&commas);
+            bounds.push(close);
+            bounds.windows(2).map(|pair| units[pair[0] + 1..pair[1]].to_vec()).collect()
+        };
+        for alternative in alternatives {
+            let mut next = units[..open].to_vec();
+            next.extend(alternative);
+            next.extend_from_slice(&units[close + 1..]);
+            if !expand_units(next, expanded) {
+                return false;
+            }
+        }
+        return true;
+    }
+    expanded.push(units);
+    expanded.len() <= MAX_WORDS
+}
+
+/// The `}` matching the `{` at `open`, and the commas directly inside the
+/// pair rather than in braces nested within it.
+fn closing_brace(units: &[Unit], open: usize) -> Option<(usize, Vec<usize>)> {
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    for (index, unit) in units.iter().enumerate().skip(open + 1) {
+        match unit {
+            Unit::Plain('{') => depth += 1,
+            Unit::Plain('}') if depth == 0 => return Some((index, commas)),
+            Unit::Plain('}') => depth -= 1,
+            Unit::Plain(',') if depth == 0 => commas.push(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The items of a sequence written `start..end` or `start..end..step`:
+/// integers, zero-padded if either end is, or single letters.
+fn sequence(units: &[Unit]) -> Option<Vec<String>> {
+    let text: String = units
+        .iter()
+        .map(|unit| match unit {
+            Unit::Plain(ch) => Some(*ch),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    let parts: Vec<&str> = text.split("..").collect();
+    let (start, end, step) = match parts[..] {
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step.parse::<i64>().ok()?.unsigned_abs().max(1)),
+        _ => return None,
+    };
+    if let (Ok(first), Ok(last)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        let padded = |text: &str| {
+            let digits = text.trim_start_matches('-');
+            digits.len() > 1 && digits.starts_with('0')
+        };
+        let width = if padded(start) || padded(end) { start.len().max(end.len()) } else { 0 };
+        let items = steps(first, last, step)?;
+        return Some(items.map(|number| format!("{:0width$}", number, width = width)).collect());
+    }
+    match (single_char(start)?, single_char(end)?) {
+        (first, last) if first.is_ascii_alphabetic() && last.is_ascii_alphabetic() => {
+            let items = steps(i64::from(first as u8), i64::from(last as u8), step)?;
+            Some(items.map(|code| char::from(code as u8).to_string()).collect())
+        }
+        _ => None,
+    }
+}
+
+fn single_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    chars.next().filter(|_| chars.next().is_none())
+}
+
+/// From `first` to `last` by `step`, counting down if `last` is lower;
+/// `None` if that is more than [`MAX_WORDS`] numbers.
+fn steps(first: i64, last: i64, step: u64) -> Option<impl Iterator<Item = i64>> {
+    let count = first.abs_diff(last) / step + 1;
+    if count > MAX_WORDS as u64 {
+        return None;
+    }
+    let step = i128::from(step) * if last < first { -1 } else { 1 };
+    Some((0..count).map(move |index| (i128::from(first) + i128::from(index) * step) as i64))
+}
\ No newline at end of file
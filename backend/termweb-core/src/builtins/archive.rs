@@ -0,0 +1,241 @@
+use crate::archive::{self, ArchiveError};
+use crate::command::{Command, CommandContext, CommandResult, Completion, Manual, EXIT_USAGE};
+use crate::fs::{Content, FsError, Node};
+use crate::path::{path_string, resolve_path};
+
+pub struct Tar;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Create,
+    Extract,
+    List,
+}
+
+impl Command for Tar {
+    fn name(&self) -> &'static str {
+        "tar"
+    }
+
+    fn help(&self) -> &'static str {
+        "tar -c|-x|-t [-v] -f <archive> [-C dir] [path]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "create and extract tar archives",
+            description: "Packs files into a tar archive with `-c`, unpacks one with `-x` or lists its \
+                contents with `-t`. `-f` names the archive, `-C` sets the directory to pack from or \
+                unpack into, and `-v` lists each member as it goes.",
+            examples: &[
+                ("tar -cf notes.tar notes", "Archive the notes directory."),
+                ("tar -tvf notes.tar", "List what an archive holds."),
+                ("tar -xf notes.tar -C /tmp", "Unpack an archive into /tmp."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut mode = None;
+        let mut verbose = false;
+        let mut archive = None;
+        let mut dir = None;
+        let mut operands = Vec::new();
+        let mut args = args.iter().enumerate();
+        while let Some((index, arg)) = args.next() {
+            // As in tar(1), the first argument's flags need no dash: `tar cvf out.tar dir`.
+            let flags = match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() => flags,
+                _ if index == 0 && !arg.is_empty() => arg.as_str(),
+                _ => {
+                    operands.push(arg);
+                    continue;
+                }
+            };
+            for flag in flags.chars() {
+                match flag {
+                    'c' | 'x' | 't' => {
+                        let chosen = match flag {
+                            'c' => Mode::Create,
+                            'x' => Mode::Extract,
+                            _ => Mode::List,
+                        };
+                        if mode.is_some_and(|mode| mode != chosen) {
+                            return usage("tar: you may not specify more than one of -c, -x and -t");
+                        }
+                        mode = Some(chosen);
+                    }
+                    'v' => verbose = true,
+                    // Each takes the next argument, as with `-cf out.tar`.
+                    'f' | 'C' => {
+                        let Some((_, value)) = args.next() else {
+                            return usage(format!("tar: option requires an argument -- '{}'", flag));
+                        };
+                        if flag == 'f' {
+                            archive = Some(value);
+                        } else {
+                            dir = Some(value);
+                        }
+                    }
+                    other => return usage(format!("tar: invalid option -- '{}'", other)),
+                }
+            }
+        }
+        let Some(mode) = mode else {
+            return usage("tar: you must specify one of -c, -x or -t");
+        };
+        let Some(archive) = archive else {
+            return usage("tar: an archive must be given with -f");
+        };
+        let base = match dir {
+            Some(dir) => resolve_path(&ctx.state.cwd, dir),
+            None => ctx.state.cwd.clone(),
+        };
+        match mode {
+            Mode::Create => create(ctx, archive, &base, &operands, verbose),
+            Mode::Extract if !operands.is_empty() => {
+                usage("tar: extracting selected members is not supported; extract the whole archive")
+            }
+            Mode::Extract => extract(ctx, "tar", archive, &base, verbose),
+            Mode::List => list(ctx, archive),
+        }
+    }
+}
+
+pub struct Untar;
+
+impl Command for Untar {
+    fn name(&self) -> &'static str {
+        "untar"
+    }
+
+    fn help(&self) -> &'static str {
+        "untar [-v] <archive> [dir]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "extract a tar archive",
+            description: "Unpacks `archive` into `dir`, or the current directory, replacing files already \
+                there. `-v` lists each member.",
+            examples: &[("untar backup.tar restore", "Unpack backup.tar into restore.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let verbose = args.iter().any(|arg| arg == "-v");
+        let operands: Vec<&String> = args.iter().filter(|arg| *arg != "-v").collect();
+        let (archive, dir) = match operands.as_slice() {
+            [archive] => (*archive, None),
+            [archive, dir] => (*archive, Some(*dir)),
+            _ => return usage("untar: expected an archive and at most one directory"),
+        };
+        let base = match dir {
+            Some(dir) => resolve_path(&ctx.state.cwd, dir),
+            None => ctx.state.cwd.clone(),
+        };
+        extract(ctx, "untar", archive, &base, verbose)
+    }
+}
+
+fn usage(message: impl Into<String>) -> CommandResult {
+    CommandResult::error(message).with_exit_code(EXIT_USAGE)
+}
+
+/// Packs `operands`, named as given relative to `base`, into the file `archive`.
+fn create(
+    ctx: &mut CommandContext<'_>,
+    archive: &str,
+    base: &[String],
+    operands: &[&String],
+    verbose: bool,
+) -> CommandResult {
+    if operands.is_empty() {
+        return usage("tar: cowardly refusing to create an empty archive");
+    }
+    let mut entries = Vec::new();
+    for operand in operands {
+        // Absolute names are stored relative, so extracting cannot escape the destination.
+        let name = operand.trim_start_matches('/').trim_end_matches('/');
+        let name = if name.is_empty() { "." } else { name };
+        entries.push((name.to_string(), resolve_path(base, operand)));
+    }
+    let bytes = match archive::pack(&ctx.state.fs, &entries) {
+        Ok(bytes) => bytes,
+        Err(error) => return CommandResult::fs_error("tar", error),
+    };
+    let output = if verbose {
+        archive::list(&bytes).map(|names| names.join("\n")).unwrap_or_default()
+    } else {
+        String::new()
+    };
+    let path = resolve_path(&ctx.state.cwd, archive);
+    if let Err(error) = ctx.state.fs.write_bytes(&path, bytes, false) {
+        return CommandResult::fs_error("tar", error);
+    }
+    CommandResult::ok(output)
+}
+
+fn extract(
+    ctx: &mut CommandContext<'_>,
+    tool: &str,
+    archive: &str,
+    dest: &[String],
+    verbose: bool,
+) -> CommandResult {
+    let bytes = match read_archive(ctx, tool, archive) {
+        Ok(bytes) => bytes,
+        Err(result) => return result,
+    };
+    if !matches!(ctx.state.fs.get_node(dest), Some(Node::Dir { .. })) {
+        let error = FsError::NotADirectory;
+        return failure(format!("{}: {}: {}", tool, path_string(dest), error), error);
+    }
+    match archive::unpack(&mut ctx.state.fs, &bytes.bytes(), dest) {
+        Ok(names) if verbose => CommandResult::ok(names.join("\n")),
+        Ok(_) => CommandResult::empty(),
+        Err(error) => archive_error(tool, error),
+    }
+}
+
+fn list(ctx: &CommandContext<'_>, archive: &str) -> CommandResult {
+    let bytes = match read_archive(ctx, "tar", archive) {
+        Ok(bytes) => bytes,
+        Err(result) => return result,
+    };
+    match archive::list(&bytes.bytes()) {
+        Ok(names) => CommandResult::ok(names.join("\n")),
+        Err(error) => archive_error("tar", error),
+    }
+}
+
+fn read_archive(ctx: &CommandContext<'_>, tool: &str, archive: &str) -> Result<Content, CommandResult> {
+    let path = resolve_path(&ctx.state.cwd, archive);
+    ctx.state
+        .fs
+        .read_bytes(&path)
+        .map_err(|error| failure(format!("{}: {}: {}", tool, archive, error), error))
+}
+
+fn archive_error(tool: &str, error: ArchiveError) -> CommandResult {
+    match error {
+        ArchiveError::Fs(_, cause) => failure(format!("{}: {}", tool, error), cause),
+        _ => CommandResult::error(format!("{}: {}", tool, error)),
+    }
+}
+
+/// Like [`CommandResult::fs_error`], with the message spelled out.
+fn failure(message: String, error: FsError) -> CommandResult {
+    CommandResult {
+        error_code: Some(error.code()),
+        ..CommandResult::error(message)
+    }
+}
@@ -0,0 +1,220 @@
+use crate::command::{Command, CommandContext, CommandResult, Completion, Manual, EXIT_USAGE};
+use crate::fs::Node;
+use crate::path::resolve_path;
+
+pub struct Du;
+
+impl Command for Du {
+    fn name(&self) -> &'static str {
+        "du"
+    }
+
+    fn help(&self) -> &'static str {
+        "du [-h] [-s] [path]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "show disk usage",
+            description: "Prints the space used by each directory beneath each path, or the current \
+                directory, in 1K blocks. `-s` gives only a total for each path and `-h` shows sizes such \
+                as `1.5K` or `12M`.",
+            examples: &[("du -sh ~", "Show how much your home directory holds.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut human = false;
+        let mut summarize = false;
+        let mut targets = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'h' => human = true,
+                            's' => summarize = true,
+                            other => {
+                                return CommandResult::error(format!("du: invalid option -- '{}'", other))
+                                    .with_exit_code(EXIT_USAGE);
+                            }
+                        }
+                    }
+                }
+                _ => targets.push(arg.as_str()),
+            }
+        }
+        if targets.is_empty() {
+            targets.push(".");
+        }
+
+        let format = |bytes: u64| if human { human_size(bytes) } else { bytes.div_ceil(1024).to_string() };
+        let mut lines = Vec::new();
+        let mut errors = Vec::new();
+        for target in targets {
+            let path = resolve_path(&ctx.state.cwd, target);
+            // Like coreutils, an operand is not followed if it is a symlink.
+            let Some(node) = ctx.state.fs.get_node_nofollow(&path) else {
+                errors.push(format!("du: cannot access '{}': No such file or directory", target));
+                continue;
+            };
+            let mut sizes = Vec::new();
+            let total = subtree_size(node, target.trim_end_matches('/'), !summarize, &mut sizes);
+            sizes.push((total, target.to_string()));
+            lines.extend(sizes.into_iter().map(|(bytes, name)| format!("{}\t{}", format(bytes), name)));
+        }
+        let exit_code = if errors.is_empty() { 0 } else { 1 };
+        CommandResult::ok(lines.join("\n")).with_errors(errors).with_exit_code(exit_code)
+    }
+}
+
+/// Bytes of file content under `node`. With `each_dir`, also collects the
+/// size of every directory beneath it, each after its own subdirectories.
+fn subtree_size(node: &Node, name: &str, each_dir: bool, sizes: &mut Vec<(u64, String)>) -> u64 {
+    match node {
+        Node::Dir { children, .. } => children
+            .iter()
+            .map(|(child_name, child)| {
+                let child_path = format!("{}/{}", name, child_name);
+                let size = subtree_size(child, &child_path, each_dir, sizes);
+                if each_dir && matches!(child, Node::Dir { .. }) {
+                    sizes.push((size, child_path));
+                }
+                size
+            })
+            .sum(),
+        _ => node.size(),
+    }
+}
+
+pub struct Df;
+
+impl Command for Df {
+    fn name(&self) -> &'static str {
+        "df"
+    }
+
+    fn help(&self) -> &'static str {
+        "df [-h]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "show filesystem usage",
+            description: "Shows the size of the filesystem, how much is used and how much is free. Size \
+                and free space are `-` when the session has no storage limit. `-h` shows sizes such as \
+                `1.5K` or `12M`.",
+            examples: &[("df -h", "See how close the session is to its storage limit.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let human = match args {
+            [] => false,
+            [flag] if flag == "-h" => true,
+            _ => return CommandResult::error(format!("usage: {}", self.help())).with_exit_code(EXIT_USAGE),
+        };
+        let fs = &ctx.state.fs;
+        let used = fs.usage() as u64;
+        let size = fs.capacity().map(|capacity| capacity as u64);
+        let format = |bytes: u64| if human { human_size(bytes) } else { bytes.div_ceil(1024).to_string() };
+        // Without a capacity there is no size to measure against.
+        let (size, avail, percent) = match size {
+            Some(size) => (
+                format(size),
+                format(size.saturating_sub(used)),
+                format!("{}%", (used * 100).div_ceil(size.max(1))),
+            ),
+            None => ("-".to_string(), "-".to_string(), "-".to_string()),
+        };
+        let header = if human {
+            ["Filesystem", "Size", "Used", "Avail", "Use%", "Mounted on"]
+        } else {
+            ["Filesystem", "1K-blocks", "Used", "Available", "Use%", "Mounted on"]
+        };
+        let row = ["termweb".to_string(), size, format(used), avail, percent, "/".to_string()];
+        let line = |cells: [&str; 6]| {
+            format!(
+                "{:<10} {:>9} {:>9} {:>9} {:>4} {}",
+                cells[0], cells[1], cells[2], cells[3], cells[4], cells[5]
+            )
+        };
+        CommandResult::ok(format!(
+            "{}\n{}",
+            line(header),
+            line(row.each_ref().map(String::as_str))
+        ))
+    }
+}
+
+/// A byte count the way `du -h` and `df -h` show it: `512`, `1.5K`, `12M`,
+/// rounding up as they do.
+pub(crate) fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    loop {
+        value /= 1024.0;
+        if value < 1024.0 || unit == UNITS.len() - 1 {
+            break;
+        }
+        unit += 1;
+    }
+    // One decimal below ten, as coreutils does; whole units above.
+    let tenths = (value * 10.0).ceil() / 10.0;
+    if tenths < 10.0 {
+        format!("{:.1}{}", tenths, UNITS[unit])
+    } else {
+        format!("{}{}", value.ceil(), UNITS[unit])
+    }
+}
+
+pub struct Quota;
+
+impl Command for Quota {
+    fn name(&self) -> &'static str {
+        "quota"
+    }
+
+    fn help(&self) -> &'static str {
+        "quota"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "show the session's limits",
+            description: "Lists each limit on the session's filesystem, such as total size, file size and \
+                number of files, with how much of it is in use.",
+            examples: &[("quota", "Check how many more files can be created.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        if !args.is_empty() {
+            return CommandResult::error(format!("usage: {}", self.help())).with_exit_code(EXIT_USAGE);
+        }
+        let fs = &ctx.state.fs;
+        let limits = fs.limits();
+        let rows = [
+            ("space (bytes)", Some(fs.usage()), fs.capacity()),
+            ("files", Some(fs.node_count()), limits.max_nodes),
+            ("file size", None, limits.max_file_size),
+            ("path depth", None, limits.max_depth),
+            ("name length", None, limits.max_name_len),
+        ];
+        let mut lines = vec![format!("{:<14} {:>10} {:>10}", "resource", "used", "limit")];
+        for (resource, used, limit) in rows {
+            let used = used.map(|used| used.to_string()).unwrap_or_default();
+            let limit = limit.map_or_else(|| "none".to_string(), |limit| limit.to_string());
+            lines.push(format!("{:<14} {:>10} {:>10}", resource, used, limit));
+        }
+        CommandResult::ok(lines.join("\n"))
+    }
+}
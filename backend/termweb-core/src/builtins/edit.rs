@@ -0,0 +1,48 @@
+use crate::command::{Command, CommandContext, CommandResult, Completion, Manual, EXIT_USAGE};
+use crate::editor::Editor;
+use crate::fs::{is_binary, FsError};
+use crate::path::resolve_path;
+
+pub struct Edit;
+
+impl Command for Edit {
+    fn name(&self) -> &'static str {
+        "edit"
+    }
+
+    fn help(&self) -> &'static str {
+        "edit <file>"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "edit a file",
+            description: "Opens `file` in the editor, which takes the place of the terminal until it is \
+                quit; a file that does not exist yet is created when first saved.\n\n\
+                In the web UI, Ctrl+S saves and Escape quits. Elsewhere, `:w` saves, `:q` quits, `:wq` \
+                does both and `:q!` quits without saving the changes.",
+            examples: &[("edit notes.txt", "Edit notes.txt.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let [file] = args else {
+            return CommandResult::error(format!("usage: {}", self.help())).with_exit_code(EXIT_USAGE);
+        };
+        let path = resolve_path(&ctx.state.cwd, file);
+        let content = match ctx.state.fs.read_bytes(&path) {
+            Ok(content) if is_binary(&content.bytes()) => {
+                return CommandResult::error(format!("edit: {}: cannot edit a binary file", file));
+            }
+            Ok(content) => Some(String::from_utf8_lossy(&content.bytes()).into_owned()),
+            Err(FsError::NotFound) => None,
+            Err(error) => return CommandResult::error(format!("edit: {}: {}", file, error)),
+        };
+        ctx.state.editor = Some(Editor::open(path, content.as_deref()));
+        CommandResult::empty()
+    }
+}
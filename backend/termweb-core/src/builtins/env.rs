@@ -0,0 +1,222 @@
+use crate::command::{Command, CommandContext, CommandResult, Manual};
+use crate::tokenizer::{is_alias_name, is_variable_name};
+
+pub struct Export;
+
+impl Command for Export {
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    fn help(&self) -> &'static str {
+        "export [name[=value]...]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "set environment variables",
+            description: "Sets each `name=value` as a variable commands can read as `$name`. With no \
+                arguments, lists the variables.",
+            examples: &[
+                ("export EDITOR=vi", "Set a variable."),
+                ("export PATH=$PATH:~/bin", "Add to a variable."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        if args.is_empty() {
+            let lines: Vec<String> = ctx
+                .state
+                .env
+                .iter()
+                .map(|(name, value)| format!("export {}=\"{}\"", name, value))
+                .collect();
+            return CommandResult::ok(lines.join("\n"));
+        }
+        let mut errors = Vec::new();
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg.as_str(), None),
+            };
+            if !is_variable_name(name) {
+                errors.push(format!("export: `{}': not a valid identifier", arg));
+                continue;
+            }
+            // Every variable is already exported, so a bare name just makes
+            // sure it exists.
+            let entry = ctx.state.env.entry(name.to_string()).or_default();
+            if let Some(value) = value {
+                *entry = value.to_string();
+            }
+        }
+        if errors.is_empty() {
+            CommandResult::empty()
+        } else {
+            CommandResult::error(errors.join("\n"))
+        }
+    }
+}
+
+pub struct Unset;
+
+impl Command for Unset {
+    fn name(&self) -> &'static str {
+        "unset"
+    }
+
+    fn help(&self) -> &'static str {
+        "unset <name>..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "remove environment variables",
+            description: "Removes each variable named.",
+            examples: &[("unset EDITOR", "Forget the EDITOR variable.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut errors = Vec::new();
+        for name in args {
+            if is_variable_name(name) {
+                ctx.state.env.remove(name);
+            } else {
+                errors.push(format!("unset: `{}': not a valid identifier", name));
+            }
+        }
+        if errors.is_empty() {
+            CommandResult::empty()
+        } else {
+            CommandResult::error(errors.join("\n"))
+        }
+    }
+}
+
+pub struct Env;
+
+impl Command for Env {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn help(&self) -> &'static str {
+        "env"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "list environment variables",
+            description: "Prints every variable as `name=value`, one per line.",
+            examples: &[("env | grep HOME", "Find the HOME variable.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        if !args.is_empty() {
+            return CommandResult::error("env: running commands is not supported");
+        }
+        let lines: Vec<String> = ctx
+            .state
+            .env
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        CommandResult::ok(lines.join("\n"))
+    }
+}
+
+pub struct Alias;
+
+impl Command for Alias {
+    fn name(&self) -> &'static str {
+        "alias"
+    }
+
+    fn help(&self) -> &'static str {
+        "alias [name[=value]...]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "define command aliases",
+            description: "Makes `name` stand for `value` at the start of a command. With no arguments, \
+                lists every alias; with just a name, shows that alias.",
+            examples: &[
+                ("alias ll='ls -l'", "Make ll a short way to list in long form."),
+                ("alias", "List the aliases."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let aliases = &mut ctx.state.aliases;
+        if args.is_empty() {
+            let lines: Vec<String> = aliases.iter().map(|(name, value)| alias_line(name, value)).collect();
+            return CommandResult::ok(lines.join("\n"));
+        }
+        let mut lines = Vec::new();
+        let mut errors = Vec::new();
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, value)) if is_alias_name(name) => {
+                    aliases.insert(name.to_string(), value.to_string());
+                }
+                Some(_) => errors.push(format!("alias: `{}': invalid alias name", arg)),
+                None => match aliases.get(arg) {
+                    Some(value) => lines.push(alias_line(arg, value)),
+                    None => errors.push(format!("alias: {}: not found", arg)),
+                },
+            }
+        }
+        let exit_code = if errors.is_empty() { 0 } else { 1 };
+        CommandResult::ok(lines.join("\n")).with_errors(errors).with_exit_code(exit_code)
+    }
+}
+
+/// An alias as `alias` lists it, quoted so it can be pasted back in.
+fn alias_line(name: &str, value: &str) -> String {
+    format!("alias {}='{}'", name, value.replace('\'', "'\"'\"'"))
+}
+
+pub struct Unalias;
+
+impl Command for Unalias {
+    fn name(&self) -> &'static str {
+        "unalias"
+    }
+
+    fn help(&self) -> &'static str {
+        "unalias [-a] <name>..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "remove command aliases",
+            description: "Removes each alias named, or every alias with `-a`.",
+            examples: &[("unalias ll", "Remove the ll alias.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        if args.iter().any(|arg| arg == "-a") {
+            ctx.state.aliases.clear();
+            return CommandResult::empty();
+        }
+        if args.is_empty() {
+            return CommandResult::error("unalias: usage: unalias [-a] name [name ...]");
+        }
+        let errors: Vec<String> = args
+            .iter()
+            .filter(|name| ctx.state.aliases.remove(*name).is_none())
+            .map(|name| format!("unalias: {}: not found", name))
+            .collect();
+        if errors.is_empty() {
+            CommandResult::empty()
+        } else {
+            CommandResult::error(errors.join("\n"))
+        }
+    }
+}
@@ -0,0 +1,1128 @@
+use crate::clock;
+use crate::command::{Command, CommandContext, CommandResult, Completion, Manual, EXIT_USAGE};
+use crate::diff;
+use crate::fs::{is_binary, FileSystem, FsError, Node, READ};
+use crate::path::{path_string, resolve_path};
+
+pub struct Mkdir;
+
+impl Command for Mkdir {
+    fn name(&self) -> &'static str {
+        "mkdir"
+    }
+
+    fn help(&self) -> &'static str {
+        "mkdir <name>..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "make directories",
+            description: "Creates each directory named. Their parents must already exist.",
+            examples: &[("mkdir notes drafts", "Make two directories in the current one.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Directories
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        if args.is_empty() {
+            return CommandResult::error("mkdir: missing operand");
+        }
+        for arg in args {
+            let path = resolve_path(&ctx.state.cwd, arg);
+            if let Err(error) = ctx.state.fs.mkdir(&path) {
+                return CommandResult::fs_error("mkdir", error);
+            }
+        }
+        CommandResult::empty()
+    }
+}
+
+pub struct Touch;
+
+impl Command for Touch {
+    fn name(&self) -> &'static str {
+        "touch"
+    }
+
+    fn help(&self) -> &'static str {
+        "touch [-c] [-t STAMP | -d DATE] <name>..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "create files or update their times",
+            description: "Creates each file that does not exist, empty, and sets the modification time of \
+                those that do to now. With `-c`, files that do not exist are skipped instead of created.\n\n\
+                `-t` sets the time to a stamp written `[[CC]YY]MMDDhhmm[.ss]`, in this year unless it \
+                says otherwise; `-d` to a date written `YYYY-MM-DD`, with an optional `HH:MM[:SS]` \
+                after it, or `@` and seconds since 1970. Times are in UTC.",
+            examples: &[
+                ("touch todo.txt", "Create an empty todo.txt, or mark it as just modified."),
+                ("touch -c *.log", "Mark the existing logs as modified, creating none."),
+                ("touch -t 202401311200 report.txt", "Date report.txt noon on 31 January 2024."),
+                ("touch -d '2024-01-31 12:00' a b", "Give a and b the same time."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut no_create = false;
+        let mut modified = None;
+        let mut targets = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+                targets.push(arg);
+                continue;
+            };
+            for (index, flag) in flags.char_indices() {
+                match flag {
+                    'c' => no_create = true,
+                    't' | 'd' => {
+                        // The value is the rest of this argument, or the next one.
+                        let value = match &flags[index + 1..] {
+                            "" => args.next().map(String::as_str),
+                            rest => Some(rest),
+                        };
+                        let Some(value) = value else {
+                            let message = format!("touch: option requires an argument -- '{}'", flag);
+                            return CommandResult::error(message).with_exit_code(EXIT_USAGE);
+                        };
+                        let now = ctx.state.fs.clock().now_millis();
+                        modified = match flag {
+                            't' => touch_stamp(value, now),
+                            _ => clock::parse_date(value),
+                        };
+                        if modified.is_none() {
+                            return CommandResult::error(format!("touch: invalid date format '{}'", value));
+                        }
+                        break;
+                    }
+                    other => {
+                        let message = format!("touch: invalid option -- '{}'\nusage: {}", other, self.help());
+                        return CommandResult::error(message).with_exit_code(EXIT_USAGE);
+                    }
+                }
+            }
+        }
+        if targets.is_empty() {
+            return CommandResult::error("touch: missing operand");
+        }
+        for target in targets {
+            let path = resolve_path(&ctx.state.cwd, target);
+            if no_create && ctx.state.fs.get_node(&path).is_none() {
+                continue;
+            }
+            if let Err(error) = ctx.state.fs.touch(&path, modified) {
+                return CommandResult::fs_error("touch", error);
+            }
+        }
+        CommandResult::empty()
+    }
+}
+
+/// The time a `touch -t` stamp, `[[CC]YY]MMDDhhmm[.ss]`, names; a stamp
+/// without a year is in the year of `now`.
+fn touch_stamp(stamp: &str, now: u64) -> Option<u64> {
+    let (digits, second) = match stamp.split_once('.') {
+        Some((digits, second)) if second.len() == 2 => (digits, second.parse().ok()?),
+        Some(_) => return None,
+        None => (stamp, 0),
+    };
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let number = |range: std::ops::Range<usize>| digits[range].parse::<u32>().ok();
+    let (year, rest) = match digits.len() {
+        8 => (clock::civil_from_days((now / 86_400_000) as i64).0, 0),
+        // Two-digit years from 69 are in the 1900s, as POSIX has it.
+        10 => match number(0..2)? {
+            year if year >= 69 => (1900 + i64::from(year), 2),
+            year => (2000 + i64::from(year), 2),
+        },
+        12 => (i64::from(number(0..4)?), 4),
+        _ => return None,
+    };
+    let (month, day) = (number(rest..rest + 2)?, number(rest + 2..rest + 4)?);
+    let (hour, minute) = (number(rest + 4..rest + 6)?, number(rest + 6..rest + 8)?);
+    clock::timestamp(year, month, day, hour, minute, second)
+}
+
+pub struct Rm;
+
+impl Command for Rm {
+    fn name(&self) -> &'static str {
+        "rm"
+    }
+
+    fn help(&self) -> &'static str {
+        "rm [-r] [-f] <path>..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "remove files",
+            description: "Removes each file or symlink named. `-r` removes directories and everything in \
+                them, and `-f` ignores paths that do not exist.",
+            examples: &[
+                ("rm old.txt", "Delete old.txt."),
+                ("rm -rf build", "Delete the build directory, if there is one."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut recursive = false;
+        let mut force = false;
+        let mut targets = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'r' | 'R' => recursive = true,
+                            'f' => force = true,
+                            other => {
+                                return CommandResult::error(format!("rm: invalid option -- '{}'", other));
+                            }
+                        }
+                    }
+                }
+                _ => targets.push(arg),
+            }
+        }
+        if targets.is_empty() {
+            return if force {
+                CommandResult::empty()
+            } else {
+                CommandResult::error("rm: missing operand")
+            };
+        }
+        for target in targets {
+            let path = resolve_path(&ctx.state.cwd, target);
+            if !path.is_empty() && ctx.state.cwd.starts_with(&path) {
+                return CommandResult::error(format!(
+                    "rm: cannot remove '{}': current directory is inside it",
+                    target
+                ));
+            }
+            if force && ctx.state.fs.get_node(&path).is_none() {
+                continue;
+            }
+            if let Err(error) = ctx.state.fs.remove(&path, recursive) {
+                return CommandResult::fs_error("rm", error);
+            }
+        }
+        CommandResult::empty()
+    }
+}
+
+pub struct Rmdir;
+
+impl Command for Rmdir {
+    fn name(&self) -> &'static str {
+        "rmdir"
+    }
+
+    fn help(&self) -> &'static str {
+        "rmdir <dir>..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "remove empty directories",
+            description: "Removes each directory named, which must be empty.",
+            examples: &[("rmdir drafts", "Delete the empty drafts directory.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Directories
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        if args.is_empty() {
+            return CommandResult::error("rmdir: missing operand");
+        }
+        for arg in args {
+            let path = resolve_path(&ctx.state.cwd, arg);
+            if !path.is_empty() && ctx.state.cwd.starts_with(&path) {
+                return CommandResult::error(format!(
+                    "rmdir: cannot remove '{}': current directory is inside it",
+                    arg
+                ));
+            }
+            if let Err(error) = ctx.state.fs.remove_dir(&path) {
+                return CommandResult::fs_error("rmdir", error);
+            }
+        }
+        CommandResult::empty()
+    }
+}
+
+pub struct Cp;
+
+impl Command for Cp {
+    fn name(&self) -> &'static str {
+        "cp"
+    }
+
+    fn help(&self) -> &'static str {
+        "cp [-r] <source>... <dest>"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "copy files",
+            description: "Copies `source` to `dest`, or several sources into the directory `dest`. `-r` \
+                copies directories and their contents.",
+            examples: &[
+                ("cp notes.txt backup.txt", "Make a copy of notes.txt."),
+                ("cp -r src src.bak", "Copy a whole directory."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let recursive = args.iter().any(|arg| arg == "-r" || arg == "-R");
+        let operands: Vec<&String> = args.iter().filter(|arg| *arg != "-r" && *arg != "-R").collect();
+        if let (false, Some((_, sources))) = (recursive, operands.split_last()) {
+            for source in sources {
+                let path = resolve_path(&ctx.state.cwd, source);
+                if let Some(Node::Dir { .. }) = ctx.state.fs.get_node(&path) {
+                    return CommandResult {
+                        error_code: Some(FsError::IsADirectory.code()),
+                        ..CommandResult::error(format!("cp: -r not specified; omitting directory '{}'", source))
+                    };
+                }
+            }
+        }
+        transfer(ctx, "cp", &operands, |fs, src, dst| fs.copy(src, dst, recursive))
+    }
+}
+
+pub struct Mv;
+
+impl Command for Mv {
+    fn name(&self) -> &'static str {
+        "mv"
+    }
+
+    fn help(&self) -> &'static str {
+        "mv <source>... <dest>"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "move or rename files",
+            description: "Renames `source` to `dest`, or moves several sources into the directory `dest`.",
+            examples: &[
+                ("mv draft.txt final.txt", "Rename a file."),
+                ("mv *.log logs", "Move every log into the logs directory."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let operands: Vec<&String> = args.iter().collect();
+        if let Some((_, sources)) = operands.split_last() {
+            for source in sources {
+                let path = resolve_path(&ctx.state.cwd, source);
+                if !path.is_empty() && ctx.state.cwd.starts_with(&path) {
+                    return CommandResult::error(format!(
+                        "mv: cannot move '{}': current directory is inside it",
+                        source
+                    ));
+                }
+            }
+        }
+        transfer(ctx, "mv", &operands, |fs, src, dst| fs.rename(src, dst))
+    }
+}
+
+/// Shared operand handling for `cp` and `mv`: several sources need an
+/// existing directory as the destination.
+fn transfer(
+    ctx: &mut CommandContext<'_>,
+    tool: &str,
+    operands: &[&String],
+    mut apply: impl FnMut(&mut FileSystem, &[String], &[String]) -> Result<(), FsError>,
+) -> CommandResult {
+    let Some((dest, sources)) = operands.split_last() else {
+        return CommandResult::error(format!("{}: missing file operand", tool));
+    };
+    if sources.is_empty() {
+        return CommandResult::error(format!(
+            "{}: missing destination file operand after '{}'",
+            tool, dest
+        ));
+    }
+    let dest_path = resolve_path(&ctx.state.cwd, dest);
+    if sources.len() > 1 && !matches!(ctx.state.fs.get_node(&dest_path), Some(Node::Dir { .. })) {
+        return CommandResult::error(format!("{}: target '{}' is not a directory", tool, dest));
+    }
+    for source in sources {
+        let source_path = resolve_path(&ctx.state.cwd, source);
+        if let Err(error) = apply(&mut ctx.state.fs, &source_path, &dest_path) {
+            return CommandResult::fs_error(tool, error);
+        }
+    }
+    CommandResult::empty()
+}
+
+pub struct Ln;
+
+impl Command for Ln {
+    fn name(&self) -> &'static str {
+        "ln"
+    }
+
+    fn help(&self) -> &'static str {
+        "ln -s <target> [link]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "make symbolic links",
+            description: "Creates a symbolic link pointing at `target`, named `link` or, without one, \
+                after the last part of the target. Only symbolic links are supported, so `-s` is \
+                required.",
+            examples: &[("ln -s /var/log logs", "Make logs a shortcut to /var/log.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut symbolic = false;
+        let mut operands = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            's' => symbolic = true,
+                            _ => return CommandResult::error(format!("ln: invalid option -- '{}'", flag)),
+                        }
+                    }
+                }
+                _ => operands.push(arg),
+            }
+        }
+        if !symbolic {
+            return CommandResult::error("ln: hard links are not supported; use ln -s");
+        }
+        let (target, link) = match operands.as_slice() {
+            [] => return CommandResult::error("ln: missing file operand"),
+            [target] => (*target, "."),
+            [target, link] => (*target, link.as_str()),
+            _ => return CommandResult::error("ln: too many operands"),
+        };
+        // Like cp, a directory as the link name means a link inside it.
+        let mut path = resolve_path(&ctx.state.cwd, link);
+        if let Some(Node::Dir { .. }) = ctx.state.fs.get_node(&path) {
+            match target.trim_end_matches('/').rsplit('/').next() {
+                Some(name) if !name.is_empty() && name != "." && name != ".." => path.push(name.to_string()),
+                _ => return CommandResult::error(format!("ln: '{}': File exists", link)),
+            }
+        }
+        match ctx.state.fs.symlink(target, &path) {
+            Ok(()) => CommandResult::empty(),
+            Err(error) => CommandResult::fs_error("ln", error),
+        }
+    }
+}
+
+pub struct Readlink;
+
+impl Command for Readlink {
+    fn name(&self) -> &'static str {
+        "readlink"
+    }
+
+    fn help(&self) -> &'static str {
+        "readlink [-f] <path>..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "show where a symlink points",
+            description: "Prints the target of each symbolic link. `-f` follows every link in the path \
+                instead and prints the absolute path it ends at.",
+            examples: &[
+                ("readlink logs", "Show what logs points to."),
+                ("readlink -f logs", "Show the real path behind logs."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let canonical = args.iter().any(|arg| arg == "-f");
+        let paths: Vec<&String> = args.iter().filter(|arg| *arg != "-f").collect();
+        if paths.is_empty() {
+            return CommandResult::error("readlink: missing operand");
+        }
+        let mut lines = Vec::new();
+        for arg in paths {
+            let path = resolve_path(&ctx.state.cwd, arg);
+            if canonical {
+                match ctx.state.fs.resolve_links(&path, true) {
+                    Ok(resolved) => lines.push(path_string(&resolved)),
+                    Err(error) => return CommandResult::fs_error(&format!("readlink: {}", arg), error),
+                }
+                continue;
+            }
+            match ctx.state.fs.get_node_nofollow(&path) {
+                Some(Node::Symlink { target, .. }) => lines.push(target.clone()),
+                Some(_) => return CommandResult::error(format!("readlink: {}: not a symbolic link", arg)),
+                None => return CommandResult::error(format!("readlink: {}: No such file or directory", arg)),
+            }
+        }
+        CommandResult::ok(lines.join("\n"))
+    }
+}
+
+pub struct Cat;
+
+impl Command for Cat {
+    fn name(&self) -> &'static str {
+        "cat"
+    }
+
+    fn help(&self) -> &'static str {
+        "cat [-v] [file]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "print files",
+            description: "Prints each file in turn, or the piped input when no file is given. `-v` shows \
+                control characters as `^X` and other non-ASCII bytes as `M-` sequences, so binary files \
+                can be looked at safely.",
+            examples: &[
+                ("cat notes.txt", "Print notes.txt."),
+                ("cat header.txt body.txt > page.txt", "Join two files into a third."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut show_nonprinting = false;
+        let mut files = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'v' => show_nonprinting = true,
+                            other => return CommandResult::error(format!("cat: invalid option -- '{}'", other)),
+                        }
+                    }
+                }
+                _ => files.push(arg),
+            }
+        }
+        if files.is_empty() {
+            return match ctx.stdin.take() {
+                Some(input) => CommandResult::ok(input),
+                None => CommandResult::error("cat: missing operand"),
+            };
+        }
+        let mut parts = Vec::new();
+        for file in files {
+            if file == "-" {
+                parts.push(ctx.stdin.take().unwrap_or_default());
+                continue;
+            }
+            let path = resolve_path(&ctx.state.cwd, file);
+            match ctx.state.fs.read_bytes(&path) {
+                Ok(content) if show_nonprinting => parts.push(nonprinting(&content.bytes())),
+                // Raw bytes would only garble the terminal.
+                Ok(content) if is_binary(&content.bytes()) => parts.push(format!(
+                    "cat: {}: binary file ({} bytes) not shown; use cat -v to see it",
+                    file,
+                    content.len()
+                )),
+                Ok(content) => parts.push(String::from_utf8_lossy(&content.bytes()).into_owned()),
+                Err(error) => return CommandResult::fs_error("cat", error),
+            }
+        }
+        CommandResult::ok(parts.join("\n"))
+    }
+}
+
+/// `content` with control characters as `^X` and high bytes as `M-`, as
+/// `cat -v` shows them. Newlines and tabs stay as they are.
+fn nonprinting(content: &[u8]) -> String {
+    let mut text = String::new();
+    for &byte in content {
+        let low = if byte >= 0x80 {
+            text.push_str("M-");
+            byte - 0x80
+        } else {
+            byte
+        };
+        match low {
+            b'\n' | b'\t' if byte < 0x80 => text.push(low as char),
+            0x7f => text.push_str("^?"),
+            0..=0x1f => {
+                text.push('^');
+                text.push((low + 0x40) as char);
+            }
+            _ => text.push(low as char),
+        }
+    }
+    text
+}
+
+pub struct Cmp;
+
+impl Command for Cmp {
+    fn name(&self) -> &'static str {
+        "cmp"
+    }
+
+    fn help(&self) -> &'static str {
+        "cmp [-s] <file1> <file2>"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "compare two files byte by byte",
+            description: "Reports the first byte and line at which two files differ, exiting with status \
+                1, or exits with status 0 and prints nothing when they are the same. `-s` prints nothing \
+                either way.",
+            examples: &[("cmp old.txt new.txt", "Find where two files start to differ.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let silent = args.iter().any(|arg| arg == "-s");
+        let files: Vec<&String> = args.iter().filter(|arg| *arg != "-s").collect();
+        // As in cmp(1): 1 means the files differ, 2 that they could not be compared.
+        if files.len() != 2 {
+            return CommandResult::error("cmp: expected two file operands").with_exit_code(EXIT_USAGE);
+        }
+        let mut contents = Vec::new();
+        for file in &files {
+            let path = resolve_path(&ctx.state.cwd, file);
+            match ctx.state.fs.read_bytes(&path) {
+                Ok(content) => contents.push(content),
+                Err(err) => {
+                    return CommandResult::error(format!("cmp: {}: {}", file, err)).with_exit_code(EXIT_USAGE);
+                }
+            }
+        }
+        // The first differing byte is cmp's output; running out of one file is reported as an error.
+        match compare_bytes(files[0], &contents[0].bytes(), files[1], &contents[1].bytes()) {
+            Some(_) if silent => CommandResult::error(""),
+            Some(Ok(report)) => CommandResult::ok(report).with_exit_code(1),
+            Some(Err(notice)) => CommandResult::error(notice),
+            None => CommandResult::empty(),
+        }
+    }
+}
+
+fn compare_bytes(
+    left_name: &str,
+    left: &[u8],
+    right_name: &str,
+    right: &[u8],
+) -> Option<Result<String, String>> {
+    let mut line = 1;
+    for (index, (a, b)) in left.iter().zip(right.iter()).enumerate() {
+        if a != b {
+            return Some(Ok(format!(
+                "{} {} differ: byte {}, line {}",
+                left_name,
+                right_name,
+                index + 1,
+                line
+            )));
+        }
+        if *a == b'\n' {
+            line += 1;
+        }
+    }
+
+    let shorter = match left.len().cmp(&right.len()) {
+        std::cmp::Ordering::Less => left_name,
+        std::cmp::Ordering::Greater => right_name,
+        std::cmp::Ordering::Equal => return None,
+    };
+    let common = left.len().min(right.len());
+    if common == 0 {
+        Some(Err(format!("cmp: EOF on {} which is empty", shorter)))
+    } else {
+        Some(Err(format!("cmp: EOF on {} after byte {}, line {}", shorter, common, line)))
+    }
+}
+
+pub struct Diff;
+
+impl Command for Diff {
+    fn name(&self) -> &'static str {
+        "diff"
+    }
+
+    fn help(&self) -> &'static str {
+        "diff [-u] [-r] [-q] <from> <to>"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "compare files line by line",
+            description: "Shows the lines that differ between two files as a unified diff: each hunk starts \
+                with `@@ -l,n +l,n @@`, giving where it begins and how many lines it spans in each \
+                file, and lists lines removed from `from` with `-`, lines added in `to` with `+` and \
+                up to three unchanged lines either side. `-u` is accepted for compatibility.\n\n\
+                Given two directories, it compares the files they both hold and names those only one \
+                has; `-r` goes on into their subdirectories. Given a file and a directory, it compares \
+                the file with the one of the same name in the directory. `-q` only says which files \
+                differ.\n\n\
+                The exit status is 0 when there are no differences, 1 when there are and 2 when \
+                something could not be compared.",
+            examples: &[
+                ("diff notes.txt notes.bak", "Show what changed between two versions of a file."),
+                ("diff -r project project.old", "Compare two directory trees."),
+                ("diff -q a.txt b.txt", "Only report whether the files differ."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut recursive = false;
+        let mut brief = false;
+        let mut operands = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'u' => {}
+                            'r' => recursive = true,
+                            'q' => brief = true,
+                            other => {
+                                return CommandResult::error(format!("diff: invalid option -- '{}'", other))
+                                    .with_exit_code(EXIT_USAGE);
+                            }
+                        }
+                    }
+                }
+                _ => operands.push(arg.as_str()),
+            }
+        }
+        let [from, to] = operands[..] else {
+            return CommandResult::error(format!("usage: {}", self.help())).with_exit_code(EXIT_USAGE);
+        };
+        let fs = &ctx.state.fs;
+        let mut comparison = Comparison {
+            fs,
+            cwd: &ctx.state.cwd,
+            recursive,
+            brief,
+            paint: ctx.state.color_enabled(),
+            lines: Vec::new(),
+            differ: false,
+            errors: Vec::new(),
+        };
+        let nodes = [from, to].map(|operand| fs.get_node(&resolve_path(&ctx.state.cwd, operand)));
+        match nodes {
+            [None, _] | [_, None] => {
+                let missing = if nodes[0].is_none() { from } else { to };
+                comparison.trouble(missing, FsError::NotFound);
+            }
+            [Some(old), Some(new @ Node::Dir { .. })] if !matches!(old, Node::Dir { .. }) => {
+                // As in GNU diff, `diff file dir` means `diff file dir/file`.
+                let name = format!("{}/{}", to.trim_end_matches('/'), base_name(from));
+                match child(new, base_name(from)) {
+                    Some(new) => comparison.compare(from, old, &name, new, false),
+                    None => comparison.trouble(&name, FsError::NotFound),
+                }
+            }
+            [Some(old @ Node::Dir { .. }), Some(new)] if !matches!(new, Node::Dir { .. }) => {
+                let name = format!("{}/{}", from.trim_end_matches('/'), base_name(to));
+                match child(old, base_name(to)) {
+                    Some(old) => comparison.compare(&name, old, to, new, false),
+                    None => comparison.trouble(&name, FsError::NotFound),
+                }
+            }
+            [Some(old), Some(new)] => comparison.compare(from, old, to, new, false),
+        }
+        // As in diff(1): 1 means the files differ, 2 that something could not be compared.
+        let exit_code = if !comparison.errors.is_empty() {
+            2
+        } else if comparison.differ {
+            1
+        } else {
+            0
+        };
+        CommandResult::ok(comparison.lines.join("\n"))
+            .with_errors(comparison.errors)
+            .with_exit_code(exit_code)
+    }
+}
+
+/// What `diff` has found so far.
+struct Comparison<'a> {
+    /// Where the files are read from, by the names they are shown under.
+    fs: &'a FileSystem,
+    cwd: &'a [String],
+    recursive: bool,
+    brief: bool,
+    paint: bool,
+    lines: Vec<String>,
+    differ: bool,
+    /// What could not be compared, for the error output.
+    errors: Vec<String>,
+}
+
+impl Comparison<'_> {
+    /// Compares two nodes; `nested` when they were found inside directories
+    /// being compared, which labels each file's diff.
+    fn compare(&mut self, from: &str, old: &Node, to: &str, new: &Node, nested: bool) {
+        match (old, new) {
+            (Node::File { .. }, Node::File { .. }) => {
+                let read = |name| {
+                    self.fs.read_bytes(&resolve_path(self.cwd, name)).map_err(|error| (name, error))
+                };
+                match read(from).and_then(|old| Ok((old, read(to)?))) {
+                    Ok((old, new)) => self.files(from, &old.bytes(), to, &new.bytes(), nested),
+                    Err((name, error)) => self.trouble(name, error),
+                }
+            }
+            (Node::Dir { children: old, .. }, Node::Dir { children: new, .. }) => {
+                if nested && !self.recursive {
+                    self.lines.push(format!("Common subdirectories: {} and {}", from, to));
+                    return;
+                }
+                // Listing a directory needs read permission, as for `ls`.
+                for dir in [from, to] {
+                    if !self.fs.permitted(&resolve_path(self.cwd, dir), READ) {
+                        return self.trouble(dir, FsError::PermissionDenied);
+                    }
+                }
+                let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+                names.sort();
+                names.dedup();
+                for name in names {
+                    let from_name = format!("{}/{}", from.trim_end_matches('/'), name);
+                    let to_name = format!("{}/{}", to.trim_end_matches('/'), name);
+                    match (old.get(name), new.get(name)) {
+                        (Some(old), Some(new)) => self.compare(&from_name, old, &to_name, new, true),
+                        (Some(_), None) => self.only_in(from, name),
+                        (None, Some(_)) => self.only_in(to, name),
+                        (None, None) => {}
+                    }
+                }
+            }
+            (Node::Symlink { target: old, .. }, Node::Symlink { target: new, .. }) => {
+                if old != new {
+                    self.differ = true;
+                    self.lines.push(format!("Symbolic links {} and {} differ", from, to));
+                }
+            }
+            _ => {
+                self.differ = true;
+                self.lines.push(format!(
+                    "File {} is a {} while file {} is a {}",
+                    from,
+                    kind(old),
+                    to,
+                    kind(new)
+                ));
+            }
+        }
+    }
+
+    fn files(&mut self, from: &str, old: &[u8], to: &str, new: &[u8], nested: bool) {
+        if old == new {
+            return;
+        }
+        self.differ = true;
+        if self.brief {
+            self.lines.push(format!("Files {} and {} differ", from, to));
+            return;
+        }
+        if is_binary(old) || is_binary(new) {
+            self.lines.push(format!("Binary files {} and {} differ", from, to));
+            return;
+        }
+        if nested {
+            let flag = if self.recursive { " -r" } else { "" };
+            self.lines.push(format!("diff{} {} {}", flag, from, to));
+        }
+        let (old, new) = (String::from_utf8_lossy(old), String::from_utf8_lossy(new));
+        self.lines.extend(diff::unified(from, to, &old, &new, self.paint));
+    }
+
+    fn only_in(&mut self, dir: &str, name: &str) {
+        self.differ = true;
+        self.lines.push(format!("Only in {}: {}", dir, name));
+    }
+
+    fn trouble(&mut self, name: &str, error: FsError) {
+        self.errors.push(format!("diff: {}: {}", name, error));
+    }
+}
+
+fn child<'a>(dir: &'a Node, name: &str) -> Option<&'a Node> {
+    match dir {
+        Node::Dir { children, .. } => children.get(name),
+        _ => None,
+    }
+}
+
+fn base_name(path: &str) -> &str {
+    let path = path.trim_end_matches('/');
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn kind(node: &Node) -> &'static str {
+    match node {
+        Node::Dir { .. } => "directory",
+        Node::File { .. } => "regular file",
+        Node::Symlink { .. } => "symbolic link",
+    }
+}
+
+pub struct Shred;
+
+impl Command for Shred {
+    fn name(&self) -> &'static str {
+        "shred"
+    }
+
+    fn help(&self) -> &'static str {
+        "shred [-u] [-z] [-n N] <file>..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "overwrite files to hide their contents",
+            description: "Overwrites each file with random bytes three times, or N times with `-n`. `-z` \
+                finishes with a pass of zeros and `-u` removes the file afterwards.\n\n\
+                Only the file itself is overwritten: other copies of it, such as one made with `cp`, \
+                keep what it held. The copies `vcs` commits keep are the exception. Every file in a \
+                commit with the same content is removed, and shred says how many went, so `vcs \
+                checkout` cannot bring it back.",
+            examples: &[("shred -u secret.txt", "Destroy secret.txt and remove it.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut remove = false;
+        let mut zero = false;
+        let mut passes = 3;
+        let mut files = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-u" => remove = true,
+                "-z" => zero = true,
+                "-n" => match args.next().and_then(|value| value.parse::<usize>().ok()) {
+                    Some(value) => passes = value,
+                    None => return CommandResult::error("shred: invalid number of passes"),
+                },
+                _ => files.push(arg),
+            }
+        }
+        if files.is_empty() {
+            return CommandResult::error("shred: missing file operand");
+        }
+        let mut notices = Vec::new();
+        for file in files {
+            let path = resolve_path(&ctx.state.cwd, file);
+            let original = ctx.state.fs.read_bytes(&path).ok().filter(|content| !content.is_empty());
+            if let Err(error) = ctx.state.fs.shred(&path, passes, zero, remove) {
+                return CommandResult::fs_error("shred", error);
+            }
+            let Some(original) = original else {
+                continue;
+            };
+            let state = &mut *ctx.state;
+            let mut purged = 0;
+            for repository in state.repositories.values_mut() {
+                for node in repository.purge(&original) {
+                    state.fs.release(&node);
+                    purged += 1;
+                }
+            }
+            if purged > 0 {
+                notices.push(format!("shred: {}: removed {} copies kept by vcs", file, purged));
+            }
+        }
+        CommandResult::empty().with_errors(notices)
+    }
+}
+
+pub struct Chmod;
+
+impl Command for Chmod {
+    fn name(&self) -> &'static str {
+        "chmod"
+    }
+
+    fn help(&self) -> &'static str {
+        "chmod [-R] <mode> <path>..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "change file permissions",
+            description: "Sets the permissions of each path, either as an octal mode such as `644` or \
+                symbolically, such as `u+x` or `go-w`. `-R` changes directories and everything in them.\n\n\
+                Permissions are enforced: a file without `r` cannot be read, one without `w` cannot be \
+                written, and a directory without `x` cannot be entered.",
+            examples: &[
+                ("chmod 600 notes.txt", "Make a file readable and writable by you alone."),
+                ("chmod -R go-rwx private", "Keep a directory to yourself."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let recursive = args.first().is_some_and(|arg| arg == "-R");
+        let args = if recursive { &args[1..] } else { args };
+        let Some((spec, targets)) = args.split_first() else {
+            return CommandResult::error("chmod: missing operand");
+        };
+        if targets.is_empty() {
+            return CommandResult::error(format!("chmod: missing operand after '{}'", spec));
+        }
+        for target in targets {
+            let path = resolve_path(&ctx.state.cwd, target);
+            let Some(node) = ctx.state.fs.get_node(&path) else {
+                return CommandResult::error(format!(
+                    "chmod: cannot access '{}': No such file or directory",
+                    target
+                ));
+            };
+            let mut changes = Vec::new();
+            collect_modes(node, path, recursive, &mut changes);
+            for (path, mode, is_dir) in changes {
+                let mode = match apply_mode(spec, mode, is_dir) {
+                    Ok(mode) => mode,
+                    Err(message) => return CommandResult::error(message),
+                };
+                if let Err(error) = ctx.state.fs.set_mode(&path, mode) {
+                    return CommandResult::fs_error("chmod", error);
+                }
+            }
+        }
+        CommandResult::empty()
+    }
+}
+
+/// Lists `node` (and with `recursive`, everything beneath it) as
+/// `(path, current mode, is directory)`.
+fn collect_modes(node: &Node, path: Vec<String>, recursive: bool, out: &mut Vec<(Vec<String>, u32, bool)>) {
+    out.push((path.clone(), node.meta().mode, matches!(node, Node::Dir { .. })));
+    if let (true, Node::Dir { children, .. }) = (recursive, node) {
+        for (name, child) in children {
+            let mut child_path = path.clone();
+            child_path.push(name.clone());
+            collect_modes(child, child_path, recursive, out);
+        }
+    }
+}
+
+/// Applies an octal (`755`) or symbolic (`u+x,go-w`, `a=r`) mode to `mode`.
+fn apply_mode(spec: &str, mode: u32, is_dir: bool) -> Result<u32, String> {
+    let invalid = || format!("chmod: invalid mode: '{}'", spec);
+    if !spec.is_empty() && spec.chars().all(|ch| ch.is_digit(8)) {
+        return u32::from_str_radix(spec, 8)
+            .ok()
+            .filter(|mode| *mode <= 0o7777)
+            .map(|mode| mode & 0o777)
+            .ok_or_else(invalid);
+    }
+    let mut mode = mode;
+    for clause in spec.split(',') {
+        let mut chars = clause.chars().peekable();
+        let mut who = 0;
+        while let Some(ch) = chars.next_if(|ch| "ugoa".contains(*ch)) {
+            who |= match ch {
+                'u' => 0o700,
+                'g' => 0o070,
+                'o' => 0o007,
+                _ => 0o777,
+            };
+        }
+        if who == 0 {
+            who = 0o777;
+        }
+        if chars.peek().is_none() {
+            return Err(invalid());
+        }
+        while let Some(op) = chars.next() {
+            if !"+-=".contains(op) {
+                return Err(invalid());
+            }
+            let mut bits = 0;
+            while let Some(ch) = chars.next_if(|ch| "rwxX".contains(*ch)) {
+                bits |= match ch {
+                    'r' => 0o444,
+                    'w' => 0o222,
+                    'x' => 0o111,
+                    // Execute only for directories and already-executable files.
+                    _ if is_dir || mode & 0o111 != 0 => 0o111,
+                    _ => 0,
+                };
+            }
+            let bits = bits & who;
+            mode = match op {
+                '+' => mode | bits,
+                '-' => mode & !bits,
+                _ => (mode & !who) | bits,
+            };
+        }
+    }
+    Ok(mode)
+}
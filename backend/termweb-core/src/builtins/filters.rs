@@ -0,0 +1,774 @@
+//! Line-oriented filters for pipelines: `sort`, `uniq`, `shuf`, `cut` and
+//! `tr`, and `xargs`, which turns its input into a command's arguments.
+
+use std::cmp::Ordering;
+
+use super::text::{read_inputs, report};
+use crate::command::{Command, CommandContext, CommandResult, Completion, Manual, EXIT_NOT_FOUND, EXIT_USAGE};
+
+pub struct Sort;
+
+impl Command for Sort {
+    fn name(&self) -> &'static str {
+        "sort"
+    }
+
+    fn help(&self) -> &'static str {
+        "sort [-r] [-n] [-u] [-k N] [-t sep] [file]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "sort lines",
+            description: "Prints the lines of every file, or of the piped input, in order. `-n` compares \
+                the numbers lines start with instead of their text, `-r` reverses the order and `-u` \
+                prints each distinct line once.\n\n\
+                `-k N` sorts on the text from field N to the end of the line, fields being separated \
+                by blanks or, with `-t`, by `sep`. Lines with equal keys are ordered by their whole text.",
+            examples: &[
+                ("sort names.txt", "Print names.txt in alphabetical order."),
+                ("du ~ | sort -rn", "List directories largest first."),
+                ("sort -t , -k 2 -n scores.csv", "Sort a CSV file on its second column."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let (options, files) = match parse_options("sort", args, "rnu", "kt") {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+        let mut key = SortKey::default();
+        let (mut reverse, mut unique) = (false, false);
+        for (flag, value) in options {
+            match (flag, value) {
+                ('r', _) => reverse = true,
+                ('n', _) => key.numeric = true,
+                ('u', _) => unique = true,
+                ('k', Some(field)) => match field.parse::<usize>() {
+                    Ok(field) if field > 0 => key.field = field,
+                    _ => return usage_error(format!("sort: invalid field '{}'", field)),
+                },
+                ('t', Some(separator)) => match one_char(&separator) {
+                    Some(separator) => key.separator = Some(separator),
+                    None => return usage_error("sort: the separator must be a single character"),
+                },
+                _ => {}
+            }
+        }
+
+        let inputs = match read_inputs(ctx, "sort", &files) {
+            Ok(inputs) => inputs,
+            Err(result) => return result,
+        };
+        let mut text = String::new();
+        let mut failures = Vec::new();
+        for (name, content) in inputs {
+            match content {
+                Ok(content) => {
+                    text.push_str(&String::from_utf8_lossy(&content.bytes()));
+                    if !text.is_empty() && !text.ends_with('\n') {
+                        text.push('\n');
+                    }
+                }
+                Err(error) => failures.push((name, error)),
+            }
+        }
+        let mut lines: Vec<&str> = text.lines().collect();
+        lines.sort_by(|a, b| key.compare(a, b));
+        if unique {
+            lines.dedup_by(|a, b| key.compare(a, b) == Ordering::Equal);
+        }
+        if reverse {
+            lines.reverse();
+        }
+        report("sort", lines.into_iter().map(str::to_string).collect(), failures)
+    }
+}
+
+/// What `sort` compares lines on.
+struct SortKey {
+    /// Fields from this one (counting from 1) to the end of the line.
+    field: usize,
+    /// `None` for runs of blanks.
+    separator: Option<char>,
+    numeric: bool,
+}
+
+impl Default for SortKey {
+    fn default() -> Self {
+        SortKey {
+            field: 1,
+            separator: None,
+            numeric: false,
+        }
+    }
+}
+
+impl SortKey {
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        let (key_a, key_b) = (self.key(a), self.key(b));
+        let ordering = if self.numeric {
+            leading_number(key_a).total_cmp(&leading_number(key_b))
+        } else {
+            key_a.cmp(key_b)
+        };
+        // As coreutils does, fall back to the whole line.
+        ordering.then_with(|| a.cmp(b))
+    }
+
+    fn key<'a>(&self, line: &'a str) -> &'a str {
+        let mut rest = line;
+        for _ in 1..self.field {
+            let next = match self.separator {
+                Some(separator) => rest.split_once(separator).map(|(_, rest)| rest),
+                None => rest
+                    .trim_start()
+                    .find(char::is_whitespace)
+                    .map(|end| &rest.trim_start()[end..]),
+            };
+            match next {
+                Some(next) => rest = next,
+                None => return "",
+            }
+        }
+        match self.separator {
+            Some(_) => rest,
+            None => rest.trim_start(),
+        }
+    }
+}
+
+/// The number `text` starts with, e.g. `-1.5` for `-1.5kg`; 0 when there is none.
+fn leading_number(text: &str) -> f64 {
+    let text = text.trim_start();
+    let mut end = 0;
+    let mut seen_point = false;
+    for (index, ch) in text.char_indices() {
+        match ch {
+            '-' | '+' if index == 0 => {}
+            '.' if !seen_point => seen_point = true,
+            '0'..='9' => {}
+            _ => break,
+        }
+        end = index + ch.len_utf8();
+    }
+    text[..end].parse().unwrap_or(0.0)
+}
+
+pub struct Uniq;
+
+impl Command for Uniq {
+    fn name(&self) -> &'static str {
+        "uniq"
+    }
+
+    fn help(&self) -> &'static str {
+        "uniq [-c] [-d | -u] [file]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "collapse repeated lines",
+            description: "Prints the lines of `file`, or of the piped input, with each run of identical \
+                adjacent lines collapsed into one. Only adjacent lines are compared, so input is usually \
+                sorted first.\n\n\
+                `-c` prefixes each line with how many times it occurred, `-d` prints only lines that \
+                were repeated and `-u` only those that were not.",
+            examples: &[
+                ("sort words.txt | uniq", "Print each word once."),
+                ("sort words.txt | uniq -c | sort -rn", "Count the words, most frequent first."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let (options, files) = match parse_options("uniq", args, "cdu", "") {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+        if files.len() > 1 {
+            return usage_error(format!("uniq: extra operand '{}'", files[1]));
+        }
+        let has = |wanted: char| options.iter().any(|(flag, _)| *flag == wanted);
+        let (count, repeated, unrepeated) = (has('c'), has('d'), has('u'));
+
+        let inputs = match read_inputs(ctx, "uniq", &files) {
+            Ok(inputs) => inputs,
+            Err(result) => return result,
+        };
+        let mut lines = Vec::new();
+        let mut failures = Vec::new();
+        for (name, content) in inputs {
+            let content = match content {
+                Ok(content) => String::from_utf8_lossy(&content.bytes()).into_owned(),
+                Err(error) => {
+                    failures.push((name, error));
+                    continue;
+                }
+            };
+            let mut runs: Vec<(&str, usize)> = Vec::new();
+            for line in content.lines() {
+                match runs.last_mut() {
+                    Some((last, times)) if *last == line => *times += 1,
+                    _ => runs.push((line, 1)),
+                }
+            }
+            for (line, times) in runs {
+                if (repeated && times == 1) || (unrepeated && times > 1) {
+                    continue;
+                }
+                lines.push(if count { format!("{:>7} {}", times, line) } else { line.to_string() });
+            }
+        }
+        report("uniq", lines, failures)
+    }
+}
+
+pub struct Shuf;
+
+/// Numbers `shuf -i` will shuffle, so a huge range cannot exhaust memory.
+const MAX_SHUF_RANGE: u64 = 1_000_000;
+
+impl Command for Shuf {
+    fn name(&self) -> &'static str {
+        "shuf"
+    }
+
+    fn help(&self) -> &'static str {
+        "shuf [-n N] [-e word... | -i lo-hi | file]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "shuffle lines",
+            description: "Prints the lines of `file`, or of the piped input, in random order, or only \
+                the first N of them with `-n`. `-e` shuffles its arguments instead, one per line, and \
+                `-i lo-hi` the whole numbers from lo to hi.\n\n\
+                The order comes from the session's random values, so a deterministic session \
+                shuffles the same way every time.",
+            examples: &[
+                ("shuf -n 1 names.txt", "Pick a random line of names.txt."),
+                ("shuf -i 1-6 -n 1", "Roll a die."),
+                ("shuf -e red green blue", "Print three words in random order."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let (options, operands) = match parse_options("shuf", args, "e", "ni") {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+        let mut count = None;
+        let mut range = None;
+        let mut echo = false;
+        for (flag, value) in options {
+            match (flag, value) {
+                ('n', Some(value)) => match value.parse::<usize>() {
+                    Ok(value) => count = Some(value),
+                    Err(_) => return usage_error(format!("shuf: invalid line count: '{}'", value)),
+                },
+                ('i', Some(value)) => match shuf_range(&value) {
+                    Some(bounds) => range = Some(bounds),
+                    None => return usage_error(format!("shuf: invalid input range: '{}'", value)),
+                },
+                ('e', _) => echo = true,
+                _ => {}
+            }
+        }
+        if echo && range.is_some() {
+            return usage_error("shuf: cannot combine -e and -i options");
+        }
+
+        let mut lines: Vec<String> = if echo {
+            operands.iter().map(|word| word.to_string()).collect()
+        } else if let Some((low, high)) = range {
+            if let Some(extra) = operands.first() {
+                return usage_error(format!("shuf: extra operand '{}'", extra));
+            }
+            (low..=high).map(|number| number.to_string()).collect()
+        } else {
+            if operands.len() > 1 {
+                return usage_error(format!("shuf: extra operand '{}'", operands[1]));
+            }
+            let inputs = match read_inputs(ctx, "shuf", &operands) {
+                Ok(inputs) => inputs,
+                Err(result) => return result,
+            };
+            let mut lines = Vec::new();
+            let mut failures = Vec::new();
+            for (name, content) in inputs {
+                match content {
+                    Ok(content) => {
+                        let text = String::from_utf8_lossy(&content.bytes()).into_owned();
+                        lines.extend(text.lines().map(str::to_string));
+                    }
+                    Err(error) => failures.push((name, error)),
+                }
+            }
+            if !failures.is_empty() {
+                return report("shuf", Vec::new(), failures);
+            }
+            lines
+        };
+
+        // Fisher-Yates, drawing from the session's generator.
+        let rng = ctx.state.fs.rng();
+        for last in (1..lines.len()).rev() {
+            let pick = (rng.next_u64() % (last as u64 + 1)) as usize;
+            lines.swap(last, pick);
+        }
+        lines.truncate(count.unwrap_or(usize::MAX));
+        CommandResult::ok(lines.join("\n"))
+    }
+}
+
+/// `lo-hi` for `shuf -i`, as long as it is not backwards or too wide.
+fn shuf_range(text: &str) -> Option<(u64, u64)> {
+    let (low, high) = text.split_once('-')?;
+    let (low, high) = (low.parse::<u64>().ok()?, high.parse::<u64>().ok()?);
+    (low <= high.saturating_add(1) && high.saturating_sub(low) < MAX_SHUF_RANGE).then_some((low, high))
+}
+
+pub struct Cut;
+
+impl Command for Cut {
+    fn name(&self) -> &'static str {
+        "cut"
+    }
+
+    fn help(&self) -> &'static str {
+        "cut -f list [-d delim] [-s] | -c list [file]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "print selected parts of lines",
+            description: "Prints parts of each line of every file, or of the piped input. `-f` picks \
+                fields separated by tabs, or by `delim` with `-d`; lines without the delimiter are \
+                printed whole unless `-s` is given. `-c` picks characters instead.\n\n\
+                A list is numbers and ranges separated by commas, counting from 1: `1,3` is the first \
+                and third, `2-4` the second to fourth, `3-` the third onwards and `-2` the first two.",
+            examples: &[
+                ("cut -d : -f 1 users.txt", "Print the first colon-separated field of each line."),
+                ("cut -c 1-8 log.txt", "Print the first eight characters of each line."),
+                (
+                    "cat access.log | cut -d ' ' -f 1 | sort | uniq -c",
+                    "Count the requests from each address in a web server log.",
+                ),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let (options, files) = match parse_options("cut", args, "s", "cdf") {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+        let mut delimiter = '\t';
+        let mut only_delimited = false;
+        let mut selection = None;
+        for (flag, value) in options {
+            match (flag, value) {
+                ('s', _) => only_delimited = true,
+                ('d', Some(value)) => match one_char(&value) {
+                    Some(value) => delimiter = value,
+                    None => return usage_error("cut: the delimiter must be a single character"),
+                },
+                (flag @ ('c' | 'f'), Some(list)) => {
+                    if selection.is_some() {
+                        return usage_error("cut: only one type of list may be specified");
+                    }
+                    match parse_list(&list) {
+                        Some(ranges) => selection = Some((flag, ranges)),
+                        None => return usage_error(format!("cut: invalid list '{}'", list)),
+                    }
+                }
+                _ => {}
+            }
+        }
+        let Some((kind, ranges)) = selection else {
+            return usage_error("cut: you must specify a list of characters or fields");
+        };
+        let wanted = |position: usize| ranges.iter().any(|(start, end)| (*start..=*end).contains(&position));
+
+        let inputs = match read_inputs(ctx, "cut", &files) {
+            Ok(inputs) => inputs,
+            Err(result) => return result,
+        };
+        let mut lines = Vec::new();
+        let mut failures = Vec::new();
+        for (name, content) in inputs {
+            let content = match content {
+                Ok(content) => String::from_utf8_lossy(&content.bytes()).into_owned(),
+                Err(error) => {
+                    failures.push((name, error));
+                    continue;
+                }
+            };
+            for line in content.lines() {
+                if kind == 'c' {
+                    let picked = line.chars().enumerate().filter(|(index, _)| wanted(index + 1));
+                    lines.push(picked.map(|(_, ch)| ch).collect());
+                } else if !line.contains(delimiter) {
+                    if !only_delimited {
+                        lines.push(line.to_string());
+                    }
+                } else {
+                    let fields: Vec<&str> = line
+                        .split(delimiter)
+                        .enumerate()
+                        .filter(|(index, _)| wanted(index + 1))
+                        .map(|(_, field)| field)
+                        .collect();
+                    lines.push(fields.join(&delimiter.to_string()));
+                }
+            }
+        }
+        report("cut", lines, failures)
+    }
+}
+
+/// A `cut` list such as `1,3-5,7-` as inclusive ranges counting from 1.
+fn parse_list(list: &str) -> Option<Vec<(usize, usize)>> {
+    let position = |text: &str| text.parse::<usize>().ok().filter(|&position| position > 0);
+    list.split(',')
+        .map(|part| match part.split_once('-') {
+            Some(("", "")) => None,
+            Some((start, end)) => {
+                let start = if start.is_empty() { 1 } else { position(start)? };
+                let end = if end.is_empty() { usize::MAX } else { position(end)? };
+                (start <= end).then_some((start, end))
+            }
+            None => position(part).map(|position| (position, position)),
+        })
+        .collect()
+}
+
+pub struct Tr;
+
+impl Command for Tr {
+    fn name(&self) -> &'static str {
+        "tr"
+    }
+
+    fn help(&self) -> &'static str {
+        "tr [-d] [-s] <set1> [set2] [file]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "translate or delete characters",
+            description: "Copies the piped input, or each file, replacing every character in `set1` with \
+                the one at the same place in `set2`, whose last character is repeated if it is the \
+                shorter. `-d` deletes the characters in `set1` instead, and `-s` squeezes runs of a \
+                repeated character from the last set given into one.\n\n\
+                Sets can hold ranges such as `a-z`, the escapes `\\n`, `\\t` and `\\\\`, and the classes \
+                `[:lower:]`, `[:upper:]`, `[:alpha:]`, `[:digit:]`, `[:alnum:]`, `[:space:]` and \
+                `[:punct:]`.",
+            examples: &[
+                ("echo hello | tr a-z A-Z", "Print HELLO."),
+                ("tr -d '\\r' < dos.txt", "Strip carriage returns."),
+                ("tr -s ' ' < table.txt", "Collapse runs of spaces into one."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let (options, operands) = match parse_options("tr", args, "ds", "") {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+        let has = |wanted: char| options.iter().any(|(flag, _)| *flag == wanted);
+        let (delete, squeeze) = (has('d'), has('s'));
+        // Two sets to translate, or to delete and then squeeze.
+        let set_count = if delete && !squeeze || !delete && squeeze && operands.len() < 2 { 1 } else { 2 };
+        if operands.len() < set_count {
+            return usage_error("tr: missing operand");
+        }
+        let (sets, files) = operands.split_at(set_count);
+        let sets: Vec<Vec<char>> = match sets.iter().map(|set| expand_set(set)).collect() {
+            Ok(sets) => sets,
+            Err(message) => return usage_error(format!("tr: {}", message)),
+        };
+        if !delete && set_count == 2 && sets[1].is_empty() && !sets[0].is_empty() {
+            return usage_error("tr: when translating, the second set must not be empty");
+        }
+        let squeezed = if squeeze { sets.last() } else { None };
+
+        let inputs = match read_inputs(ctx, "tr", files) {
+            Ok(inputs) => inputs,
+            Err(result) => return result,
+        };
+        let mut output = String::new();
+        let mut failures = Vec::new();
+        for (name, content) in inputs {
+            let content = match content {
+                Ok(content) => String::from_utf8_lossy(&content.bytes()).into_owned(),
+                Err(error) => {
+                    failures.push((name, error));
+                    continue;
+                }
+            };
+            for ch in content.chars() {
+                let ch = if delete {
+                    if sets[0].contains(&ch) {
+                        continue;
+                    }
+                    ch
+                } else if set_count == 2 {
+                    match sets[0].iter().rposition(|&from| from == ch) {
+                        Some(index) => sets[1][index.min(sets[1].len() - 1)],
+                        None => ch,
+                    }
+                } else {
+                    ch
+                };
+                if squeezed.is_some_and(|set| set.contains(&ch)) && output.ends_with(ch) {
+                    continue;
+                }
+                output.push(ch);
+            }
+        }
+        // The output ends with a newline anyway.
+        if output.ends_with('\n') {
+            output.pop();
+        }
+        let lines = if output.is_empty() { Vec::new() } else { vec![output] };
+        report("tr", lines, failures)
+    }
+}
+
+pub struct Xargs;
+
+impl Command for Xargs {
+    fn name(&self) -> &'static str {
+        "xargs"
+    }
+
+    fn help(&self) -> &'static str {
+        "xargs [-n N] [-I <replace>] [command [argument]...]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "run a command with arguments from the input",
+            description: "Reads words separated by spaces and newlines from the piped input and runs \
+                `command`, with its own arguments, followed by those words; `echo` when no command is \
+                given. `-n N` passes at most N words to each run, running the command as many times as \
+                needed. Nothing runs if there are no words.\n\n\
+                With `-I replace`, each line of the input is one item, and the command runs once per \
+                line with `replace` in its arguments replaced by the line, so names with spaces in them \
+                survive.\n\n\
+                Only built-in commands can be run. The output of every run is printed in turn; if any \
+                run fails, xargs exits with status 123, or 127 if the command does not exist.",
+            examples: &[
+                ("find . -name '*.tmp' | xargs rm", "Remove every .tmp file."),
+                ("ls | xargs -n 1 echo file:", "Print each name on a line of its own."),
+                ("cat list.txt | xargs -I {} cp {} backup/{}", "Copy each file listed into backup."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let (options, command) = match parse_options("xargs", args, "", "nI") {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+        let mut per_run = None;
+        let mut replace = None;
+        for (flag, value) in options {
+            match (flag, value) {
+                ('n', Some(value)) => match value.parse::<usize>() {
+                    Ok(count) if count > 0 => per_run = Some(count),
+                    _ => return usage_error(format!("xargs: invalid number for -n option: '{}'", value)),
+                },
+                ('I', Some(value)) => replace = Some(value),
+                _ => {}
+            }
+        }
+        let (name, fixed) = match command.split_first() {
+            Some((name, fixed)) => (*name, fixed),
+            None => ("echo", &[][..]),
+        };
+        let Some(command) = ctx.registry.get(name) else {
+            return CommandResult::error(format!("xargs: {}: command not found", name))
+                .with_exit_code(EXIT_NOT_FOUND);
+        };
+        let input = ctx.stdin.take().unwrap_or_default();
+
+        let runs: Vec<Vec<String>> = match &replace {
+            Some(replace) => input
+                .lines()
+                .map(str::trim_start)
+                .filter(|line| !line.is_empty())
+                .map(|line| fixed.iter().map(|arg| arg.replace(replace.as_str(), line)).collect())
+                .collect(),
+            None => {
+                let words: Vec<&str> = input.split_whitespace().collect();
+                words
+                    .chunks(per_run.unwrap_or(words.len().max(1)))
+                    .map(|chunk| fixed.iter().copied().chain(chunk.iter().copied()).map(String::from).collect())
+                    .collect()
+            }
+        };
+        let mut outputs = Vec::new();
+        let mut errors = Vec::new();
+        let mut failed = false;
+        for arguments in runs {
+            let mut run_ctx = CommandContext {
+                state: &mut *ctx.state,
+                registry: ctx.registry,
+                stdin: None,
+            };
+            let result = command.run(&mut run_ctx, &arguments);
+            failed |= !result.success();
+            if !result.output.is_empty() {
+                outputs.push(result.output);
+            }
+            if !result.error_output.is_empty() {
+                errors.push(result.error_output);
+            }
+        }
+        let result = CommandResult::ok(outputs.join("\n")).with_errors(errors);
+        if failed {
+            result.with_exit_code(XARGS_FAILED)
+        } else {
+            result
+        }
+    }
+}
+
+/// xargs's status when a command it ran failed, as in GNU xargs.
+const XARGS_FAILED: i32 = 123;
+
+type CharClass = fn(&char) -> bool;
+
+/// The characters a `tr` set stands for, in order.
+fn expand_set(set: &str) -> Result<Vec<char>, String> {
+    const CLASSES: &[(&str, CharClass)] = &[
+        ("[:lower:]", char::is_ascii_lowercase),
+        ("[:upper:]", char::is_ascii_uppercase),
+        ("[:alpha:]", char::is_ascii_alphabetic),
+        ("[:digit:]", char::is_ascii_digit),
+        ("[:alnum:]", char::is_ascii_alphanumeric),
+        ("[:space:]", char::is_ascii_whitespace),
+        ("[:punct:]", char::is_ascii_punctuation),
+    ];
+    let mut chars = Vec::new();
+    let mut rest = set;
+    while !rest.is_empty() {
+        if let Some((class, matches)) = CLASSES.iter().find(|(class, _)| rest.starts_with(class)) {
+            chars.extend((0..=127u8).map(char::from).filter(matches));
+            rest = &rest[class.len()..];
+            continue;
+        }
+        let (ch, after) = set_char(rest);
+        // `a-z`, but not a `-` at either end.
+        if let Some(range_end) = after.strip_prefix('-').filter(|end| !end.is_empty()) {
+            let (end, after) = set_char(range_end);
+            if end < ch {
+                return Err(format!(
+                    "range-endpoints of '{}-{}' are in reverse collating sequence order",
+                    ch, end
+                ));
+            }
+            chars.extend(ch..=end);
+            rest = after;
+        } else {
+            chars.push(ch);
+            rest = after;
+        }
+    }
+    Ok(chars)
+}
+
+/// The first character of a `tr` set, with `\` escapes decoded, and the rest.
+fn set_char(text: &str) -> (char, &str) {
+    let mut chars = text.chars();
+    let ch = chars.next().unwrap_or_default();
+    if ch != '\\' {
+        return (ch, chars.as_str());
+    }
+    let escaped = match chars.next() {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some(other) => other,
+        None => '\\',
+    };
+    (escaped, chars.as_str())
+}
+
+/// An option letter and, for those that take one, its value.
+type ParsedOption = (char, Option<String>);
+
+/// Splits `args` into options and operands. `flags` are the options that
+/// stand alone; those in `valued` take a value, attached (`-f1`) or as the
+/// next argument (`-f 1`). Options may only come before the operands.
+pub(super) fn parse_options<'a>(
+    tool: &str,
+    args: &'a [String],
+    flags: &str,
+    valued: &str,
+) -> Result<(Vec<ParsedOption>, Vec<&'a str>), CommandResult> {
+    let mut options = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(cluster) = arg.strip_prefix('-').filter(|cluster| !cluster.is_empty()) else {
+            // `-` is an operand: the piped input.
+            return Ok((options, std::iter::once(arg).chain(args).map(String::as_str).collect()));
+        };
+        if cluster == "-" {
+            return Ok((options, args.map(String::as_str).collect()));
+        }
+        for (index, flag) in cluster.char_indices() {
+            if flags.contains(flag) {
+                options.push((flag, None));
+            } else if valued.contains(flag) {
+                let attached = &cluster[index + flag.len_utf8()..];
+                let value = match attached {
+                    "" => match args.next() {
+                        Some(value) => value.clone(),
+                        None => {
+                            let message = format!("{}: option requires an argument -- '{}'", tool, flag);
+                            return Err(usage_error(message));
+                        }
+                    },
+                    _ => attached.to_string(),
+                };
+                options.push((flag, Some(value)));
+                break;
+            } else {
+                return Err(usage_error(format!("{}: invalid option -- '{}'", tool, flag)));
+            }
+        }
+    }
+    Ok((options, Vec::new()))
+}
+
+fn one_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    chars.next().filter(|_| chars.next().is_none())
+}
+
+pub(super) fn usage_error(message: impl Into<String>) -> CommandResult {
+    CommandResult::error(message).with_exit_code(EXIT_USAGE)
+}
@@ -0,0 +1,146 @@
+use crate::command::{Command, CommandContext, CommandResult, Manual, EXIT_USAGE};
+
+pub struct Jobs;
+
+impl Command for Jobs {
+    fn name(&self) -> &'static str {
+        "jobs"
+    }
+
+    fn help(&self) -> &'static str {
+        "jobs [-l] [%job]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "list background jobs",
+            description: "Lists the jobs started with `&` in this session: each one's number, whether it is \
+                still running or how it ended, and its command. `+` marks the current job, which `fg` and \
+                `kill` take when given no job, and `-` the one before it.\n\n\
+                With `-l`, each job's process id is shown too; it is also the id the server's job API \
+                knows the job by, except in deterministic sessions, which count pids from 1. Finished \
+                jobs stay listed until `fg` collects their output.",
+            examples: &[
+                ("jobs", "List the background jobs."),
+                ("jobs -l %1", "Show job 1 and its process id."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let long = args.iter().any(|arg| arg == "-l");
+        let specs: Vec<&str> = args.iter().map(String::as_str).filter(|arg| *arg != "-l").collect();
+        if let Some(option) = specs.iter().find(|arg| arg.starts_with('-')) {
+            return CommandResult::error(format!("jobs: {}: invalid option\nusage: {}", option, self.help()))
+                .with_exit_code(EXIT_USAGE);
+        }
+        let table = &ctx.state.jobs;
+        let jobs = if specs.is_empty() {
+            table.jobs().to_vec()
+        } else {
+            match specs.iter().map(|spec| table.find(Some(spec))).collect::<Result<Vec<_>, _>>() {
+                Ok(jobs) => jobs,
+                Err(message) => return CommandResult::error(format!("jobs: {}", message)),
+            }
+        };
+        let lines: Vec<String> = jobs.iter().map(|job| table.describe(job, long)).collect();
+        CommandResult::ok(lines.join("\n"))
+    }
+}
+
+pub struct Fg;
+
+impl Command for Fg {
+    fn name(&self) -> &'static str {
+        "fg"
+    }
+
+    fn help(&self) -> &'static str {
+        "fg [%job]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "show a background job's output",
+            description: "Prints the command of `job`, or of the current job, followed by everything it has \
+                printed. A job that has finished is then forgotten, and its exit status becomes fg's.\n\n\
+                A job still running keeps running, and its output so far is shown; run fg again later for \
+                the rest.",
+            examples: &[("fg", "Collect the output of the current job."), ("fg %2", "Collect job 2's output.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        if args.len() > 1 {
+            return CommandResult::error(format!("usage: {}", self.help())).with_exit_code(EXIT_USAGE);
+        }
+        let job = match ctx.state.jobs.find(args.first().map(String::as_str)) {
+            Ok(job) => job,
+            Err(message) => return CommandResult::error(format!("fg: {}", message)),
+        };
+        let mut lines = vec![job.command.clone()];
+        let output = job.output();
+        if !output.is_empty() {
+            lines.push(output);
+        }
+        let exit_code = match job.status().exit_code() {
+            Some(exit_code) => {
+                ctx.state.jobs.remove(job.number);
+                exit_code
+            }
+            None => 0,
+        };
+        CommandResult::ok(lines.join("\n")).with_exit_code(exit_code)
+    }
+}
+
+pub struct Kill;
+
+impl Command for Kill {
+    fn name(&self) -> &'static str {
+        "kill"
+    }
+
+    fn help(&self) -> &'static str {
+        "kill [-SIGNAL] %job..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "stop background jobs",
+            description: "Stops each job given, which is then listed as Terminated with status 143. Jobs \
+                are named as `%1` by number, `%%` for the current one, `%-` for the one before, or `%text` \
+                for the latest whose command starts with `text`.\n\n\
+                A signal such as `-9` or `-KILL` is accepted for familiarity, but every signal simply stops \
+                the job.",
+            examples: &[("kill %1", "Stop job 1."), ("kill %%", "Stop the current job.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let specs: Vec<&String> = match args.first() {
+            Some(signal) if signal.starts_with('-') && signal.len() > 1 => args[1..].iter().collect(),
+            _ => args.iter().collect(),
+        };
+        if specs.is_empty() {
+            return CommandResult::error(format!("usage: {}", self.help())).with_exit_code(EXIT_USAGE);
+        }
+        let mut errors = Vec::new();
+        for spec in specs {
+            if !spec.starts_with('%') {
+                errors.push(format!("kill: {}: arguments must be job IDs", spec));
+                continue;
+            }
+            match ctx.state.jobs.find(Some(spec)) {
+                Ok(job) if !job.kill() => errors.push(format!("kill: {}: job has already finished", spec)),
+                Ok(_) => {}
+                Err(message) => errors.push(format!("kill: {}", message)),
+            }
+        }
+        if errors.is_empty() {
+            CommandResult::empty()
+        } else {
+            CommandResult::error(errors.join("\n"))
+        }
+    }
+}
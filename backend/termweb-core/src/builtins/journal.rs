@@ -0,0 +1,66 @@
+use crate::command::{Command, CommandContext, CommandResult, Manual};
+
+pub struct Journal;
+
+impl Command for Journal {
+    fn name(&self) -> &'static str {
+        "journal"
+    }
+
+    fn help(&self) -> &'static str {
+        "journal [since]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "show recent filesystem changes",
+            description: "Lists the changes made to the filesystem, one per line: sequence number, time, \
+                operation, change in size and path, followed by the user who made it when the session \
+                belongs to one. Given a sequence number, lists only the changes after it.",
+            examples: &[
+                ("journal", "See everything that has changed."),
+                ("journal 40", "See the changes after number 40."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let since = match args {
+            [] => 0,
+            [since] => match since.parse() {
+                Ok(since) => since,
+                Err(_) => {
+                    return CommandResult::error(format!("journal: invalid sequence number '{}'", since));
+                }
+            },
+            _ => return CommandResult::error(format!("usage: {}", self.help())),
+        };
+        let lines: Vec<String> = ctx
+            .state
+            .fs
+            .journal()
+            .since(since)
+            .map(|entry| {
+                let line = format!(
+                    "{:>5}  {}  {:<7} {:>+7}  {}",
+                    entry.seq,
+                    clock_time(entry.timestamp),
+                    entry.op,
+                    entry.size_delta,
+                    entry.path
+                );
+                match &entry.user {
+                    Some(user) => format!("{}  ({})", line, user),
+                    None => line,
+                }
+            })
+            .collect();
+        CommandResult::ok(lines.join("\n"))
+    }
+}
+
+/// `HH:MM:SS` in UTC for a millisecond Unix timestamp.
+fn clock_time(timestamp: u64) -> String {
+    let seconds = timestamp / 1000 % 86_400;
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
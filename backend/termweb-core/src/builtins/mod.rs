@@ -0,0 +1,111 @@
+mod archive;
+mod disk;
+mod edit;
+mod env;
+mod files;
+mod filters;
+mod jobs;
+mod journal;
+mod navigation;
+mod script;
+mod sed;
+mod session;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod test;
+mod text;
+mod time;
+mod vcs;
+
+use crate::registry::CommandRegistry;
+
+pub use archive::{Tar, Untar};
+pub use disk::{Df, Du, Quota};
+pub use edit::Edit;
+pub use env::{Alias, Env, Export, Unalias, Unset};
+pub use files::{Cat, Chmod, Cmp, Cp, Diff, Ln, Mkdir, Mv, Readlink, Rm, Rmdir, Shred, Touch};
+pub use filters::{Cut, Shuf, Sort, Tr, Uniq, Xargs};
+pub use jobs::{Fg, Jobs, Kill};
+pub use journal::Journal;
+pub use navigation::{Cd, Dirs, Find, Ls, Popd, Pushd, Pwd, Stat, Tree};
+pub use script::{Dot, Sh, Source};
+pub use sed::Sed;
+pub use session::{Clear, Help, History, Man, Notify};
+#[cfg(feature = "sqlite")]
+pub use sqlite::Sqlite;
+pub use test::{Bracket, False, Test, True};
+pub use text::{Echo, Grep, Head, Printf, Tail, Wc};
+pub use time::{Cal, Date, Sleep};
+pub use vcs::Vcs;
+
+/// Registers every built-in command, in the order `help` lists them.
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(Pwd);
+    registry.register(Ls);
+    registry.register(Stat);
+    registry.register(Cd);
+    registry.register(Pushd);
+    registry.register(Popd);
+    registry.register(Dirs);
+    registry.register(Find);
+    registry.register(Tree);
+    registry.register(Mkdir);
+    registry.register(Touch);
+    registry.register(Rm);
+    registry.register(Rmdir);
+    registry.register(Cp);
+    registry.register(Mv);
+    registry.register(Ln);
+    registry.register(Readlink);
+    registry.register(Cat);
+    registry.register(Cmp);
+    registry.register(Diff);
+    registry.register(Echo);
+    registry.register(Printf);
+    registry.register(Grep);
+    registry.register(Head);
+    registry.register(Tail);
+    registry.register(Wc);
+    registry.register(Sort);
+    registry.register(Uniq);
+    registry.register(Shuf);
+    registry.register(Cut);
+    registry.register(Tr);
+    registry.register(Xargs);
+    registry.register(Sed);
+    registry.register(Edit);
+    registry.register(Shred);
+    registry.register(Chmod);
+    registry.register(Tar);
+    registry.register(Untar);
+    registry.register(Du);
+    registry.register(Df);
+    registry.register(Quota);
+    registry.register(Vcs);
+    registry.register(Journal);
+    registry.register(Export);
+    registry.register(Unset);
+    registry.register(Env);
+    registry.register(Alias);
+    registry.register(Unalias);
+    #[cfg(feature = "sqlite")]
+    registry.register(Sqlite);
+    registry.register(Sh);
+    registry.register(Source);
+    registry.register(Dot);
+    registry.register(Test);
+    registry.register(Bracket);
+    registry.register(True);
+    registry.register(False);
+    registry.register(Jobs);
+    registry.register(Fg);
+    registry.register(Kill);
+    registry.register(Sleep);
+    registry.register(Date);
+    registry.register(Cal);
+    registry.register(History);
+    registry.register(Notify);
+    registry.register(Clear);
+    registry.register(Help);
+    registry.register(Man);
+}
@@ -0,0 +1,820 @@
+use std::collections::BTreeMap;
+
+use crate::clock::format_timestamp;
+use crate::color::{paint, LsColors};
+use crate::command::{Command, CommandContext, CommandResult, Completion, Manual, EXIT_USAGE};
+use crate::fs::{FileSystem, FsError, Node, EXECUTE, READ};
+use crate::glob::fnmatch;
+use crate::path::resolve_path;
+
+pub struct Pwd;
+
+impl Command for Pwd {
+    fn name(&self) -> &'static str {
+        "pwd"
+    }
+
+    fn help(&self) -> &'static str {
+        "pwd"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "print the working directory",
+            description: "Prints the absolute path of the directory the shell is in.",
+            examples: &[("pwd", "Show where you are, e.g. /home/user.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, _args: &[String]) -> CommandResult {
+        CommandResult::ok(ctx.state.cwd_string())
+    }
+}
+
+pub struct Ls;
+
+/// What `ls` sorts entries by, before `-r`.
+#[derive(Clone, Copy, PartialEq)]
+enum SortKey {
+    Name,
+    /// Newest first.
+    Time,
+    /// Largest first.
+    Size,
+}
+
+struct LsOptions {
+    long: bool,
+    /// Dotfiles, and `.` and `..` too.
+    all: bool,
+    /// Dotfiles, but not `.` and `..`.
+    almost_all: bool,
+    recursive: bool,
+    one_per_line: bool,
+    sort: SortKey,
+    reverse: bool,
+}
+
+impl Command for Ls {
+    fn name(&self) -> &'static str {
+        "ls"
+    }
+
+    fn help(&self) -> &'static str {
+        "ls [-l] [-a | -A] [-R] [-t | -S] [-r] [-1] [path]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "list directory contents",
+            description: "Lists the files in each directory given, or the current directory, and names the \
+                files given directly. Directories are marked with a trailing `/` and symlinks show where \
+                they point. Names starting with `.` are hidden unless `-a` or `-A` is given; `-a` also \
+                shows `.` and `..`.\n\n\
+                Names are laid out in columns as wide as the terminal, or one per line when piped or \
+                redirected, or with `-1`. `-l` gives one line per file with its type and permissions, \
+                size and modification time. `-R` lists subdirectories too.\n\n\
+                Entries are sorted by name; `-t` sorts them newest first, `-S` largest first, and `-r` \
+                reverses the order.",
+            examples: &[
+                ("ls -la", "List everything in the current directory, dotfiles included, in long form."),
+                ("ls -tr ~/notes", "List notes oldest first, so the newest are at the bottom."),
+                ("ls -R src", "List src and every directory beneath it."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut options = LsOptions {
+            long: false,
+            all: false,
+            almost_all: false,
+            recursive: false,
+            one_per_line: false,
+            sort: SortKey::Name,
+            reverse: false,
+        };
+        let mut targets = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'l' => options.long = true,
+                            'a' => options.all = true,
+                            'A' => options.almost_all = true,
+                            'R' => options.recursive = true,
+                            '1' => options.one_per_line = true,
+                            't' => options.sort = SortKey::Time,
+                            'S' => options.sort = SortKey::Size,
+                            'r' => options.reverse = true,
+                            other => {
+                                return CommandResult::error(format!("ls: invalid option -- '{}'", other))
+                                    .with_exit_code(EXIT_USAGE);
+                            }
+                        }
+                    }
+                }
+                _ => targets.push(arg.as_str()),
+            }
+        }
+        let headers = targets.len() > 1 || options.recursive;
+        if targets.is_empty() {
+            targets.push(".");
+        }
+
+        // Like coreutils: file operands first, then a section per directory, and
+        // status 2 for an operand that cannot be listed, after listing the rest.
+        let fs = &ctx.state.fs;
+        let colors = ctx.state.color_enabled().then(|| LsColors::from_env(&ctx.state.env));
+        let listing = Listing {
+            fs,
+            options: &options,
+            colors: colors.as_ref(),
+            // Piped or redirected, one name per line, as coreutils does.
+            width: ctx.state.terminal_width(),
+        };
+        let mut files = Vec::new();
+        let mut sections = Vec::new();
+        let mut errors = Vec::new();
+        for target in targets {
+            let path = resolve_path(&ctx.state.cwd, target);
+            let Some(entry) = fs.get_node_nofollow(&path) else {
+                errors.push(format!("ls: cannot access '{}': No such file or directory", target));
+                continue;
+            };
+            // A symlink operand lists what it points to, except in long form.
+            let node = match entry {
+                Node::Symlink { .. } if options.long => entry,
+                _ => fs.get_node(&path).unwrap_or(entry),
+            };
+            if !fs.permitted(&path, 0) {
+                errors.push(format!("ls: cannot access '{}': Permission denied", target));
+                continue;
+            }
+            if let Node::Dir { .. } = node {
+                if let Err(result) = listing.directory(&path, target, headers, &mut sections) {
+                    errors.push(result.error_output);
+                }
+            } else {
+                files.push((target, node));
+            }
+        }
+        if !files.is_empty() {
+            sort_entries(&mut files, &options);
+            sections.insert(0, listing.render(&files));
+        }
+        let exit_code = if errors.is_empty() { 0 } else { EXIT_USAGE };
+        CommandResult::ok(sections.join("\n\n")).with_errors(errors).with_exit_code(exit_code)
+    }
+}
+
+/// Everything `ls` needs to turn entries into text.
+struct Listing<'a> {
+    fs: &'a FileSystem,
+    options: &'a LsOptions,
+    colors: Option<&'a LsColors>,
+    /// Columns to fill, or `None` for one name per line.
+    width: Option<usize>,
+}
+
+impl Listing<'_> {
+    /// Adds the listing of the directory at `path`, shown as `shown`, to
+    /// `sections`, followed by its subdirectories' with `-R`.
+    fn directory(
+        &self,
+        path: &[String],
+        shown: &str,
+        header: bool,
+        sections: &mut Vec<String>,
+    ) -> Result<(), CommandResult> {
+        if !self.fs.permitted(path, READ) {
+            return Err(CommandResult::error(format!(
+                "ls: cannot open directory '{}': Permission denied",
+                shown
+            ))
+            .with_exit_code(EXIT_USAGE));
+        }
+        let Some(node @ Node::Dir { children, .. }) = self.fs.get_node(path) else {
+            return Ok(());
+        };
+        let mut entries: Vec<(&str, &Node)> = Vec::new();
+        if self.options.all {
+            let parent = self.fs.get_node(&path[..path.len().saturating_sub(1)]).unwrap_or(node);
+            entries.push((".", node));
+            entries.push(("..", parent));
+        }
+        // Names starting with `.` are hidden without `-a` or `-A`.
+        let hidden = !self.options.all && !self.options.almost_all;
+        entries.extend(
+            children
+                .iter()
+                .filter(|(name, _)| !(hidden && name.starts_with('.')))
+                .map(|(name, child)| (name.as_str(), child)),
+        );
+        sort_entries(&mut entries, self.options);
+        let listing = self.render(&entries);
+        sections.push(if header { format!("{}:\n{}", shown, listing) } else { listing });
+
+        if self.options.recursive {
+            for (name, child) in entries {
+                // Symlinks are not followed, so a loop cannot recurse forever.
+                if matches!(name, "." | "..") || !matches!(child, Node::Dir { .. }) {
+                    continue;
+                }
+                let mut child_path = path.to_vec();
+                child_path.push(name.to_string());
+                let child_shown = format!("{}/{}", shown.trim_end_matches('/'), name);
+                self.directory(&child_path, &child_shown, true, sections)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn render(&self, entries: &[(&str, &Node)]) -> String {
+        if self.options.long {
+            let lines: Vec<String> =
+                entries.iter().map(|(name, node)| long_entry(name, node, self.colors)).collect();
+            return lines.join("\n");
+        }
+        let cells: Vec<(String, usize)> = entries
+            .iter()
+            .map(|(name, node)| {
+                let width = short_entry(name, node, None).chars().count();
+                (short_entry(name, node, self.colors), width)
+            })
+            .collect();
+        match self.width {
+            Some(width) if !self.options.one_per_line => columns(&cells, width),
+            _ => {
+                let lines: Vec<&str> = cells.iter().map(|(cell, _)| cell.as_str()).collect();
+                lines.join("\n")
+            }
+        }
+    }
+}
+
+fn sort_entries(entries: &mut [(&str, &Node)], options: &LsOptions) {
+    // Stable, so ties stay in name order.
+    match options.sort {
+        SortKey::Name => entries.sort_by_key(|(name, _)| *name),
+        SortKey::Time => entries.sort_by_key(|(_, node)| std::cmp::Reverse(node.meta().modified)),
+        SortKey::Size => entries.sort_by_key(|(_, node)| std::cmp::Reverse(node.size())),
+    }
+    if options.reverse {
+        entries.reverse();
+    }
+}
+
+/// Lays `cells`, each with its width on screen, out in as many columns as
+/// fit in `width`, filling each column top to bottom like coreutils.
+fn columns(cells: &[(String, usize)], width: usize) -> String {
+    const GAP: usize = 2;
+    if cells.is_empty() {
+        return String::new();
+    }
+    // Every cell is at least one character wide.
+    let most = cells.len().min(width.div_ceil(1 + GAP)).max(1);
+    let (rows, widths) = (1..=most)
+        .rev()
+        .find_map(|count| {
+            let rows = cells.len().div_ceil(count);
+            let widths: Vec<usize> = cells
+                .chunks(rows)
+                .map(|column| column.iter().map(|(_, width)| *width).max().unwrap_or(0))
+                .collect();
+            let total = widths.iter().sum::<usize>() + GAP * (widths.len() - 1);
+            (total <= width || rows == cells.len()).then_some((rows, widths))
+        })
+        .expect("a single column always fits");
+
+    let mut lines = Vec::with_capacity(rows);
+    for row in 0..rows {
+        let mut line = String::new();
+        let mut index = row;
+        for (column, column_width) in widths.iter().enumerate() {
+            let (cell, cell_width) = &cells[index];
+            line.push_str(cell);
+            index += rows;
+            if index >= cells.len() || column + 1 == widths.len() {
+                break;
+            }
+            line.push_str(&" ".repeat(column_width - cell_width + GAP));
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+/// One name as plain `ls` shows it: directories marked with a `/`, and
+/// where a symlink points.
+fn short_entry(name: &str, node: &Node, colors: Option<&LsColors>) -> String {
+    let painted = painted_name(name, node, colors);
+    match node {
+        Node::Dir { .. } => format!("{}/", painted),
+        Node::File { .. } => painted,
+        Node::Symlink { target, .. } => format!("{} -> {}", painted, target),
+    }
+}
+
+/// One `ls -l` line: type and mode, size, modification time and name,
+/// plus where a symlink points.
+fn long_entry(name: &str, node: &Node, colors: Option<&LsColors>) -> String {
+    let modified = format_timestamp(node.meta().modified);
+    let painted = painted_name(name, node, colors);
+    let name = match node {
+        Node::Symlink { target, .. } => format!("{} -> {}", painted, target),
+        _ => painted,
+    };
+    format!("{} {:>8} {} {}", node.mode_string(), node.size(), &modified[..16], name)
+}
+
+/// `name` in the colour `ls` gives nodes like `node`, if colours are on.
+fn painted_name(name: &str, node: &Node, colors: Option<&LsColors>) -> String {
+    let Some(colors) = colors else {
+        return name.to_string();
+    };
+    match node {
+        Node::Dir { .. } => paint(name, &colors.dir),
+        Node::Symlink { .. } => paint(name, &colors.link),
+        Node::File { meta, .. } if meta.mode & EXECUTE != 0 => paint(name, &colors.executable),
+        Node::File { .. } => name.to_string(),
+    }
+}
+
+pub struct Stat;
+
+impl Command for Stat {
+    fn name(&self) -> &'static str {
+        "stat"
+    }
+
+    fn help(&self) -> &'static str {
+        "stat <path>..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "show a file's details",
+            description: "Shows the type, size, permissions and modification and creation times of each \
+                path. Symlinks are described themselves, not what they point to.",
+            examples: &[("stat notes.txt", "Show when notes.txt was created and last changed.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        if args.is_empty() {
+            return CommandResult::error("stat: missing operand");
+        }
+        let mut reports = Vec::new();
+        for arg in args {
+            let Some(node) = ctx.state.fs.get_node_nofollow(&resolve_path(&ctx.state.cwd, arg)) else {
+                return CommandResult::error(format!(
+                    "stat: cannot stat '{}': No such file or directory",
+                    arg
+                ));
+            };
+            let (kind, name) = match node {
+                Node::Dir { .. } => ("directory", arg.clone()),
+                Node::File { .. } => ("regular file", arg.clone()),
+                Node::Symlink { target, .. } => ("symbolic link", format!("{} -> {}", arg, target)),
+            };
+            let meta = node.meta();
+            reports.push(format!(
+                "  File: {}\n  Size: {:<10} {}\nAccess: ({:04o}/{})\nModify: {}\n Birth: {}",
+                name,
+                node.size(),
+                kind,
+                meta.mode,
+                node.mode_string(),
+                format_timestamp(meta.modified),
+                format_timestamp(meta.created)
+            ));
+        }
+        CommandResult::ok(reports.join("\n"))
+    }
+}
+
+pub struct Cd;
+
+impl Command for Cd {
+    fn name(&self) -> &'static str {
+        "cd"
+    }
+
+    fn help(&self) -> &'static str {
+        "cd [path | -]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "change the working directory",
+            description: "Moves the shell to another directory. With no argument it goes to `$HOME`, and \
+                `cd -` goes back to the previous directory.",
+            examples: &[("cd /tmp", "Go to /tmp."), ("cd -", "Return to where you were before.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Directories
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let back = args.first().is_some_and(|arg| arg == "-");
+        let path = match args.first() {
+            Some(_) if back => match &ctx.state.previous_dir {
+                Some(previous) => previous.clone(),
+                None => return CommandResult::error("cd: OLDPWD not set"),
+            },
+            Some(target) => resolve_path(&ctx.state.cwd, target),
+            None => match ctx.state.env.get("HOME") {
+                Some(home) => resolve_path(&ctx.state.cwd, home),
+                None => return CommandResult::error("cd: HOME not set"),
+            },
+        };
+        match ctx.state.fs.is_dir(&path) {
+            Ok(true) if !ctx.state.fs.permitted(&path, EXECUTE) => {
+                CommandResult::fs_error("cd", FsError::PermissionDenied)
+            }
+            Ok(true) => {
+                ctx.state.set_cwd(path);
+                // Like bash, say where `cd -` went.
+                if back {
+                    CommandResult::ok(ctx.state.cwd_string())
+                } else {
+                    CommandResult::empty()
+                }
+            }
+            Ok(false) => CommandResult::fs_error("cd", FsError::NotADirectory),
+            Err(error) => CommandResult::fs_error("cd", error),
+        }
+    }
+}
+
+pub struct Pushd;
+
+impl Command for Pushd {
+    fn name(&self) -> &'static str {
+        "pushd"
+    }
+
+    fn help(&self) -> &'static str {
+        "pushd [dir | +N | -N]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "save the directory and change to another",
+            description: "Pushes the current directory onto the directory stack and changes to `dir`. With \
+                no argument it swaps the top two entries; `+N` and `-N` rotate the stack so that entry \
+                N, counting from the left or the right of `dirs`, comes to the top.",
+            examples: &[
+                ("pushd /etc", "Go to /etc, remembering where you were."),
+                ("pushd +1", "Rotate the stack by one."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Directories
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        match ctx.state.pushd(args.first().map(String::as_str)) {
+            Ok(()) => CommandResult::ok(ctx.state.dirs(false)),
+            Err(message) => CommandResult::error(message),
+        }
+    }
+}
+
+pub struct Popd;
+
+impl Command for Popd {
+    fn name(&self) -> &'static str {
+        "popd"
+    }
+
+    fn help(&self) -> &'static str {
+        "popd [+N | -N]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "return to a saved directory",
+            description: "Removes the top of the directory stack and changes to the new top. `+N` and `-N` \
+                remove entry N, counting from the left or the right of `dirs`, without changing \
+                directory.",
+            examples: &[("popd", "Go back to the directory saved by the last pushd.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        match ctx.state.popd(args.first().map(String::as_str)) {
+            Ok(()) => CommandResult::ok(ctx.state.dirs(false)),
+            Err(message) => CommandResult::error(message),
+        }
+    }
+}
+
+pub struct Dirs;
+
+impl Command for Dirs {
+    fn name(&self) -> &'static str {
+        "dirs"
+    }
+
+    fn help(&self) -> &'static str {
+        "dirs [-c] [-v]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "show the directory stack",
+            description: "Prints the directory stack, starting with the current directory. `-v` numbers \
+                the entries one per line, and `-c` clears the stack.",
+            examples: &[("dirs -v", "Show the stack with the numbers pushd and popd take.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        if args.iter().any(|arg| arg == "-c") {
+            ctx.state.dir_stack.clear();
+            CommandResult::empty()
+        } else {
+            CommandResult::ok(ctx.state.dirs(args.iter().any(|arg| arg == "-v")))
+        }
+    }
+}
+
+pub struct Find;
+
+impl Command for Find {
+    fn name(&self) -> &'static str {
+        "find"
+    }
+
+    fn help(&self) -> &'static str {
+        "find [path]... [-name <glob>] [-type f|d|l] [-maxdepth N]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "search for files",
+            description: "Walks each path given, or the current directory, printing every file and \
+                directory beneath it that matches the tests. `-name` matches names against a wildcard \
+                pattern, `-type` keeps only files (`f`), directories (`d`) or symlinks (`l`), and \
+                `-maxdepth` stops descending after N levels.",
+            examples: &[
+                ("find . -name '*.txt'", "List every text file below the current directory."),
+                ("find / -type d -maxdepth 2", "List the top two levels of directories."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut roots = Vec::new();
+        let mut filter = FindFilter::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') || arg == "-" {
+                roots.push(arg.as_str());
+                continue;
+            }
+            let Some(value) = args.next() else {
+                return CommandResult::error(format!("find: missing argument to `{}'", arg));
+            };
+            match arg.as_str() {
+                "-name" => filter.name = Some(value.clone()),
+                "-type" => match value.as_str() {
+                    "f" | "d" | "l" => filter.kind = value.chars().next(),
+                    _ => return CommandResult::error(format!("find: unknown argument to -type: {}", value)),
+                },
+                "-maxdepth" => match value.parse() {
+                    Ok(depth) => filter.max_depth = Some(depth),
+                    Err(_) => {
+                        return CommandResult::error(format!(
+                            "find: invalid argument `{}' to `-maxdepth'",
+                            value
+                        ));
+                    }
+                },
+                _ => return CommandResult::error(format!("find: unknown predicate `{}'", arg)),
+            }
+        }
+        if roots.is_empty() {
+            roots.push(".");
+        }
+
+        let mut found = Vec::new();
+        let mut errors = Vec::new();
+        for root in roots {
+            match ctx.state.fs.get_node(&resolve_path(&ctx.state.cwd, root)) {
+                Some(node) => {
+                    let name = root.trim_end_matches('/').rsplit('/').next().unwrap_or(root);
+                    filter.walk(node, name, root, 0, &mut found);
+                }
+                None => errors.push(format!("find: '{}': No such file or directory", root)),
+            }
+        }
+        let exit_code = if errors.is_empty() { 0 } else { 1 };
+        CommandResult::ok(found.join("\n")).with_errors(errors).with_exit_code(exit_code)
+    }
+}
+
+#[derive(Default)]
+struct FindFilter {
+    name: Option<String>,
+    /// `-type`: `f` for files, `d` for directories, `l` for symlinks.
+    kind: Option<char>,
+    max_depth: Option<usize>,
+}
+
+impl FindFilter {
+    /// Lists `node` and, in name order, everything beneath it that passes.
+    fn walk(&self, node: &Node, name: &str, path: &str, depth: usize, found: &mut Vec<String>) {
+        let kind = match node {
+            Node::Dir { .. } => 'd',
+            Node::File { .. } => 'f',
+            Node::Symlink { .. } => 'l',
+        };
+        let name_matches = self.name.as_ref().is_none_or(|pattern| fnmatch(pattern, name));
+        if name_matches && self.kind.is_none_or(|wanted| wanted == kind) {
+            found.push(path.to_string());
+        }
+        if self.max_depth.is_some_and(|max| depth >= max) {
+            return;
+        }
+        if let Node::Dir { children, .. } = node {
+            for (child_name, child) in children {
+                let child_path = format!("{}/{}", path.trim_end_matches('/'), child_name);
+                self.walk(child, child_name, &child_path, depth + 1, found);
+            }
+        }
+    }
+}
+
+pub struct Tree;
+
+impl Command for Tree {
+    fn name(&self) -> &'static str {
+        "tree"
+    }
+
+    fn help(&self) -> &'static str {
+        "tree [-a] [-d] [-L depth] [path]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "show a directory tree",
+            description: "Draws the directories and files below `path`, or the current directory, as a \
+                tree, followed by a count of each. Hidden names are left out unless `-a` is given; `-d` \
+                shows directories only and `-L` limits how deep the tree goes.",
+            examples: &[
+                ("tree -L 2", "Show two levels of the current directory."),
+                ("tree -d /", "Show every directory."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Directories
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut options = TreeOptions::default();
+        let mut operands = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'a' => options.all = true,
+                            'd' => options.dirs_only = true,
+                            'L' => match args.next().and_then(|value| value.parse().ok()) {
+                                Some(depth) if depth > 0 => options.max_depth = Some(depth),
+                                _ => {
+                                    return CommandResult::error("tree: invalid level, must be greater than 0");
+                                }
+                            },
+                            other => {
+                                return CommandResult::error(format!("tree: invalid option -- '{}'", other));
+                            }
+                        }
+                    }
+                }
+                _ => operands.push(arg.as_str()),
+            }
+        }
+        let root = match operands.as_slice() {
+            [] => ".",
+            [root] => *root,
+            _ => return CommandResult::error("tree: too many operands"),
+        };
+
+        let path = resolve_path(&ctx.state.cwd, root);
+        let fs = &ctx.state.fs;
+        let children = match fs.get_node(&path) {
+            Some(Node::Dir { children, .. }) => children,
+            Some(_) => return CommandResult::fs_error(&format!("tree: {}", root), FsError::NotADirectory),
+            None => return CommandResult::fs_error(&format!("tree: {}", root), FsError::NotFound),
+        };
+        if !fs.permitted(&path, READ) {
+            return CommandResult::fs_error(&format!("tree: {}", root), FsError::PermissionDenied);
+        }
+        let mut listing = TreeListing {
+            lines: vec![root.to_string()],
+            ..TreeListing::default()
+        };
+        options.walk(fs, &path, children, "", 1, &mut listing);
+
+        let plural = |count: usize, one: &str, many: &str| {
+            format!("{} {}", count, if count == 1 { one } else { many })
+        };
+        let mut summary = plural(listing.dirs, "directory", "directories");
+        if !options.dirs_only {
+            summary = format!("{}, {}", summary, plural(listing.files, "file", "files"));
+        }
+        listing.lines.push(String::new());
+        listing.lines.push(summary);
+        CommandResult::ok(listing.lines.join("\n"))
+    }
+}
+
+#[derive(Default)]
+struct TreeOptions {
+    /// `-a`: include names starting with `.`.
+    all: bool,
+    /// `-d`: list directories only.
+    dirs_only: bool,
+    /// `-L`: how many levels below the root to show.
+    max_depth: Option<usize>,
+}
+
+#[derive(Default)]
+struct TreeListing {
+    lines: Vec<String>,
+    dirs: usize,
+    /// Everything that is not a directory, symlinks included.
+    files: usize,
+}
+
+impl TreeOptions {
+    /// Draws the entries of the directory at `path` beneath `prefix`, and
+    /// their own entries in turn; `depth` is the entries' level.
+    fn walk(
+        &self,
+        fs: &FileSystem,
+        path: &[String],
+        children: &BTreeMap<String, Node>,
+        prefix: &str,
+        depth: usize,
+        listing: &mut TreeListing,
+    ) {
+        let entries: Vec<(&String, &Node)> = children
+            .iter()
+            .filter(|(name, _)| self.all || !name.starts_with('.'))
+            .filter(|(_, node)| !self.dirs_only || matches!(node, Node::Dir { .. }))
+            .collect();
+        for (index, (name, node)) in entries.iter().enumerate() {
+            let last = index + 1 == entries.len();
+            let mut child_path = path.to_vec();
+            child_path.push(name.to_string());
+            let mut label = match node {
+                Node::Symlink { target, .. } => format!("{} -> {}", name, target),
+                _ => name.to_string(),
+            };
+            let readable = fs.permitted(&child_path, READ);
+            match node {
+                Node::Dir { .. } if !readable => {
+                    listing.dirs += 1;
+                    label.push_str("  [error opening dir]");
+                }
+                Node::Dir { .. } => listing.dirs += 1,
+                _ => listing.files += 1,
+            }
+            let branch = if last { "└── " } else { "├── " };
+            listing.lines.push(format!("{}{}{}", prefix, branch, label));
+            if let Node::Dir { children, .. } = node
+                && readable
+                && self.max_depth.is_none_or(|max| depth < max)
+            {
+                let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                self.walk(fs, &child_path, children, &prefix, depth + 1, listing);
+            }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::command::{Command, CommandContext, CommandResult, Completion, Manual, EXIT_USAGE};
+use crate::path::resolve_path;
+use crate::shell::run_script;
+
+pub struct Sh;
+
+impl Command for Sh {
+    fn name(&self) -> &'static str {
+        "sh"
+    }
+
+    fn help(&self) -> &'static str {
+        "sh [file]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "run a script",
+            description: "Runs the commands in `file`, or piped in, as a child shell: changes to the \
+                directory, variables and aliases do not outlast it. The script stops at the first \
+                command that fails.",
+            examples: &[("sh setup.sh", "Run setup.sh."), ("cat setup.sh | sh", "Run a script piped in.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let (name, script) = match args {
+            [] => match ctx.stdin.take() {
+                // `cat setup.sh | sh`
+                Some(stdin) => ("sh", stdin),
+                None => return CommandResult::error("sh: missing file operand").with_exit_code(EXIT_USAGE),
+            },
+            [file] => match read_script(ctx, "sh", file) {
+                Ok(script) => (file.as_str(), script),
+                Err(result) => return result,
+            },
+            _ => return too_many_arguments("sh"),
+        };
+        // A child shell: what the script does to the directory, variables
+        // and aliases stays with it.
+        let state = &mut *ctx.state;
+        let saved = (
+            state.cwd.clone(),
+            state.dir_stack.clone(),
+            state.env.clone(),
+            state.aliases.clone(),
+        );
+        let result = run_script(ctx.registry, state, name, &script);
+        (state.cwd, state.dir_stack, state.env, state.aliases) = saved;
+        result
+    }
+}
+
+pub struct Source;
+
+impl Command for Source {
+    fn name(&self) -> &'static str {
+        "source"
+    }
+
+    fn help(&self) -> &'static str {
+        "source <file>"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "run a script in the current shell",
+            description: "Runs the commands in `file` as if they were typed, so its `cd`s, variables and \
+                aliases last. It stops at the first command that fails.",
+            examples: &[("source ~/.bashrc", "Reload your aliases and variables.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        source(ctx, "source", args)
+    }
+}
+
+/// `.`, the POSIX spelling of `source`.
+pub struct Dot;
+
+impl Command for Dot {
+    fn name(&self) -> &'static str {
+        "."
+    }
+
+    fn help(&self) -> &'static str {
+        ". <file>"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "run a script in the current shell",
+            description: "The POSIX name for `source`: runs the commands in `file` as if they were typed.",
+            examples: &[(". ~/.profile", "Re-run your profile.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        source(ctx, ".", args)
+    }
+}
+
+/// Runs a script in the current shell, so its `cd`s and `export`s last.
+fn source(ctx: &mut CommandContext<'_>, tool: &str, args: &[String]) -> CommandResult {
+    let file = match args {
+        [file] => file,
+        [] => {
+            return CommandResult::error(format!("{}: filename argument required", tool))
+                .with_exit_code(EXIT_USAGE);
+        }
+        _ => return too_many_arguments(tool),
+    };
+    match read_script(ctx, tool, file) {
+        Ok(script) => run_script(ctx.registry, ctx.state, file, &script),
+        Err(result) => result,
+    }
+}
+
+fn read_script(ctx: &CommandContext<'_>, tool: &str, file: &str) -> Result<String, CommandResult> {
+    let path = resolve_path(&ctx.state.cwd, file);
+    ctx.state.fs.read_file(&path).map_err(|error| CommandResult {
+        error_code: Some(error.code()),
+        ..CommandResult::error(format!("{}: {}: {}", tool, file, error))
+    })
+}
+
+fn too_many_arguments(tool: &str) -> CommandResult {
+    CommandResult::error(format!("{}: script arguments are not supported", tool)).with_exit_code(EXIT_USAGE)
+}
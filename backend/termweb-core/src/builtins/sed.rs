@@ -0,0 +1,653 @@
+//! `sed`, the stream editor: enough of it for scripted edits, i.e.
+//! substitution, deletion and printing of addressed lines, with `-i` to edit
+//! files in place.
+
+use regex::{Captures, Regex, RegexBuilder};
+
+use super::filters::{parse_options, usage_error};
+use super::text::{read_inputs, report};
+use crate::command::{Command, CommandContext, CommandResult, Completion, Manual};
+use crate::path::resolve_path;
+
+pub struct Sed;
+
+impl Command for Sed {
+    fn name(&self) -> &'static str {
+        "sed"
+    }
+
+    fn help(&self) -> &'static str {
+        "sed [-n] [-i] [-E] (-e script)... | script [file]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "edit lines with a script",
+            description: "Runs `script` on each line of every file, or of the piped input, and prints the \
+                result. With `-i` each file is rewritten in place instead. `-n` stops lines being \
+                printed unless the script prints them, and `-E` makes patterns extended rather than \
+                basic regular expressions.\n\n\
+                A script is commands separated by `;` or newlines, each optionally preceded by an \
+                address: a line number, `$` for the last line, `/pattern/` for lines matching it, or \
+                two of these separated by a comma for the lines from one to the other. `!` after an \
+                address picks the other lines instead. The commands are:\n\n\
+                `s/pattern/replacement/flags` replaces the first match, every match with the `g` flag, \
+                or the Nth with a number; `i` ignores case and `p` prints the line if a replacement \
+                was made. In the replacement, `&` is the whole match and `\\1` to `\\9` are groups. \
+                `d` deletes the line, `p` prints it, `=` prints its number and `q` stops after it.",
+            examples: &[
+                ("sed 's/colour/color/g' notes.txt", "Print notes.txt with every colour spelt color."),
+                ("sed -i '1d' data.csv", "Delete the header line of data.csv."),
+                ("sed -n '/ERROR/p' app.log", "Print only the lines containing ERROR."),
+                (
+                    "sed -E 's/([a-z]+)@example.com/\\1@example.org/' users.txt",
+                    "Move addresses to a new domain.",
+                ),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let (options, mut operands) = match parse_options("sed", args, "niEr", "e") {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+        let has = |wanted: char| options.iter().any(|(flag, _)| *flag == wanted);
+        let (quiet, in_place, extended) = (has('n'), has('i'), has('E') || has('r'));
+        let expressions: Vec<&str> = options.iter().filter_map(|(_, value)| value.as_deref()).collect();
+        let source = if expressions.is_empty() {
+            if operands.is_empty() {
+                return usage_error(format!("usage: {}", self.help()));
+            }
+            operands.remove(0).to_string()
+        } else {
+            expressions.join("\n")
+        };
+        let mut script = match parse_script(&source, extended) {
+            Ok(script) => script,
+            Err(message) => return usage_error(format!("sed: {}", message)),
+        };
+
+        if in_place {
+            if operands.is_empty() {
+                return usage_error("sed: no input files");
+            }
+            let mut failures = Vec::new();
+            for file in operands {
+                let path = resolve_path(&ctx.state.cwd, file);
+                let edited = ctx.state.fs.read_file(&path).and_then(|content| {
+                    let lines: Vec<&str> = content.lines().collect();
+                    let mut edited = script.run(&lines, quiet).join("\n");
+                    if !edited.is_empty() && content.ends_with('\n') {
+                        edited.push('\n');
+                    }
+                    ctx.state.fs.write_file(&path, edited, false)
+                });
+                if let Err(error) = edited {
+                    failures.push((file, error));
+                }
+            }
+            return report("sed", Vec::new(), failures);
+        }
+
+        let inputs = match read_inputs(ctx, "sed", &operands) {
+            Ok(inputs) => inputs,
+            Err(result) => return result,
+        };
+        // One stream, so line numbers run on and `$` is the last file's last line.
+        let mut text = String::new();
+        let mut failures = Vec::new();
+        for (name, content) in inputs {
+            match content {
+                Ok(content) => {
+                    text.push_str(&String::from_utf8_lossy(&content.bytes()));
+                    if !text.is_empty() && !text.ends_with('\n') {
+                        text.push('\n');
+                    }
+                }
+                Err(error) => failures.push((name, error)),
+            }
+        }
+        let lines: Vec<&str> = text.lines().collect();
+        report("sed", script.run(&lines, quiet), failures)
+    }
+}
+
+struct Script {
+    commands: Vec<SedCommand>,
+}
+
+struct SedCommand {
+    start: Option<Address>,
+    /// The end of a range such as `2,5`.
+    end: Option<Address>,
+    /// `!`: the command runs on the lines not addressed.
+    negated: bool,
+    /// Whether the current line is inside the range.
+    in_range: bool,
+    action: Action,
+}
+
+enum Address {
+    Line(usize),
+    Last,
+    Pattern(Regex),
+}
+
+enum Action {
+    Substitute(Substitution),
+    Delete,
+    Print,
+    LineNumber,
+    Quit,
+}
+
+struct Substitution {
+    regex: Regex,
+    replacement: Vec<Piece>,
+    global: bool,
+    /// Which match to replace, counting from 1; with `global`, the first.
+    occurrence: usize,
+    print: bool,
+}
+
+/// Part of a replacement: text, or a group of the match (0 for `&`).
+enum Piece {
+    Text(String),
+    Group(usize),
+}
+
+impl Script {
+    /// The output of running the script over `lines`, the whole input.
+    fn run(&mut self, lines: &[&str], quiet: bool) -> Vec<String> {
+        for command in &mut self.commands {
+            command.in_range = false;
+        }
+        let mut output = Vec::new();
+        for (index, line) in lines.iter().enumerate() {
+            let position = (index + 1, index + 1 == lines.len());
+            let mut pattern_space = line.to_string();
+            let (mut deleted, mut quit) = (false, false);
+            for command in &mut self.commands {
+                if !command.selects(position, &pattern_space) {
+                    continue;
+                }
+                match &command.action {
+                    Action::Substitute(substitution) => {
+                        if let Some(replaced) = substitution.apply(&pattern_space) {
+                            pattern_space = replaced;
+                            if substitution.print {
+                                output.push(pattern_space.clone());
+                            }
+                        }
+                    }
+                    Action::Delete => {
+                        deleted = true;
+                        break;
+                    }
+                    Action::Print => output.push(pattern_space.clone()),
+                    Action::LineNumber => output.push(position.0.to_string()),
+                    Action::Quit => {
+                        quit = true;
+                        break;
+                    }
+                }
+            }
+            if !deleted && !quiet {
+                output.push(pattern_space);
+            }
+            if quit {
+                break;
+            }
+        }
+        output
+    }
+}
+
+impl SedCommand {
+    /// Whether the command applies to the line at `(number, is_last)`.
+    fn selects(&mut self, position: (usize, bool), line: &str) -> bool {
+        let selected = match (&self.start, &self.end) {
+            (None, _) => true,
+            (Some(start), None) => start.matches(position, line),
+            (Some(start), Some(end)) => {
+                if self.in_range {
+                    // A line number already passed ends the range at once.
+                    self.in_range = !end.matches(position, line)
+                        && !matches!(end, Address::Line(last) if *last <= position.0);
+                    true
+                } else if start.matches(position, line) {
+                    // As in GNU sed, the end is only looked for from the next line,
+                    // unless it is a line number that start has already reached.
+                    self.in_range = !matches!(end, Address::Line(last) if *last <= position.0);
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+        selected != self.negated
+    }
+}
+
+impl Address {
+    fn matches(&self, (number, is_last): (usize, bool), line: &str) -> bool {
+        match self {
+            Address::Line(wanted) => number == *wanted,
+            Address::Last => is_last,
+            Address::Pattern(regex) => regex.is_match(line),
+        }
+    }
+}
+
+impl Substitution {
+    /// `line` with the substitution made, or `None` when nothing matched.
+    fn apply(&self, line: &str) -> Option<String> {
+        let mut result = String::new();
+        let mut copied = 0;
+        let mut replaced = false;
+        for (index, captures) in self.regex.captures_iter(line).enumerate() {
+            let count = index + 1;
+            if count < self.occurrence {
+                continue;
+            }
+            let whole = captures.get(0).expect("group 0 is the whole match");
+            result.push_str(&line[copied..whole.start()]);
+            self.expand(&captures, &mut result);
+            copied = whole.end();
+            replaced = true;
+            if !self.global {
+                break;
+            }
+        }
+        if !replaced {
+            return None;
+        }
+        result.push_str(&line[copied..]);
+        Some(result)
+    }
+
+    fn expand(&self, captures: &Captures<'_>, result: &mut String) {
+        for piece in &self.replacement {
+            match piece {
+                Piece::Text(text) => result.push_str(text),
+                Piece::Group(group) => {
+                    result.push_str(captures.get(*group).map(|found| found.as_str()).unwrap_or_default())
+                }
+            }
+        }
+    }
+}
+
+/// Parses a whole script, e.g. `2,/^$/d; s/a/b/g`.
+fn parse_script(source: &str, extended: bool) -> Result<Script, String> {
+    let mut parser = Parser {
+        rest: source,
+        extended,
+    };
+    let mut commands = Vec::new();
+    loop {
+        parser.skip(|ch| ch.is_whitespace() || ch == ';');
+        if parser.rest.is_empty() {
+            break;
+        }
+        commands.push(parser.command()?);
+        parser.skip(|ch| ch == ' ' || ch == '\t');
+        match parser.rest.chars().next() {
+            None | Some(';' | '\n') => {}
+            Some(other) => return Err(format!("extra characters after command: '{}'", other)),
+        }
+    }
+    Ok(Script { commands })
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+    extended: bool,
+}
+
+impl Parser<'_> {
+    fn command(&mut self) -> Result<SedCommand, String> {
+        let start = self.address()?;
+        let end = match (&start, self.rest.strip_prefix(',')) {
+            (Some(_), Some(rest)) => {
+                self.rest = rest;
+                match self.address()? {
+                    Some(end) => Some(end),
+                    None => return Err("unexpected ','".to_string()),
+                }
+            }
+            _ => None,
+        };
+        self.skip(|ch| ch == ' ');
+        let negated = match self.rest.strip_prefix('!') {
+            Some(rest) => {
+                self.rest = rest;
+                self.skip(|ch| ch == ' ');
+                true
+            }
+            None => false,
+        };
+        let mut chars = self.rest.chars();
+        let action = match chars.next() {
+            Some('s') => {
+                self.rest = chars.as_str();
+                Action::Substitute(self.substitution()?)
+            }
+            Some(letter @ ('d' | 'p' | '=' | 'q')) => {
+                self.rest = chars.as_str();
+                match letter {
+                    'd' => Action::Delete,
+                    'p' => Action::Print,
+                    '=' => Action::LineNumber,
+                    _ => Action::Quit,
+                }
+            }
+            Some(other) => return Err(format!("unknown command: '{}'", other)),
+            None => return Err("missing command".to_string()),
+        };
+        Ok(SedCommand {
+            start,
+            end,
+            negated,
+            in_range: false,
+            action,
+        })
+    }
+
+    fn address(&mut self) -> Result<Option<Address>, String> {
+        let digits = self.rest.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(self.rest.len());
+        if digits > 0 {
+            let number = self.rest[..digits].parse().map_err(|_| "invalid line number".to_string())?;
+            if number == 0 {
+                return Err("invalid usage of line address 0".to_string());
+            }
+            self.rest = &self.rest[digits..];
+            return Ok(Some(Address::Line(number)));
+        }
+        if let Some(rest) = self.rest.strip_prefix('$') {
+            self.rest = rest;
+            return Ok(Some(Address::Last));
+        }
+        if let Some(rest) = self.rest.strip_prefix('/') {
+            self.rest = rest;
+            let pattern = self.delimited('/').ok_or("unterminated address regex")?;
+            return Ok(Some(Address::Pattern(self.regex(&pattern, false)?)));
+        }
+        Ok(None)
+    }
+
+    fn substitution(&mut self) -> Result<Substitution, String> {
+        let mut chars = self.rest.chars();
+        let delimiter = match chars.next() {
+            Some(delimiter) if delimiter != '\n' && delimiter != '\\' => delimiter,
+            _ => return Err("unterminated 's' command".to_string()),
+        };
+        self.rest = chars.as_str();
+        let pattern = self.delimited(delimiter).ok_or("unterminated 's' command")?;
+        let replacement = self.delimited(delimiter).ok_or("unterminated 's' command")?;
+
+        let (mut global, mut ignore_case, mut print) = (false, false, false);
+        let mut occurrence = None;
+        let flags = self.rest.find([';', '\n']).unwrap_or(self.rest.len());
+        let flags_text = self.rest[..flags].trim_end();
+        let mut number = String::new();
+        for flag in flags_text.chars() {
+            match flag {
+                'g' => global = true,
+                'i' | 'I' => ignore_case = true,
+                'p' => print = true,
+                '0'..='9' => number.push(flag),
+                other => return Err(format!("unknown option to 's': '{}'", other)),
+            }
+        }
+        if !number.is_empty() {
+            match number.parse::<usize>() {
+                Ok(count) if count > 0 => occurrence = Some(count),
+                _ => return Err("number option to 's' command may not be zero".to_string()),
+            }
+        }
+        self.rest = &self.rest[flags_text.len()..];
+
+        Ok(Substitution {
+            regex: self.regex(&pattern, ignore_case)?,
+            replacement: parse_replacement(&replacement),
+            global,
+            occurrence: occurrence.unwrap_or(1),
+            print,
+        })
+    }
+
+    /// The text up to the next unescaped `delimiter`, which is consumed;
+    /// `\delimiter` stands for the delimiter itself.
+    fn delimited(&mut self, delimiter: char) -> Option<String> {
+        let mut text = String::new();
+        let mut chars = self.rest.char_indices();
+        while let Some((index, ch)) = chars.next() {
+            if ch == delimiter {
+                self.rest = &self.rest[index + ch.len_utf8()..];
+                return Some(text);
+            }
+            if ch == '\\' {
+                match chars.next() {
+                    Some((_, escaped)) if escaped == delimiter => text.push(escaped),
+                    Some((_, escaped)) => {
+                        text.push('\\');
+                        text.push(escaped);
+                    }
+                    None => return None,
+                }
+            } else {
+                text.push(ch);
+            }
+        }
+        None
+    }
+
+    fn regex(&self, pattern: &str, ignore_case: bool) -> Result<Regex, String> {
+        RegexBuilder::new(&translate_pattern(pattern, self.extended))
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|error| format!("invalid pattern '{}': {}", pattern, error))
+    }
+
+    fn skip(&mut self, skipped: impl Fn(char) -> bool) {
+        self.rest = self.rest.trim_start_matches(skipped);
+    }
+}
+
+/// A POSIX regular expression in the syntax of the `regex` crate. In basic
+/// ones `\(`, `\{`, `\+`, `\?` and `\|` are the operators and the bare
+/// characters are literal; `\<` and `\>` are word boundaries in both.
+fn translate_pattern(pattern: &str, extended: bool) -> String {
+    const OPERATORS: &[char] = &['(', ')', '{', '}', '+', '?', '|'];
+    let mut translated = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some('<' | '>') => translated.push_str(r"\b"),
+                Some(operator) if !extended && OPERATORS.contains(&operator) => translated.push(operator),
+                Some(escaped) => {
+                    translated.push('\\');
+                    translated.push(escaped);
+                }
+                None => translated.push_str(r"\\"),
+            },
+            '[' => {
+                // Brackets are copied as they are, bar escaping what the crate
+                // would read as nested classes or escapes.
+                translated.push('[');
+                if chars.peek() == Some(&'^') {
+                    translated.push(chars.next().unwrap_or_default());
+                }
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                    translated.push_str(r"\]");
+                }
+                while let Some(ch) = chars.next() {
+                    match ch {
+                        ']' => break,
+                        '[' if chars.peek() == Some(&':') => {
+                            translated.push('[');
+                            for ch in chars.by_ref() {
+                                translated.push(ch);
+                                if ch == ']' {
+                                    break;
+                                }
+                            }
+                            continue;
+                        }
+                        '\\' | '[' | '&' | '~' => {
+                            translated.push('\\');
+                        }
+                        _ => {}
+                    }
+                    translated.push(ch);
+                }
+                translated.push(']');
+            }
+            // A leading `*` is literal.
+            '*' if translated.is_empty() || translated.ends_with(['^', '(']) => translated.push_str(r"\*"),
+            operator if !extended && OPERATORS.contains(&operator) => {
+                translated.push('\\');
+                translated.push(operator);
+            }
+            _ => translated.push(ch),
+        }
+    }
+    translated
+}
+
+/// A replacement such as `<\1>&`, with `\n` and `\t` decoded.
+fn parse_replacement(replacement: &str) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = replacement.chars();
+    while let Some(ch) = chars.next() {
+        let group = match ch {
+            '&' => 0,
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => digit as usize - '0' as usize,
+                Some('n') => {
+                    text.push('\n');
+                    continue;
+                }
+                Some('t') => {
+                    text.push('\t');
+                    continue;
+                }
+                Some(escaped) => {
+                    text.push(escaped);
+                    continue;
+                }
+                None => {
+                    text.push('\\');
+                    continue;
+                }
+            },
+            _ => {
+                text.push(ch);
+                continue;
+            }
+        };
+        if !text.is_empty() {
+            pieces.push(Piece::Text(std::mem::take(&mut text)));
+        }
+        pieces.push(Piece::Group(group));
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_script;
+    use crate::Shell;
+
+    const LINES: [&str; 5] = ["one", "two", "three", "four", "five"];
+
+    /// The output of `script` run over [`LINES`].
+    fn sed(script: &str, quiet: bool) -> Vec<String> {
+        parse_script(script, false).unwrap().run(&LINES, quiet)
+    }
+
+    #[test]
+    fn substitutes_with_flags() {
+        let mut script = parse_script("s/o/0/", false).unwrap();
+        assert_eq!(script.run(&["foo boo"], false), ["f0o boo"]);
+        let mut script = parse_script("s/o/0/g", false).unwrap();
+        assert_eq!(script.run(&["foo boo"], false), ["f00 b00"]);
+        let mut script = parse_script("s/o/0/3", false).unwrap();
+        assert_eq!(script.run(&["foo boo"], false), ["foo b0o"]);
+        let mut script = parse_script("s/o/0/2g", false).unwrap();
+        assert_eq!(script.run(&["foo boo"], false), ["fo0 b00"]);
+        let mut script = parse_script("s/FOO/bar/i", false).unwrap();
+        assert_eq!(script.run(&["foo"], false), ["bar"]);
+        // With `p`, a line is printed again only when something was replaced.
+        assert_eq!(sed("s/t/T/p", true), ["Two", "Three"]);
+    }
+
+    #[test]
+    fn expands_the_match_and_groups_in_replacements() {
+        let mut script = parse_script("s/[a-z]*/<&>/", false).unwrap();
+        assert_eq!(script.run(&["ab cd"], false), ["<ab> cd"]);
+        let mut script = parse_script(r"s/\(a*\)\(b*\)/\2\1/", false).unwrap();
+        assert_eq!(script.run(&["aabbb"], false), ["bbbaa"]);
+        let mut script = parse_script(r"s/(a*)(b*)/\2\1/", true).unwrap();
+        assert_eq!(script.run(&["aabbb"], false), ["bbbaa"]);
+        let mut script = parse_script(r"s/a/\&/g", false).unwrap();
+        assert_eq!(script.run(&["aa"], false), ["&&"]);
+        let mut script = parse_script("s|/|:|g", false).unwrap();
+        assert_eq!(script.run(&["/usr/bin"], false), [":usr:bin"]);
+        // A match of nothing that replaces nothing still counts.
+        let mut script = parse_script("s/x*//p", false).unwrap();
+        assert_eq!(script.run(&["ab"], true), ["ab"]);
+    }
+
+    #[test]
+    fn deletes_and_prints_addressed_lines() {
+        assert_eq!(sed("2d", false), ["one", "three", "four", "five"]);
+        assert_eq!(sed("$d", false), ["one", "two", "three", "four"]);
+        assert_eq!(sed("/^t/d", false), ["one", "four", "five"]);
+        assert_eq!(sed("3p", false), ["one", "two", "three", "three", "four", "five"]);
+        assert_eq!(sed("3p", true), ["three"]);
+        assert_eq!(sed("/e$/p", true), ["one", "three", "five"]);
+        assert_eq!(sed("2!d", false), ["two"]);
+        assert_eq!(sed("$=", true), ["5"]);
+        assert_eq!(sed("2q", false), ["one", "two"]);
+    }
+
+    #[test]
+    fn addresses_ranges() {
+        assert_eq!(sed("2,4d", false), ["one", "five"]);
+        assert_eq!(sed("2,$p", true), ["two", "three", "four", "five"]);
+        assert_eq!(sed("/two/,/four/p", true), ["two", "three", "four"]);
+        assert_eq!(sed("3,/o/p", true), ["three", "four"]);
+        // The end is only looked for after the start, and a passed line number ends it at once.
+        assert_eq!(sed("/o/,/o/p", true), ["one", "two", "four", "five"]);
+        assert_eq!(sed("4,2p", true), ["four"]);
+        assert_eq!(sed("2,3!d", false), ["two", "three"]);
+        // A range with no end runs to the last line.
+        assert_eq!(sed("/four/,/nope/d", false), ["one", "two", "three"]);
+        assert!(parse_script("/t/,+1p", false).is_err());
+    }
+
+    #[test]
+    fn runs_on_files_and_in_place() {
+        let mut shell = Shell::new();
+        shell.exec("printf 'one\\ntwo\\nthree\\n' > f");
+        assert_eq!(shell.exec("sed -n '2p' f").output, "two");
+        assert_eq!(shell.exec("sed -e 1d -e 's/e$/E/' f").output, "two\nthreE");
+        assert_eq!(shell.exec("sed -i '/two/d' f").exit_code, 0);
+        assert_eq!(shell.exec("cat f").output, "one\nthree");
+        let response = shell.exec("sed 's/a/b' f");
+        assert_eq!(response.exit_code, 2);
+    }
+}
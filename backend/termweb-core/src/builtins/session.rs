@@ -0,0 +1,248 @@
+use crate::command::{Command, CommandContext, CommandResult, Manual, Notification};
+
+/// Columns `man` wraps to when the terminal is wider.
+const MAN_WIDTH: usize = 80;
+/// How far `man` indents the body of each section.
+const MAN_INDENT: usize = 7;
+
+pub struct Help;
+
+impl Command for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn help(&self) -> &'static str {
+        "help [command]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "list commands and how to use them",
+            description: "With no arguments, lists every command with its usage. Given command names, \
+                shows just their usage lines; `man` explains a command in full.",
+            examples: &[("help", "List every command."), ("help ls cd", "Show how to call ls and cd.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        if args.is_empty() {
+            let mut lines = vec!["Available commands:".to_string()];
+            lines.extend(ctx.registry.iter().map(|command| format!("  {}", command.help())));
+            lines.push(String::new());
+            lines.push("Type `help <command>` for its usage, or `man <command>` for its manual.".to_string());
+            return CommandResult::ok(lines.join("\n"));
+        }
+        let mut lines = Vec::new();
+        for name in args {
+            match ctx.registry.get(name) {
+                Some(command) => lines.push(format!("usage: {}", command.help())),
+                None => return CommandResult::error(format!("help: no help topics match '{}'", name)),
+            }
+        }
+        CommandResult::ok(lines.join("\n"))
+    }
+}
+
+pub struct Man;
+
+impl Command for Man {
+    fn name(&self) -> &'static str {
+        "man"
+    }
+
+    fn help(&self) -> &'static str {
+        "man <command>..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "show a command's manual",
+            description: "Shows the manual page of each command given: what it is for, how to call \
+                it, what it does and examples of its use.",
+            examples: &[("man ls", "Read about ls."), ("man grep | grep -i example", "Search a manual.")],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        if args.is_empty() {
+            return CommandResult::error("What manual page do you want?\nFor example, try 'man man'.");
+        }
+        let width = ctx.state.terminal_width().unwrap_or(MAN_WIDTH).min(MAN_WIDTH);
+        let mut pages = Vec::new();
+        for name in args {
+            match ctx.registry.get(name) {
+                Some(command) => pages.push(manual_page(command, width)),
+                // As man(1) does.
+                None => {
+                    return CommandResult::error(format!("No manual entry for {}", name)).with_exit_code(16);
+                }
+            }
+        }
+        CommandResult::ok(pages.join("\n\n"))
+    }
+}
+
+/// `command`'s manual laid out as man(1) does, wrapped to `width` columns.
+fn manual_page(command: &dyn Command, width: usize) -> String {
+    let manual = command.manual();
+    let indent = " ".repeat(MAN_INDENT);
+    let text_width = width.saturating_sub(MAN_INDENT).max(20);
+    let mut lines = vec!["NAME".to_string()];
+    lines.push(match manual.summary {
+        "" => format!("{}{}", indent, command.name()),
+        summary => format!("{}{} - {}", indent, command.name(), summary),
+    });
+    lines.push(String::new());
+    lines.push("SYNOPSIS".to_string());
+    lines.push(format!("{}{}", indent, command.help()));
+    if !manual.description.is_empty() {
+        lines.push(String::new());
+        lines.push("DESCRIPTION".to_string());
+        for (index, paragraph) in manual.description.split("\n\n").enumerate() {
+            if index > 0 {
+                lines.push(String::new());
+            }
+            lines.extend(wrap(paragraph, text_width).into_iter().map(|line| format!("{}{}", indent, line)));
+        }
+    }
+    if !manual.examples.is_empty() {
+        lines.push(String::new());
+        lines.push("EXAMPLES".to_string());
+        for (index, (example, meaning)) in manual.examples.iter().enumerate() {
+            if index > 0 {
+                lines.push(String::new());
+            }
+            lines.push(format!("{}{}", indent, example));
+            let inner = " ".repeat(MAN_INDENT * 2);
+            for line in wrap(meaning, text_width.saturating_sub(MAN_INDENT).max(20)) {
+                lines.push(format!("{}{}", inner, line));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+/// Splits `text` into lines of at most `width` characters, breaking between
+/// words. A word longer than `width` gets a line to itself.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+pub struct History;
+
+impl Command for History {
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    fn help(&self) -> &'static str {
+        "history [-c] [count]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "list earlier commands",
+            description: "Lists the command lines run in this session, numbered, or just the last `count`. \
+                `-c` clears the history. `!!` repeats the last command and `!N` command N.",
+            examples: &[
+                ("history 10", "Show the last ten commands."),
+                ("history | grep tar", "Find an earlier tar command."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let history = &mut ctx.state.history;
+        let count = match args.first().map(String::as_str) {
+            None => history.len(),
+            Some("-c") => {
+                history.clear();
+                return CommandResult::empty();
+            }
+            Some(arg) => match arg.parse::<usize>() {
+                Ok(count) => count.min(history.len()),
+                Err(_) => return CommandResult::error(format!("history: {}: numeric argument required", arg)),
+            },
+        };
+        let start = history.len() - count;
+        let lines: Vec<String> = history[start..]
+            .iter()
+            .enumerate()
+            .map(|(offset, line)| format!("{:5}  {}", start + offset + 1, line))
+            .collect();
+        CommandResult::ok(lines.join("\n"))
+    }
+}
+
+pub struct Notify;
+
+impl Command for Notify {
+    fn name(&self) -> &'static str {
+        "notify"
+    }
+
+    fn help(&self) -> &'static str {
+        "notify <message>..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "send a notification",
+            description: "Shows `message` as a notification in the browser, for example when a long \
+                command finishes.",
+            examples: &[("sh build.sh; notify build finished", "Get a notification once a script is done.")],
+        }
+    }
+
+    fn run(&self, _ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        if args.is_empty() {
+            return CommandResult::error("notify: missing message");
+        }
+        CommandResult {
+            notifications: vec![Notification::Message { text: args.join(" ") }],
+            ..CommandResult::empty()
+        }
+    }
+}
+
+pub struct Clear;
+
+impl Command for Clear {
+    fn name(&self) -> &'static str {
+        "clear"
+    }
+
+    fn help(&self) -> &'static str {
+        "clear"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "clear the screen",
+            description: "Clears the terminal. Output after it in the same command line is still shown.",
+            examples: &[("clear", "Start with a clean screen.")],
+        }
+    }
+
+    fn run(&self, _ctx: &mut CommandContext<'_>, _args: &[String]) -> CommandResult {
+        CommandResult {
+            clear: true,
+            ..CommandResult::empty()
+        }
+    }
+}
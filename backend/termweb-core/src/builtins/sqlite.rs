@@ -0,0 +1,174 @@
+//! `sqlite3` over database files in the virtual filesystem.
+//!
+//! Virtual files hold text, so a database file is stored as the SQL dump that
+//! recreates it. Each invocation loads the dump into an in-memory SQLite
+//! database, runs the statements, and writes a fresh dump back if anything
+//! changed.
+
+use rusqlite::types::ValueRef;
+use rusqlite::Connection;
+
+use crate::command::{Command, CommandContext, CommandResult, Completion, Manual};
+use crate::fs::FsError;
+use crate::path::resolve_path;
+
+pub struct Sqlite;
+
+impl Command for Sqlite {
+    fn name(&self) -> &'static str {
+        "sqlite3"
+    }
+
+    fn help(&self) -> &'static str {
+        "sqlite3 [-header] <db> <sql>"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "query a SQLite database",
+            description: "Runs `sql` against the SQLite database file `db`, creating it if needed, and \
+                prints any rows returned with `|` between columns. `-header` adds a line of column \
+                names.",
+            examples: &[
+                ("sqlite3 app.db 'create table notes (body text)'", "Create a table."),
+                ("sqlite3 -header app.db 'select * from notes'", "Print a table with column names."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let header = args.iter().any(|arg| arg == "-header");
+        let operands: Vec<&String> = args.iter().filter(|arg| *arg != "-header").collect();
+        let [db, sql] = operands.as_slice() else {
+            return CommandResult::error("sqlite3: usage: sqlite3 [-header] <db> <sql>");
+        };
+        let path = resolve_path(&ctx.state.cwd, db);
+        let dump = match ctx.state.fs.read_file(&path) {
+            Ok(dump) => dump,
+            Err(FsError::NotFound) => String::new(),
+            Err(err) => return CommandResult::error(format!("sqlite3: {}: {}", db, err)),
+        };
+
+        let result = run_sql(&dump, sql, header);
+        let (output, new_dump) = match result {
+            Ok(result) => result,
+            Err(err) => return CommandResult::error(format!("Error: {}", err)),
+        };
+        if new_dump != dump
+            && let Err(error) = ctx.state.fs.write_file(&path, new_dump, false)
+        {
+            return CommandResult::fs_error("sqlite3", error);
+        }
+        CommandResult::ok(output)
+    }
+}
+
+fn run_sql(dump: &str, sql: &str, header: bool) -> rusqlite::Result<(String, String)> {
+    let conn = Connection::open_in_memory()?;
+    conn.execute_batch(dump)?;
+
+    let mut lines = Vec::new();
+    for text in split_statements(sql) {
+        let mut statement = conn.prepare(text)?;
+        let columns = statement.column_count();
+        if header && columns > 0 {
+            lines.push(statement.column_names().join("|"));
+        }
+        let mut rows = statement.raw_query();
+        while let Some(row) = rows.next()? {
+            let values: Vec<String> = (0..columns)
+                .map(|index| row.get_ref(index).map(format_value))
+                .collect::<rusqlite::Result<_>>()?;
+            lines.push(values.join("|"));
+        }
+    }
+
+    Ok((lines.join("\n"), dump_database(&conn)?))
+}
+
+/// Splits `sql` on semicolons that are outside quoted strings and identifiers.
+fn split_statements(sql: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+    for (index, ch) in sql.char_indices() {
+        match quote {
+            Some(active) if ch == active => quote = None,
+            Some(_) => {}
+            None if ch == '\'' || ch == '"' => quote = Some(ch),
+            None if ch == ';' => {
+                statements.push(&sql[start..index]);
+                start = index + 1;
+            }
+            None => {}
+        }
+    }
+    statements.push(&sql[start..]);
+    statements
+        .into_iter()
+        .map(str::trim)
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+fn format_value(value: ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(number) => number.to_string(),
+        ValueRef::Real(number) => number.to_string(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+        ValueRef::Blob(blob) => String::from_utf8_lossy(blob).into_owned(),
+    }
+}
+
+fn sql_literal(value: ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(number) => number.to_string(),
+        ValueRef::Real(number) => format!("{:?}", number),
+        ValueRef::Text(text) => format!("'{}'", String::from_utf8_lossy(text).replace('\'', "''")),
+        ValueRef::Blob(blob) => {
+            let hex: String = blob.iter().map(|byte| format!("{:02X}", byte)).collect();
+            format!("X'{}'", hex)
+        }
+    }
+}
+
+/// Serialises the schema and rows as SQL, like the sqlite3 shell's `.dump`.
+fn dump_database(conn: &Connection) -> rusqlite::Result<String> {
+    let mut schema = conn.prepare(
+        "SELECT type, name, sql FROM sqlite_master \
+         WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%' \
+         ORDER BY CASE type WHEN 'table' THEN 0 ELSE 1 END, rowid",
+    )?;
+    let objects: Vec<(String, String, String)> = schema
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    if objects.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut lines = vec!["BEGIN TRANSACTION;".to_string()];
+    for (kind, name, sql) in &objects {
+        lines.push(format!("{};", sql));
+        if kind != "table" {
+            continue;
+        }
+        let quoted = name.replace('"', "\"\"");
+        let mut rows = conn.prepare(&format!("SELECT * FROM \"{}\"", quoted))?;
+        let columns = rows.column_count();
+        let mut query = rows.raw_query();
+        while let Some(row) = query.next()? {
+            let values: Vec<String> = (0..columns)
+                .map(|index| row.get_ref(index).map(sql_literal))
+                .collect::<rusqlite::Result<_>>()?;
+            lines.push(format!("INSERT INTO \"{}\" VALUES({});", quoted, values.join(",")));
+        }
+    }
+    lines.push("COMMIT;".to_string());
+    Ok(lines.join("\n"))
+}
@@ -0,0 +1,274 @@
+use crate::command::{Command, CommandContext, CommandResult, Completion, Manual, EXIT_USAGE};
+use crate::fs::{FileSystem, Node, EXECUTE, READ, WRITE};
+use crate::path::resolve_path;
+
+pub struct Test;
+
+impl Command for Test {
+    fn name(&self) -> &'static str {
+        "test"
+    }
+
+    fn help(&self) -> &'static str {
+        "test <expression>"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "check a condition",
+            description: "Exits with status 0 if `expression` is true and 1 if it is false, printing \
+                nothing, for `if`, `while`, `&&` and `||` to act on. A malformed expression is an error \
+                with status 2.\n\n\
+                Files are checked with `-e` (exists), `-f` (is a file), `-d` (is a directory), `-s` (is \
+                readable and not empty), `-L` or `-h` (is a symbolic link), and `-r`, `-w` and `-x` (is \
+                readable, writable or executable). Text is compared with `=` or `==` and `!=`, and `-n` \
+                and `-z` check whether it is empty; a lone argument is true unless it is empty. Whole \
+                numbers are compared with `-eq`, `-ne`, `-lt`, `-le`, `-gt` and `-ge`.\n\n\
+                `!` negates what follows, `-a` and `-o` join two expressions with and and or, `-a` \
+                binding tighter, and `(` and `)` group them. With no expression at all, test is false.",
+            examples: &[
+                ("test -f config && cat config", "Show config if it is a file."),
+                ("test \"$USER\" = root || echo not root", "Compare a variable with some text."),
+                ("test $count -gt 10", "Check whether a number is over 10."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        evaluate(ctx, "test", args)
+    }
+}
+
+/// `[ expression ]`, test by another name that wants a closing `]`.
+pub struct Bracket;
+
+impl Command for Bracket {
+    fn name(&self) -> &'static str {
+        "["
+    }
+
+    fn help(&self) -> &'static str {
+        "[ <expression> ]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "check a condition",
+            description: "The same as `test expression`, with a `]` as its last argument to close the \
+                brackets. The spaces inside the brackets are needed, as `[` and `]` are arguments of \
+                their own.",
+            examples: &[
+                ("[ -f config ] && cat config", "Show config if it is a file."),
+                ("if [ -d build ]; then rm -r build; fi", "Remove build if it is a directory."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        match args.split_last() {
+            Some((last, expression)) if last == "]" => evaluate(ctx, "[", expression),
+            _ => CommandResult::error("[: missing ']'").with_exit_code(EXIT_USAGE),
+        }
+    }
+}
+
+pub struct True;
+
+impl Command for True {
+    fn name(&self) -> &'static str {
+        "true"
+    }
+
+    fn help(&self) -> &'static str {
+        "true"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "do nothing, successfully",
+            description: "Exits with status 0, whatever its arguments.",
+            examples: &[("while true; do date; sleep 60; done", "Print the date every minute.")],
+        }
+    }
+
+    fn run(&self, _ctx: &mut CommandContext<'_>, _args: &[String]) -> CommandResult {
+        CommandResult::empty()
+    }
+}
+
+pub struct False;
+
+impl Command for False {
+    fn name(&self) -> &'static str {
+        "false"
+    }
+
+    fn help(&self) -> &'static str {
+        "false"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "do nothing, unsuccessfully",
+            description: "Exits with status 1, whatever its arguments.",
+            examples: &[("false || echo failed", "Run the command after `||`.")],
+        }
+    }
+
+    fn run(&self, _ctx: &mut CommandContext<'_>, _args: &[String]) -> CommandResult {
+        CommandResult::error("")
+    }
+}
+
+fn evaluate(ctx: &CommandContext<'_>, tool: &str, args: &[String]) -> CommandResult {
+    let mut expression = Expression {
+        fs: &ctx.state.fs,
+        cwd: &ctx.state.cwd,
+        args,
+        position: 0,
+    };
+    let value = match args {
+        [] => Ok(false),
+        _ => expression.or().and_then(|value| match args.get(expression.position) {
+            Some(extra) => Err(format!("{}: unexpected argument", extra)),
+            None => Ok(value),
+        }),
+    };
+    match value {
+        Ok(true) => CommandResult::empty(),
+        Ok(false) => CommandResult::error(""),
+        Err(message) => CommandResult::error(format!("{}: {}", tool, message)).with_exit_code(EXIT_USAGE),
+    }
+}
+
+const UNARY_OPERATORS: [&str; 11] = ["-e", "-f", "-d", "-s", "-L", "-h", "-r", "-w", "-x", "-n", "-z"];
+
+const BINARY_OPERATORS: [&str; 9] = ["=", "==", "!=", "-eq", "-ne", "-lt", "-le", "-gt", "-ge"];
+
+/// A recursive-descent reader of test's arguments, evaluating as it goes.
+struct Expression<'a> {
+    fs: &'a FileSystem,
+    cwd: &'a [String],
+    args: &'a [String],
+    position: usize,
+}
+
+impl<'a> Expression<'a> {
+    fn peek(&self, offset: usize) -> Option<&'a str> {
+        self.args.get(self.position + offset).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Result<&'a str, String> {
+        let arg = self.args.get(self.position).ok_or("argument expected")?;
+        self.position += 1;
+        Ok(arg)
+    }
+
+    fn or(&mut self) -> Result<bool, String> {
+        let mut value = self.and()?;
+        while self.peek(0) == Some("-o") {
+            self.position += 1;
+            // Both sides are read, to check the whole expression is well formed.
+            value |= self.and()?;
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<bool, String> {
+        let mut value = self.not()?;
+        while self.peek(0) == Some("-a") {
+            self.position += 1;
+            value &= self.not()?;
+        }
+        Ok(value)
+    }
+
+    fn not(&mut self) -> Result<bool, String> {
+        // `[ ! = x ]` compares `!` with `x`.
+        if self.peek(0) == Some("!") && !self.peek(1).is_some_and(|next| BINARY_OPERATORS.contains(&next)) {
+            self.position += 1;
+            return Ok(!self.not()?);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<bool, String> {
+        if let Some(operator) = self.peek(1)
+            && BINARY_OPERATORS.contains(&operator)
+            && self.peek(2).is_some()
+        {
+            let (left, operator, right) = (self.next()?, self.next()?, self.next()?);
+            return compare(left, operator, right);
+        }
+        let arg = self.next()?;
+        if arg == "(" {
+            let value = self.or()?;
+            return match self.next() {
+                Ok(")") => Ok(value),
+                _ => Err("missing ')'".to_string()),
+            };
+        }
+        if !UNARY_OPERATORS.contains(&arg) || self.peek(0).is_none() {
+            // A lone word is true unless it is empty, even one like `-f`.
+            return Ok(!arg.is_empty());
+        }
+        let operand = self.next()?;
+        Ok(match arg {
+            "-n" => !operand.is_empty(),
+            "-z" => operand.is_empty(),
+            _ => self.check_file(arg, operand),
+        })
+    }
+
+    fn check_file(&self, operator: &str, name: &str) -> bool {
+        let path = resolve_path(self.cwd, name);
+        if matches!(operator, "-L" | "-h") {
+            return matches!(self.fs.get_node_nofollow(&path), Some(Node::Symlink { .. }));
+        }
+        let Some(node) = self.fs.get_node(&path) else {
+            return false;
+        };
+        match operator {
+            "-f" => matches!(node, Node::File { .. }),
+            "-d" => matches!(node, Node::Dir { .. }),
+            "-s" => match node {
+                Node::File { .. } => self.fs.read_bytes(&path).is_ok_and(|content| !content.is_empty()),
+                _ => true,
+            },
+            "-r" => self.fs.permitted(&path, READ),
+            "-w" => self.fs.permitted(&path, WRITE),
+            "-x" => self.fs.permitted(&path, EXECUTE),
+            _ => true,
+        }
+    }
+}
+
+fn compare(left: &str, operator: &str, right: &str) -> Result<bool, String> {
+    match operator {
+        "=" | "==" => return Ok(left == right),
+        "!=" => return Ok(left != right),
+        _ => {}
+    }
+    let number = |text: &str| {
+        text.trim()
+            .parse::<i64>()
+            .map_err(|_| format!("{}: integer expression expected", text))
+    };
+    let (left, right) = (number(left)?, number(right)?);
+    Ok(match operator {
+        "-eq" => left == right,
+        "-ne" => left != right,
+        "-lt" => left < right,
+        "-le" => left <= right,
+        "-gt" => left > right,
+        _ => left >= right,
+    })
+}
\ No newline at end of file
@@ -0,0 +1,723 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::color;
+use crate::command::{Command, CommandContext, CommandResult, Completion, Manual, EXIT_USAGE};
+use crate::fs::{is_binary, Content, FileSystem, FsError, Node, READ};
+use crate::path::resolve_path;
+
+pub struct Echo;
+
+impl Command for Echo {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn help(&self) -> &'static str {
+        "echo [text]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "print text",
+            description: "Prints its arguments separated by single spaces.",
+            examples: &[
+                ("echo Hello, $USER", "Print a greeting with a variable in it."),
+                ("echo done >> log.txt", "Append a line to a file."),
+            ],
+        }
+    }
+
+    fn run(&self, _ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        CommandResult::ok(args.join(" "))
+    }
+}
+
+pub struct Printf;
+
+impl Command for Printf {
+    fn name(&self) -> &'static str {
+        "printf"
+    }
+
+    fn help(&self) -> &'static str {
+        "printf <format> [argument]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "print arguments under a format",
+            description: "Prints `format`, with each conversion in it replaced by the next argument: `%s` \
+                as text, `%d` or `%i` as a decimal number, `%x` or `%X` in hexadecimal, and `%%` as a \
+                single `%`. A width such as `%5d` pads to that many characters, on the left, or on the \
+                right with `-` as in `%-10s`; `0` pads numbers with zeros, and `%.3s` keeps only the \
+                first 3 characters. `\\n`, `\\t`, `\\r` and `\\\\` in the format are a newline, a tab, a \
+                carriage return and a backslash.\n\n\
+                Arguments left over once the format has been used up go through it again, so \
+                `printf '%s\\n' a b c` prints three lines. Missing arguments count as empty, or 0. As every \
+                command's output ends its line here, one newline at the end is implied and need not be \
+                written.\n\n\
+                An argument that is not a number where one is needed is reported, printed as 0, and makes \
+                the status 1.",
+            examples: &[
+                ("printf '%-10s|%5d\\n' apples 3", "Print a name and a number in aligned columns."),
+                ("printf '%s\\n' one two three", "Print each argument on a line of its own."),
+                ("printf '%04x' 255", "Print 255 in hexadecimal, padded to 4 digits."),
+            ],
+        }
+    }
+
+    fn run(&self, _ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let Some((format, mut arguments)) = args.split_first() else {
+            return CommandResult::error(format!("usage: {}", self.help())).with_exit_code(EXIT_USAGE);
+        };
+        let mut output = String::new();
+        let mut errors = Vec::new();
+        loop {
+            let used = match format_once(format, arguments, &mut output, &mut errors) {
+                Ok(used) => used,
+                Err(message) => return CommandResult::error(format!("printf: {}", message)),
+            };
+            arguments = &arguments[used..];
+            // A format that takes no arguments would go round for ever.
+            if arguments.is_empty() || used == 0 {
+                break;
+            }
+        }
+        if output.ends_with('\n') {
+            output.pop();
+        }
+        let exit_code = if errors.is_empty() { 0 } else { 1 };
+        CommandResult::ok(output).with_errors(errors).with_exit_code(exit_code)
+    }
+}
+
+/// Writes `format` once onto `output`, taking conversions' values from
+/// `arguments`, and returns how many of them it used. Arguments that are not
+/// numbers where one is wanted are noted in `errors`.
+fn format_once(
+    format: &str,
+    arguments: &[String],
+    output: &mut String,
+    errors: &mut Vec<String>,
+) -> Result<usize, String> {
+    let mut used = 0;
+    let mut chars = format.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some('n') => output.push('\n'),
+                Some('t') => output.push('\t'),
+                Some('r') => output.push('\r'),
+                Some('\\') => output.push('\\'),
+                Some(other) => {
+                    output.push('\\');
+                    output.push(other);
+                }
+                None => output.push('\\'),
+            },
+            '%' if chars.next_if_eq(&'%').is_some() => output.push('%'),
+            '%' => {
+                let mut spec = String::from("%");
+                let (mut left, mut zero) = (false, false);
+                while let Some(flag) = chars.next_if(|&next| next == '-' || next == '0') {
+                    left |= flag == '-';
+                    zero |= flag == '0';
+                    spec.push(flag);
+                }
+                let mut width = String::new();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    width.push(digit);
+                }
+                spec.push_str(&width);
+                let precision = if chars.next_if_eq(&'.').is_some() {
+                    let mut digits = String::new();
+                    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(digit);
+                    }
+                    spec.push('.');
+                    spec.push_str(&digits);
+                    Some(digits.parse().unwrap_or(0))
+                } else {
+                    None
+                };
+                let Some(conversion) = chars.next() else {
+                    return Err(format!("{}: missing format character", spec));
+                };
+                let argument = arguments.get(used).map_or("", String::as_str);
+                used = (used + 1).min(arguments.len());
+                let text = match conversion {
+                    's' => match precision {
+                        Some(precision) => argument.chars().take(precision).collect(),
+                        None => argument.to_string(),
+                    },
+                    'd' | 'i' | 'x' | 'X' => {
+                        let number = printf_number(argument).unwrap_or_else(|| {
+                            errors.push(format!("printf: {}: invalid number", argument));
+                            0
+                        });
+                        match conversion {
+                            'x' => format!("{:x}", number),
+                            'X' => format!("{:X}", number),
+                            _ => number.to_string(),
+                        }
+                    }
+                    other => return Err(format!("{}{}: invalid format character", spec, other)),
+                };
+                let width: usize = width.parse().unwrap_or(0);
+                let numeric = conversion != 's';
+                match text.chars().count() {
+                    length if length >= width => output.push_str(&text),
+                    _ if left => output.push_str(&format!("{:<width$}", text, width = width)),
+                    _ if zero && numeric => match text.strip_prefix('-') {
+                        Some(digits) => output.push_str(&format!("-{:0>width$}", digits, width = width - 1)),
+                        None => output.push_str(&format!("{:0>width$}", text, width = width)),
+                    },
+                    _ => output.push_str(&format!("{:>width$}", text, width = width)),
+                }
+            }
+            ch => output.push(ch),
+        }
+    }
+    Ok(used)
+}
+
+/// A number as printf takes one: decimal, `0x` hexadecimal or `0` octal,
+/// or `'c` for a character's code. Empty counts as 0.
+fn printf_number(text: &str) -> Option<i64> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return Some(0);
+    }
+    if let Some(quoted) = trimmed.strip_prefix('\'').or_else(|| trimmed.strip_prefix('"')) {
+        return quoted.chars().next().map(|ch| i64::from(u32::from(ch)));
+    }
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let magnitude = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+pub struct Grep;
+
+impl Command for Grep {
+    fn name(&self) -> &'static str {
+        "grep"
+    }
+
+    fn help(&self) -> &'static str {
+        "grep [-r] [-i] [-n] <pattern> [path]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "print lines matching a pattern",
+            description: "Prints the lines of each file, or of the piped input, that match the regular \
+                expression `pattern`. With several files, or `-r`, each line is prefixed with its file \
+                name. `-i` ignores case, `-n` prefixes line numbers and `-r` searches directories \
+                recursively.\n\n\
+                grep exits with status 0 when something matched, 1 when nothing did and 2 on errors.",
+            examples: &[
+                ("grep -n TODO notes.txt", "Find the TODOs in notes.txt, with line numbers."),
+                ("grep -ri 'error' logs", "Search every file under logs for errors, in any case."),
+                ("history | grep cd", "Find earlier cd commands."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Paths
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut recursive = false;
+        let mut ignore_case = false;
+        let mut options = GrepOptions {
+            color: ctx.state.color_enabled().then(|| color::grep_match(&ctx.state.env).to_string()),
+            ..GrepOptions::default()
+        };
+        let mut operands = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() && operands.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'r' | 'R' => recursive = true,
+                            'i' => ignore_case = true,
+                            'n' => options.line_numbers = true,
+                            other => {
+                                return CommandResult::error(format!("grep: invalid option -- '{}'", other))
+                                    .with_exit_code(EXIT_USAGE);
+                            }
+                        }
+                    }
+                }
+                _ => operands.push(arg.as_str()),
+            }
+        }
+        let Some((pattern, paths)) = operands.split_first() else {
+            return CommandResult::error("grep: missing pattern").with_exit_code(EXIT_USAGE);
+        };
+        let regex = match RegexBuilder::new(pattern).case_insensitive(ignore_case).build() {
+            Ok(regex) => regex,
+            Err(error) => {
+                return CommandResult::error(format!("grep: invalid pattern: {}", error))
+                    .with_exit_code(EXIT_USAGE);
+            }
+        };
+
+        let mut paths = paths.to_vec();
+        if paths.is_empty() {
+            match ctx.stdin.take() {
+                Some(input) => {
+                    let mut lines = Vec::new();
+                    let matched = grep_text(&regex, &input, None, &options, &mut lines);
+                    return grep_result(lines, Vec::new(), matched);
+                }
+                // Like GNU grep, names found this way carry no `./` prefix.
+                None if recursive => paths.push(""),
+                None => return CommandResult::error("grep: missing file operand").with_exit_code(EXIT_USAGE),
+            }
+        }
+        options.with_names = recursive || paths.len() > 1;
+
+        let mut found = Found::default();
+        let mut matched = false;
+        for arg in paths {
+            if arg == "-" {
+                let input = ctx.stdin.take().unwrap_or_default();
+                matched |= grep_text(&regex, &input, Some("(standard input)"), &options, &mut found.lines);
+                continue;
+            }
+            let path = resolve_path(&ctx.state.cwd, arg);
+            match ctx.state.fs.get_node(&path) {
+                Some(Node::Dir { .. }) if !recursive => {
+                    found.errors.push(format!("grep: {}: Is a directory", arg));
+                }
+                Some(node) => {
+                    matched |= grep_node(&regex, &ctx.state.fs, &path, node, arg, &options, &mut found)
+                }
+                None => found.errors.push(format!("grep: {}: No such file or directory", arg)),
+            }
+        }
+        grep_result(found.lines, found.errors, matched)
+    }
+}
+
+#[derive(Default)]
+struct GrepOptions {
+    line_numbers: bool,
+    /// Prefix each match with the file it came from.
+    with_names: bool,
+    /// The colour matches are highlighted in, when colours are on.
+    color: Option<String>,
+}
+
+/// The matching lines `grep` has found, and what it could not search.
+#[derive(Default)]
+struct Found {
+    lines: Vec<String>,
+    errors: Vec<String>,
+}
+
+/// Searches a file, or every file under a directory in name order. `at` is
+/// where `node` is in `fs`, and `path` what it is called in the output; what
+/// may not be read is reported rather than searched.
+fn grep_node(
+    regex: &Regex,
+    fs: &FileSystem,
+    at: &[String],
+    node: &Node,
+    path: &str,
+    options: &GrepOptions,
+    found: &mut Found,
+) -> bool {
+    if !matches!(node, Node::Symlink { .. }) && !fs.permitted(at, READ) {
+        found.errors.push(format!("grep: {}: Permission denied", path));
+        return false;
+    }
+    match node {
+        // As GNU grep does, say that binary content matches rather than print it.
+        Node::File { content, .. } if is_binary(&content.bytes()) => {
+            let matched = regex.is_match(&String::from_utf8_lossy(&content.bytes()));
+            if matched {
+                found.lines.push(format!("Binary file {} matches", path));
+            }
+            matched
+        }
+        Node::File { content, .. } => {
+            grep_text(regex, &String::from_utf8_lossy(&content.bytes()), Some(path), options, &mut found.lines)
+        }
+        Node::Dir { children, .. } => {
+            let mut matched = false;
+            for (name, child) in children {
+                let child_path = match path {
+                    "" => name.clone(),
+                    _ => format!("{}/{}", path.trim_end_matches('/'), name),
+                };
+                let child_at = [at, std::slice::from_ref(name)].concat();
+                matched |= grep_node(regex, fs, &child_at, child, &child_path, options, found);
+            }
+            matched
+        }
+        // As with grep -r, links met along the way are not followed.
+        Node::Symlink { .. } => false,
+    }
+}
+
+fn grep_text(
+    regex: &Regex,
+    text: &str,
+    name: Option<&str>,
+    options: &GrepOptions,
+    lines: &mut Vec<String>,
+) -> bool {
+    let mut matched = false;
+    for (index, line) in text.lines().enumerate().filter(|(_, line)| regex.is_match(line)) {
+        matched = true;
+        // GNU grep's colours for names, line numbers and separators.
+        let paint = |text: &str, code: &str| match options.color {
+            Some(_) => color::paint(text, code),
+            None => text.to_string(),
+        };
+        let mut prefix = String::new();
+        if let Some(name) = name.filter(|_| options.with_names) {
+            prefix.push_str(&paint(name, "35"));
+            prefix.push_str(&paint(":", "36"));
+        }
+        if options.line_numbers {
+            prefix.push_str(&paint(&(index + 1).to_string(), "32"));
+            prefix.push_str(&paint(":", "36"));
+        }
+        let line = match &options.color {
+            Some(code) => highlight(regex, line, code),
+            None => line.to_string(),
+        };
+        lines.push(format!("{}{}", prefix, line));
+    }
+    matched
+}
+
+/// `line` with every match of `regex` painted in `code`.
+fn highlight(regex: &Regex, line: &str, code: &str) -> String {
+    let mut painted = String::new();
+    let mut end = 0;
+    for found in regex.find_iter(line).filter(|found| !found.is_empty()) {
+        painted.push_str(&line[end..found.start()]);
+        painted.push_str(&color::paint(found.as_str(), code));
+        end = found.end();
+    }
+    painted.push_str(&line[end..]);
+    painted
+}
+
+/// Like grep(1), finding nothing is a failure (status 1), and any
+/// unreadable path is trouble (status 2).
+fn grep_result(lines: Vec<String>, errors: Vec<String>, matched: bool) -> CommandResult {
+    let exit_code = match (matched, errors.is_empty()) {
+        (_, false) => EXIT_USAGE,
+        (true, true) => 0,
+        (false, true) => 1,
+    };
+    CommandResult::ok(lines.join("\n")).with_errors(errors).with_exit_code(exit_code)
+}
+
+pub struct Head;
+
+impl Command for Head {
+    fn name(&self) -> &'static str {
+        "head"
+    }
+
+    fn help(&self) -> &'static str {
+        "head [-n N] [file]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "print the first lines of files",
+            description: "Prints the first ten lines of each file, or of the piped input, or the first N \
+                with `-n`.",
+            examples: &[("head -n 3 notes.txt", "Print the first three lines of notes.txt.")],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let (count, files) = match split_count("head", args) {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+        let Ok(count) = count.parse::<usize>() else {
+            return CommandResult::error(format!("head: invalid number of lines: '{}'", count));
+        };
+        excerpt(ctx, "head", &files, |len| 0..count.min(len))
+    }
+}
+
+pub struct Tail;
+
+impl Command for Tail {
+    fn name(&self) -> &'static str {
+        "tail"
+    }
+
+    fn help(&self) -> &'static str {
+        "tail [-n [+]N] [file]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "print the last lines of files",
+            description: "Prints the last ten lines of each file, or of the piped input, or the last N \
+                with `-n`. `-n +N` prints from line N onwards instead.",
+            examples: &[
+                ("tail -n 20 log.txt", "Print the end of a log."),
+                ("tail -n +2 table.csv", "Skip a header line."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let (count, files) = match split_count("tail", args) {
+            Ok(parsed) => parsed,
+            Err(result) => return result,
+        };
+        // `+N` counts from the start: everything from line N on.
+        let (from_start, digits) = match count.strip_prefix('+') {
+            Some(digits) => (true, digits),
+            None => (false, count),
+        };
+        let Ok(count) = digits.parse::<usize>() else {
+            return CommandResult::error(format!("tail: invalid number of lines: '{}'", count));
+        };
+        excerpt(ctx, "tail", &files, |len| {
+            if from_start {
+                count.saturating_sub(1).min(len)..len
+            } else {
+                len.saturating_sub(count)..len
+            }
+        })
+    }
+}
+
+/// Splits `-n N`, `-nN` or the older `-N` from the file operands, giving the
+/// count as written; 10 if there is none.
+fn split_count<'a>(tool: &str, args: &'a [String]) -> Result<(&'a str, Vec<&'a str>), CommandResult> {
+    let mut count = "10";
+    let mut files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-n" {
+            let Some(value) = args.next() else {
+                return Err(CommandResult::error(format!("{}: option requires an argument -- 'n'", tool)));
+            };
+            count = value;
+        } else if let Some(value) = arg.strip_prefix("-n") {
+            count = value;
+        } else if let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) {
+            if !flags.chars().all(|ch| ch.is_ascii_digit()) {
+                let flag = flags.chars().next().unwrap_or_default();
+                return Err(CommandResult::error(format!("{}: invalid option -- '{}'", tool, flag)));
+            }
+            count = flags;
+        } else {
+            files.push(arg.as_str());
+        }
+    }
+    Ok((count, files))
+}
+
+/// Shared by `head` and `tail`: `pick` chooses which of an input's lines to
+/// show given how many it has. Several inputs each get a `==> name <==` header.
+fn excerpt(
+    ctx: &mut CommandContext<'_>,
+    tool: &str,
+    files: &[&str],
+    pick: impl Fn(usize) -> std::ops::Range<usize>,
+) -> CommandResult {
+    let inputs = match read_inputs(ctx, tool, files) {
+        Ok(inputs) => inputs,
+        Err(result) => return result,
+    };
+    let headers = inputs.len() > 1;
+    let mut lines = Vec::new();
+    let mut failures = Vec::new();
+    for (name, content) in inputs {
+        let content = match content {
+            Ok(content) => content,
+            Err(error) => {
+                failures.push((name, error));
+                continue;
+            }
+        };
+        if headers {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            let name = if name == "-" { "standard input" } else { name };
+            lines.push(format!("==> {} <==", name));
+        }
+        // Only the lines shown are copied out of the file.
+        let shown = pick(content.line_count());
+        let (start, end) = (content.line_offset(shown.start), content.line_offset(shown.end));
+        let text = content.range(start, end - start);
+        lines.extend(String::from_utf8_lossy(&text).lines().map(str::to_string));
+    }
+    report(tool, lines, failures)
+}
+
+pub struct Wc;
+
+impl Command for Wc {
+    fn name(&self) -> &'static str {
+        "wc"
+    }
+
+    fn help(&self) -> &'static str {
+        "wc [-l] [-w] [-c] [file]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "count lines, words and bytes",
+            description: "Prints the number of lines, words and bytes in each file, or in the piped input, \
+                with a total when there are several files. `-l`, `-w` and `-c` pick which counts to \
+                show.",
+            examples: &[
+                ("wc -l notes.txt", "Count the lines in notes.txt."),
+                ("ls | wc -l", "Count the files in a directory."),
+            ],
+        }
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::Files
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let (mut lines, mut words, mut bytes) = (false, false, false);
+        let mut files = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'l' => lines = true,
+                            'w' => words = true,
+                            'c' => bytes = true,
+                            other => return CommandResult::error(format!("wc: invalid option -- '{}'", other)),
+                        }
+                    }
+                }
+                _ => files.push(arg.as_str()),
+            }
+        }
+        if !(lines || words || bytes) {
+            (lines, words, bytes) = (true, true, true);
+        }
+        let shown = [lines, words, bytes];
+
+        let inputs = match read_inputs(ctx, "wc", &files) {
+            Ok(inputs) => inputs,
+            Err(result) => return result,
+        };
+        let named = !files.is_empty();
+        let mut rows = Vec::new();
+        let mut failures = Vec::new();
+        let mut total = [0; 3];
+        for (name, content) in inputs {
+            match content {
+                Ok(content) => {
+                    let text = String::from_utf8_lossy(&content.bytes()).into_owned();
+                    let counts = [
+                        text.lines().count(),
+                        text.split_whitespace().count(),
+                        content.len(),
+                    ];
+                    total.iter_mut().zip(counts).for_each(|(sum, count)| *sum += count);
+                    rows.push((counts, named.then_some(name)));
+                }
+                Err(error) => failures.push((name, error)),
+            }
+        }
+        if files.len() > 1 {
+            rows.push((total, Some("total")));
+        }
+
+        // Like coreutils, columns are as wide as the largest number shown.
+        let width = rows
+            .iter()
+            .flat_map(|(counts, _)| counts.iter().zip(shown).filter(|(_, shown)| *shown))
+            .map(|(count, _)| count.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let lines = rows
+            .into_iter()
+            .map(|(counts, name)| {
+                let mut columns: Vec<String> = counts
+                    .iter()
+                    .zip(shown)
+                    .filter(|(_, shown)| *shown)
+                    .map(|(count, _)| format!("{:>width$}", count, width = width))
+                    .collect();
+                columns.extend(name.map(str::to_string));
+                columns.join(" ")
+            })
+            .collect();
+        report("wc", lines, failures)
+    }
+}
+
+/// A file operand and what reading it gave.
+type Input<'a> = (&'a str, Result<Content, FsError>);
+
+/// The contents of each file operand, or of the piped input for `-` and
+/// when there are no operands, paired with the operand.
+pub(super) fn read_inputs<'a>(
+    ctx: &mut CommandContext<'_>,
+    tool: &str,
+    files: &[&'a str],
+) -> Result<Vec<Input<'a>>, CommandResult> {
+    if files.is_empty() {
+        return match ctx.stdin.take() {
+            Some(input) => Ok(vec![("-", Ok(Content::from(input.into_bytes())))]),
+            None => Err(CommandResult::error(format!("{}: missing file operand", tool))),
+        };
+    }
+    Ok(files
+        .iter()
+        .map(|file| match *file {
+            "-" => (*file, Ok(Content::from(ctx.stdin.take().unwrap_or_default().into_bytes()))),
+            _ => (*file, ctx.state.fs.read_bytes(&resolve_path(&ctx.state.cwd, file))),
+        })
+        .collect())
+}
+
+/// Output for several inputs: as in coreutils, any that could not be read
+/// are reported on the error output and make the command fail.
+pub(super) fn report(tool: &str, lines: Vec<String>, failures: Vec<(&str, FsError)>) -> CommandResult {
+    let error_code = failures.first().map(|(_, error)| error.code());
+    let exit_code = if failures.is_empty() { 0 } else { 1 };
+    let errors = failures
+        .into_iter()
+        .map(|(name, error)| format!("{}: {}: {}", tool, name, error));
+    CommandResult {
+        error_code,
+        ..CommandResult::ok(lines.join("\n")).with_errors(errors).with_exit_code(exit_code)
+    }
+}
@@ -0,0 +1,279 @@
+use crate::clock::{self, civil_from_days, days_from_civil, days_in_month, weekday};
+use crate::color;
+use crate::command::{Command, CommandContext, CommandResult, Manual, EXIT_INTERRUPTED, EXIT_USAGE};
+
+const DAY_NAMES: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// What `date` prints with no format, as GNU date does in the C locale.
+const DEFAULT_DATE_FORMAT: &str = "%a %b %e %H:%M:%S %Z %Y";
+
+/// Width of one month in `cal`: seven two-digit days, a space between each.
+const MONTH_WIDTH: usize = 20;
+
+pub struct Sleep;
+
+impl Command for Sleep {
+    fn name(&self) -> &'static str {
+        "sleep"
+    }
+
+    fn help(&self) -> &'static str {
+        "sleep <number>[smhd]..."
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "wait for a while",
+            description: "Waits for the total of the durations given: seconds, which may have a fraction, or \
+                minutes, hours or days with an `m`, `h` or `d` suffix.\n\n\
+                Run in the background with `&`, sleep leaves the terminal free for other commands. In a \
+                deterministic session the clock moves on at once instead of waiting, and in the browser \
+                nothing waits.",
+            examples: &[
+                ("sleep 2", "Wait two seconds."),
+                ("sleep 1m 30 && echo done &", "Say done in the background after a minute and a half."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        if args.is_empty() {
+            return CommandResult::error(format!("sleep: missing operand\nusage: {}", self.help()))
+                .with_exit_code(EXIT_USAGE);
+        }
+        let mut millis = 0u64;
+        for arg in args {
+            match clock::parse_duration(arg) {
+                Some(duration) => millis = millis.saturating_add(duration),
+                None => return CommandResult::error(format!("sleep: invalid time interval '{}'", arg)),
+            }
+        }
+        clock::sleep(ctx.state.fs.clock(), millis, &ctx.state.interrupt);
+        if ctx.interrupted() {
+            return CommandResult::error("").with_exit_code(EXIT_INTERRUPTED);
+        }
+        CommandResult::empty()
+    }
+}
+
+pub struct Date;
+
+impl Command for Date {
+    fn name(&self) -> &'static str {
+        "date"
+    }
+
+    fn help(&self) -> &'static str {
+        "date [-u] [+format]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "print the date and time",
+            description: "Prints the current date and time in UTC, the only time zone here, so `-u` changes \
+                nothing. A `+format` argument lays it out with strftime conversions: `%Y` year, `%m` month, \
+                `%d` day, `%H`, `%M` and `%S` for the time, `%a`/`%A` and `%b`/`%B` for day and month \
+                names, `%j` day of the year, `%s` seconds since 1970, `%F` for `%Y-%m-%d`, `%T` for \
+                `%H:%M:%S`, and more as in date(1). `%%` is a percent sign.",
+            examples: &[
+                ("date", "Print the date and time."),
+                ("date +%F", "Print the date as 2024-01-31."),
+                ("date '+%H:%M:%S'", "Print only the time."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let mut format = DEFAULT_DATE_FORMAT;
+        for arg in args {
+            match arg.as_str() {
+                "-u" | "--utc" => {}
+                _ if arg.starts_with('+') => format = &arg[1..],
+                _ if arg.starts_with('-') => {
+                    let message = format!("date: invalid option '{}'\nusage: {}", arg, self.help());
+                    return CommandResult::error(message).with_exit_code(EXIT_USAGE);
+                }
+                _ => return CommandResult::error(format!("date: invalid date '{}'", arg)),
+            }
+        }
+        CommandResult::ok(strftime(ctx.state.fs.clock().now_millis(), format))
+    }
+}
+
+/// `millis` laid out by `format`'s conversions; ones date(1) does not know
+/// are copied as written.
+fn strftime(millis: u64, format: &str) -> String {
+    let seconds = millis / 1000;
+    let days = (seconds / 86_400) as i64;
+    let (year, month, day) = civil_from_days(days);
+    let time = seconds % 86_400;
+    let (hour, minute, second) = (time / 3600, time / 60 % 60, time % 60);
+    let day_of_week = weekday(days) as usize;
+    let day_of_year = days - days_from_civil(year, 1, 1) + 1;
+    let hour12 = if hour % 12 == 0 { 12 } else { hour % 12 };
+    let month_name = MONTH_NAMES[month as usize - 1];
+
+    let mut text = String::new();
+    let mut chars = format.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            text.push(ch);
+            continue;
+        }
+        let Some(conversion) = chars.next() else {
+            text.push('%');
+            break;
+        };
+        let piece = match conversion {
+            'a' => DAY_NAMES[day_of_week][..3].to_string(),
+            'A' => DAY_NAMES[day_of_week].to_string(),
+            'b' | 'h' => month_name[..3].to_string(),
+            'B' => month_name.to_string(),
+            'c' => strftime(millis, "%a %b %e %H:%M:%S %Y"),
+            'C' => format!("{:02}", year / 100),
+            'd' => format!("{:02}", day),
+            'D' | 'x' => strftime(millis, "%m/%d/%y"),
+            'e' => format!("{:>2}", day),
+            'F' => strftime(millis, "%Y-%m-%d"),
+            'H' => format!("{:02}", hour),
+            'I' => format!("{:02}", hour12),
+            'j' => format!("{:03}", day_of_year),
+            'k' => format!("{:>2}", hour),
+            'l' => format!("{:>2}", hour12),
+            'm' => format!("{:02}", month),
+            'M' => format!("{:02}", minute),
+            'n' => "\n".to_string(),
+            'N' => format!("{:09}", millis % 1000 * 1_000_000),
+            'p' => if hour < 12 { "AM" } else { "PM" }.to_string(),
+            'P' => if hour < 12 { "am" } else { "pm" }.to_string(),
+            'r' => strftime(millis, "%I:%M:%S %p"),
+            'R' => strftime(millis, "%H:%M"),
+            's' => seconds.to_string(),
+            'S' => format!("{:02}", second),
+            't' => "\t".to_string(),
+            'T' | 'X' => strftime(millis, "%H:%M:%S"),
+            'u' => (if day_of_week == 0 { 7 } else { day_of_week }).to_string(),
+            'w' => day_of_week.to_string(),
+            'y' => format!("{:02}", year % 100),
+            'Y' => year.to_string(),
+            'z' => "+0000".to_string(),
+            'Z' => "UTC".to_string(),
+            '%' => "%".to_string(),
+            other => format!("%{}", other),
+        };
+        text.push_str(&piece);
+    }
+    text
+}
+
+pub struct Cal;
+
+impl Command for Cal {
+    fn name(&self) -> &'static str {
+        "cal"
+    }
+
+    fn help(&self) -> &'static str {
+        "cal [[month] year]"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "show a calendar",
+            description: "Shows this month's calendar, with today highlighted when colours are on. Given a \
+                year, shows all twelve months of it, three to a row; given a month (1 to 12) and a year, \
+                shows that month.",
+            examples: &[
+                ("cal", "Show this month."),
+                ("cal 2024", "Show the whole of 2024."),
+                ("cal 2 2024", "Show February 2024."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let days = (ctx.state.fs.clock().now_millis() / 86_400_000) as i64;
+        let today = civil_from_days(days);
+        // Only the live terminal gets today marked.
+        let highlight = ctx.state.color_enabled().then_some(today);
+        let year = |arg: &String| match arg.parse::<i64>() {
+            Ok(year) if (1..=9999).contains(&year) => Ok(year),
+            _ => Err(CommandResult::error(format!("cal: year '{}' not in range 1..9999", arg))),
+        };
+        let lines = match args {
+            [] => month_lines(today.0, today.1, true, highlight),
+            [only] => match year(only) {
+                Ok(year) => year_lines(year, highlight),
+                Err(error) => return error,
+            },
+            [month, only] => {
+                let month = match month.parse::<u32>() {
+                    Ok(month) if (1..=12).contains(&month) => month,
+                    _ => return CommandResult::error(format!("cal: {} is not a valid month", month)),
+                };
+                match year(only) {
+                    Ok(year) => month_lines(year, month, true, highlight),
+                    Err(error) => return error,
+                }
+            }
+            _ => return CommandResult::error(format!("usage: {}", self.help())).with_exit_code(EXIT_USAGE),
+        };
+        let lines: Vec<&str> = lines.iter().map(|line| line.trim_end()).collect();
+        CommandResult::ok(lines.join("\n").trim_end().to_string())
+    }
+}
+
+/// One month as `cal` lays it out: a centred title, the day names and six
+/// weeks, every line [`MONTH_WIDTH`] wide. `today`, if in this month, is
+/// shown in reverse video.
+fn month_lines(year: i64, month: u32, with_year: bool, today: Option<(i64, u32, u32)>) -> Vec<String> {
+    let name = MONTH_NAMES[month as usize - 1];
+    let title = if with_year { format!("{} {}", name, year) } else { name.to_string() };
+    let mut lines = vec![
+        format!("{:^width$}", title, width = MONTH_WIDTH),
+        format!("{:<width$}", "Su Mo Tu We Th Fr Sa", width = MONTH_WIDTH),
+    ];
+    let first = weekday(days_from_civil(year, month, 1)) as usize;
+    let mut cells: Vec<String> = vec!["  ".to_string(); first];
+    for day in 1..=days_in_month(year, month) {
+        let cell = format!("{:>2}", day);
+        cells.push(match today {
+            Some(today) if today == (year, month, day) => color::paint(&cell, "7"),
+            _ => cell,
+        });
+    }
+    cells.resize(42, "  ".to_string());
+    lines.extend(cells.chunks(7).map(|week| week.join(" ")));
+    lines
+}
+
+/// A whole year: the title, then the months three abreast.
+fn year_lines(year: i64, today: Option<(i64, u32, u32)>) -> Vec<String> {
+    let mut lines = vec![format!("{:^width$}", year, width = MONTH_WIDTH * 3 + 4), String::new()];
+    for quarter in 0..4 {
+        let months: Vec<Vec<String>> =
+            (1..=3).map(|index| month_lines(year, quarter * 3 + index, false, today)).collect();
+        for row in 0..months[0].len() {
+            let row: Vec<&str> = months.iter().map(|month| month[row].as_str()).collect();
+            lines.push(row.join("  "));
+        }
+        if quarter < 3 {
+            lines.push(String::new());
+        }
+    }
+    lines
+}
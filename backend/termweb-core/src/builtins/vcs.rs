@@ -0,0 +1,220 @@
+use crate::command::{Command, CommandContext, CommandResult, Manual};
+use crate::diff;
+use crate::fs::{is_binary, Content, FsError, Node, READ};
+use crate::path::{path_string, resolve_path};
+use crate::state::TerminalState;
+use crate::vcs::{changes, lookup, snapshot, Change, Repository, META_DIR};
+
+pub struct Vcs;
+
+impl Command for Vcs {
+    fn name(&self) -> &'static str {
+        "vcs"
+    }
+
+    fn help(&self) -> &'static str {
+        "vcs init | status | commit -m <msg> | log | diff [rev [rev]] | checkout <rev>"
+    }
+
+    fn manual(&self) -> Manual {
+        Manual {
+            summary: "track changes to files",
+            description: "A small version control system. `init` makes the current directory a repository, \
+                `commit -m` saves a snapshot of it and `log` lists the snapshots. `status` lists the \
+                files changed since the last commit and `diff` shows the changes to their lines, since \
+                the last commit, since `rev` or between two commits. `checkout` restores the files of \
+                an earlier one.\n\n\
+                Only files you can read are committed, and each commit counts towards the space and \
+                the number of files the session may hold, as a copy of the repository would.",
+            examples: &[
+                ("vcs init", "Start tracking the current directory."),
+                ("vcs commit -m 'first draft'", "Save a snapshot."),
+                ("vcs diff", "Show what changed since the last commit."),
+            ],
+        }
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let Some((subcommand, rest)) = args.split_first() else {
+            return CommandResult::error(format!("usage: {}", self.help()));
+        };
+        let state = &mut *ctx.state;
+        let result = match subcommand.as_str() {
+            "init" => init(state),
+            "status" => status(state),
+            "commit" => commit(state, rest),
+            "log" => log(state),
+            "diff" => diff(state, rest),
+            "checkout" => checkout(state, rest),
+            other => Err(format!("vcs: unknown subcommand '{}'", other)),
+        };
+        result.into()
+    }
+}
+
+fn init(state: &mut TerminalState) -> Result<String, String> {
+    let mut marker = state.cwd.clone();
+    marker.push(META_DIR.to_string());
+    if state.repositories.contains_key(&state.cwd) && state.fs.get_node(&marker).is_some() {
+        return Err("vcs: repository already initialised".to_string());
+    }
+    let now = state.fs.clock().now_millis();
+    state.fs.replace_node(&marker, Node::dir(now)).map_err(|error| format!("vcs: {}", error))?;
+    // A repository whose marker was removed is started afresh; its commits go.
+    if let Some(old) = state.repositories.insert(state.cwd.clone(), Repository::default()) {
+        for commit in &old.commits {
+            state.fs.release(&commit.tree);
+        }
+    }
+    Ok(format!(
+        "Initialised empty repository in {}",
+        path_string(&state.cwd)
+    ))
+}
+
+/// Finds the repository containing the cwd; its root must still hold the marker directory.
+fn find_repo(state: &TerminalState) -> Result<Vec<String>, String> {
+    let mut candidate = state.cwd.clone();
+    loop {
+        let mut marker = candidate.clone();
+        marker.push(META_DIR.to_string());
+        if state.repositories.contains_key(&candidate) && state.fs.get_node(&marker).is_some() {
+            return Ok(candidate);
+        }
+        if candidate.pop().is_none() {
+            return Err("vcs: not a repository (run 'vcs init')".to_string());
+        }
+    }
+}
+
+fn working_tree(state: &TerminalState, root: &[String]) -> Result<Node, String> {
+    snapshot(&state.fs, root)
+}
+
+fn format_changes(list: Vec<(Change, String)>) -> String {
+    list.into_iter()
+        .map(|(change, path)| format!("{} {}", change.code(), path))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn status(state: &TerminalState) -> Result<String, String> {
+    let root = find_repo(state)?;
+    let repo = &state.repositories[&root];
+    let tree = working_tree(state, &root)?;
+    // Before the first commit, everything is new.
+    let base = repo
+        .head_commit()
+        .map(|commit| commit.tree.clone())
+        .unwrap_or_else(|| Node::dir(0));
+    let list = changes(&base, &tree);
+    if list.is_empty() {
+        Ok("nothing to commit, working tree clean".to_string())
+    } else {
+        Ok(format_changes(list))
+    }
+}
+
+fn commit(state: &mut TerminalState, args: &[String]) -> Result<String, String> {
+    let message = match args {
+        [flag, message] if flag == "-m" => message,
+        _ => return Err("vcs: usage: vcs commit -m <message>".to_string()),
+    };
+    let root = find_repo(state)?;
+    let tree = working_tree(state, &root)?;
+    let repo = state.repositories.get_mut(&root).expect("repository was found");
+    if repo.head_commit().is_some_and(|head| head.tree == tree) {
+        return Err("vcs: nothing to commit".to_string());
+    }
+    state.fs.hold(&tree).map_err(|error| format!("vcs: {}", error))?;
+    let commit = repo.commit(message, tree);
+    Ok(format!("[{}] {}", commit.id, commit.message))
+}
+
+fn log(state: &TerminalState) -> Result<String, String> {
+    let root = find_repo(state)?;
+    let repo = &state.repositories[&root];
+    let history = repo.history();
+    if history.is_empty() {
+        return Err("vcs: no commits yet".to_string());
+    }
+    Ok(history
+        .iter()
+        .map(|commit| format!("{} {}", commit.id, commit.message))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn diff(state: &TerminalState, args: &[String]) -> Result<String, String> {
+    let root = find_repo(state)?;
+    let repo = &state.repositories[&root];
+    let (old, new) = match args {
+        [] => (
+            repo.head_commit().map(|commit| commit.tree.clone()).unwrap_or_else(|| Node::dir(0)),
+            working_tree(state, &root)?,
+        ),
+        [from] => (repo.commits[repo.find(from)?].tree.clone(), working_tree(state, &root)?),
+        [from, to] => (
+            repo.commits[repo.find(from)?].tree.clone(),
+            repo.commits[repo.find(to)?].tree.clone(),
+        ),
+        _ => return Err("vcs: usage: vcs diff [rev [rev]]".to_string()),
+    };
+    let paint = state.color_enabled();
+    let mut output = Vec::new();
+    for (change, path) in changes(&old, &new) {
+        let (from, to) = match change {
+            Change::Added => ("/dev/null".to_string(), format!("b/{}", path)),
+            Change::Deleted => (format!("a/{}", path), "/dev/null".to_string()),
+            Change::Modified => (format!("a/{}", path), format!("b/{}", path)),
+        };
+        // What a file held before is as private as what it holds now.
+        if !state.fs.permitted(&resolve_path(&root, &path), READ) {
+            return Err(format!("vcs: {}: {}", path, FsError::PermissionDenied));
+        }
+        let (old_content, new_content) = (content(&old, &path), content(&new, &path));
+        let (old_content, new_content) = (old_content.bytes(), new_content.bytes());
+        output.push(format!("diff --vcs a/{} b/{}", path, path));
+        if is_binary(&old_content) || is_binary(&new_content) {
+            output.push(format!("Binary files {} and {} differ", from, to));
+            continue;
+        }
+        let old_text = String::from_utf8_lossy(&old_content);
+        let new_text = String::from_utf8_lossy(&new_content);
+        // A file added or deleted empty has no lines to show.
+        output.extend(diff::unified(&from, &to, &old_text, &new_text, paint));
+    }
+    Ok(output.join("\n"))
+}
+
+/// What a changed path holds in `tree`: a file's content, or a symlink's
+/// target, as git shows them; nothing where it was added or deleted.
+fn content(tree: &Node, path: &str) -> Content {
+    match lookup(tree, path) {
+        Some(Node::File { content, .. }) => content.clone(),
+        Some(Node::Symlink { target, .. }) => Content::from(target.as_bytes().to_vec()),
+        _ => Content::default(),
+    }
+}
+
+fn checkout(state: &mut TerminalState, args: &[String]) -> Result<String, String> {
+    let [reference] = args else {
+        return Err("vcs: usage: vcs checkout <rev>".to_string());
+    };
+    let root = find_repo(state)?;
+    let repo = state.repositories.get_mut(&root).expect("repository was found");
+    let index = repo.find(reference)?;
+    let mut tree = repo.commits[index].tree.clone();
+    let id = repo.commits[index].id.clone();
+    repo.head = Some(index);
+
+    // Keep the marker directory so the checkout stays inside the repository.
+    if let Node::Dir { children, .. } = &mut tree {
+        children.insert(META_DIR.to_string(), Node::dir(state.fs.clock().now_millis()));
+    }
+    state.fs.replace_node(&root, tree).map_err(|error| format!("vcs: {}", error))?;
+    if state.fs.get_node(&state.cwd).is_none() {
+        state.cwd = root;
+    }
+    Ok(format!("HEAD is now at {}", id))
+}
@@ -0,0 +1,197 @@
+//! Where the engine gets the current time. Swapping in a [`FixedClock`]
+//! makes timestamps reproducible across runs.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub trait Clock: Send + Sync {
+    /// Milliseconds since the Unix epoch.
+    fn now_millis(&self) -> u64;
+
+    /// Lets `millis` milliseconds pass, for `sleep`. A clock that keeps its
+    /// own time moves on and returns true; the host's returns false, leaving
+    /// the caller to wait.
+    fn advance(&self, _millis: u64) -> bool {
+        false
+    }
+}
+
+/// The host's wall clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    // `SystemTime::now` panics in the browser; ask JavaScript instead.
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    fn now_millis(&self) -> u64 {
+        js_sys::Date::now() as u64
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn now_millis(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default()
+    }
+}
+
+/// A clock stopped at one instant, which only `sleep` moves on.
+pub struct FixedClock(AtomicU64);
+
+impl FixedClock {
+    pub fn new(epoch_millis: u64) -> Self {
+        FixedClock(AtomicU64::new(epoch_millis))
+    }
+}
+
+impl Clock for FixedClock {
+    fn now_millis(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn advance(&self, millis: u64) -> bool {
+        self.0.fetch_add(millis, Ordering::Relaxed);
+        true
+    }
+}
+
+/// How often a sleep on the host's clock looks to see whether it should stop.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+const INTERRUPT_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Lets `millis` milliseconds pass on `clock`, blocking the thread when it
+/// is the host's until they have or `interrupt` is set. In the browser,
+/// where the thread may not block, nothing waits.
+pub fn sleep(clock: &dyn Clock, millis: u64, interrupt: &AtomicBool) {
+    if clock.advance(millis) {
+        return;
+    }
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    let _ = interrupt;
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    {
+        let deadline = Instant::now() + Duration::from_millis(millis);
+        while !interrupt.load(Ordering::Relaxed) {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            std::thread::sleep(left.min(INTERRUPT_CHECK_INTERVAL));
+        }
+    }
+}
+
+/// Milliseconds in a `sleep` duration: a number of seconds, which may have
+/// a fraction, or of minutes, hours or days with an `m`, `h` or `d` suffix.
+pub fn parse_duration(text: &str) -> Option<u64> {
+    let (number, unit) = match text.strip_suffix(['s', 'm', 'h', 'd']) {
+        Some(number) => (number, &text[number.len()..]),
+        None => (text, "s"),
+    };
+    let seconds: f64 = number.parse().ok().filter(|seconds: &f64| seconds.is_finite() && *seconds >= 0.0)?;
+    let scale = match unit {
+        "m" => 60.0,
+        "h" => 3600.0,
+        "d" => 86_400.0,
+        _ => 1.0,
+    };
+    Some((seconds * scale * 1000.0).round() as u64)
+}
+
+/// The millisecond Unix timestamp of a date as `touch -d` takes it: `@`
+/// and a number of seconds since 1970, or `YYYY-MM-DD` with an optional
+/// `HH:MM` or `HH:MM:SS` after a space or `T`, in UTC.
+pub fn parse_date(text: &str) -> Option<u64> {
+    if let Some(seconds) = text.strip_prefix('@') {
+        return seconds.parse::<u64>().ok()?.checked_mul(1000);
+    }
+    let text = text.trim().trim_end_matches('Z').trim_end_matches(" UTC");
+    let (date, time) = match text.split_once([' ', 'T']) {
+        Some((date, time)) => (date, time.trim()),
+        None => (text, "00:00"),
+    };
+    let (year, month, day) = match date.split('-').collect::<Vec<_>>()[..] {
+        [year, month, day] => (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?),
+        _ => return None,
+    };
+    let time: Vec<u32> = time.split(':').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    let (hour, minute, second) = match time[..] {
+        [hour, minute] => (hour, minute, 0),
+        [hour, minute, second] => (hour, minute, second),
+        _ => return None,
+    };
+    timestamp(year, month, day, hour, minute, second)
+}
+
+/// The millisecond Unix timestamp of a UTC date and time, if it is a real
+/// one no earlier than 1970.
+pub fn timestamp(year: i64, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> Option<u64> {
+    let valid = (1..=12).contains(&month)
+        && (1..=days_in_month(year, month)).contains(&day)
+        && hour < 24
+        && minute < 60
+        && second < 60;
+    let days = u64::try_from(days_from_civil(year, month, day)).ok().filter(|_| valid)?;
+    let seconds = days * 86_400 + u64::from(hour * 3600 + minute * 60 + second);
+    Some(seconds * 1000)
+}
+
+/// `YYYY-MM-DD HH:MM:SS` in UTC for a millisecond Unix timestamp.
+pub fn format_timestamp(millis: u64) -> String {
+    let seconds = millis / 1000;
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let time = seconds % 86_400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// The proleptic Gregorian date `days` after 1970-01-01, after Howard
+/// Hinnant's `civil_from_days`.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Days from 1970-01-01 to the given date; the inverse of [`civil_from_days`].
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let shifted_month = i64::from(if month > 2 { month - 3 } else { month + 9 });
+    let day_of_year = (153 * shifted_month + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The day of the week `days` after 1970-01-01, from 0 for Sunday.
+pub fn weekday(days: i64) -> u32 {
+    // 1970-01-01 was a Thursday.
+    (days + 4).rem_euclid(7) as u32
+}
+
+/// How many days `month` of `year` has.
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
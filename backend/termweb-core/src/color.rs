@@ -0,0 +1,60 @@
+//! ANSI colours, for clients such as xterm.js that render them. They are off
+//! until a client asks for them (see [`TerminalState::color`]), so plain
+//! clients keep getting clean text, and never reach pipes or files.
+//!
+//! Like GNU `ls` and `grep`, the colours can be changed with `LS_COLORS`
+//! (`di`, `ln` and `ex` entries, e.g. `di=01;36:ln=35`) and `GREP_COLOR`.
+//!
+//! [`TerminalState::color`]: crate::state::TerminalState::color
+
+use std::collections::BTreeMap;
+
+/// Failing commands' output.
+pub const ERROR: &str = "31";
+
+/// Wraps `text` in the SGR sequence `code`, e.g. `01;34` for bold blue.
+pub fn paint(text: &str, code: &str) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+/// How `ls` colours names, by the kind of node.
+pub struct LsColors {
+    pub dir: String,
+    pub link: String,
+    pub executable: String,
+}
+
+impl LsColors {
+    /// GNU's defaults, overridden by any entries in `LS_COLORS`.
+    pub fn from_env(env: &BTreeMap<String, String>) -> Self {
+        let mut colors = LsColors {
+            dir: "01;34".to_string(),
+            link: "01;36".to_string(),
+            executable: "01;32".to_string(),
+        };
+        let entries = env.get("LS_COLORS").map(String::as_str).unwrap_or_default();
+        for entry in entries.split(':') {
+            let Some((key, code)) = entry.split_once('=') else {
+                continue;
+            };
+            match key {
+                "di" => colors.dir = code.to_string(),
+                "ln" => colors.link = code.to_string(),
+                "ex" => colors.executable = code.to_string(),
+                _ => {}
+            }
+        }
+        colors
+    }
+}
+
+/// How `grep` highlights matches: `GREP_COLOR`, or bold red.
+pub fn grep_match(env: &BTreeMap<String, String>) -> &str {
+    env.get("GREP_COLOR")
+        .map(String::as_str)
+        .filter(|code| !code.is_empty())
+        .unwrap_or("01;31")
+}
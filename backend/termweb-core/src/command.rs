@@ -0,0 +1,173 @@
+use serde::Serialize;
+
+use crate::fs::FsError;
+use crate::registry::CommandRegistry;
+use crate::state::TerminalState;
+
+/// What kind of argument a command expects, so clients can offer tab completion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Completion {
+    None,
+    Paths,
+    Directories,
+    Files,
+}
+
+/// Everything a built-in can touch while it runs.
+pub struct CommandContext<'a> {
+    pub state: &'a mut TerminalState,
+    pub registry: &'a CommandRegistry,
+    /// Output of the previous pipeline stage; `None` when not reading from a pipe.
+    pub stdin: Option<String>,
+}
+
+impl CommandContext<'_> {
+    /// Whether the host has asked the command line to stop, which commands
+    /// that can run for long, such as `sleep`, check as they go.
+    pub fn interrupted(&self) -> bool {
+        self.state.interrupted()
+    }
+}
+
+/// An out-of-band event for the client, e.g. a toast when a long job ends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Notification {
+    /// The output rang the terminal bell (BEL, `\x07`).
+    Bell,
+    Message { text: String },
+}
+
+/// Exit status for a command that could not be found, as in sh.
+pub const EXIT_NOT_FOUND: i32 = 127;
+/// Exit status for a command line that could not be parsed, and for
+/// commands given arguments they cannot make sense of.
+pub const EXIT_USAGE: i32 = 2;
+/// Exit status for a command line its host stopped, as for one killed by SIGINT.
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+#[derive(Debug, Default)]
+pub struct CommandResult {
+    /// What the command printed: the data `>` saves and `|` passes on.
+    pub output: String,
+    /// What went wrong, apart from the output, as a real command would
+    /// print it to stderr: `2>` saves it, and a pipe shows it rather than
+    /// passing it on.
+    pub error_output: String,
+    /// 0 for success; anything else is a failure.
+    pub exit_code: i32,
+    /// Machine-readable cause of a failure, e.g. `not_found`.
+    pub error_code: Option<&'static str>,
+    pub clear: bool,
+    pub notifications: Vec<Notification>,
+}
+
+impl CommandResult {
+    pub fn ok(output: impl Into<String>) -> Self {
+        CommandResult {
+            output: output.into(),
+            ..CommandResult::default()
+        }
+    }
+
+    pub fn empty() -> Self {
+        CommandResult::ok(String::new())
+    }
+
+    /// A failure with exit status 1, with `message` as its error output;
+    /// see [`CommandResult::with_exit_code`] for other statuses.
+    pub fn error(message: impl Into<String>) -> Self {
+        CommandResult {
+            error_output: message.into(),
+            exit_code: 1,
+            ..CommandResult::default()
+        }
+    }
+
+    /// A failed filesystem operation, shown as `tool: reason` and carrying
+    /// the error's code.
+    pub fn fs_error(tool: &str, error: FsError) -> Self {
+        CommandResult {
+            error_code: Some(error.code()),
+            ..CommandResult::error(format!("{}: {}", tool, error))
+        }
+    }
+
+    pub fn with_exit_code(mut self, exit_code: i32) -> Self {
+        self.exit_code = exit_code;
+        self
+    }
+
+    /// Adds `messages` as error output, one per line, for a command that
+    /// printed something and also hit trouble. The status is left alone.
+    pub fn with_errors(mut self, messages: impl IntoIterator<Item = String>) -> Self {
+        let mut lines: Vec<String> = messages.into_iter().collect();
+        if !self.error_output.is_empty() {
+            lines.insert(0, std::mem::take(&mut self.error_output));
+        }
+        self.error_output = lines.join("\n");
+        self
+    }
+
+    /// The output and then the error output, for showing both at once.
+    pub fn combined_output(&self) -> String {
+        match (self.output.is_empty(), self.error_output.is_empty()) {
+            (_, true) => self.output.clone(),
+            (true, false) => self.error_output.clone(),
+            (false, false) => format!("{}\n{}", self.output, self.error_output),
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+}
+
+impl From<Result<String, String>> for CommandResult {
+    fn from(result: Result<String, String>) -> Self {
+        match result {
+            Ok(output) => CommandResult::ok(output),
+            Err(message) => CommandResult::error(message),
+        }
+    }
+}
+
+impl From<Result<(), String>> for CommandResult {
+    fn from(result: Result<(), String>) -> Self {
+        match result {
+            Ok(()) => CommandResult::empty(),
+            Err(message) => CommandResult::error(message),
+        }
+    }
+}
+
+/// A command's manual page, as `man` shows it. Its name and synopsis come
+/// from [`Command::name`] and [`Command::help`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Manual {
+    /// What the command does, in a few words, e.g. `list directory contents`.
+    pub summary: &'static str,
+    /// Paragraphs separated by blank lines, which `man` wraps to fit.
+    pub description: &'static str,
+    /// Command lines, each with what it does.
+    pub examples: &'static [(&'static str, &'static str)],
+}
+
+pub trait Command: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// One-line usage shown by `help`, e.g. `cat <file>...`.
+    fn help(&self) -> &'static str;
+
+    /// The page `man` shows. Without one, `man` gives just the usage.
+    fn manual(&self) -> Manual {
+        Manual::default()
+    }
+
+    fn completion(&self) -> Completion {
+        Completion::None
+    }
+
+    /// Runs the command; `args` excludes the command name itself.
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult;
+}
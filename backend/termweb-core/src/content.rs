@@ -0,0 +1,134 @@
+//! File content as a rope of fixed-size shared chunks. Reads take a range
+//! without copying the rest of the file, appends copy at most the last
+//! chunk, and a clone shares every chunk with the original.
+
+use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Bytes in every chunk but the last.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Default)]
+pub struct Content {
+    chunks: Arc<Vec<Arc<[u8]>>>,
+    len: usize,
+}
+
+impl Content {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The content a chunk at a time, in order.
+    pub fn chunks(&self) -> impl Iterator<Item = &[u8]> {
+        self.chunks.iter().map(|chunk| &**chunk)
+    }
+
+    /// All of the content in one slice, borrowed when it fits in one chunk.
+    pub fn bytes(&self) -> Cow<'_, [u8]> {
+        match self.chunks.as_slice() {
+            [] => Cow::Borrowed(&[]),
+            [only] => Cow::Borrowed(only),
+            _ => Cow::Owned(self.to_vec()),
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.len);
+        self.chunks().for_each(|chunk| bytes.extend_from_slice(chunk));
+        bytes
+    }
+
+    /// Up to `len` bytes starting at `offset`; fewer where the content ends
+    /// first, and none from past its end.
+    pub fn range(&self, offset: usize, len: usize) -> Vec<u8> {
+        let end = offset.saturating_add(len).min(self.len);
+        let mut bytes = Vec::with_capacity(end.saturating_sub(offset));
+        let mut position = offset;
+        while position < end {
+            let chunk = &self.chunks[position / CHUNK_SIZE];
+            let start = position % CHUNK_SIZE;
+            let stop = chunk.len().min(start + end - position);
+            bytes.extend_from_slice(&chunk[start..stop]);
+            position += stop - start;
+        }
+        bytes
+    }
+
+    /// Adds `bytes` at the end, topping up the last chunk before starting
+    /// new ones.
+    pub fn append(&mut self, mut bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        self.len += bytes.len();
+        let chunks = Arc::make_mut(&mut self.chunks);
+        if let Some(last) = chunks.last_mut()
+            && last.len() < CHUNK_SIZE
+        {
+            let take = bytes.len().min(CHUNK_SIZE - last.len());
+            *last = [&last[..], &bytes[..take]].concat().into();
+            bytes = &bytes[take..];
+        }
+        chunks.extend(bytes.chunks(CHUNK_SIZE).map(Arc::from));
+    }
+
+    /// How many lines there are, counting a last one with no newline.
+    pub fn line_count(&self) -> usize {
+        let newlines: usize = self.chunks().map(|chunk| newlines(chunk).count()).sum();
+        let unterminated = self.chunks.last().is_some_and(|chunk| chunk.last() != Some(&b'\n'));
+        newlines + usize::from(unterminated)
+    }
+
+    /// The offset at which line `line`, counted from 0, starts, or the
+    /// length if there are not that many lines.
+    pub fn line_offset(&self, line: usize) -> usize {
+        if line == 0 {
+            return 0;
+        }
+        let mut seen = 0;
+        for (index, chunk) in self.chunks().enumerate() {
+            let count = newlines(chunk).count();
+            if seen + count >= line {
+                let found = newlines(chunk).nth(line - seen - 1).expect("newline was counted");
+                return index * CHUNK_SIZE + found + 1;
+            }
+            seen += count;
+        }
+        self.len
+    }
+}
+
+fn newlines(chunk: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    chunk.iter().enumerate().filter(|(_, byte)| **byte == b'\n').map(|(index, _)| index)
+}
+
+impl From<Vec<u8>> for Content {
+    fn from(bytes: Vec<u8>) -> Self {
+        let mut content = Content::default();
+        content.append(&bytes);
+        content
+    }
+}
+
+impl PartialEq for Content {
+    fn eq(&self, other: &Self) -> bool {
+        let shared = Arc::ptr_eq(&self.chunks, &other.chunks);
+        self.len == other.len && (shared || self.chunks().flatten().eq(other.chunks().flatten()))
+    }
+}
+
+impl Eq for Content {}
+
+/// Hashes as the same bytes in a slice would, whatever the chunks.
+impl Hash for Content {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len);
+        self.chunks().for_each(|chunk| state.write(chunk));
+    }
+}
\ No newline at end of file
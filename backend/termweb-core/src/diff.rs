@@ -0,0 +1,274 @@
+//! Line diffs, found with Myers' O(ND) algorithm and printed in the unified
+//! format of `diff -u`, for `diff` and `vcs diff`.
+
+use crate::color;
+
+/// Unchanged lines shown around each change.
+pub const CONTEXT: usize = 3;
+
+/// One step from the old lines to the new: indices into each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// A shortest edit script turning `old` into `new`.
+pub fn diff_lines<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Op> {
+    // Lines shared at either end are kept without searching them.
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut ops: Vec<Op> = (0..prefix).map(|index| Op::Equal(index, index)).collect();
+    let middle = shortest_edit(&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+    ops.extend(middle.into_iter().map(|op| match op {
+        Op::Equal(a, b) => Op::Equal(a + prefix, b + prefix),
+        Op::Delete(a) => Op::Delete(a + prefix),
+        Op::Insert(b) => Op::Insert(b + prefix),
+    }));
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    ops.extend((0..suffix).map(|index| Op::Equal(old_end + index, new_end + index)));
+    ops
+}
+
+/// Myers' algorithm: walks the diagonals `k = x - y` of the edit graph,
+/// furthest first, keeping each round's furthest points to trace the path
+/// back from the end.
+fn shortest_edit<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Op> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let offset = n + m + 1;
+    let at = |k: isize| (k + offset) as usize;
+    let mut furthest = vec![0isize; 2 * offset as usize + 1];
+    // Round d's furthest x for k in -d..=d.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    'search: for d in 0..=n + m {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && furthest[at(k - 1)] < furthest[at(k + 1)]) {
+                furthest[at(k + 1)]
+            } else {
+                furthest[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[at(k)] = x;
+            if x >= n && y >= m {
+                trace.push(furthest[at(-d)..=at(d)].to_vec());
+                break 'search;
+            }
+        }
+        trace.push(furthest[at(-d)..=at(d)].to_vec());
+    }
+
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() as isize).rev() {
+        let previous = &trace[d as usize - 1];
+        let reached = |k: isize| previous[(k + d - 1) as usize];
+        let k = x - y;
+        let down = k == -d || (k != d && reached(k - 1) < reached(k + 1));
+        let previous_k = if down { k + 1 } else { k - 1 };
+        let previous_x = reached(previous_k);
+        let previous_y = previous_x - previous_k;
+        let snake_start = if down { previous_x } else { previous_x + 1 };
+        while x > snake_start {
+            x -= 1;
+            y -= 1;
+            ops.push(Op::Equal(x as usize, y as usize));
+        }
+        ops.push(if down {
+            Op::Insert(previous_y as usize)
+        } else {
+            Op::Delete(previous_x as usize)
+        });
+        (x, y) = (previous_x, previous_y);
+    }
+    while x > 0 {
+        x -= 1;
+        y -= 1;
+        ops.push(Op::Equal(x as usize, y as usize));
+    }
+    ops.reverse();
+    ops
+}
+
+/// `old` and `new` as a unified diff with `---`/`+++` headers, or `None`
+/// when they are the same. With `paint`, lines are coloured as GNU
+/// `diff --color` does.
+pub fn unified(old_label: &str, new_label: &str, old: &str, new: &str, paint: bool) -> Option<String> {
+    if old == new {
+        return None;
+    }
+    // Files here seldom end in a newline, as `echo` writes none, so a
+    // missing one is only marked when it is all that differs.
+    let exact = split_lines(old, false) == split_lines(new, false);
+    let (old_lines, new_lines) = (split_lines(old, exact), split_lines(new, exact));
+    let ops = diff_lines(&old_lines, &new_lines);
+    let style = |text: String, code: &str| if paint { color::paint(&text, code) } else { text };
+
+    let mut output = vec![
+        style(format!("--- {}", old_label), "1"),
+        style(format!("+++ {}", new_label), "1"),
+    ];
+    for hunk in hunks(&ops) {
+        let (mut old_start, mut new_start) = position(&ops[..hunk.start]);
+        let old_count = ops[hunk.clone()].iter().filter(|op| !matches!(op, Op::Insert(_))).count();
+        let new_count = ops[hunk.clone()].iter().filter(|op| !matches!(op, Op::Delete(_))).count();
+        // An empty side is numbered by the line before it, as in GNU diff.
+        if old_count > 0 {
+            old_start += 1;
+        }
+        if new_count > 0 {
+            new_start += 1;
+        }
+        output.push(style(
+            format!("@@ -{} +{} @@", range(old_start, old_count), range(new_start, new_count)),
+            "36",
+        ));
+        for op in &ops[hunk] {
+            let (marker, line, code) = match *op {
+                Op::Equal(index, _) => (' ', old_lines[index], ""),
+                Op::Delete(index) => ('-', old_lines[index], "31"),
+                Op::Insert(index) => ('+', new_lines[index], "32"),
+            };
+            let text = format!("{}{}", marker, line.strip_suffix('\n').unwrap_or(line));
+            output.push(if code.is_empty() { text } else { style(text, code) });
+            if exact && !line.ends_with('\n') {
+                output.push("\\ No newline at end of file".to_string());
+            }
+        }
+    }
+    Some(output.join("\n"))
+}
+
+/// `text`'s lines, keeping their newlines when `exact`.
+fn split_lines(text: &str, exact: bool) -> Vec<&str> {
+    if exact {
+        text.split_inclusive('\n').collect()
+    } else if text.is_empty() {
+        Vec::new()
+    } else {
+        text.strip_suffix('\n').unwrap_or(text).split('\n').collect()
+    }
+}
+
+/// The ranges of `ops` to print: each change with up to [`CONTEXT`] lines
+/// either side, merged where they would touch.
+fn hunks(ops: &[Op]) -> Vec<std::ops::Range<usize>> {
+    let changed = |op: &Op| !matches!(op, Op::Equal(..));
+    let mut hunks: Vec<std::ops::Range<usize>> = Vec::new();
+    for (index, _) in ops.iter().enumerate().filter(|(_, op)| changed(op)) {
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + 1 + CONTEXT).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => hunks.push(start..end),
+        }
+    }
+    hunks
+}
+
+/// How many old and new lines come before `ops`.
+fn position(ops: &[Op]) -> (usize, usize) {
+    ops.iter().fold((0, 0), |(old, new), op| match op {
+        Op::Equal(..) => (old + 1, new + 1),
+        Op::Delete(_) => (old + 1, new),
+        Op::Insert(_) => (old, new + 1),
+    })
+}
+
+/// A hunk header range: `start,count`, or just `start` for one line.
+fn range(start: usize, count: usize) -> String {
+    if count == 1 {
+        start.to_string()
+    } else {
+        format!("{},{}", start, count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_lines, unified, Op};
+
+    fn lines(text: &str) -> Vec<&str> {
+        text.split_whitespace().collect()
+    }
+
+    /// Lines 1 to `count`, each on its own line.
+    fn numbered(count: usize) -> String {
+        (1..=count).map(|line| format!("{}\n", line)).collect()
+    }
+
+    #[test]
+    fn identical_inputs_have_no_diff() {
+        assert_eq!(diff_lines(&lines("a b"), &lines("a b")), vec![Op::Equal(0, 0), Op::Equal(1, 1)]);
+        assert_eq!(unified("a", "b", "x\ny\n", "x\ny\n", false), None);
+        assert_eq!(unified("a", "b", "", "", false), None);
+    }
+
+    #[test]
+    fn finds_insertions_and_deletions() {
+        assert_eq!(
+            diff_lines(&lines("a c"), &lines("a b c")),
+            vec![Op::Equal(0, 0), Op::Insert(1), Op::Equal(1, 2)]
+        );
+        assert_eq!(
+            diff_lines(&lines("a b c"), &lines("a c")),
+            vec![Op::Equal(0, 0), Op::Delete(1), Op::Equal(2, 1)]
+        );
+        assert_eq!(diff_lines(&lines(""), &lines("a")), vec![Op::Insert(0)]);
+        assert_eq!(diff_lines(&lines("a"), &lines("")), vec![Op::Delete(0)]);
+    }
+
+    #[test]
+    fn edit_scripts_are_shortest() {
+        let (old, new) = (lines("a b c a b b a"), lines("c b a b a c"));
+        let ops = diff_lines(&old, &new);
+        // Myers' example: five edits, leaving four lines in common.
+        assert_eq!(ops.iter().filter(|op| !matches!(op, Op::Equal(..))).count(), 5);
+        for op in ops {
+            if let Op::Equal(a, b) = op {
+                assert_eq!(old[a], new[b]);
+            }
+        }
+    }
+
+    #[test]
+    fn prints_hunks_with_context() {
+        let diff = unified("old", "new", "a\nb\nc\n", "a\nB\nc\n", false).unwrap();
+        assert_eq!(diff, "--- old\n+++ new\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c");
+        let diff = unified("old", "new", "", "a\n", false).unwrap();
+        assert_eq!(diff, "--- old\n+++ new\n@@ -0,0 +1 @@\n+a");
+    }
+
+    #[test]
+    fn merges_hunks_whose_context_touches() {
+        let old = numbered(20);
+        // Changes six lines apart share their context; ones further apart do not.
+        let near = old.replace("\n4\n", "\nfour\n").replace("\n10\n", "\nten\n");
+        let diff = unified("old", "new", &old, &near, false).unwrap();
+        assert_eq!(diff.matches("@@ -").count(), 1);
+        assert!(diff.contains("@@ -1,13 +1,13 @@"));
+        let far = old.replace("\n4\n", "\nfour\n").replace("\n12\n", "\ntwelve\n");
+        let diff = unified("old", "new", &old, &far, false).unwrap();
+        assert_eq!(diff.matches("@@ -").count(), 2);
+        assert!(diff.contains("@@ -1,7 +1,7 @@"));
+        assert!(diff.contains("@@ -9,7 +9,7 @@"));
+    }
+
+    #[test]
+    fn marks_a_missing_trailing_newline_only_when_it_is_the_difference() {
+        let diff = unified("old", "new", "a\nb\n", "a\nb", false).unwrap();
+        assert_eq!(diff, "--- old\n+++ new\n@@ -1,2 +1,2 @@\n a\n-b\n+b\n\\ No newline at end of file");
+        let diff = unified("old", "new", "a\nb", "a\nc\n", false).unwrap();
+        assert!(!diff.contains("No newline"));
+        assert!(diff.ends_with("-b\n+c"));
+    }
+}
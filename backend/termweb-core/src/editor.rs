@@ -0,0 +1,166 @@
+//! The line editor `edit` opens. While a file is open the session is in
+//! editor mode: clients draw it full screen from the [`EditorView`] each
+//! response carries, and change it with [`EditorOp`]s rather than command
+//! lines, until it is quit.
+//!
+//! Clients that only send command lines can still leave: `:w` saves, `:q`
+//! quits, `:wq` does both and `:q!` throws the changes away.
+
+use serde::{Deserialize, Serialize};
+
+use crate::command::CommandResult;
+use crate::path::path_string;
+use crate::shell::CommandResponse;
+use crate::state::TerminalState;
+
+/// A file open in the editor.
+pub struct Editor {
+    pub path: Vec<String>,
+    pub lines: Vec<String>,
+    /// Whether there are changes not yet saved.
+    pub modified: bool,
+    /// Whether the file ended in a newline when opened, kept when saving.
+    trailing_newline: bool,
+}
+
+/// What a client needs to draw the editor.
+#[derive(Debug, Clone, Serialize)]
+pub struct EditorView {
+    pub path: String,
+    pub lines: Vec<String>,
+    pub modified: bool,
+}
+
+/// A change to the open file, or leaving the editor. Lines count from 1.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum EditorOp {
+    /// Puts `text` before line `line`, or after the last line when `line` is
+    /// one past it.
+    Insert { line: usize, text: String },
+    /// Replaces lines `start` to `end` with `lines`; with no lines, deletes
+    /// them. An `end` one before `start` inserts the lines there, so a whole
+    /// file, even an empty one, is replaced with `start` 1 and `end` its length.
+    Replace {
+        start: usize,
+        end: usize,
+        #[serde(default)]
+        lines: Vec<String>,
+    },
+    Save,
+    /// Closes the editor. Unsaved changes are refused unless `force` is set.
+    Quit {
+        #[serde(default)]
+        force: bool,
+    },
+}
+
+impl Editor {
+    /// Opens `content`, the file at `path`, or an empty one for a new file.
+    pub fn open(path: Vec<String>, content: Option<&str>) -> Self {
+        let content = content.unwrap_or_default();
+        Editor {
+            path,
+            lines: content.lines().map(str::to_string).collect(),
+            modified: false,
+            trailing_newline: content.ends_with('\n'),
+        }
+    }
+
+    pub fn view(&self) -> EditorView {
+        EditorView {
+            path: path_string(&self.path),
+            lines: self.lines.clone(),
+            modified: self.modified,
+        }
+    }
+
+    /// The file as it will be saved.
+    pub fn content(&self) -> String {
+        let mut content = self.lines.join("\n");
+        if self.trailing_newline && !content.is_empty() {
+            content.push('\n');
+        }
+        content
+    }
+
+    fn insert(&mut self, line: usize, text: String) -> Result<(), String> {
+        if line == 0 || line > self.lines.len() + 1 {
+            return Err(self.out_of_range(line));
+        }
+        self.lines.insert(line - 1, text);
+        self.modified = true;
+        Ok(())
+    }
+
+    fn replace(&mut self, start: usize, end: usize, lines: Vec<String>) -> Result<(), String> {
+        if start == 0 || start > self.lines.len() + 1 {
+            return Err(self.out_of_range(start));
+        }
+        if end + 1 < start || end > self.lines.len() {
+            return Err(self.out_of_range(end));
+        }
+        self.lines.splice(start - 1..end, lines);
+        self.modified = true;
+        Ok(())
+    }
+
+    fn out_of_range(&self, line: usize) -> String {
+        format!("edit: no line {} (the file has {} lines)", line, self.lines.len())
+    }
+}
+
+/// Applies `op` to the session's open file and reports the result, with
+/// the editor's new state.
+pub fn apply(state: &mut TerminalState, op: EditorOp) -> CommandResponse {
+    let Some(editor) = state.editor.as_mut() else {
+        return CommandResponse::new(state, CommandResult::error("edit: no file is open"));
+    };
+    let result = match op {
+        EditorOp::Insert { line, text } => editor.insert(line, text).map(|()| String::new()),
+        EditorOp::Replace { start, end, lines } => editor.replace(start, end, lines).map(|()| String::new()),
+        EditorOp::Save => save(state),
+        EditorOp::Quit { force } => quit(state, force),
+    };
+    CommandResponse::new(state, result.into())
+}
+
+/// Runs a command line typed while a file is open: only the `:` commands
+/// mean anything.
+pub fn command(state: &mut TerminalState, input: &str) -> CommandResponse {
+    let result = match input.trim() {
+        ":w" => save(state),
+        ":q" => quit(state, false),
+        ":q!" => quit(state, true),
+        ":wq" | ":x" => save(state).and_then(|saved| quit(state, false).map(|_| saved)),
+        _ => Err(format!(
+            "edit: {} is open; save with :w, quit with :q, or discard changes with :q!",
+            state.editor.as_ref().map(|editor| path_string(&editor.path)).unwrap_or_default()
+        )),
+    };
+    CommandResponse::new(state, result.into())
+}
+
+fn save(state: &mut TerminalState) -> Result<String, String> {
+    let editor = state.editor.as_mut().ok_or("edit: no file is open")?;
+    let content = editor.content();
+    state
+        .fs
+        .write_file(&editor.path, content, false)
+        .map_err(|error| format!("edit: {}: {}", path_string(&editor.path), error))?;
+    editor.modified = false;
+    Ok(format!("\"{}\" {} lines written", path_string(&editor.path), editor.lines.len()))
+}
+
+fn quit(state: &mut TerminalState, force: bool) -> Result<String, String> {
+    match &state.editor {
+        Some(editor) if editor.modified && !force => {
+            Err("edit: there are unsaved changes; save them, or quit with :q! to discard them".to_string())
+        }
+        Some(_) => {
+            state.editor = None;
+            Ok(String::new())
+        }
+        None => Err("edit: no file is open".to_string()),
+    }
+}
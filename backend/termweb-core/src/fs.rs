@@ -0,0 +1,1062 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::{Clock, SystemClock};
+pub use crate::content::Content;
+use crate::journal::{node_size, Journal};
+use crate::path::{resolve_path, split_parent};
+use crate::rng::{OsRng, Rng};
+
+pub struct FileSystem {
+    root: Node,
+    journal: Journal,
+    /// Bytes of file content currently stored.
+    used: usize,
+    /// Bytes and nodes kept outside the tree on its behalf; see [`FileSystem::hold`].
+    held_bytes: usize,
+    held_nodes: usize,
+    capacity: Option<usize>,
+    limits: Limits,
+    clock: Arc<dyn Clock>,
+    rng: Arc<dyn Rng>,
+}
+
+/// Bounds on the shape of a filesystem, alongside its byte capacity, so one
+/// session cannot grow without end. `None` means unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Bytes in any one file.
+    pub max_file_size: Option<usize>,
+    /// Files, directories and symlinks, not counting the root.
+    pub max_nodes: Option<usize>,
+    /// Components in a path, e.g. 3 for `/a/b/c`.
+    pub max_depth: Option<usize>,
+    /// Bytes in one path component.
+    pub max_name_len: Option<usize>,
+}
+
+impl Default for FileSystem {
+    fn default() -> Self {
+        FileSystem {
+            root: Node::dir(SystemClock.now_millis()),
+            journal: Journal::default(),
+            used: 0,
+            held_bytes: 0,
+            held_nodes: 0,
+            capacity: None,
+            limits: Limits::default(),
+            clock: Arc::new(SystemClock),
+            rng: Arc::new(OsRng::default()),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Node {
+    Dir {
+        children: BTreeMap<String, Node>,
+        #[serde(default)]
+        meta: Metadata,
+    },
+    File {
+        #[serde(with = "content_serde")]
+        content: Content,
+        #[serde(default)]
+        meta: Metadata,
+    },
+    /// Stands in for the node at `target`, an absolute path or one relative
+    /// to the link's directory. The target need not exist.
+    Symlink {
+        target: String,
+        #[serde(default)]
+        meta: Metadata,
+    },
+}
+
+/// Owner permission bits. There is a single user, so group and other bits
+/// are kept and shown but never consulted.
+pub const READ: u32 = 0o400;
+pub const WRITE: u32 = 0o200;
+pub const EXECUTE: u32 = 0o100;
+
+pub const DEFAULT_DIR_MODE: u32 = 0o755;
+pub const DEFAULT_FILE_MODE: u32 = 0o644;
+pub const SYMLINK_MODE: u32 = 0o777;
+
+/// Symlinks followed while resolving one path before giving up, as on Linux.
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// Bytes one read of `/dev/urandom` returns. The device never ends, but
+/// everything that reads a file here reads all of it at once.
+const URANDOM_BLOCK: usize = 4096;
+
+/// Why a filesystem operation failed. Callers prefix the tool name when
+/// showing it, e.g. `rm: no such file or directory`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsError {
+    NotFound,
+    NotADirectory,
+    IsADirectory,
+    AlreadyExists,
+    PermissionDenied,
+    /// The path cannot name what the operation needs, for the reason given.
+    InvalidPath(&'static str),
+    DirectoryNotEmpty,
+    NoSpace,
+    /// The filesystem already holds as many nodes as it may.
+    QuotaExceeded,
+    FileTooLarge,
+    /// A name, or the path as a whole, is longer than the limits allow.
+    NameTooLong,
+    SymlinkLoop,
+}
+
+impl FsError {
+    /// A stable machine-readable name for the error, e.g. `not_found`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            FsError::NotFound => "not_found",
+            FsError::NotADirectory => "not_a_directory",
+            FsError::IsADirectory => "is_a_directory",
+            FsError::AlreadyExists => "already_exists",
+            FsError::PermissionDenied => "permission_denied",
+            FsError::InvalidPath(_) => "invalid_path",
+            FsError::DirectoryNotEmpty => "directory_not_empty",
+            FsError::NoSpace => "no_space",
+            FsError::QuotaExceeded => "quota_exceeded",
+            FsError::FileTooLarge => "file_too_large",
+            FsError::NameTooLong => "name_too_long",
+            FsError::SymlinkLoop => "symlink_loop",
+        }
+    }
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FsError::NotFound => "no such file or directory",
+            FsError::NotADirectory => "not a directory",
+            FsError::IsADirectory => "is a directory",
+            FsError::AlreadyExists => "already exists",
+            FsError::PermissionDenied => "permission denied",
+            FsError::InvalidPath(reason) => reason,
+            FsError::DirectoryNotEmpty => "directory not empty",
+            FsError::NoSpace => "no space left on device",
+            FsError::QuotaExceeded => "disk quota exceeded",
+            FsError::FileTooLarge => "file too large",
+            FsError::NameTooLong => "file name too long",
+            FsError::SymlinkLoop => "too many levels of symbolic links",
+        })
+    }
+}
+
+impl std::error::Error for FsError {}
+
+/// What the filesystem tracks about a node besides its contents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Milliseconds since the Unix epoch.
+    pub created: u64,
+    /// Milliseconds since the Unix epoch. For a directory, the last time an
+    /// entry was added or removed.
+    pub modified: u64,
+    /// Unix-style `rwxrwxrwx` permission bits.
+    #[serde(default = "default_mode")]
+    pub mode: u32,
+}
+
+/// Nodes saved before modes existed stay fully usable.
+fn default_mode() -> u32 {
+    DEFAULT_DIR_MODE
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Metadata::new(0, default_mode())
+    }
+}
+
+impl Metadata {
+    /// Metadata for a node created at `now`.
+    pub fn new(now: u64, mode: u32) -> Self {
+        Metadata {
+            created: now,
+            modified: now,
+            mode,
+        }
+    }
+}
+
+impl Node {
+    pub fn dir(now: u64) -> Self {
+        Node::Dir {
+            children: BTreeMap::new(),
+            meta: Metadata::new(now, DEFAULT_DIR_MODE),
+        }
+    }
+
+    pub fn file(content: impl Into<Vec<u8>>, now: u64) -> Self {
+        Node::File {
+            content: Content::from(content.into()),
+            meta: Metadata::new(now, DEFAULT_FILE_MODE),
+        }
+    }
+
+    pub fn symlink(target: impl Into<String>, now: u64) -> Self {
+        Node::Symlink {
+            target: target.into(),
+            meta: Metadata::new(now, SYMLINK_MODE),
+        }
+    }
+
+    pub fn meta(&self) -> &Metadata {
+        match self {
+            Node::Dir { meta, .. } | Node::File { meta, .. } | Node::Symlink { meta, .. } => meta,
+        }
+    }
+
+    fn meta_mut(&mut self) -> &mut Metadata {
+        match self {
+            Node::Dir { meta, .. } | Node::File { meta, .. } | Node::Symlink { meta, .. } => meta,
+        }
+    }
+
+    /// The type and permissions as `ls -l` shows them, e.g. `drwxr-xr-x`.
+    pub fn mode_string(&self) -> String {
+        let mut text = String::from(match self {
+            Node::Dir { .. } => "d",
+            Node::File { .. } => "-",
+            Node::Symlink { .. } => "l",
+        });
+        let mode = self.meta().mode;
+        for shift in [6, 3, 0] {
+            let bits = mode >> shift;
+            text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+            text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+            text.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+        }
+        text
+    }
+
+    /// Bytes of file content, counting everything beneath a directory. A
+    /// symlink's size is the length of its target, as `lstat` reports it.
+    pub fn size(&self) -> u64 {
+        match self {
+            Node::Symlink { target, .. } => target.len() as u64,
+            _ => node_size(self) as u64,
+        }
+    }
+
+    /// Marks this node and everything beneath it as created at `now`.
+    fn restamp(&mut self, now: u64) {
+        let meta = self.meta_mut();
+        meta.created = now;
+        meta.modified = now;
+        if let Node::Dir { children, .. } = self {
+            children.values_mut().for_each(|child| child.restamp(now));
+        }
+    }
+}
+
+impl FileSystem {
+    /// An empty filesystem except for the directories at `paths` (and their
+    /// parents), which are there from the start rather than journaled.
+    pub fn with_dirs(paths: &[&str]) -> Self {
+        let mut fs = FileSystem::default();
+        let now = fs.clock.now_millis();
+        for path in paths {
+            let mut current = &mut fs.root;
+            for segment in resolve_path(&[], path) {
+                let Node::Dir { children, .. } = current else {
+                    break;
+                };
+                current = children.entry(segment).or_insert_with(|| Node::dir(now));
+            }
+        }
+        fs
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    /// Swaps in a whole new tree, e.g. one read back from outside the shell.
+    /// Not subject to the capacity limit: the tree has already been produced.
+    pub fn replace_root(&mut self, root: Node) {
+        let delta = node_size(&root) - node_size(&self.root);
+        self.root = root;
+        self.record("replace", &[], delta);
+    }
+
+    /// Caps the total bytes of file content; writes that would exceed it fail.
+    pub fn set_capacity(&mut self, capacity: Option<usize>) {
+        self.capacity = capacity;
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Bytes of file content currently stored, in the tree or held outside it.
+    pub fn usage(&self) -> usize {
+        self.used + self.held_bytes
+    }
+
+    /// Fails if growing the stored content by `delta` bytes would exceed the capacity.
+    fn ensure_room(&self, delta: i64) -> Result<(), FsError> {
+        match self.capacity {
+            Some(capacity) if delta > 0 && self.usage() as i64 + delta > capacity as i64 => {
+                Err(FsError::NoSpace)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Sets the limits checked when nodes are created or files grow. What is
+    /// already there stays, even if it breaks them.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Files, directories and symlinks, not counting the root.
+    pub fn node_count(&self) -> usize {
+        node_count(&self.root) - 1
+    }
+
+    /// Fails if creating `count` nodes at `path`, reaching `depth` levels
+    /// down from it, would break the limits.
+    fn ensure_fits(&self, path: &[String], count: usize, depth: usize) -> Result<(), FsError> {
+        let limits = &self.limits;
+        if let (Some(max), Some(name)) = (limits.max_name_len, path.last())
+            && name.len() > max
+        {
+            return Err(FsError::NameTooLong);
+        }
+        if limits.max_depth.is_some_and(|max| path.len() + depth.saturating_sub(1) > max) {
+            return Err(FsError::NameTooLong);
+        }
+        if count > 0 && limits.max_nodes.is_some_and(|max| self.node_count() + self.held_nodes + count > max) {
+            return Err(FsError::QuotaExceeded);
+        }
+        Ok(())
+    }
+
+    /// Charges a copy of `tree` kept outside the filesystem, such as a `vcs`
+    /// commit, against the capacity and the node limit as if it were inside.
+    /// Fails, charging nothing, if it would not fit.
+    pub fn hold(&mut self, tree: &Node) -> Result<(), FsError> {
+        let (bytes, nodes) = (node_size(tree), node_count(tree));
+        self.ensure_room(bytes)?;
+        if self.limits.max_nodes.is_some_and(|max| self.node_count() + self.held_nodes + nodes > max) {
+            return Err(FsError::QuotaExceeded);
+        }
+        self.held_bytes += bytes as usize;
+        self.held_nodes += nodes;
+        Ok(())
+    }
+
+    /// Gives back what [`FileSystem::hold`] charged for `tree`.
+    pub fn release(&mut self, tree: &Node) {
+        self.held_bytes = self.held_bytes.saturating_sub(node_size(tree) as usize);
+        self.held_nodes = self.held_nodes.saturating_sub(node_count(tree));
+    }
+
+    /// Whether `path` is free for a new node, in an existing directory.
+    fn is_vacant(&self, path: &[String]) -> Result<bool, FsError> {
+        let (parent, name) = split_parent(path);
+        match self.get_node(parent) {
+            Some(Node::Dir { children, .. }) => Ok(!children.contains_key(name)),
+            Some(_) => Err(FsError::NotADirectory),
+            None => Err(FsError::NotFound),
+        }
+    }
+
+    /// Replaces the time source used for timestamps. A filesystem nothing
+    /// has touched yet also has its root re-dated by the new clock.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+        if self.journal.last_seq() == 0 {
+            self.root.restamp(self.clock.now_millis());
+        }
+    }
+
+    /// Replaces the randomness source, e.g. what `shred` overwrites with.
+    pub fn set_rng(&mut self, rng: Arc<dyn Rng>) {
+        self.rng = rng;
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    pub fn rng(&self) -> &dyn Rng {
+        self.rng.as_ref()
+    }
+
+    fn record(&mut self, op: &'static str, path: &[String], delta: i64) {
+        self.used = (self.used as i64 + delta).max(0) as usize;
+        let timestamp = self.clock.now_millis();
+        self.journal.record(op, path, timestamp, delta);
+    }
+
+    /// Sets the modified time of the node at `path`, and of its parent too
+    /// when `entries_changed` (a node was created or removed there).
+    fn stamp(&mut self, path: &[String], entries_changed: bool) {
+        let now = self.clock.now_millis();
+        if let Some(node) = self.get_node_mut(path) {
+            node.meta_mut().modified = now;
+        }
+        if entries_changed && !path.is_empty() {
+            let (parent, _) = split_parent(path);
+            if let Some(node) = self.get_node_mut(parent) {
+                node.meta_mut().modified = now;
+            }
+        }
+    }
+
+    /// Names the user and session that mutations from now on are made by,
+    /// for the journal to record alongside them.
+    pub fn set_actor(&mut self, user: Option<String>, session: Option<String>) {
+        self.journal.set_actor(user, session);
+    }
+
+    /// The user and session named by [`FileSystem::set_actor`].
+    pub fn actor(&self) -> (Option<&str>, Option<&str>) {
+        self.journal.actor()
+    }
+
+    /// Every mutation made through this filesystem, most recent last.
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    /// The node at `path`, following symlinks all the way.
+    pub fn get_node<'a>(&'a self, path: &[String]) -> Option<&'a Node> {
+        self.walk(&self.resolve_links(path, true).ok()?)
+    }
+
+    /// The node at `path`, or the symlink itself if that is what `path` names.
+    pub fn get_node_nofollow<'a>(&'a self, path: &[String]) -> Option<&'a Node> {
+        self.walk(&self.resolve_links(path, false).ok()?)
+    }
+
+    pub(crate) fn get_node_mut<'a>(&'a mut self, path: &[String]) -> Option<&'a mut Node> {
+        let path = self.resolve_links(path, true).ok()?;
+        let mut current = &mut self.root;
+        for segment in &path {
+            match current {
+                Node::Dir { children, .. } => {
+                    current = children.get_mut(segment)?;
+                }
+                _ => return None,
+            }
+        }
+        Some(current)
+    }
+
+    /// Looks up `path` without following any symlinks.
+    fn walk<'a>(&'a self, path: &[String]) -> Option<&'a Node> {
+        let mut current = &self.root;
+        for segment in path {
+            match current {
+                Node::Dir { children, .. } => {
+                    current = children.get(segment)?;
+                }
+                _ => return None,
+            }
+        }
+        Some(current)
+    }
+
+    /// `path` with each symlink along it replaced by what it points to, so the
+    /// result names a real node, or where one would be created. A final
+    /// symlink is kept unless `follow_last`. Anything after a missing entry is
+    /// kept as written; only a symlink loop is an error.
+    pub fn resolve_links(&self, path: &[String], follow_last: bool) -> Result<Vec<String>, FsError> {
+        let mut pending: Vec<String> = path.iter().rev().cloned().collect();
+        let mut resolved = Vec::new();
+        let mut hops = 0;
+        while let Some(segment) = pending.pop() {
+            let entry = match self.walk(&resolved) {
+                Some(Node::Dir { children, .. }) => children.get(&segment),
+                _ => None,
+            };
+            match entry {
+                Some(Node::Symlink { target, .. }) if follow_last || !pending.is_empty() => {
+                    hops += 1;
+                    if hops > MAX_SYMLINK_HOPS {
+                        return Err(FsError::SymlinkLoop);
+                    }
+                    pending.extend(resolve_path(&resolved, target).into_iter().rev());
+                    resolved.clear();
+                }
+                Some(_) => resolved.push(segment),
+                None => {
+                    resolved.push(segment);
+                    resolved.extend(pending.drain(..).rev());
+                }
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Replaces the node at `path` wholesale, e.g. when restoring a snapshot.
+    pub fn replace_node(&mut self, path: &[String], node: Node) -> Result<(), FsError> {
+        self.ensure_room(node_size(&node) - self.get_node(path).map(node_size).unwrap_or(0))?;
+        if path.is_empty() {
+            self.replace_root(node);
+            return Ok(());
+        }
+        let (parent, name) = split_parent(path);
+        let delta = match self.get_node_mut(parent) {
+            Some(Node::Dir { children, .. }) => {
+                let added = node_size(&node);
+                let removed = children.insert(name.to_string(), node);
+                added - removed.as_ref().map(node_size).unwrap_or(0)
+            }
+            Some(_) => return Err(FsError::NotADirectory),
+            None => return Err(FsError::NotFound),
+        };
+        self.record("replace", path, delta);
+        Ok(())
+    }
+
+    /// Whether the owner bits of the node at `path` include all of `want`,
+    /// and every directory above it is searchable (`x`). A path that does not
+    /// exist is not refused here, so the caller reports it as missing instead.
+    pub fn permitted(&self, path: &[String], want: u32) -> bool {
+        let Ok(path) = self.resolve_links(path, true) else {
+            return true;
+        };
+        let mut current = &self.root;
+        for segment in &path {
+            match current {
+                Node::Dir { children, meta } => {
+                    if meta.mode & EXECUTE == 0 {
+                        return false;
+                    }
+                    match children.get(segment) {
+                        Some(child) => current = child,
+                        None => return true,
+                    }
+                }
+                _ => return true,
+            }
+        }
+        current.meta().mode & want == want
+    }
+
+    /// Fails unless entries may be added to or removed from `path`'s parent.
+    fn ensure_parent_writable(&self, path: &[String]) -> Result<(), FsError> {
+        let (parent, _) = split_parent(path);
+        if self.permitted(parent, WRITE | EXECUTE) {
+            Ok(())
+        } else {
+            Err(FsError::PermissionDenied)
+        }
+    }
+
+    /// Sets the permission bits of the node at `path`.
+    pub fn set_mode(&mut self, path: &[String], mode: u32) -> Result<(), FsError> {
+        if !self.permitted(path, 0) {
+            return Err(FsError::PermissionDenied);
+        }
+        let node = self.get_node_mut(path).ok_or(FsError::NotFound)?;
+        node.meta_mut().mode = mode & 0o777;
+        self.record("chmod", path, 0);
+        Ok(())
+    }
+
+    pub fn is_dir(&self, path: &[String]) -> Result<bool, FsError> {
+        match self.get_node(path) {
+            Some(Node::Dir { .. }) => Ok(true),
+            Some(_) => Ok(false),
+            None => Err(FsError::NotFound),
+        }
+    }
+
+    pub fn list(&self, path: &[String]) -> Result<String, FsError> {
+        match self.get_node(path) {
+            Some(Node::Dir { children, .. }) => {
+                let mut entries = Vec::new();
+                for (name, node) in children.iter() {
+                    entries.push(match node {
+                        Node::Dir { .. } => format!("{}/", name),
+                        Node::File { .. } => name.to_string(),
+                        Node::Symlink { target, .. } => format!("{} -> {}", name, target),
+                    });
+                }
+                Ok(entries.join("  "))
+            }
+            Some(_) => Ok(path
+                .last()
+                .map(|name| name.to_string())
+                .unwrap_or_default()),
+            None => Err(FsError::NotFound),
+        }
+    }
+
+    pub fn mkdir(&mut self, path: &[String]) -> Result<(), FsError> {
+        if path.is_empty() {
+            return Err(FsError::InvalidPath("invalid path"));
+        }
+        self.ensure_parent_writable(path)?;
+        if self.is_vacant(path)? {
+            self.ensure_fits(path, 1, 1)?;
+        }
+        let now = self.clock.now_millis();
+        let (parent, name) = split_parent(path);
+        let parent_node = self.get_node_mut(parent).ok_or(FsError::NotFound)?;
+
+        match parent_node {
+            Node::Dir { children, .. } => {
+                if children.contains_key(name) {
+                    return Err(FsError::AlreadyExists);
+                }
+                children.insert(name.to_string(), Node::dir(now));
+                self.stamp(path, true);
+                self.record("mkdir", path, 0);
+                Ok(())
+            }
+            _ => Err(FsError::NotADirectory),
+        }
+    }
+
+    /// Creates an empty file at `path` if there is none, and sets the file's
+    /// modification time to `modified`, or to now.
+    pub fn touch(&mut self, path: &[String], modified: Option<u64>) -> Result<(), FsError> {
+        let path = &self.resolve_links(path, true)?;
+        if path.is_empty() {
+            return Err(FsError::InvalidPath("invalid path"));
+        }
+        let now = self.clock.now_millis();
+        let (parent, name) = split_parent(path);
+        let created = match self.get_node(parent) {
+            Some(Node::Dir { children, .. }) => match children.get(name) {
+                Some(Node::Dir { .. }) => return Err(FsError::IsADirectory),
+                Some(_) => false,
+                None => true,
+            },
+            Some(_) => return Err(FsError::NotADirectory),
+            None => return Err(FsError::NotFound),
+        };
+        if created {
+            self.ensure_parent_writable(path)?;
+            self.ensure_fits(path, 1, 1)?;
+        } else if !self.permitted(path, WRITE) {
+            return Err(FsError::PermissionDenied);
+        }
+        if let Some(Node::Dir { children, .. }) = self.get_node_mut(parent) {
+            children
+                .entry(name.to_string())
+                .or_insert_with(|| Node::file("", now));
+        }
+        self.stamp(path, created);
+        if let Some(modified) = modified
+            && let Some(node) = self.get_node_mut(path)
+        {
+            node.meta_mut().modified = modified;
+        }
+        self.record("touch", path, 0);
+        Ok(())
+    }
+
+    /// The file's content as text; bytes that are not UTF-8 come out as U+FFFD.
+    pub fn read_file(&self, path: &[String]) -> Result<String, FsError> {
+        self.read_bytes(path)
+            .map(|content| String::from_utf8_lossy(&content.bytes()).into_owned())
+    }
+
+    /// The file's bytes, shared with the tree rather than copied.
+    pub fn read_bytes(&self, path: &[String]) -> Result<Content, FsError> {
+        if let Some(device) = self.device(path) {
+            return Ok(device);
+        }
+        let path = &self.resolve_links(path, true)?;
+        if !self.permitted(path, READ) {
+            return Err(FsError::PermissionDenied);
+        }
+        match self.get_node(path) {
+            Some(Node::File { content, .. }) => Ok(content.clone()),
+            Some(_) => Err(FsError::IsADirectory),
+            None => Err(FsError::NotFound),
+        }
+    }
+
+    /// What reading a device gives, if `path` is one. There is no `/dev` to
+    /// list, but `/dev/null` reads as empty and each read of `/dev/urandom`
+    /// is a fresh block of bytes from [`FileSystem::rng`].
+    fn device(&self, path: &[String]) -> Option<Content> {
+        match path {
+            [dev, name] if dev == "dev" && name == "null" => Some(Content::default()),
+            [dev, name] if dev == "dev" && name == "urandom" => {
+                Some(Content::from(random_fill(self.rng.as_ref(), URANDOM_BLOCK)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Up to `len` bytes of the file from `offset`, copying only those.
+    pub fn read_range(&self, path: &[String], offset: usize, len: usize) -> Result<Vec<u8>, FsError> {
+        self.read_bytes(path).map(|content| content.range(offset, len))
+    }
+
+    /// Writes text to the file, creating it if needed. Appending puts a
+    /// newline between the old content and the new.
+    pub fn write_file(&mut self, path: &[String], content: String, append: bool) -> Result<(), FsError> {
+        self.write_bytes(path, content.into_bytes(), append)
+    }
+
+    pub fn write_bytes(&mut self, path: &[String], content: Vec<u8>, append: bool) -> Result<(), FsError> {
+        let path = &self.resolve_links(path, true)?;
+        if path.is_empty() {
+            return Err(FsError::InvalidPath("invalid path"));
+        }
+        let allowed = match self.get_node(path) {
+            Some(_) => self.permitted(path, WRITE),
+            None => self.ensure_parent_writable(path).is_ok(),
+        };
+        if !allowed {
+            return Err(FsError::PermissionDenied);
+        }
+        let before = match self.get_node(path) {
+            Some(Node::File { content, .. }) => content.len(),
+            _ => 0,
+        };
+        let after = match (append, before) {
+            (true, 0) | (false, _) => content.len(),
+            (true, before) => before + 1 + content.len(),
+        };
+        if self.limits.max_file_size.is_some_and(|max| after > max) {
+            return Err(FsError::FileTooLarge);
+        }
+        self.ensure_room(after as i64 - before as i64)?;
+        if self.is_vacant(path)? {
+            self.ensure_fits(path, 1, 1)?;
+        }
+        let now = self.clock.now_millis();
+        let (parent, name) = split_parent(path);
+        let parent_node = self.get_node_mut(parent).ok_or(FsError::NotFound)?;
+
+        let created = match parent_node {
+            Node::Dir { children, .. } => !children.contains_key(name),
+            _ => false,
+        };
+        let delta = match parent_node {
+            Node::Dir { children, .. } => {
+                let entry = children
+                    .entry(name.to_string())
+                    .or_insert_with(|| Node::file("", now));
+                match entry {
+                    Node::File { content: file_content, .. } => {
+                        let before = file_content.len() as i64;
+                        if append && !file_content.is_empty() {
+                            file_content.append(b"\n");
+                            file_content.append(&content);
+                        } else {
+                            *file_content = Content::from(content);
+                        }
+                        file_content.len() as i64 - before
+                    }
+                    _ => return Err(FsError::IsADirectory),
+                }
+            }
+            _ => return Err(FsError::NotADirectory),
+        };
+        self.stamp(path, created);
+        self.record(if append { "append" } else { "write" }, path, delta);
+        Ok(())
+    }
+
+    pub fn shred(&mut self, path: &[String], passes: usize, zero: bool, remove: bool) -> Result<(), FsError> {
+        let path = &self.resolve_links(path, true)?;
+        if path.is_empty() {
+            return Err(FsError::InvalidPath("invalid path"));
+        }
+        if !self.permitted(path, WRITE) {
+            return Err(FsError::PermissionDenied);
+        }
+        if remove {
+            self.ensure_parent_writable(path)?;
+        }
+        let rng = self.rng.clone();
+        let (parent, name) = split_parent(path);
+        let parent_node = self.get_node_mut(parent).ok_or(FsError::NotFound)?;
+
+        match parent_node {
+            Node::Dir { children, .. } => {
+                let len = match children.get_mut(name) {
+                    Some(Node::File { content, .. }) => {
+                        let len = content.len();
+                        for _ in 0..passes {
+                            *content = Content::from(random_fill(rng.as_ref(), len));
+                        }
+                        if zero {
+                            *content = Content::from(vec![0; len]);
+                        }
+                        len as i64
+                    }
+                    Some(_) => return Err(FsError::IsADirectory),
+                    None => return Err(FsError::NotFound),
+                };
+                // Unlinking drops the node outright; the overwritten content is the last
+                // copy of the data, so nothing recoverable stays behind in the tree.
+                if remove {
+                    children.remove(name);
+                }
+                self.stamp(path, remove);
+                self.record("shred", path, if remove { -len } else { 0 });
+                Ok(())
+            }
+            _ => Err(FsError::NotADirectory),
+        }
+    }
+
+    /// Creates a symlink at `path` pointing at `target`, which is stored as
+    /// written and need not exist.
+    pub fn symlink(&mut self, target: &str, path: &[String]) -> Result<(), FsError> {
+        if path.is_empty() || target.is_empty() {
+            return Err(FsError::InvalidPath("invalid path"));
+        }
+        if self.get_node_nofollow(path).is_some() {
+            return Err(FsError::AlreadyExists);
+        }
+        let (parent, _) = split_parent(path);
+        match self.get_node(parent) {
+            Some(Node::Dir { .. }) => {}
+            Some(_) => return Err(FsError::NotADirectory),
+            None => return Err(FsError::NotFound),
+        }
+        self.ensure_parent_writable(path)?;
+        self.ensure_fits(path, 1, 1)?;
+        let now = self.clock.now_millis();
+        self.link(path, Node::symlink(target, now), "symlink");
+        Ok(())
+    }
+
+    /// Deletes a file, or a directory when `recursive` is set.
+    pub fn remove(&mut self, path: &[String], recursive: bool) -> Result<(), FsError> {
+        if path.is_empty() {
+            return Err(FsError::InvalidPath("refusing to remove root directory"));
+        }
+        match self.get_node_nofollow(path) {
+            Some(Node::Dir { .. }) if !recursive => return Err(FsError::IsADirectory),
+            Some(_) => {}
+            None => return Err(FsError::NotFound),
+        }
+        self.ensure_parent_writable(path)?;
+        self.unlink(path, "remove");
+        Ok(())
+    }
+
+    /// Deletes an empty directory.
+    pub fn remove_dir(&mut self, path: &[String]) -> Result<(), FsError> {
+        if path.is_empty() {
+            return Err(FsError::InvalidPath("refusing to remove root directory"));
+        }
+        match self.get_node_nofollow(path) {
+            Some(Node::Dir { children, .. }) if children.is_empty() => {}
+            Some(Node::Dir { .. }) => return Err(FsError::DirectoryNotEmpty),
+            Some(_) => return Err(FsError::NotADirectory),
+            None => return Err(FsError::NotFound),
+        }
+        self.ensure_parent_writable(path)?;
+        self.unlink(path, "rmdir");
+        Ok(())
+    }
+
+    /// Copies `src` to `dst`, or into `dst` if that is an existing directory.
+    pub fn copy(&mut self, src: &[String], dst: &[String], recursive: bool) -> Result<(), FsError> {
+        let node = match self.get_node(src) {
+            Some(Node::Dir { .. }) if !recursive => return Err(FsError::IsADirectory),
+            Some(node) => node.clone(),
+            None => return Err(FsError::NotFound),
+        };
+        // A copy is a new file; only `mv` keeps the original's times.
+        let mut node = node;
+        node.restamp(self.clock.now_millis());
+        if !self.permitted(src, READ) {
+            return Err(FsError::PermissionDenied);
+        }
+        let target = self.transfer_target(src, &node, dst)?;
+        self.ensure_parent_writable(&target)?;
+        self.ensure_room(node_size(&node) - self.get_node(&target).map(node_size).unwrap_or(0))?;
+        let replaced = self.get_node_nofollow(&target).map(node_count).unwrap_or(0);
+        self.ensure_fits(&target, node_count(&node).saturating_sub(replaced), node_depth(&node))?;
+        self.link(&target, node, "copy");
+        Ok(())
+    }
+
+    /// Moves `src` to `dst`, or into `dst` if that is an existing directory.
+    pub fn rename(&mut self, src: &[String], dst: &[String]) -> Result<(), FsError> {
+        if src.is_empty() {
+            return Err(FsError::InvalidPath("cannot move root directory"));
+        }
+        let Some(node) = self.get_node_nofollow(src) else {
+            return Err(FsError::NotFound);
+        };
+        let target = self.transfer_target(src, node, dst)?;
+        self.ensure_fits(&target, 0, node_depth(node))?;
+        self.ensure_parent_writable(src)?;
+        self.ensure_parent_writable(&target)?;
+        if let Some(node) = self.unlink(src, "move") {
+            self.link(&target, node, "move");
+        }
+        Ok(())
+    }
+
+    /// Resolves where `src` lands for `cp`/`mv` and checks the move is legal.
+    /// `node` is what will be placed there.
+    fn transfer_target(&self, src: &[String], node: &Node, dst: &[String]) -> Result<Vec<String>, FsError> {
+        let mut target = dst.to_vec();
+        if let Some(Node::Dir { .. }) = self.get_node(dst) {
+            let Some(name) = src.last() else {
+                return Err(FsError::InvalidPath("cannot copy root directory"));
+            };
+            target.push(name.clone());
+        }
+        // Compare where things really are, so a symlink cannot smuggle a
+        // directory inside itself.
+        let physical_src = self.resolve_links(src, false)?;
+        let physical_target = self.resolve_links(&target, false)?;
+        if physical_target == physical_src {
+            return Err(FsError::InvalidPath("source and destination are the same"));
+        }
+        if physical_target.starts_with(&physical_src) {
+            return Err(FsError::InvalidPath("cannot put a directory inside itself"));
+        }
+        let (parent, _) = split_parent(&target);
+        match self.get_node(parent) {
+            Some(Node::Dir { .. }) => {}
+            Some(_) => return Err(FsError::NotADirectory),
+            None => return Err(FsError::NotFound),
+        }
+        match (node, self.get_node_nofollow(&target)) {
+            (Node::Dir { .. }, Some(Node::Dir { children, .. })) if !children.is_empty() => {
+                Err(FsError::DirectoryNotEmpty)
+            }
+            (Node::Dir { .. }, Some(Node::Dir { .. })) | (_, None) => Ok(target),
+            (_, Some(Node::Dir { .. })) => Err(FsError::IsADirectory),
+            (Node::Dir { .. }, Some(_)) => Err(FsError::NotADirectory),
+            _ => Ok(target),
+        }
+    }
+
+    /// Detaches the node at non-root `path` from its parent.
+    fn unlink(&mut self, path: &[String], op: &'static str) -> Option<Node> {
+        let (parent, name) = split_parent(path);
+        let removed = match self.get_node_mut(parent) {
+            Some(Node::Dir { children, .. }) => children.remove(name)?,
+            _ => return None,
+        };
+        self.stamp(path, true);
+        self.record(op, path, -node_size(&removed));
+        Some(removed)
+    }
+
+    /// Attaches `node` at non-root `path`, whose parent must be a directory,
+    /// replacing whatever was there.
+    fn link(&mut self, path: &[String], node: Node, op: &'static str) {
+        let added = node_size(&node);
+        let now = self.clock.now_millis();
+        let (parent, name) = split_parent(path);
+        if let Some(Node::Dir { children, .. }) = self.get_node_mut(parent) {
+            let replaced = children.insert(name.to_string(), node);
+            let delta = added - replaced.as_ref().map(node_size).unwrap_or(0);
+            // Only the parent: a moved node keeps its own modified time.
+            if let Some(parent) = self.get_node_mut(parent) {
+                parent.meta_mut().modified = now;
+            }
+            self.record(op, path, delta);
+        }
+    }
+}
+
+/// `node` and everything beneath it.
+fn node_count(node: &Node) -> usize {
+    match node {
+        Node::Dir { children, .. } => 1 + children.values().map(node_count).sum::<usize>(),
+        _ => 1,
+    }
+}
+
+/// Levels from `node` down to its deepest descendant, counting itself.
+fn node_depth(node: &Node) -> usize {
+    match node {
+        Node::Dir { children, .. } => 1 + children.values().map(node_depth).max().unwrap_or(0),
+        _ => 1,
+    }
+}
+
+/// Whether `path` names one of the devices reads know about; writing to
+/// one throws the data away.
+pub fn is_device(path: &[String]) -> bool {
+    matches!(path, [dev, name] if dev == "dev" && (name == "null" || name == "urandom"))
+}
+
+fn random_fill(rng: &dyn Rng, len: usize) -> Vec<u8> {
+    (0..len).map(|_| rng.next_u64() as u8).collect()
+}
+
+/// Whether `content` looks like something other than text: it has a NUL
+/// byte or is not valid UTF-8.
+pub fn is_binary(content: &[u8]) -> bool {
+    content.contains(&0) || std::str::from_utf8(content).is_err()
+}
+
+/// A short token identifying a version of some file content, given a chunk
+/// at a time as [`Content::chunks`] yields it, used to detect concurrent
+/// modification. It is the 64-bit FNV-1a hash of the bytes, so it stays the
+/// same across builds and platforms, and with it the ETags clients keep.
+pub fn content_revision<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    let hash = chunks
+        .into_iter()
+        .flatten()
+        .fold(OFFSET_BASIS, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(PRIME));
+    format!("{:016x}", hash)
+}
+
+/// File content is saved as a plain string when it is UTF-8, which keeps
+/// snapshots readable, and as `{"base64": "..."}` otherwise.
+mod content_serde {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Content;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Saved<'a> {
+        Text(std::borrow::Cow<'a, str>),
+        Binary { base64: String },
+    }
+
+    pub fn serialize<S: Serializer>(content: &Content, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = content.bytes();
+        match std::str::from_utf8(&bytes) {
+            Ok(text) => Saved::Text(text.into()),
+            Err(_) => Saved::Binary {
+                base64: STANDARD.encode(&bytes),
+            },
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Content, D::Error> {
+        let content = match Saved::deserialize(deserializer)? {
+            Saved::Text(text) => text.into_owned().into_bytes(),
+            Saved::Binary { base64 } => STANDARD.decode(base64).map_err(serde::de::Error::custom)?,
+        };
+        Ok(Content::from(content))
+    }
+}
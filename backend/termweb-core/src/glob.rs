@@ -0,0 +1,235 @@
+//! Shell wildcards: `*`, `?` and `[...]` classes, matched against the
+//! virtual filesystem. A `\` makes the next character literal.
+
+use crate::fs::{FileSystem, Node, READ};
+use crate::path::resolve_path;
+
+/// Paths matching `pattern`, spelled the way the pattern spells them, so a
+/// relative pattern gives relative paths. Empty if nothing matches.
+pub fn expand(fs: &FileSystem, cwd: &[String], pattern: &str) -> Vec<String> {
+    let mut candidates = vec![if pattern.starts_with('/') {
+        "/".to_string()
+    } else {
+        String::new()
+    }];
+    for component in pattern.split('/').filter(|component| !component.is_empty()) {
+        let mut next = Vec::new();
+        for prefix in &candidates {
+            if !has_wildcard(component) {
+                let candidate = join(prefix, &unescape(component));
+                if fs.get_node(&resolve_path(cwd, &candidate)).is_some() {
+                    next.push(candidate);
+                }
+                continue;
+            }
+            let dir = resolve_path(cwd, prefix);
+            if !fs.permitted(&dir, READ) {
+                continue;
+            }
+            if let Some(Node::Dir { children, .. }) = fs.get_node(&dir) {
+                next.extend(
+                    children
+                        .keys()
+                        .filter(|name| matches(component, name))
+                        .map(|name| join(prefix, name)),
+                );
+            }
+        }
+        candidates = next;
+    }
+    if pattern.ends_with('/') {
+        candidates.retain(|candidate| matches!(fs.is_dir(&resolve_path(cwd, candidate)), Ok(true)));
+        candidates.iter_mut().for_each(|candidate| candidate.push('/'));
+    }
+    candidates
+}
+
+/// Whether the file name `name` matches the single-component `pattern`.
+/// As in sh, a leading `.` must be matched explicitly.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    fnmatch(pattern, name)
+}
+
+/// Whether `text` matches `pattern`, with no special case for dotfiles.
+pub fn fnmatch(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text)
+}
+
+/// Whether `text` has an unescaped `*`, `?` or `[`.
+pub fn has_wildcard(text: &str) -> bool {
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' | '[' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => unescaped.extend(chars.next()),
+            _ => unescaped.push(ch),
+        }
+    }
+    unescaped
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else if prefix.ends_with('/') {
+        format!("{}{}", prefix, name)
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+fn match_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern {
+        [] => name.is_empty(),
+        ['*', rest @ ..] => (0..=name.len()).any(|skip| match_from(rest, &name[skip..])),
+        ['?', rest @ ..] => !name.is_empty() && match_from(rest, &name[1..]),
+        ['[', class_rest @ ..] => match (name.first(), class(class_rest, name.first().copied())) {
+            (Some(_), Some((true, rest))) => match_from(rest, &name[1..]),
+            (_, Some(_)) => false,
+            // An unclosed `[` is an ordinary character.
+            (first, None) => first == Some(&'[') && match_from(class_rest, &name[1..]),
+        },
+        ['\\', literal, rest @ ..] | [literal, rest @ ..] => {
+            name.first() == Some(literal) && match_from(rest, &name[1..])
+        }
+    }
+}
+
+/// Parses the class after a `[`, returning whether `ch` is in it and the
+/// pattern after the closing `]`, or `None` if the class never closes.
+fn class(pattern: &[char], ch: Option<char>) -> Option<(bool, &[char])> {
+    let (negated, mut rest) = match pattern {
+        ['!' | '^', rest @ ..] => (true, rest),
+        _ => (false, pattern),
+    };
+    let mut found = false;
+    let mut first = true;
+    loop {
+        let (low, after) = match rest {
+            [']', after @ ..] if !first => return Some((ch.is_some() && found != negated, after)),
+            ['\\', escaped, after @ ..] | [escaped, after @ ..] => (*escaped, after),
+            [] => return None,
+        };
+        first = false;
+        rest = after;
+        let high = match rest {
+            ['-', high, after @ ..] if *high != ']' => {
+                rest = after;
+                *high
+            }
+            _ => low,
+        };
+        found |= ch.is_some_and(|ch| low <= ch && ch <= high);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand, fnmatch, has_wildcard, matches};
+    use crate::fs::FileSystem;
+    use crate::path::resolve_path;
+    use crate::Shell;
+
+    #[test]
+    fn star_and_question_mark() {
+        assert!(fnmatch("*", ""));
+        assert!(fnmatch("*.txt", "notes.txt"));
+        assert!(fnmatch("a*b*c", "aXXbYbc"));
+        assert!(!fnmatch("*.txt", "notes.txt.bak"));
+        assert!(fnmatch("tmp?", "tmp1"));
+        assert!(!fnmatch("tmp?", "tmp"));
+        assert!(!fnmatch("tmp?", "tmp12"));
+        assert!(fnmatch("??", "é!"));
+    }
+
+    #[test]
+    fn classes_with_ranges_and_negation() {
+        assert!(fnmatch("file[0-9]", "file7"));
+        assert!(!fnmatch("file[0-9]", "filex"));
+        assert!(fnmatch("[abc]x", "bx"));
+        assert!(fnmatch("[!abc]x", "dx"));
+        assert!(!fnmatch("[!abc]x", "ax"));
+        assert!(fnmatch("[^a-c]", "z"));
+        // A `]` first in a class, or a `-` last, is a member.
+        assert!(fnmatch("[]a]", "]"));
+        assert!(fnmatch("[a-]", "-"));
+        assert!(!fnmatch("[a-z]", ""));
+        // An unclosed `[` is literal.
+        assert!(fnmatch("[ab", "[ab"));
+        assert!(!fnmatch("[ab", "a"));
+    }
+
+    #[test]
+    fn escapes_make_wildcards_literal() {
+        assert!(fnmatch(r"\*", "*"));
+        assert!(!fnmatch(r"\*", "x"));
+        assert!(fnmatch(r"what\?", "what?"));
+        assert!(has_wildcard("*.rs"));
+        assert!(has_wildcard("a[b]"));
+        assert!(!has_wildcard(r"a\*b"));
+        assert!(!has_wildcard("plain"));
+    }
+
+    #[test]
+    fn dotfiles_need_an_explicit_dot() {
+        assert!(!matches("*", ".profile"));
+        assert!(!matches("?profile", ".profile"));
+        assert!(!matches("[.]profile", ".profile"));
+        assert!(matches(".*", ".profile"));
+        assert!(matches(".p*", ".profile"));
+        assert!(fnmatch("*", ".profile"));
+    }
+
+    #[test]
+    fn expands_against_the_filesystem() {
+        let mut fs = FileSystem::with_dirs(&["/home/user/src", "/home/user/docs"]);
+        for file in ["a.txt", "b.txt", ".hidden.txt", "src/main.rs", "src/lib.rs", "docs/guide.md"] {
+            fs.write_file(&resolve_path(&[], &format!("/home/user/{}", file)), String::new(), false)
+                .unwrap();
+        }
+        let cwd = resolve_path(&[], "/home/user");
+        let mut found = expand(&fs, &cwd, "*.txt");
+        found.sort();
+        assert_eq!(found, ["a.txt", "b.txt"]);
+        let mut found = expand(&fs, &cwd, "src/*.rs");
+        found.sort();
+        assert_eq!(found, ["src/lib.rs", "src/main.rs"]);
+        let mut found = expand(&fs, &cwd, "*/");
+        found.sort();
+        assert_eq!(found, ["docs/", "src/"]);
+        assert_eq!(expand(&fs, &cwd, "/home/*/docs/*.md"), ["/home/user/docs/guide.md"]);
+        assert!(expand(&fs, &cwd, "*.nope").is_empty());
+    }
+
+    #[test]
+    fn patterns_matching_nothing_stay_as_typed() {
+        let mut shell = Shell::new();
+        shell.exec("touch tmp1 tmp2 notes.txt");
+        assert_eq!(shell.exec("echo tmp?").output, "tmp1 tmp2");
+        assert_eq!(shell.exec("echo *.nope").output, "*.nope");
+        assert_eq!(shell.exec("echo 'tmp?' tmp\\?").output, "tmp? tmp?");
+        // The command gets the pattern itself.
+        shell.exec("touch *.new");
+        assert_eq!(shell.exec("echo \\*.new").output, "*.new");
+        assert_eq!(shell.exec("test -f '*.new'").exit_code, 0);
+    }
+}
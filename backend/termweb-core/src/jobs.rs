@@ -0,0 +1,261 @@
+//! Background jobs: command lists ended with `&`. The shell only records
+//! them; whoever runs the shell then drives each with
+//! [`crate::shell::step_job`], on a task of its own where there is a
+//! runtime for one, or with [`crate::shell::finish_jobs`]. A job's output
+//! collects in a buffer for `fg`, and for the server's job API, to hand
+//! back.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::parser::Segment;
+
+/// Finished jobs kept for `fg` before the oldest are dropped.
+const MAX_FINISHED_JOBS: usize = 32;
+
+/// Status `kill` leaves, as for a process ended by SIGTERM.
+pub const EXIT_TERMINATED: i32 = 143;
+
+static NEXT_PID: AtomicU64 = AtomicU64::new(1);
+
+/// A number no other job in this process has had; the server's job API
+/// numbers its jobs from the same count.
+pub fn next_pid() -> u64 {
+    NEXT_PID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Running,
+    Done(i32),
+    /// Stopped by `kill`.
+    Terminated,
+}
+
+impl JobStatus {
+    /// The exit status, once the job has finished.
+    pub fn exit_code(self) -> Option<i32> {
+        match self {
+            JobStatus::Running => None,
+            JobStatus::Done(code) => Some(code),
+            JobStatus::Terminated => Some(EXIT_TERMINATED),
+        }
+    }
+
+    /// As `jobs` shows it: `Running`, `Done`, `Exit 2` or `Terminated`.
+    fn describe(self) -> String {
+        match self {
+            JobStatus::Running => "Running".to_string(),
+            JobStatus::Done(0) => "Done".to_string(),
+            JobStatus::Done(code) => format!("Exit {}", code),
+            JobStatus::Terminated => "Terminated".to_string(),
+        }
+    }
+}
+
+/// One command list running, or run, in the background.
+pub struct Job {
+    /// The job's number in its session, as in `%1`.
+    pub number: usize,
+    /// Unique in the process, from [`next_pid`]: what the server's job API
+    /// knows the job by.
+    pub id: u64,
+    /// The process id `&` and `jobs -l` show; the same as `id` unless the
+    /// session numbers its pids itself.
+    pub pid: u64,
+    /// The list as written, without the `&`.
+    pub command: String,
+    pub(crate) body: Vec<Segment>,
+    subshell: Mutex<Subshell>,
+    progress: Mutex<Progress>,
+    /// Whether the job's finish has been reported at the prompt.
+    reported: AtomicBool,
+}
+
+/// What a job keeps of its own between steps, as a subshell would: changes
+/// to these never reach the session.
+pub(crate) struct Subshell {
+    pub(crate) cwd: Vec<String>,
+    pub(crate) env: BTreeMap<String, String>,
+    pub(crate) exit_code: i32,
+    /// The index in the body of the next segment to run.
+    pub(crate) next: usize,
+}
+
+struct Progress {
+    output: Vec<String>,
+    status: JobStatus,
+}
+
+impl Job {
+    pub fn status(&self) -> JobStatus {
+        self.progress().status
+    }
+
+    /// Everything the job has printed so far.
+    pub fn output(&self) -> String {
+        self.progress().output.join("\n")
+    }
+
+    pub(crate) fn write(&self, text: String) {
+        self.progress().output.push(text);
+    }
+
+    pub(crate) fn clear_output(&self) {
+        self.progress().output.clear();
+    }
+
+    /// Records the job's exit status, unless it was killed first.
+    pub(crate) fn finish(&self, exit_code: i32) {
+        let mut progress = self.progress();
+        if progress.status == JobStatus::Running {
+            progress.status = JobStatus::Done(exit_code);
+        }
+    }
+
+    /// Stops the job, returning false if it had already finished.
+    pub(crate) fn kill(&self) -> bool {
+        let mut progress = self.progress();
+        if progress.status != JobStatus::Running {
+            return false;
+        }
+        progress.status = JobStatus::Terminated;
+        true
+    }
+
+    pub fn killed(&self) -> bool {
+        self.status() == JobStatus::Terminated
+    }
+
+    pub(crate) fn subshell(&self) -> MutexGuard<'_, Subshell> {
+        self.subshell.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn progress(&self) -> MutexGuard<'_, Progress> {
+        self.progress.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A session's jobs, oldest first.
+#[derive(Default)]
+pub struct JobTable {
+    jobs: Vec<Arc<Job>>,
+    /// Jobs started by the line running now, waiting to be run.
+    pending: Vec<Arc<Job>>,
+    /// The pid for the next job, when pids are counted within the session.
+    next_local_pid: Option<u64>,
+}
+
+impl JobTable {
+    pub fn jobs(&self) -> &[Arc<Job>] {
+        &self.jobs
+    }
+
+    /// Counts pids from 1 within the session, so they are the same on every
+    /// run, as deterministic sessions need.
+    pub fn number_pids_locally(&mut self) {
+        self.next_local_pid.get_or_insert(1);
+    }
+
+    /// Adds a job for `body` and queues it to be run, starting in `cwd` with
+    /// the variables `env`.
+    pub(crate) fn start(
+        &mut self,
+        command: String,
+        body: Vec<Segment>,
+        cwd: Vec<String>,
+        env: BTreeMap<String, String>,
+    ) -> Arc<Job> {
+        let finished = |job: &&Arc<Job>| job.status() != JobStatus::Running;
+        if self.jobs.iter().filter(finished).count() >= MAX_FINISHED_JOBS
+            && let Some(index) = self.jobs.iter().position(|job| finished(&job))
+        {
+            self.jobs.remove(index);
+        }
+        let id = next_pid();
+        let pid = match &mut self.next_local_pid {
+            Some(next) => {
+                *next += 1;
+                *next - 1
+            }
+            None => id,
+        };
+        let job = Arc::new(Job {
+            number: self.jobs.last().map_or(1, |job| job.number + 1),
+            id,
+            pid,
+            command,
+            body,
+            subshell: Mutex::new(Subshell {
+                cwd,
+                env,
+                exit_code: 0,
+                next: 0,
+            }),
+            progress: Mutex::new(Progress {
+                output: Vec::new(),
+                status: JobStatus::Running,
+            }),
+            reported: AtomicBool::new(false),
+        });
+        self.jobs.push(job.clone());
+        self.pending.push(job.clone());
+        job
+    }
+
+    /// The jobs started since this was last called, for the caller to run.
+    pub fn take_pending(&mut self) -> Vec<Arc<Job>> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// The job `spec` names: `%N` by number, `%%` or `%+` the current job,
+    /// `%-` the one before, or `%text` the one whose command starts with
+    /// `text`. With no spec, the current job.
+    pub(crate) fn find(&self, spec: Option<&str>) -> Result<Arc<Job>, String> {
+        let spec = spec.unwrap_or("%%");
+        let name = spec.strip_prefix('%').unwrap_or(spec);
+        let found = match name {
+            "" | "%" | "+" => self.jobs.last(),
+            "-" => self.jobs.iter().rev().nth(1),
+            _ => match name.parse::<usize>() {
+                Ok(number) => self.jobs.iter().find(|job| job.number == number),
+                Err(_) => self.jobs.iter().rev().find(|job| job.command.starts_with(name)),
+            },
+        };
+        found.cloned().ok_or_else(|| format!("{}: no such job", spec))
+    }
+
+    pub(crate) fn remove(&mut self, number: usize) {
+        self.jobs.retain(|job| job.number != number);
+    }
+
+    /// A line for `job` as `jobs` lists it, marked `+` if it is the current
+    /// job and `-` if it is the one before, and with its pid if `long`. A
+    /// finished job shown this way is not reported again at the prompt.
+    pub(crate) fn describe(&self, job: &Job, long: bool) -> String {
+        if job.status() != JobStatus::Running {
+            job.reported.store(true, Ordering::Relaxed);
+        }
+        let position = self.jobs.iter().rev().position(|other| other.number == job.number);
+        let mark = match position {
+            Some(0) => '+',
+            Some(1) => '-',
+            _ => ' ',
+        };
+        let status = job.status();
+        let background = if status == JobStatus::Running { " &" } else { "" };
+        let pid = if long { format!("{:<6}", job.pid) } else { String::new() };
+        format!("[{}]{}  {}{:<24}{}{}", job.number, mark, pid, status.describe(), job.command, background)
+    }
+
+    /// Lines for the jobs that have finished since the last call, as bash
+    /// prints before the next prompt.
+    pub(crate) fn notices(&self) -> Vec<String> {
+        self.jobs
+            .iter()
+            .filter(|job| job.status() != JobStatus::Running && !job.reported.swap(true, Ordering::Relaxed))
+            .map(|job| self.describe(job, false))
+            .collect()
+    }
+}
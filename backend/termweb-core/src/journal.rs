@@ -0,0 +1,84 @@
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
+use crate::fs::Node;
+use crate::path::path_string;
+
+/// How many mutations the journal remembers before dropping the oldest.
+const MAX_ENTRIES: usize = 1024;
+
+/// One recorded filesystem mutation.
+#[derive(Clone, Debug, Serialize)]
+pub struct JournalEntry {
+    /// Monotonic sequence number; pass the last one seen as `since` to resume.
+    pub seq: u64,
+    pub op: &'static str,
+    pub path: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Change in total file content size, in bytes.
+    pub size_delta: i64,
+    /// The user whose session made the change, when it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// The session the change was made in, when there is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+}
+
+/// A bounded log of mutations made through [`FileSystem`](crate::FileSystem).
+#[derive(Default)]
+pub struct Journal {
+    entries: VecDeque<JournalEntry>,
+    last_seq: u64,
+    /// Who the mutations recorded from now on are made by.
+    user: Option<String>,
+    session: Option<String>,
+}
+
+impl Journal {
+    pub(crate) fn record(&mut self, op: &'static str, path: &[String], timestamp: u64, size_delta: i64) {
+        self.last_seq += 1;
+        self.entries.push_back(JournalEntry {
+            seq: self.last_seq,
+            op,
+            path: path_string(path),
+            timestamp,
+            size_delta,
+            user: self.user.clone(),
+            session: self.session.clone(),
+        });
+        while self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    pub(crate) fn set_actor(&mut self, user: Option<String>, session: Option<String>) {
+        self.user = user;
+        self.session = session;
+    }
+
+    pub(crate) fn actor(&self) -> (Option<&str>, Option<&str>) {
+        (self.user.as_deref(), self.session.as_deref())
+    }
+
+    /// Entries recorded after sequence number `since`, oldest first.
+    pub fn since(&self, since: u64) -> impl Iterator<Item = &JournalEntry> {
+        self.entries.iter().filter(move |entry| entry.seq > since)
+    }
+
+    /// The sequence number of the most recent mutation, or 0 if none.
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+}
+
+/// Total bytes of file content under `node`.
+pub(crate) fn node_size(node: &Node) -> i64 {
+    match node {
+        Node::File { content, .. } => content.len() as i64,
+        Node::Dir { children, .. } => children.values().map(node_size).sum(),
+        Node::Symlink { .. } => 0,
+    }
+}
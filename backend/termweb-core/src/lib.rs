@@ -0,0 +1,44 @@
+//! The virtual shell behind termweb: an in-memory filesystem, a command
+//! line parser and the built-in commands, with no dependency on the server.
+//!
+//! ```
+//! let mut shell = termweb_core::Shell::new();
+//! shell.exec("mkdir notes && echo hi > notes/todo.txt");
+//! assert_eq!(shell.exec("cat notes/todo.txt").output, "hi");
+//! ```
+
+pub mod archive;
+pub mod brace;
+pub mod builtins;
+pub mod clock;
+pub mod color;
+pub mod command;
+pub mod content;
+pub mod diff;
+pub mod editor;
+pub mod fs;
+pub mod glob;
+pub mod jobs;
+pub mod journal;
+pub mod parser;
+pub mod path;
+pub mod plugin;
+pub mod registry;
+pub mod rng;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod shell;
+pub mod startup;
+pub mod state;
+pub mod tokenizer;
+pub mod vcs;
+#[cfg(feature = "wasi-sandbox")]
+pub mod wasi;
+#[cfg(feature = "wasm-plugins")]
+pub mod wasm;
+
+pub use command::{Command, CommandContext, CommandResult, Completion, Manual, Notification};
+pub use fs::{content_revision, FileSystem, FsError, Limits, Node};
+pub use registry::CommandRegistry;
+pub use shell::{execute_command, execute_command_streaming, CommandResponse, OutputChunk, Shell};
+pub use state::TerminalState;
@@ -0,0 +1,614 @@
+//! Parses the tokens of a command line into a [`CommandLine`]: pipelines
+//! joined by `;`, `&&`, `||` and `&`, each stage's words, assignments and
+//! redirections, and compound commands holding command lists of their own.
+//!
+//! ```
+//! use termweb_core::parser::{parse, Connector};
+//!
+//! let line = parse("A=1 ls -l > out && cat out | wc").unwrap();
+//! assert_eq!(line.segments.len(), 2);
+//! assert_eq!(line.segments[1].connector, Connector::And);
+//! assert_eq!(line.segments[0].stages[0].assignments[0].name, "A");
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::tokenizer::{is_variable_name, lex, Token, Word, WordPart};
+
+/// How a segment of a command list is joined to the one before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    /// The first segment, or one after `;`: always runs.
+    Always,
+    /// After `&&`: runs only if the previous status was success.
+    And,
+    /// After `||`: runs only if the previous status was failure.
+    Or,
+}
+
+/// One pipeline in a command list, with the operator that precedes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub connector: Connector,
+    /// Each `|`-separated stage; never empty.
+    pub stages: Vec<Stage>,
+}
+
+/// A whole command line, as parsed; its segments run in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandLine {
+    pub segments: Vec<Segment>,
+}
+
+/// One command in a pipeline.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stage {
+    /// `NAME=value` words before the command. With no command they set
+    /// shell variables; otherwise they are set only while it runs.
+    pub assignments: Vec<Assignment>,
+    /// The command and its arguments; empty for a compound command.
+    pub words: Vec<Word>,
+    /// An `if`, `for` or `while` run in place of a simple command.
+    pub compound: Option<Box<Compound>>,
+    /// In the order written; a later one for the same stream wins.
+    pub redirections: Vec<Redirection>,
+}
+
+impl Stage {
+    fn is_empty(&self) -> bool {
+        self.assignments.is_empty()
+            && self.words.is_empty()
+            && self.compound.is_none()
+            && self.redirections.is_empty()
+    }
+}
+
+/// A `NAME=value` word. The value is expanded like a word, but never split,
+/// brace expanded or matched against files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    pub name: String,
+    pub value: Word,
+}
+
+impl Assignment {
+    /// The assignment `word` spells, if it starts with a variable name and
+    /// `=`, written plainly.
+    fn from_word(word: &Word) -> Option<Assignment> {
+        let Some(WordPart::Literal(first)) = word.parts.first() else { return None };
+        let (name, rest) = first.split_once('=')?;
+        if !is_variable_name(name) {
+            return None;
+        }
+        let mut parts = word.parts[1..].to_vec();
+        if !rest.is_empty() {
+            parts.insert(0, WordPart::Literal(rest.to_string()));
+        }
+        Some(Assignment {
+            name: name.to_string(),
+            value: Word {
+                parts,
+                quoted: word.quoted,
+            },
+        })
+    }
+}
+
+/// A command built out of command lists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Compound {
+    /// `if list; then list; [elif list; then list;]... [else list;] fi`: the
+    /// body of the first branch whose condition succeeds.
+    If {
+        branches: Vec<(Vec<Segment>, Vec<Segment>)>,
+        otherwise: Option<Vec<Segment>>,
+    },
+    /// `for name in word...; do list; done`: the body once for each word,
+    /// with the variable set to it.
+    For {
+        variable: String,
+        words: Vec<Word>,
+        body: Vec<Segment>,
+    },
+    /// `while list; do list; done`: the body for as long as the condition
+    /// succeeds, or with `until` for as long as it fails.
+    While {
+        condition: Vec<Segment>,
+        body: Vec<Segment>,
+        until: bool,
+    },
+    /// `list &`: the list run as a job in the background, with the text it
+    /// was written as, which `jobs` lists.
+    Background { body: Vec<Segment>, text: String },
+}
+
+/// A redirection operator and the file word after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirection {
+    pub kind: RedirectKind,
+    pub target: Word,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectKind {
+    /// `<`: the command reads the file as its input.
+    Input,
+    /// `>` or `>>`: the command's output goes to the file.
+    Output { append: bool },
+    /// `2>` or `2>>`: the command's error output goes to the file.
+    Error { append: bool },
+    /// `&>` or `&>>`: both go to the file.
+    Both { append: bool },
+}
+
+impl RedirectKind {
+    pub fn operator(self) -> &'static str {
+        match self {
+            RedirectKind::Input => "<",
+            RedirectKind::Output { append: false } => ">",
+            RedirectKind::Output { append: true } => ">>",
+            RedirectKind::Error { append: false } => "2>",
+            RedirectKind::Error { append: true } => "2>>",
+            RedirectKind::Both { append: false } => "&>",
+            RedirectKind::Both { append: true } => "&>>",
+        }
+    }
+}
+
+/// Why a command line could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input stops inside a quote, after a `\`, `|`, `&&` or `||`, or before
+    /// an `if`, `for` or `while` is closed; more lines may complete it.
+    Incomplete(String),
+    Syntax(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Incomplete(message) | ParseError::Syntax(message) => f.write_str(message),
+        }
+    }
+}
+
+/// Words that start or continue a compound command when they stand in
+/// command position, unquoted.
+const RESERVED_WORDS: [&str; 10] = ["if", "then", "elif", "else", "fi", "for", "while", "until", "do", "done"];
+
+/// Parses a command line into pipelines joined by `;`, newlines, `&&` and
+/// `||`, or ended by `&` to run in the background, and picks out each
+/// stage's assignments, redirections and compound commands. Operators
+/// inside quotes or escaped with `\` are ordinary characters, and a `#`
+/// starting a word comments out the rest of the line. A trailing `;` is
+/// allowed but any other empty segment or stage is a syntax error.
+/// Variables are kept as references so each command sees the values left by
+/// the ones before it.
+pub fn parse(input: &str) -> Result<CommandLine, ParseError> {
+    let (tokens, ends) = lex(input)?;
+    let mut parser = Parser {
+        tokens,
+        ends,
+        position: 0,
+        source: input,
+    };
+    let segments = parser.list(&[])?;
+    match parser.peek() {
+        None => Ok(CommandLine { segments }),
+        Some(token) => Err(unexpected(token)),
+    }
+}
+
+impl Token {
+    /// The reserved word this token spells, if it is one written plainly.
+    fn keyword(&self) -> Option<&str> {
+        match self {
+            Token::Word(word) if !word.quoted => match word.parts.as_slice() {
+                [WordPart::Literal(text)] if RESERVED_WORDS.contains(&text.as_str()) => Some(text),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+fn unexpected(token: &Token) -> ParseError {
+    let text = match token {
+        Token::Word(word) => word.expand(&BTreeMap::new()),
+        Token::Operator("\n") => "newline".to_string(),
+        Token::Operator(operator) => operator.to_string(),
+        Token::Redirect(kind) => kind.operator().to_string(),
+    };
+    ParseError::Syntax(format!("syntax error near unexpected token '{}'", text))
+}
+
+/// A recursive-descent parser over the tokens of a command line.
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    /// Where each token ends in `source`.
+    ends: Vec<usize>,
+    position: usize,
+    source: &'a str,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Where the token before `position` ends, or 0 at the start.
+    fn end_before(&self, position: usize) -> usize {
+        position.checked_sub(1).map_or(0, |index| self.ends[index])
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Token::Operator("\n")) {
+            self.position += 1;
+        }
+    }
+
+    /// Parses pipelines up to the end of the input or, in command position,
+    /// one of `terminators`, which is left for the caller.
+    fn list(&mut self, terminators: &[&str]) -> Result<Vec<Segment>, ParseError> {
+        let mut segments = Vec::new();
+        let mut connector = Connector::Always;
+        // Where the current `&&`/`||` list starts, in `segments` and in the source.
+        let mut first = 0;
+        let mut start = self.end_before(self.position);
+        loop {
+            self.skip_newlines();
+            match self.peek() {
+                None if connector != Connector::Always => {
+                    return Err(ParseError::Incomplete(
+                        "syntax error: command list ends with an operator".to_string(),
+                    ));
+                }
+                None => break,
+                Some(token)
+                    if connector == Connector::Always
+                        && token.keyword().is_some_and(|word| terminators.contains(&word)) =>
+                {
+                    break;
+                }
+                Some(_) => {}
+            }
+            let stages = self.pipeline()?;
+            segments.push(Segment { connector, stages });
+            connector = match self.peek() {
+                Some(Token::Operator("&&")) => Connector::And,
+                Some(Token::Operator("||")) => Connector::Or,
+                Some(Token::Operator(";" | "\n")) => Connector::Always,
+                Some(Token::Operator("&")) => {
+                    let body = segments.split_off(first);
+                    let text = self.source[start..self.ends[self.position] - 1].trim().to_string();
+                    segments.push(Segment {
+                        connector: Connector::Always,
+                        stages: vec![Stage {
+                            compound: Some(Box::new(Compound::Background { body, text })),
+                            ..Stage::default()
+                        }],
+                    });
+                    Connector::Always
+                }
+                None => break,
+                Some(token) if token.keyword().is_some_and(|word| terminators.contains(&word)) => break,
+                Some(token) => return Err(unexpected(token)),
+            };
+            self.position += 1;
+            if connector == Connector::Always {
+                first = segments.len();
+                start = self.end_before(self.position);
+            }
+        }
+        Ok(segments)
+    }
+
+    /// Like [`Parser::list`], for the parts of a compound command that may
+    /// not be empty.
+    fn body(&mut self, terminators: &[&str]) -> Result<Vec<Segment>, ParseError> {
+        let segments = self.list(terminators)?;
+        if segments.is_empty() {
+            return Err(match self.peek() {
+                Some(token) => unexpected(token),
+                None => incomplete(terminators[0]),
+            });
+        }
+        Ok(segments)
+    }
+
+    /// Consumes the reserved word `keyword`, which must come next.
+    fn expect(&mut self, keyword: &str) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(token) if token.keyword() == Some(keyword) => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(token) => Err(unexpected(token)),
+            None => Err(incomplete(keyword)),
+        }
+    }
+
+    fn pipeline(&mut self) -> Result<Vec<Stage>, ParseError> {
+        let mut stages = vec![self.stage()?];
+        while self.peek() == Some(&Token::Operator("|")) {
+            self.position += 1;
+            self.skip_newlines();
+            if self.peek().is_none() {
+                return Err(ParseError::Incomplete("syntax error: pipeline ends with '|'".to_string()));
+            }
+            stages.push(self.stage()?);
+        }
+        Ok(stages)
+    }
+
+    fn stage(&mut self) -> Result<Stage, ParseError> {
+        let mut stage = Stage::default();
+        match self.peek().and_then(Token::keyword) {
+            Some("if") => stage.compound = Some(Box::new(self.if_clause()?)),
+            Some("for") => stage.compound = Some(Box::new(self.for_clause()?)),
+            Some(keyword @ ("while" | "until")) => {
+                let until = keyword == "until";
+                stage.compound = Some(Box::new(self.while_clause(until)?));
+            }
+            Some(_) => return Err(unexpected(self.peek().expect("peeked"))),
+            None => {}
+        }
+        loop {
+            match self.peek() {
+                Some(Token::Word(_)) if stage.compound.is_some() => {
+                    return Err(unexpected(self.peek().expect("peeked")));
+                }
+                Some(Token::Word(_)) => {
+                    let Some(Token::Word(word)) = self.next() else { unreachable!() };
+                    match Assignment::from_word(&word) {
+                        Some(assignment) if stage.words.is_empty() => stage.assignments.push(assignment),
+                        _ => stage.words.push(word),
+                    }
+                }
+                Some(Token::Redirect(kind)) => {
+                    let kind = *kind;
+                    self.position += 1;
+                    match self.next() {
+                        Some(Token::Word(target)) => stage.redirections.push(Redirection { kind, target }),
+                        Some(token) => return Err(unexpected(&token)),
+                        None => {
+                            return Err(ParseError::Syntax(
+                                "syntax error near unexpected token 'newline'".to_string(),
+                            ));
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+        if stage.is_empty() {
+            return Err(match self.peek() {
+                Some(token) => unexpected(token),
+                None => ParseError::Syntax("syntax error near unexpected token 'newline'".to_string()),
+            });
+        }
+        Ok(stage)
+    }
+
+    fn if_clause(&mut self) -> Result<Compound, ParseError> {
+        let mut branches = Vec::new();
+        let mut otherwise = None;
+        self.expect("if")?;
+        loop {
+            let condition = self.body(&["then"])?;
+            self.expect("then")?;
+            let body = self.body(&["elif", "else", "fi"])?;
+            branches.push((condition, body));
+            match self.peek().and_then(Token::keyword) {
+                Some("elif") => self.position += 1,
+                Some("else") => {
+                    self.position += 1;
+                    otherwise = Some(self.body(&["fi"])?);
+                    break;
+                }
+                _ => break,
+            }
+        }
+        self.expect("fi")?;
+        Ok(Compound::If { branches, otherwise })
+    }
+
+    fn for_clause(&mut self) -> Result<Compound, ParseError> {
+        self.expect("for")?;
+        let variable = match self.next() {
+            Some(Token::Word(word)) => {
+                let name = word.expand(&BTreeMap::new());
+                if word.quoted || !is_variable_name(&name) {
+                    return Err(ParseError::Syntax(format!("for: '{}': not a valid identifier", name)));
+                }
+                name
+            }
+            Some(token) => return Err(unexpected(&token)),
+            None => return Err(incomplete("in")),
+        };
+        self.skip_newlines();
+        match self.peek() {
+            Some(Token::Word(word)) if !word.quoted && word.parts == [WordPart::Literal("in".to_string())] => {
+                self.position += 1;
+            }
+            Some(token) => return Err(unexpected(token)),
+            None => return Err(incomplete("in")),
+        }
+        let mut words = Vec::new();
+        while let Some(Token::Word(word)) = self.peek() {
+            words.push(word.clone());
+            self.position += 1;
+        }
+        match self.next() {
+            Some(Token::Operator(";" | "\n")) => {}
+            Some(token) => return Err(unexpected(&token)),
+            None => return Err(incomplete("do")),
+        }
+        self.skip_newlines();
+        self.expect("do")?;
+        let body = self.body(&["done"])?;
+        self.expect("done")?;
+        Ok(Compound::For { variable, words, body })
+    }
+
+    fn while_clause(&mut self, until: bool) -> Result<Compound, ParseError> {
+        self.expect(if until { "until" } else { "while" })?;
+        let condition = self.body(&["do"])?;
+        self.expect("do")?;
+        let body = self.body(&["done"])?;
+        self.expect("done")?;
+        Ok(Compound::While { condition, body, until })
+    }
+}
+
+/// The input ended while `keyword` was still expected.
+fn incomplete(keyword: &str) -> ParseError {
+    ParseError::Incomplete(format!("syntax error: expected '{}'", keyword))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Compound, Connector, ParseError, Segment, Stage};
+    use crate::tokenizer::tests::render_word;
+
+    /// `segments` written back out, one space between words and operators,
+    /// with words as [`render_word`] gives them.
+    fn render(segments: &[Segment]) -> String {
+        let mut parts = Vec::new();
+        for (index, segment) in segments.iter().enumerate() {
+            match segment.connector {
+                Connector::Always if index > 0 => parts.push(";".to_string()),
+                Connector::Always => {}
+                Connector::And => parts.push("&&".to_string()),
+                Connector::Or => parts.push("||".to_string()),
+            }
+            let stages: Vec<String> = segment.stages.iter().map(render_stage).collect();
+            parts.push(stages.join(" | "));
+        }
+        parts.join(" ")
+    }
+
+    fn render_stage(stage: &Stage) -> String {
+        let mut parts: Vec<String> = stage
+            .assignments
+            .iter()
+            .map(|assignment| format!("{}={}", assignment.name, render_word(&assignment.value)))
+            .collect();
+        parts.extend(stage.words.iter().map(render_word));
+        match stage.compound.as_deref() {
+            Some(Compound::If { branches, otherwise }) => {
+                for (condition, body) in branches {
+                    parts.push(format!("if {{{}}} then {{{}}}", render(condition), render(body)));
+                }
+                if let Some(otherwise) = otherwise {
+                    parts.push(format!("else {{{}}}", render(otherwise)));
+                }
+            }
+            Some(Compound::For { variable, words, body }) => {
+                let words: Vec<String> = words.iter().map(render_word).collect();
+                parts.push(format!("for {} in {{{}}} do {{{}}}", variable, words.join(" "), render(body)));
+            }
+            Some(Compound::While { condition, body, until }) => {
+                let keyword = if *until { "until" } else { "while" };
+                parts.push(format!("{} {{{}}} do {{{}}}", keyword, render(condition), render(body)));
+            }
+            Some(Compound::Background { body, .. }) => parts.push(format!("{{{}}} &", render(body))),
+            None => {}
+        }
+        for redirection in &stage.redirections {
+            parts.push(format!("{}{}", redirection.kind.operator(), render_word(&redirection.target)));
+        }
+        parts.join(" ")
+    }
+
+    #[test]
+    fn parses_command_lines() {
+        let cases: &[(&str, &str)] = &[
+            ("ls -l", "ls -l"),
+            ("a; b", "a ; b"),
+            ("a;b;", "a ; b"),
+            ("a\nb", "a ; b"),
+            ("a && b || c", "a && b || c"),
+            ("a | b | c && d", "a | b | c && d"),
+            ("echo 'a | b' \"c && d\"", "echo [a | b] [c && d]"),
+            ("echo a\\;b", "echo a[;]b"),
+            ("cat < in > out", "cat <in >out"),
+            ("cat in >> out 2> err", "cat in >>out 2>err"),
+            ("> out echo hi", "echo hi >out"),
+            ("make &> log | wc", "make &>log | wc"),
+            ("sleep 1 &", "{sleep 1} &"),
+            ("a && b & c", "{a && b} & ; c"),
+            ("if a; then b; else c; fi", "if {a} then {b} else {c}"),
+            ("for x in 1 2; do echo $x; done", "for x in {1 2} do {echo $x}"),
+            ("until a; do b; done", "until {a} do {b}"),
+            ("echo if", "echo if"),
+        ];
+        for (input, expected) in cases {
+            let line = parse(input).unwrap_or_else(|error| panic!("{:?}: {}", input, error));
+            assert_eq!(render(&line.segments), *expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn parses_assignments() {
+        let cases: &[(&str, &str)] = &[
+            ("A=1", "A=1"),
+            ("A=1 B=two", "A=1 B=two"),
+            ("A= ls", "A= ls"),
+            ("A='a b' ls", "A=[a b] ls"),
+            ("A=$HOME/x ls", "A=$HOME/x ls"),
+            ("ls A=1", "ls A=1"),
+            ("A=1 ls B=2", "A=1 ls B=2"),
+            ("'A'=1 ls", "[A]=1 ls"),
+            ("1A=1 ls", "1A=1 ls"),
+            ("A=1 > out", "A=1 >out"),
+            ("A=1 && echo $A", "A=1 && echo $A"),
+        ];
+        for (input, expected) in cases {
+            let line = parse(input).unwrap_or_else(|error| panic!("{:?}: {}", input, error));
+            assert_eq!(render(&line.segments), *expected, "{:?}", input);
+        }
+        let line = parse("A=1 B=2 ls").unwrap();
+        assert_eq!(line.segments[0].stages[0].assignments.len(), 2);
+        assert_eq!(line.segments[0].stages[0].words.len(), 1);
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let incomplete: &[&str] = &[
+            "echo 'abc",
+            "echo \"abc",
+            "echo abc\\",
+            "ls |",
+            "ls &&",
+            "ls ||",
+            "if true; then ls",
+            "for x in a; do",
+            "while true; do ls; ",
+        ];
+        for input in incomplete {
+            assert!(matches!(parse(input), Err(ParseError::Incomplete(_))), "{:?}", input);
+        }
+        let syntax: &[&str] = &[
+            "| ls",
+            "ls | | wc",
+            "ls ;; wc",
+            "&& ls",
+            "ls >",
+            "cat < | wc",
+            "fi",
+            "done",
+            "ls; then",
+        ];
+        for input in syntax {
+            assert!(matches!(parse(input), Err(ParseError::Syntax(_))), "{:?}: {:?}", input, parse(input));
+        }
+    }
+}
@@ -0,0 +1,34 @@
+/// Resolves `input` against `cwd` into normalized path segments.
+pub fn resolve_path(cwd: &[String], input: &str) -> Vec<String> {
+    let mut parts = if input.starts_with('/') {
+        Vec::new()
+    } else {
+        cwd.to_vec()
+    };
+
+    for segment in input.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(segment.to_string()),
+        }
+    }
+
+    parts
+}
+
+/// Formats path segments as an absolute path string.
+pub fn path_string(path: &[String]) -> String {
+    if path.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{}", path.join("/"))
+    }
+}
+
+pub(crate) fn split_parent(path: &[String]) -> (&[String], &String) {
+    let len = path.len();
+    (&path[..len - 1], &path[len - 1])
+}
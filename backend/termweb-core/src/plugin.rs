@@ -0,0 +1,41 @@
+//! Commands shipped in their own crates, such as a course's exercises, and
+//! installed as one unit with [`CommandRegistry::install`].
+//!
+//! ```
+//! use termweb_core::plugin::CommandPlugin;
+//! use termweb_core::{Command, CommandContext, CommandRegistry, CommandResult};
+//!
+//! struct Greet;
+//!
+//! impl Command for Greet {
+//!     fn name(&self) -> &'static str { "greet" }
+//!     fn help(&self) -> &'static str { "greet" }
+//!     fn run(&self, _ctx: &mut CommandContext<'_>, _args: &[String]) -> CommandResult {
+//!         CommandResult::ok("hello")
+//!     }
+//! }
+//!
+//! struct Exercises;
+//!
+//! impl CommandPlugin for Exercises {
+//!     fn name(&self) -> &str { "exercises" }
+//!     fn register(&self, registry: &mut CommandRegistry) {
+//!         registry.register(Greet);
+//!     }
+//! }
+//!
+//! let mut registry = CommandRegistry::with_builtins();
+//! registry.install(&Exercises);
+//! assert!(registry.get("greet").is_some());
+//! ```
+
+use crate::registry::CommandRegistry;
+
+pub trait CommandPlugin {
+    /// Identifies the plugin in logs.
+    fn name(&self) -> &str;
+
+    /// Adds the plugin's commands. One named like a command already
+    /// registered replaces it.
+    fn register(&self, registry: &mut CommandRegistry);
+}
@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+
+use crate::builtins;
+use crate::command::Command;
+use crate::plugin::CommandPlugin;
+
+/// The set of commands a shell can dispatch to, kept in registration order.
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: Vec<Box<dyn Command>>,
+    index: HashMap<&'static str, usize>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        builtins::register(&mut registry);
+        registry
+    }
+
+    /// Adds a command, replacing any existing command with the same name.
+    pub fn register(&mut self, command: impl Command + 'static) {
+        let name = command.name();
+        match self.index.get(name) {
+            Some(&position) => self.commands[position] = Box::new(command),
+            None => {
+                self.index.insert(name, self.commands.len());
+                self.commands.push(Box::new(command));
+            }
+        }
+    }
+
+    /// Adds every command `plugin` provides.
+    pub fn install(&mut self, plugin: &dyn CommandPlugin) {
+        plugin.register(self);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Command> {
+        self.index
+            .get(name)
+            .map(|&position| self.commands[position].as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Command> {
+        self.commands.iter().map(|command| command.as_ref())
+    }
+}
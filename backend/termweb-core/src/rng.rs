@@ -0,0 +1,49 @@
+//! Where the engine gets random numbers. A [`SeededRng`] replays the same
+//! sequence for the same seed.
+
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub trait Rng: Send + Sync {
+    fn next_u64(&self) -> u64;
+}
+
+/// Unpredictable values from the standard library's per-process hash keys.
+#[derive(Default)]
+pub struct OsRng {
+    counter: AtomicU64,
+    keys: std::collections::hash_map::RandomState,
+}
+
+impl Rng for OsRng {
+    fn next_u64(&self) -> u64 {
+        let mut hasher = self.keys.build_hasher();
+        hasher.write_u64(self.counter.fetch_add(1, Ordering::Relaxed));
+        hasher.finish()
+    }
+}
+
+/// A SplitMix64 generator: fast, seedable and good enough for shell use.
+pub struct SeededRng {
+    state: AtomicU64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng {
+            state: AtomicU64::new(seed),
+        }
+    }
+}
+
+impl Rng for SeededRng {
+    fn next_u64(&self) -> u64 {
+        let mut z = self
+            .state
+            .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+            .wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
@@ -0,0 +1,210 @@
+//! Custom commands written in Rhai.
+//!
+//! Scripts come from two places: a host directory loaded once at startup with
+//! [`load_dir`], and `*.rhai` files under `/usr/local/bin` in the virtual
+//! filesystem, looked up whenever a command name is not registered. Scripts
+//! only see the API registered here: their arguments, piped input as `stdin`,
+//! `print`, and a handful of functions over the virtual filesystem.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, AST};
+
+use crate::command::{Command, CommandContext, CommandResult};
+use crate::fs::{FileSystem, FsError, Node};
+use crate::path::resolve_path;
+use crate::registry::CommandRegistry;
+use crate::state::TerminalState;
+
+/// Directory in the virtual filesystem searched for script commands.
+pub const SCRIPT_DIR: &str = "/usr/local/bin";
+
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+pub struct ScriptCommand {
+    name: &'static str,
+    help: &'static str,
+    ast: AST,
+}
+
+impl ScriptCommand {
+    pub fn new(name: &str, source: &str) -> Result<Self, String> {
+        let ast = sandboxed_engine()
+            .compile(source)
+            .map_err(|err| format!("{}: {}", name, err))?;
+        // Commands live for the whole process, so leaking the names is bounded.
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let help = Box::leak(format!("{} [args...]", name).into_boxed_str());
+        Ok(ScriptCommand { name, help, ast })
+    }
+}
+
+impl Command for ScriptCommand {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn help(&self) -> &'static str {
+        self.help
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        run_ast(self.name, &self.ast, ctx.state, args, ctx.stdin.as_deref())
+    }
+}
+
+/// Registers every `*.rhai` file in `dir` as a command named after the file stem.
+pub fn load_dir(registry: &mut CommandRegistry, dir: &Path) -> Result<usize, String> {
+    let entries = std::fs::read_dir(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    let mut loaded = 0;
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("rhai") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let source =
+            std::fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        registry.register(ScriptCommand::new(name, &source)?);
+        loaded += 1;
+    }
+    Ok(loaded)
+}
+
+/// Runs `/usr/local/bin/<name>.rhai` from the virtual filesystem, if it exists.
+pub fn run_vfs_script(
+    state: &mut TerminalState,
+    name: &str,
+    args: &[String],
+    stdin: Option<&str>,
+) -> Option<CommandResult> {
+    let path = resolve_path(&[], &format!("{}/{}.rhai", SCRIPT_DIR, name));
+    let source = match state.fs.read_file(&path) {
+        Ok(source) => source,
+        Err(FsError::NotFound | FsError::IsADirectory) => return None,
+        Err(err) => return Some(CommandResult::error(format!("{}: {}", name, err))),
+    };
+    Some(match sandboxed_engine().compile(&source) {
+        Ok(ast) => run_ast(name, &ast, state, args, stdin),
+        Err(err) => CommandResult::error(format!("{}: {}", name, err)),
+    })
+}
+
+fn sandboxed_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(32);
+    engine.set_max_string_size(1 << 20);
+    engine.set_max_array_size(10_000);
+    engine.set_max_map_size(10_000);
+    engine
+}
+
+fn run_ast(
+    name: &str,
+    ast: &AST,
+    state: &mut TerminalState,
+    args: &[String],
+    stdin: Option<&str>,
+) -> CommandResult {
+    // The engine's callbacks must be 'static, so the filesystem is moved into a
+    // shared handle for the duration of the script and put back afterwards.
+    let fs = Arc::new(Mutex::new(std::mem::take(&mut state.fs)));
+    let output = Arc::new(Mutex::new(Vec::<String>::new()));
+    let mut engine = sandboxed_engine();
+    register_api(&mut engine, &fs, &output, &state.cwd);
+
+    let mut scope = rhai::Scope::new();
+    let args: Array = args.iter().cloned().map(Dynamic::from).collect();
+    scope.push("args", args);
+    scope.push("stdin", stdin.unwrap_or_default().to_string());
+    let result = engine.run_ast_with_scope(&mut scope, ast);
+
+    drop(engine);
+    state.fs = Arc::try_unwrap(fs)
+        .ok()
+        .and_then(|fs| fs.into_inner().ok())
+        .expect("script engine released the filesystem");
+    let output = output.lock().map(|lines| lines.join("\n")).unwrap_or_default();
+
+    match result {
+        Ok(()) => CommandResult::ok(output),
+        Err(err) => CommandResult::ok(output)
+            .with_errors([format!("{}: {}", name, err)])
+            .with_exit_code(1),
+    }
+}
+
+fn register_api(
+    engine: &mut Engine,
+    fs: &Arc<Mutex<FileSystem>>,
+    output: &Arc<Mutex<Vec<String>>>,
+    cwd: &[String],
+) {
+    let sink = output.clone();
+    engine.on_print(move |text| {
+        if let Ok(mut lines) = sink.lock() {
+            lines.push(text.to_string());
+        }
+    });
+
+    let cwd_string = crate::path::path_string(cwd);
+    engine.register_fn("cwd", move || cwd_string.clone());
+
+    let (handle, base) = (fs.clone(), cwd.to_vec());
+    engine.register_fn("read_file", move |path: &str| -> Result<String, Box<EvalAltResult>> {
+        let fs = handle.lock().map_err(|_| "filesystem unavailable")?;
+        Ok(fs.read_file(&resolve_path(&base, path)).map_err(|error| error.to_string())?)
+    });
+
+    let (handle, base) = (fs.clone(), cwd.to_vec());
+    engine.register_fn(
+        "write_file",
+        move |path: &str, content: &str| -> Result<(), Box<EvalAltResult>> {
+            let mut fs = handle.lock().map_err(|_| "filesystem unavailable")?;
+            fs.write_file(&resolve_path(&base, path), content.to_string(), false)
+                .map_err(|error| error.to_string().into())
+        },
+    );
+
+    let (handle, base) = (fs.clone(), cwd.to_vec());
+    engine.register_fn(
+        "append_file",
+        move |path: &str, content: &str| -> Result<(), Box<EvalAltResult>> {
+            let mut fs = handle.lock().map_err(|_| "filesystem unavailable")?;
+            fs.write_file(&resolve_path(&base, path), content.to_string(), true)
+                .map_err(|error| error.to_string().into())
+        },
+    );
+
+    let (handle, base) = (fs.clone(), cwd.to_vec());
+    engine.register_fn("exists", move |path: &str| -> bool {
+        handle
+            .lock()
+            .map(|fs| fs.get_node(&resolve_path(&base, path)).is_some())
+            .unwrap_or(false)
+    });
+
+    let (handle, base) = (fs.clone(), cwd.to_vec());
+    engine.register_fn("is_dir", move |path: &str| -> bool {
+        handle
+            .lock()
+            .map(|fs| matches!(fs.is_dir(&resolve_path(&base, path)), Ok(true)))
+            .unwrap_or(false)
+    });
+
+    let (handle, base) = (fs.clone(), cwd.to_vec());
+    engine.register_fn("list_dir", move |path: &str| -> Result<Array, Box<EvalAltResult>> {
+        let fs = handle.lock().map_err(|_| "filesystem unavailable")?;
+        match fs.get_node(&resolve_path(&base, path)) {
+            Some(Node::Dir { children, .. }) => {
+                Ok(children.keys().cloned().map(Dynamic::from).collect())
+            }
+            Some(_) => Err("list_dir: not a directory".into()),
+            None => Err("list_dir: path not found".into()),
+        }
+    });
+}
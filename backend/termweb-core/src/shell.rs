@@ -0,0 +1,980 @@
+use std::ops::ControlFlow;
+
+use serde::Serialize;
+
+use crate::brace;
+use crate::clock;
+use crate::color;
+use crate::command::{CommandContext, CommandResult, Notification, EXIT_INTERRUPTED, EXIT_NOT_FOUND, EXIT_USAGE};
+use crate::editor::{self, Editor, EditorView};
+use crate::fs::is_device;
+use crate::path::resolve_path;
+use crate::registry::CommandRegistry;
+use crate::startup::init_session;
+use crate::state::TerminalState;
+use crate::glob;
+use crate::jobs::{Job, JobStatus, EXIT_TERMINATED};
+use crate::parser::{parse, Compound, Connector, ParseError, RedirectKind, Segment, Stage};
+use crate::tokenizer::{expand_aliases, Word};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandResponse {
+    pub output: String,
+    pub cwd: String,
+    /// `"ok"` or `"error"`, following `exit_code`; kept for older clients.
+    pub status: String,
+    pub exit_code: i32,
+    /// Why the last command failed, when it was a filesystem error, e.g. `not_found`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<&'static str>,
+    pub clear: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notifications: Vec<Notification>,
+    /// `"editor"` while a file is open in `edit`; absent at the prompt.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<&'static str>,
+    /// The open file, for clients to draw in place of the terminal.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub editor: Option<EditorView>,
+    /// Whether `output` is only the start of what the command printed.
+    pub truncated: bool,
+    /// Names the rest of a truncated output, for the server's
+    /// `GET /api/output/{token}`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuation: Option<String>,
+}
+
+impl CommandResponse {
+    pub(crate) fn new(state: &TerminalState, result: CommandResult) -> Self {
+        let mut output = result.combined_output();
+        let mut notifications = result.notifications;
+        if strip_bells(&mut output) {
+            notifications.push(Notification::Bell);
+        }
+        CommandResponse {
+            output,
+            cwd: state.cwd_string(),
+            status: if result.exit_code == 0 { "ok" } else { "error" }.to_string(),
+            exit_code: result.exit_code,
+            error_code: result.error_code,
+            clear: result.clear,
+            notifications,
+            mode: state.editor.as_ref().map(|_| "editor"),
+            editor: state.editor.as_ref().map(Editor::view),
+            truncated: false,
+            continuation: None,
+        }
+    }
+}
+
+/// How many scripts may run inside one another, so one that sources itself stops.
+const MAX_SCRIPT_DEPTH: usize = 16;
+
+/// Times a `while` or `until` body may run, so a loop that never ends does not hang the session.
+const MAX_LOOP_ITERATIONS: usize = 10_000;
+
+/// A piece of a command line's output, handed over while the line runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputChunk {
+    Text(String),
+    /// Everything sent before this should be discarded, as after `clear`.
+    Clear,
+}
+
+/// Joins streamed chunks into the output a single response would carry.
+pub fn join_output(chunks: impl IntoIterator<Item = OutputChunk>) -> String {
+    let mut texts = Vec::new();
+    for chunk in chunks {
+        match chunk {
+            OutputChunk::Text(text) => texts.push(text),
+            OutputChunk::Clear => texts.clear(),
+        }
+    }
+    texts.join("\n")
+}
+
+/// Where a running command line's output goes, with BELs turned into a
+/// notification on the way.
+struct Output<'a> {
+    sink: &'a mut dyn FnMut(OutputChunk),
+    /// Where error output is kept apart, while the output of commands run
+    /// inside another is collected; otherwise it goes to `sink` too.
+    errors: Option<&'a mut Vec<String>>,
+    bell: bool,
+}
+
+impl Output<'_> {
+    fn text(&mut self, mut text: String) {
+        self.bell |= strip_bells(&mut text);
+        if !text.is_empty() {
+            (self.sink)(OutputChunk::Text(text));
+        }
+    }
+
+    fn error(&mut self, mut text: String) {
+        match &mut self.errors {
+            Some(errors) if !text.is_empty() => {
+                self.bell |= strip_bells(&mut text);
+                errors.push(text);
+            }
+            Some(_) => {}
+            None => self.text(text),
+        }
+    }
+
+    fn clear(&mut self) {
+        (self.sink)(OutputChunk::Clear);
+    }
+}
+
+/// Removes BEL characters from `output`, returning whether there were any.
+pub fn strip_bells(output: &mut String) -> bool {
+    let before = output.len();
+    output.retain(|c| c != '\u{7}');
+    output.len() != before
+}
+
+/// A registry and a session's state together: everything needed to run
+/// command lines without the HTTP server.
+pub struct Shell {
+    pub registry: CommandRegistry,
+    pub state: TerminalState,
+}
+
+impl Shell {
+    /// A shell with the built-in commands and a fresh filesystem.
+    pub fn new() -> Self {
+        Shell::with_registry(CommandRegistry::with_builtins())
+    }
+
+    /// A shell with `registry`'s commands, once its dotfiles have run.
+    pub fn with_registry(registry: CommandRegistry) -> Self {
+        let mut state = TerminalState::default();
+        init_session(&registry, &mut state);
+        Shell { registry, state }
+    }
+
+    /// Runs a command line, as typed at the prompt.
+    pub fn exec(&mut self, input: &str) -> CommandResponse {
+        let response = execute_command(&self.registry, &mut self.state, input);
+        finish_jobs(&self.registry, &mut self.state);
+        response
+    }
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Shell::new()
+    }
+}
+
+pub fn execute_command(
+    registry: &CommandRegistry,
+    state: &mut TerminalState,
+    input: &str,
+) -> CommandResponse {
+    let mut chunks = Vec::new();
+    let mut response = execute_command_streaming(registry, state, input, &mut |chunk| chunks.push(chunk));
+    response.output = join_output(chunks);
+    response
+}
+
+/// Runs `input` like [`execute_command`], but hands each command's output to
+/// `sink` as soon as that command finishes. The returned response carries
+/// everything else; its `output` is empty.
+pub fn execute_command_streaming(
+    registry: &CommandRegistry,
+    state: &mut TerminalState,
+    input: &str,
+    sink: &mut dyn FnMut(OutputChunk),
+) -> CommandResponse {
+    let mut output = Output {
+        sink,
+        errors: None,
+        bell: false,
+    };
+    // An open editor takes the input instead of the shell.
+    if state.editor.is_some() {
+        let mut response = editor::command(state, input);
+        output.text(std::mem::take(&mut response.output));
+        return response;
+    }
+    let result = run_line(registry, state, input, &mut output);
+    // As bash does before the next prompt.
+    for notice in state.jobs.notices() {
+        output.text(notice);
+    }
+    let mut response = CommandResponse::new(state, result);
+    if output.bell {
+        response.notifications.push(Notification::Bell);
+    }
+    response
+}
+
+/// Runs a whole command line, sending output as it goes. The result has no
+/// output of its own.
+fn run_line(
+    registry: &CommandRegistry,
+    state: &mut TerminalState,
+    input: &str,
+    output: &mut Output<'_>,
+) -> CommandResult {
+    let expanded = match expand_history(&state.history, input) {
+        Ok(expanded) => expanded,
+        Err(message) => {
+            output.text(message);
+            state.last_exit_code = 1;
+            return CommandResult::error("");
+        }
+    };
+    let input = expanded.as_deref().unwrap_or(input);
+    if !input.trim().is_empty() {
+        state.record_history(input);
+    }
+
+    let line = match parse(&expand_aliases(input, &state.aliases)) {
+        Ok(line) => line,
+        Err(error) => {
+            output.text(error.to_string());
+            state.last_exit_code = EXIT_USAGE;
+            return CommandResult::error("").with_exit_code(EXIT_USAGE);
+        }
+    };
+
+    // Like bash, show what a history reference expanded to before running it.
+    if let Some(expanded) = expanded {
+        output.text(expanded);
+    }
+    match run_segments(registry, state, &line.segments, output, false) {
+        ControlFlow::Continue(result) | ControlFlow::Break(result) => result,
+    }
+}
+
+/// Runs a parsed command list. With `errexit`, as under `set -e`, a failing
+/// command stops the list unless `&&` or `||` follows it; the result is then
+/// a `Break`.
+fn run_segments(
+    registry: &CommandRegistry,
+    state: &mut TerminalState,
+    segments: &[Segment],
+    output: &mut Output<'_>,
+    errexit: bool,
+) -> ControlFlow<CommandResult, CommandResult> {
+    // A skipped segment leaves the status alone, so `false && a || b` runs `b`.
+    let mut exit_code = 0;
+    let mut error_code = None;
+    let mut clear = false;
+    let mut notifications = Vec::new();
+    for (index, segment) in segments.iter().enumerate() {
+        let run = match segment.connector {
+            Connector::Always => true,
+            Connector::And => exit_code == 0,
+            Connector::Or => exit_code != 0,
+        };
+        if !run {
+            continue;
+        }
+        if state.interrupted() {
+            return ControlFlow::Break(CommandResult::error("").with_exit_code(EXIT_INTERRUPTED));
+        }
+        let mut result = run_pipeline(registry, state, &segment.stages, errexit);
+        exit_code = result.exit_code;
+        error_code = result.error_code;
+        state.last_exit_code = exit_code;
+        notifications.append(&mut result.notifications);
+        if result.clear {
+            clear = true;
+            output.clear();
+        }
+        // Error output that brings its own colours is left alone.
+        if state.color_enabled() && !result.error_output.contains('\x1b') {
+            result.error_output = color::paint(&result.error_output, color::ERROR);
+        }
+        output.text(result.output);
+        output.error(result.error_output);
+        let tested = segments
+            .get(index + 1)
+            .is_some_and(|next| next.connector != Connector::Always);
+        if errexit && exit_code != 0 && !tested {
+            return ControlFlow::Break(CommandResult {
+                output: String::new(),
+                error_output: String::new(),
+                exit_code,
+                error_code,
+                clear,
+                notifications,
+            });
+        }
+    }
+
+    ControlFlow::Continue(CommandResult {
+        output: String::new(),
+        error_output: String::new(),
+        exit_code,
+        error_code,
+        clear,
+        notifications,
+    })
+}
+
+/// Runs a shell script, as `sh` and `source` do, stopping at the first
+/// command that fails as under `set -e`. Each command runs as soon as the
+/// lines making it up have been read, and nothing goes into the history.
+/// `name` prefixes syntax errors. The result carries the script's output and
+/// the status of the last command run.
+pub fn run_script(
+    registry: &CommandRegistry,
+    state: &mut TerminalState,
+    name: &str,
+    script: &str,
+) -> CommandResult {
+    if state.script_depth >= MAX_SCRIPT_DEPTH {
+        return CommandResult::error(format!("{}: scripts nested more than {} deep", name, MAX_SCRIPT_DEPTH));
+    }
+    state.script_depth += 1;
+    let result = collected(|output| {
+        let mut result = CommandResult::empty();
+        let mut notifications = Vec::new();
+        let mut clear = false;
+        // Lines read so far of a command that spans several, like an `if`.
+        let mut pending = String::new();
+        let mut lines = script.lines().enumerate().peekable();
+        while let Some((number, line)) = lines.next() {
+            pending.push_str(line);
+            pending.push('\n');
+            let line = match parse(&expand_aliases(&pending, &state.aliases)) {
+                Ok(line) => line,
+                Err(ParseError::Incomplete(_)) if lines.peek().is_some() => continue,
+                Err(error) => {
+                    output.text(format!("{}: line {}: {}", name, number + 1, error));
+                    state.last_exit_code = EXIT_USAGE;
+                    result = CommandResult::error("").with_exit_code(EXIT_USAGE);
+                    break;
+                }
+            };
+            pending.clear();
+            let flow = run_segments(registry, state, &line.segments, output, true);
+            let stopped = flow.is_break();
+            result = match flow {
+                ControlFlow::Continue(result) | ControlFlow::Break(result) => result,
+            };
+            notifications.append(&mut result.notifications);
+            clear |= result.clear;
+            if stopped {
+                break;
+            }
+        }
+        result.notifications = notifications;
+        result.clear = clear;
+        result
+    });
+    state.script_depth -= 1;
+    result
+}
+
+/// What to do next for a background job; see [`step_job`].
+pub enum JobStep {
+    /// The job is sleeping: step it again once this many milliseconds have
+    /// passed.
+    Sleep(u64),
+    /// The job is over; this is what the job API reports for it.
+    Finished(Box<CommandResponse>),
+}
+
+/// Runs a background job started by `&` up to its next `sleep`, or to its
+/// end. The job is a subshell, with a directory and variables of its own,
+/// and its output goes to its buffer rather than the terminal. Only a
+/// `sleep` at the top of the job's list is handed back to the caller, to
+/// wait for without holding the terminal; a killed job stops before its
+/// next command.
+pub fn step_job(registry: &CommandRegistry, state: &mut TerminalState, job: &Job) -> JobStep {
+    let mut subshell = job.subshell();
+    while job.status() == JobStatus::Running {
+        let Some(segment) = job.body.get(subshell.next) else {
+            job.finish(subshell.exit_code);
+            break;
+        };
+        subshell.next += 1;
+        let run = match segment.connector {
+            Connector::Always => true,
+            Connector::And => subshell.exit_code == 0,
+            Connector::Or => subshell.exit_code != 0,
+        };
+        if !run {
+            continue;
+        }
+        let session = (
+            std::mem::replace(&mut state.cwd, std::mem::take(&mut subshell.cwd)),
+            std::mem::replace(&mut state.env, std::mem::take(&mut subshell.env)),
+            std::mem::replace(&mut state.last_exit_code, subshell.exit_code),
+            std::mem::replace(&mut state.redirected, true),
+        );
+        let sleep = sleep_millis(state, segment);
+        if sleep.is_some() {
+            subshell.exit_code = 0;
+        } else {
+            let mut sink = |chunk| match chunk {
+                OutputChunk::Text(text) => job.write(text),
+                OutputChunk::Clear => job.clear_output(),
+            };
+            let mut output = Output {
+                sink: &mut sink,
+                errors: None,
+                bell: false,
+            };
+            let (ControlFlow::Continue(result) | ControlFlow::Break(result)) =
+                run_segments(registry, state, std::slice::from_ref(segment), &mut output, false);
+            subshell.exit_code = result.exit_code;
+        }
+        subshell.cwd = std::mem::replace(&mut state.cwd, session.0);
+        subshell.env = std::mem::replace(&mut state.env, session.1);
+        (state.last_exit_code, state.redirected) = (session.2, session.3);
+        match sleep {
+            // A clock of the session's own just moves on.
+            Some(millis) if !state.fs.clock().advance(millis) => return JobStep::Sleep(millis),
+            _ => {}
+        }
+    }
+    let exit_code = job.status().exit_code().unwrap_or(EXIT_TERMINATED);
+    let result = CommandResult::ok(job.output()).with_exit_code(exit_code);
+    JobStep::Finished(Box::new(CommandResponse::new(state, result)))
+}
+
+/// Runs the jobs the last command line started to their end, for callers
+/// with nothing to run them alongside.
+pub fn finish_jobs(registry: &CommandRegistry, state: &mut TerminalState) {
+    for job in state.jobs.take_pending() {
+        while let JobStep::Sleep(millis) = step_job(registry, state, &job) {
+            clock::sleep(state.fs.clock(), millis, &state.interrupt);
+        }
+    }
+}
+
+/// How long `segment` sleeps for, if it is a plain `sleep` with valid
+/// durations.
+fn sleep_millis(state: &TerminalState, segment: &Segment) -> Option<u64> {
+    let [stage] = segment.stages.as_slice() else { return None };
+    if stage.compound.is_some() || !stage.redirections.is_empty() || !stage.assignments.is_empty() {
+        return None;
+    }
+    let words = expand_stage(state, &stage.words);
+    let (name, durations) = words.split_first()?;
+    if name != "sleep" || durations.is_empty() {
+        return None;
+    }
+    durations.iter().map(|duration| clock::parse_duration(duration)).sum()
+}
+
+/// Runs an `if`, `for` or `while`. Failing conditions never stop a script,
+/// as under `set -e`; `errexit` applies to the bodies.
+fn run_compound(
+    registry: &CommandRegistry,
+    state: &mut TerminalState,
+    compound: &Compound,
+    errexit: bool,
+) -> CommandResult {
+    collected(|output| {
+        let mut notifications = Vec::new();
+        let mut clear = false;
+        let mut run = |state: &mut TerminalState, segments: &[Segment], output: &mut Output<'_>, errexit| {
+            let flow = run_segments(registry, state, segments, output, errexit);
+            let stopped = flow.is_break();
+            let (ControlFlow::Continue(mut result) | ControlFlow::Break(mut result)) = flow;
+            notifications.append(&mut result.notifications);
+            clear |= result.clear;
+            (result, stopped)
+        };
+        let mut result = match compound {
+            Compound::If { branches, otherwise } => {
+                let mut taken = None;
+                for (condition, body) in branches {
+                    if run(state, condition, output, false).0.success() {
+                        taken = Some(body);
+                        break;
+                    }
+                }
+                match taken.or(otherwise.as_ref()) {
+                    Some(body) => run(state, body, output, errexit).0,
+                    // No branch ran: success, as in sh.
+                    None => CommandResult::empty(),
+                }
+            }
+            Compound::For { variable, words, body } => {
+                let mut result = CommandResult::empty();
+                for value in expand_stage(state, words) {
+                    state.env.insert(variable.clone(), value);
+                    let (last, stopped) = run(state, body, output, errexit);
+                    result = last;
+                    if stopped {
+                        break;
+                    }
+                }
+                result
+            }
+            Compound::Background { body, text } => {
+                let job = state.jobs.start(text.clone(), body.clone(), state.cwd.clone(), state.env.clone());
+                CommandResult::ok(format!("[{}] {}", job.number, job.pid))
+            }
+            Compound::While { condition, body, until } => {
+                let mut result = CommandResult::empty();
+                let mut iterations = 0;
+                while run(state, condition, output, false).0.success() != *until {
+                    if iterations == MAX_LOOP_ITERATIONS {
+                        let keyword = if *until { "until" } else { "while" };
+                        result = CommandResult::error(format!(
+                            "{}: stopped after {} iterations",
+                            keyword, MAX_LOOP_ITERATIONS
+                        ));
+                        break;
+                    }
+                    iterations += 1;
+                    let (last, stopped) = run(state, body, output, errexit);
+                    result = last;
+                    if stopped {
+                        break;
+                    }
+                }
+                result
+            }
+        };
+        // The output went out as it was made; what is left is the last message, if any.
+        output.text(std::mem::take(&mut result.output));
+        output.error(std::mem::take(&mut result.error_output));
+        result.notifications = notifications;
+        result.clear = clear;
+        result
+    })
+}
+
+/// Calls `run` with an [`Output`] that gathers what is sent to it, and puts
+/// that in the result, output and error output apart, for commands that run
+/// command lists of their own.
+fn collected(run: impl FnOnce(&mut Output<'_>) -> CommandResult) -> CommandResult {
+    let mut chunks = Vec::new();
+    let mut errors = Vec::new();
+    let mut sink = |chunk| chunks.push(chunk);
+    let mut output = Output {
+        sink: &mut sink,
+        errors: Some(&mut errors),
+        bell: false,
+    };
+    let mut result = run(&mut output);
+    if output.bell {
+        result.notifications.push(Notification::Bell);
+    }
+    result.output = join_output(chunks);
+    result.error_output = errors.join("\n");
+    result
+}
+
+/// Replaces `!!`, `!N` and `!-N` outside single quotes, unless escaped as
+/// `\!`, with the last, Nth or Nth-from-last history entry. Returns `None`
+/// if the line has none.
+fn expand_history(history: &[String], input: &str) -> Result<Option<String>, String> {
+    let mut expanded = String::new();
+    let mut changed = false;
+    let mut single_quoted = false;
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' && !single_quoted {
+            expanded.push(ch);
+            expanded.extend(chars.next());
+            continue;
+        }
+        if ch == '\'' {
+            single_quoted = !single_quoted;
+        }
+        if ch != '!' || single_quoted {
+            expanded.push(ch);
+            continue;
+        }
+        let (reference, index) = if chars.next_if_eq(&'!').is_some() {
+            ("!!".to_string(), history.len().checked_sub(1))
+        } else {
+            let back = chars.next_if_eq(&'-').is_some();
+            let mut digits = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            if digits.is_empty() {
+                // A lone `!` (as in `!=` or `[!a]`) is an ordinary character.
+                expanded.push('!');
+                if back {
+                    expanded.push('-');
+                }
+                continue;
+            }
+            let number: usize = digits.parse().unwrap_or(usize::MAX);
+            let index = if back {
+                history.len().checked_sub(number)
+            } else {
+                number.checked_sub(1)
+            };
+            (format!("!{}{}", if back { "-" } else { "" }, digits), index)
+        };
+        match index.and_then(|index| history.get(index)) {
+            Some(entry) => expanded.push_str(entry),
+            None => return Err(format!("{}: event not found", reference)),
+        }
+        changed = true;
+    }
+    Ok(changed.then_some(expanded))
+}
+
+fn run_pipeline(
+    registry: &CommandRegistry,
+    state: &mut TerminalState,
+    stages: &[Stage],
+    errexit: bool,
+) -> CommandResult {
+    // Each stage's output becomes the next one's stdin, whatever its status.
+    // Error output is shown rather than piped, and the pipeline's status is
+    // that of the last stage.
+    let mut result = CommandResult::empty();
+    let mut stdin = None;
+    let mut diagnostics = Vec::new();
+    let mut notifications = Vec::new();
+    for (index, stage) in stages.iter().enumerate() {
+        let piped = index + 1 < stages.len();
+        result = run_redirected(registry, state, stage, stdin.take(), piped, errexit);
+        notifications.append(&mut result.notifications);
+        if piped {
+            stdin = Some(std::mem::take(&mut result.output));
+            diagnostics.push(std::mem::take(&mut result.error_output));
+        }
+    }
+    diagnostics.push(result.error_output);
+    diagnostics.retain(|text| !text.is_empty());
+    result.error_output = diagnostics.join("\n");
+    result.notifications = notifications;
+    result
+}
+
+/// Expands a stage's braces, variables and wildcards. A wildcard that
+/// matches nothing is passed on as written, as in sh.
+fn expand_stage(state: &TerminalState, words: &[Word]) -> Vec<String> {
+    let mut variables = state.variables();
+    let mut expanded = Vec::new();
+    for word in words.iter().flat_map(brace::expand) {
+        // A new number for each word, so `echo $RANDOM $RANDOM` prints two.
+        variables.insert("RANDOM".to_string(), state.random().to_string());
+        let matches = word
+            .glob_pattern(&variables)
+            .map(|pattern| glob::expand(&state.fs, &state.cwd, &pattern))
+            .unwrap_or_default();
+        if !matches.is_empty() {
+            expanded.extend(matches);
+            continue;
+        }
+        let text = word.expand(&variables);
+        if word.quoted || !text.is_empty() {
+            expanded.push(text);
+        }
+    }
+    expanded
+}
+
+/// Runs one stage with its redirections: `>` captures a command's output,
+/// `2>` its error output, and `&>` both. Files are opened (and `>` ones
+/// emptied) before the command runs, so one that cannot be opened stops it
+/// from running at all. Output sent to a device such as `/dev/null` is
+/// thrown away.
+fn run_redirected(
+    registry: &CommandRegistry,
+    state: &mut TerminalState,
+    stage: &Stage,
+    mut stdin: Option<String>,
+    piped: bool,
+    errexit: bool,
+) -> CommandResult {
+    // Expand as late as possible, so `export A=1; echo $A` sees the new value.
+    let words = expand_stage(state, &stage.words);
+    let variables = state.variables();
+    let mut outputs = Vec::new();
+    for redirection in &stage.redirections {
+        let target = redirection.target.expand(&variables);
+        let path = resolve_path(&state.cwd, &target);
+        if redirection.kind != RedirectKind::Input && is_device(&path) {
+            outputs.push((redirection.kind, target, None));
+            continue;
+        }
+        let opened = match redirection.kind {
+            RedirectKind::Input => state.fs.read_file(&path).map(|content| stdin = Some(content)),
+            RedirectKind::Output { append: false }
+            | RedirectKind::Error { append: false }
+            | RedirectKind::Both { append: false } => state.fs.write_file(&path, String::new(), false),
+            _ => state.fs.touch(&path, None),
+        };
+        if let Err(error) = opened {
+            return CommandResult::fs_error(&target, error);
+        }
+        if redirection.kind != RedirectKind::Input {
+            outputs.push((redirection.kind, target, Some(path)));
+        }
+    }
+
+    // Assignments alone set variables for good; before a command, only for it.
+    let mut saved = Vec::new();
+    for assignment in &stage.assignments {
+        let value = assignment.value.expand(&state.variables());
+        let previous = state.env.insert(assignment.name.clone(), value);
+        saved.push((&assignment.name, previous));
+    }
+    if words.is_empty() && stage.compound.is_none() {
+        saved.clear();
+    }
+
+    // Colours are for the client's eyes only.
+    let redirected = state.redirected;
+    state.redirected |= piped || !outputs.is_empty();
+    let mut result = match (&stage.compound, words.split_first()) {
+        // The commands inside read nothing piped in.
+        (Some(compound), _) => run_compound(registry, state, compound, errexit),
+        (None, Some((name, args))) => run_stage(registry, state, name, args, stdin),
+        // Nothing but assignments or unset variables: a no-op, as in sh.
+        (None, None) => CommandResult::empty(),
+    };
+    state.redirected = redirected;
+    // The latest first, so a name assigned twice gets its first value back.
+    for (name, previous) in saved.into_iter().rev() {
+        match previous {
+            Some(value) => state.env.insert(name.clone(), value),
+            None => state.env.remove(name),
+        };
+    }
+    // The last redirection of each stream wins.
+    let last = |stream: fn(RedirectKind) -> bool| outputs.iter().rposition(|(kind, ..)| stream(*kind));
+    let stdout = last(|kind| matches!(kind, RedirectKind::Output { .. } | RedirectKind::Both { .. }));
+    let stderr = last(|kind| matches!(kind, RedirectKind::Error { .. } | RedirectKind::Both { .. }));
+    let mut captured: Vec<(usize, String)> = Vec::new();
+    if let Some(index) = stdout {
+        captured.push((index, std::mem::take(&mut result.output)));
+    }
+    if let Some(index) = stderr {
+        let errors = std::mem::take(&mut result.error_output);
+        match captured.first_mut() {
+            // `&>`: the output, then the error output.
+            Some((first, text)) if *first == index => {
+                if !text.is_empty() && !errors.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(&errors);
+            }
+            _ => captured.push((index, errors)),
+        }
+    }
+    for (index, text) in captured {
+        let (_, target, path) = &outputs[index];
+        if let Some(path) = path
+            && !text.is_empty()
+            && let Err(error) = state.fs.write_file(path, text, true)
+        {
+            return CommandResult::fs_error(target, error);
+        }
+    }
+    result
+}
+
+fn run_stage(
+    registry: &CommandRegistry,
+    state: &mut TerminalState,
+    name: &str,
+    args: &[String],
+    stdin: Option<String>,
+) -> CommandResult {
+    match registry.get(name) {
+        Some(command) => {
+            let mut ctx = CommandContext {
+                state,
+                registry,
+                stdin,
+            };
+            command.run(&mut ctx, args)
+        }
+        None => run_fallback(state, name, args, stdin)
+            .unwrap_or_else(|| {
+                CommandResult::error(format!("Unknown command: {}", name)).with_exit_code(EXIT_NOT_FOUND)
+            }),
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn run_fallback(
+    state: &mut TerminalState,
+    name: &str,
+    args: &[String],
+    stdin: Option<String>,
+) -> Option<CommandResult> {
+    crate::scripting::run_vfs_script(state, name, args, stdin.as_deref())
+}
+
+#[cfg(not(feature = "scripting"))]
+fn run_fallback(
+    _state: &mut TerminalState,
+    _name: &str,
+    _args: &[String],
+    _stdin: Option<String>,
+) -> Option<CommandResult> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Shell;
+    use crate::command::EXIT_INTERRUPTED;
+    use std::sync::atomic::Ordering;
+    use std::time::{Duration, Instant};
+
+    /// A shell with `a` and `b` written, differing in their second line.
+    fn shell() -> Shell {
+        let mut shell = Shell::new();
+        shell.exec("printf 'a\\nb\\n' > a; printf 'a\\nc\\n' > b");
+        shell
+    }
+
+    #[test]
+    fn redirects_output_of_a_failing_command() {
+        let mut shell = shell();
+        let response = shell.exec("grep a a b nope > found");
+        assert_eq!(response.exit_code, 2);
+        assert_eq!(response.output, "grep: nope: No such file or directory");
+        assert_eq!(shell.exec("cat found").output, "a:a\nb:a");
+    }
+
+    #[test]
+    fn redirects_errors_apart_from_output() {
+        let mut shell = shell();
+        shell.exec("grep a a nope > found 2> errors");
+        assert_eq!(shell.exec("cat found").output, "a:a");
+        assert_eq!(shell.exec("cat errors").output, "grep: nope: No such file or directory");
+        shell.exec("grep a a nope &> both");
+        assert_eq!(shell.exec("cat both").output, "a:a\ngrep: nope: No such file or directory");
+    }
+
+    #[test]
+    fn discards_output_sent_to_dev_null() {
+        let mut shell = shell();
+        let response = shell.exec("ls nope a 2>/dev/null");
+        assert_eq!(response.exit_code, 2);
+        assert_eq!(response.output, "a");
+        let response = shell.exec("echo hi > /dev/null");
+        assert_eq!((response.exit_code, response.output.as_str()), (0, ""));
+        assert_eq!(shell.exec("cd / && wc -c < /dev/null").output, "0");
+        assert_eq!(shell.exec("ls /dev").exit_code, 2);
+    }
+
+    #[test]
+    fn draws_random_values_from_the_session_seed() {
+        let run = |line: &str| {
+            let mut shell = shell();
+            shell.state.make_deterministic(7, 0);
+            shell.exec(line)
+        };
+        let line = "echo $RANDOM $RANDOM; shuf -i 1-20; wc -c /dev/urandom /dev/null";
+        let first = run(line);
+        assert_eq!(first.exit_code, 0);
+        assert_eq!(first.output, run(line).output);
+        let lines: Vec<&str> = first.output.lines().collect();
+        let numbers: Vec<&str> = lines[0].split(' ').collect();
+        assert_eq!(numbers.len(), 2);
+        assert_ne!(numbers[0], numbers[1]);
+        let mut shuffled: Vec<u32> = lines[1..21].iter().map(|line| line.parse().unwrap()).collect();
+        assert_ne!(shuffled, (1..=20).collect::<Vec<_>>());
+        shuffled.sort();
+        assert_eq!(shuffled, (1..=20).collect::<Vec<_>>());
+        assert_eq!(lines[21..], ["4096 /dev/urandom", "   0 /dev/null", "4096 total"]);
+        assert_eq!(run("shuf -n 2 -e a b c").output.lines().count(), 2);
+    }
+
+    #[test]
+    fn pipes_output_of_a_failing_command() {
+        let mut shell = shell();
+        let response = shell.exec("grep a a nope | wc -l");
+        assert_eq!(response.exit_code, 0);
+        assert_eq!(response.output, "1\ngrep: nope: No such file or directory");
+    }
+
+    #[test]
+    fn redirects_and_pipes_a_diff() {
+        let mut shell = shell();
+        assert_eq!(shell.exec("diff a b > changes.patch").exit_code, 1);
+        assert_eq!(shell.exec("cat changes.patch").output, "--- a\n+++ b\n@@ -1,2 +1,2 @@\n a\n-b\n+c");
+        assert_eq!(shell.exec("diff a b | wc -l").output, "6");
+        let response = shell.exec("diff a nope > changes.patch");
+        assert_eq!(response.exit_code, 2);
+        assert_eq!(response.output, "diff: nope: no such file or directory");
+    }
+
+    #[test]
+    fn redirects_a_cmp_report() {
+        let mut shell = shell();
+        assert_eq!(shell.exec("cmp a b > report").exit_code, 1);
+        assert_eq!(shell.exec("cat report").output, "a b differ: byte 3, line 2");
+        shell.exec("echo a > short");
+        let response = shell.exec("cmp short a > report");
+        assert_eq!(response.output, "cmp: EOF on short after byte 1, line 1");
+        assert_eq!(shell.exec("cat report").output, "");
+    }
+
+    #[test]
+    fn cmp_needs_read_permission() {
+        let mut shell = shell();
+        shell.exec("chmod 000 a");
+        let response = shell.exec("cmp a b");
+        assert_eq!((response.exit_code, response.output.as_str()), (2, "cmp: a: permission denied"));
+        shell.exec("mkdir d");
+        assert_eq!(shell.exec("cmp b d").output, "cmp: d: is a directory");
+        assert_eq!(shell.exec("cmp b nope").output, "cmp: nope: no such file or directory");
+    }
+
+    #[test]
+    fn readers_need_read_permission() {
+        let mut shell = shell();
+        shell.exec("mkdir r; echo secret > r/s; vcs init; vcs commit -m one");
+        shell.exec("chmod 000 a r/s");
+        assert_eq!(shell.exec("diff a b").output, "diff: a: permission denied");
+        assert_eq!(shell.exec("grep -r secret r").output, "grep: r/s: Permission denied");
+        assert_eq!(shell.exec("edit a").output, "edit: a: permission denied");
+        assert_eq!(shell.exec("test -s a").exit_code, 1);
+        shell.exec("chmod 600 a r/s; echo more >> a; vcs commit -m two; chmod 000 a");
+        let first = shell.exec("vcs log").output.lines().last().unwrap()[..7].to_string();
+        let response = shell.exec(&format!("vcs diff {} HEAD", first));
+        assert_eq!(response.output, "vcs: a: permission denied");
+    }
+
+    #[test]
+    fn keeps_status_of_a_redirected_command() {
+        let mut shell = shell();
+        assert_eq!(shell.exec("false > out").exit_code, 1);
+        assert_eq!(shell.exec("grep zz a > out").exit_code, 1);
+        assert_eq!(shell.exec("cat out").output, "");
+    }
+
+    #[test]
+    fn stops_when_interrupted() {
+        let mut shell = shell();
+        let interrupt = shell.state.interrupt.clone();
+        let started = Instant::now();
+        let setter = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            interrupt.store(true, Ordering::Relaxed);
+        });
+        let response = shell.exec("sleep 10; echo done");
+        setter.join().unwrap();
+        assert_eq!(response.exit_code, EXIT_INTERRUPTED);
+        assert_eq!(response.output, "");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(shell.exec("echo a; echo b").exit_code, EXIT_INTERRUPTED);
+    }
+
+    #[test]
+    fn journals_who_made_a_change() {
+        let mut shell = shell();
+        shell.state.fs.set_actor(Some("al".to_string()), Some("s1".to_string()));
+        shell.exec("echo hi > f");
+        let entry = shell.state.fs.journal().since(0).last().unwrap();
+        assert_eq!(entry.path, "/home/user/f");
+        assert_eq!((entry.user.as_deref(), entry.session.as_deref()), (Some("al"), Some("s1")));
+        assert!(shell.exec("journal").output.ends_with("/home/user/f  (al)"));
+    }
+}
@@ -0,0 +1,63 @@
+//! What a new session starts with: dotfiles in the home directory that run
+//! before the first command, as a login shell runs its own. Before them
+//! comes [`PROFILE`], which a server can use to set every session up the
+//! same way; after them, `~/.termwebrc` if a user has one.
+
+use crate::command::CommandResult;
+use crate::path::resolve_path;
+use crate::registry::CommandRegistry;
+use crate::shell::run_script;
+use crate::state::{TerminalState, HOME_DIR};
+
+/// The system-wide startup script, run first.
+pub const PROFILE: &str = "/etc/profile";
+
+/// Every startup script, in the order they run; those missing are skipped.
+/// A leading `~` is the home directory.
+pub const STARTUP_FILES: &[&str] = &[PROFILE, "~/.profile", "~/.bashrc", "~/.termwebrc"];
+
+/// The dotfiles a new home directory gets.
+pub const DOTFILES: &[(&str, &str)] = &[
+    (".profile", "# Runs when a session starts, before ~/.bashrc.\n"),
+    (
+        ".bashrc",
+        "# Runs when a session starts. Aliases and exports set here last.\n\
+         alias ll='ls -l'\n\
+         alias la='ls -a'\n",
+    ),
+];
+
+/// Gives a fresh session its dotfiles, keeping any already there, and
+/// sources the [`STARTUP_FILES`]. The result carries what they printed,
+/// such as a greeting, and the status of the last command run.
+pub fn init_session(registry: &CommandRegistry, state: &mut TerminalState) -> CommandResult {
+    let home = resolve_path(&[], HOME_DIR);
+    for (name, default) in DOTFILES {
+        let mut path = home.clone();
+        path.push(name.to_string());
+        if state.fs.get_node(&path).is_none() {
+            // A filesystem too small for it just starts without.
+            let _ = state.fs.write_file(&path, default.to_string(), false);
+        }
+    }
+
+    let mut outputs = Vec::new();
+    let mut result = CommandResult::empty();
+    for file in STARTUP_FILES {
+        let path = match file.strip_prefix("~/") {
+            Some(name) => resolve_path(&home, name),
+            None => resolve_path(&[], file),
+        };
+        let Ok(script) = state.fs.read_file(&path) else {
+            continue;
+        };
+        result = run_script(registry, state, file, &script);
+        let printed = result.combined_output();
+        if !printed.is_empty() {
+            outputs.push(printed);
+        }
+    }
+    result.output = outputs.join("\n");
+    result.error_output.clear();
+    result
+}
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::clock::FixedClock;
+
+use crate::editor::Editor;
+use crate::fs::{FileSystem, EXECUTE};
+use crate::jobs::JobTable;
+use crate::path::{path_string, resolve_path};
+use crate::rng::SeededRng;
+use crate::vcs::Repository;
+
+/// Command lines kept in the history before the oldest is dropped.
+const MAX_HISTORY: usize = 1000;
+
+/// Terminal width assumed when neither `COLUMNS` nor the client gives one.
+const DEFAULT_COLUMNS: usize = 80;
+
+/// Where a new session starts, and what `cd` and `~` go to.
+pub const HOME_DIR: &str = "/home/user";
+
+pub struct TerminalState {
+    pub fs: FileSystem,
+    pub cwd: Vec<String>,
+    pub dir_stack: Vec<Vec<String>>,
+    /// Where the shell was before the last change of directory; `cd -` goes back there.
+    pub previous_dir: Option<Vec<String>>,
+    /// `vcs` repositories keyed by the directory they were initialised in.
+    pub repositories: BTreeMap<Vec<String>, Repository>,
+    /// Shell variables, set with `export` and expanded from `$NAME`.
+    pub env: BTreeMap<String, String>,
+    /// Command aliases, set with `alias` and expanded before a line is parsed.
+    pub aliases: BTreeMap<String, String>,
+    /// Command lines run so far, oldest first, as `history` lists them.
+    pub history: Vec<String>,
+    /// Exit status of the last command run, expanded from `$?`.
+    pub last_exit_code: i32,
+    /// How many `sh` or `source` scripts are running inside one another.
+    pub script_depth: usize,
+    /// Whether the client renders ANSI colours; see [`crate::color`].
+    pub color: bool,
+    /// How many characters wide the client's terminal is, if it said.
+    pub columns: Option<usize>,
+    /// Set while output goes to a pipe or a file rather than the client.
+    pub(crate) redirected: bool,
+    /// The file open in `edit`, which puts the session in editor mode.
+    pub editor: Option<Editor>,
+    /// Command lists started in the background with `&`.
+    pub jobs: JobTable,
+    /// Set from another thread to stop the command line running, e.g. when
+    /// it outlives a timeout. Hosts give each command line a flag of its own.
+    pub interrupt: Arc<AtomicBool>,
+}
+
+impl Default for TerminalState {
+    fn default() -> Self {
+        TerminalState {
+            fs: FileSystem::with_dirs(&[HOME_DIR]),
+            cwd: resolve_path(&[], HOME_DIR),
+            dir_stack: Vec::new(),
+            previous_dir: None,
+            repositories: BTreeMap::new(),
+            env: BTreeMap::from([("HOME".to_string(), HOME_DIR.to_string())]),
+            aliases: BTreeMap::new(),
+            history: Vec::new(),
+            last_exit_code: 0,
+            script_depth: 0,
+            color: false,
+            columns: None,
+            redirected: false,
+            editor: None,
+            jobs: JobTable::default(),
+            interrupt: Arc::default(),
+        }
+    }
+}
+
+impl TerminalState {
+    /// Makes the session reproducible: its clock starts at `epoch_millis`
+    /// and moves only when `sleep` skips ahead, random values come from a
+    /// generator seeded with `seed`, and job pids count up from 1 within the
+    /// session rather than across the process.
+    pub fn make_deterministic(&mut self, seed: u64, epoch_millis: u64) {
+        self.fs.set_clock(Arc::new(FixedClock::new(epoch_millis)));
+        self.fs.set_rng(Arc::new(SeededRng::new(seed)));
+        self.jobs.number_pids_locally();
+    }
+
+    pub fn cwd_string(&self) -> String {
+        path_string(&self.cwd)
+    }
+
+    /// What `$NAME` expands against: the shell variables plus `$?` and
+    /// `$RANDOM`.
+    pub fn variables(&self) -> BTreeMap<String, String> {
+        let mut variables = self.env.clone();
+        variables.insert("?".to_string(), self.last_exit_code.to_string());
+        variables.insert("RANDOM".to_string(), self.random().to_string());
+        variables
+    }
+
+    /// A number from 0 to 32767, as `$RANDOM` gives, from the session's
+    /// random values.
+    pub fn random(&self) -> u16 {
+        (self.fs.rng().next_u64() % 32768) as u16
+    }
+
+    /// Whether [`TerminalState::interrupt`] has been set.
+    pub fn interrupted(&self) -> bool {
+        self.interrupt.load(Ordering::Relaxed)
+    }
+
+    /// Whether commands should colour what they print right now: the client
+    /// asked for colour, or `CLICOLOR` is set, and `NO_COLOR` is not, and
+    /// the output is going straight to the client.
+    pub fn color_enabled(&self) -> bool {
+        let requested = self.color || self.env.get("CLICOLOR").is_some_and(|value| value != "0");
+        requested && !self.env.contains_key("NO_COLOR") && !self.redirected
+    }
+
+    /// How wide output going to the client can be: `COLUMNS`, else what the
+    /// client reported, else 80. `None` while output is redirected.
+    pub fn terminal_width(&self) -> Option<usize> {
+        if self.redirected {
+            return None;
+        }
+        let columns = self.env.get("COLUMNS").and_then(|columns| columns.parse().ok());
+        Some(columns.or(self.columns).unwrap_or(DEFAULT_COLUMNS))
+    }
+
+    /// Roughly how many bytes the session holds: its files' content and its
+    /// history.
+    pub fn memory_usage(&self) -> usize {
+        self.fs.usage() + self.history.iter().map(String::len).sum::<usize>()
+    }
+
+    pub fn record_history(&mut self, line: &str) {
+        self.history.push(line.to_string());
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+    }
+
+    /// The directory stack as `dirs` shows it: the cwd first, then the saved entries.
+    fn stack_entries(&self) -> Vec<Vec<String>> {
+        let mut entries = vec![self.cwd.clone()];
+        entries.extend(self.dir_stack.iter().cloned());
+        entries
+    }
+
+    fn set_stack_entries(&mut self, mut entries: Vec<Vec<String>>) -> Result<(), String> {
+        let cwd = entries.remove(0);
+        if !matches!(self.fs.is_dir(&cwd), Ok(true)) {
+            return Err(format!("{}: Not a directory", path_string(&cwd)));
+        }
+        self.set_cwd(cwd);
+        self.dir_stack = entries;
+        Ok(())
+    }
+
+    /// Moves to `cwd`, remembering where the shell was for `cd -`.
+    pub fn set_cwd(&mut self, cwd: Vec<String>) {
+        if cwd != self.cwd {
+            self.previous_dir = Some(std::mem::replace(&mut self.cwd, cwd));
+        }
+    }
+
+    pub fn pushd(&mut self, arg: Option<&str>) -> Result<(), String> {
+        let mut entries = self.stack_entries();
+        match arg {
+            None => {
+                if entries.len() < 2 {
+                    return Err("pushd: no other directory".to_string());
+                }
+                entries.swap(0, 1);
+            }
+            Some(arg) if arg.starts_with('+') || arg.starts_with('-') => {
+                let index = stack_index("pushd", arg, entries.len())?;
+                entries.rotate_left(index);
+            }
+            Some(arg) => {
+                let path = resolve_path(&self.cwd, arg);
+                match self.fs.is_dir(&path) {
+                    Ok(true) if !self.fs.permitted(&path, EXECUTE) => {
+                        return Err(format!("pushd: {}: Permission denied", arg));
+                    }
+                    Ok(true) => entries.insert(0, path),
+                    Ok(false) => return Err(format!("pushd: {}: Not a directory", arg)),
+                    Err(_) => return Err(format!("pushd: {}: No such file or directory", arg)),
+                }
+            }
+        }
+        self.set_stack_entries(entries)
+    }
+
+    pub fn popd(&mut self, arg: Option<&str>) -> Result<(), String> {
+        if self.dir_stack.is_empty() {
+            return Err("popd: directory stack empty".to_string());
+        }
+        let mut entries = self.stack_entries();
+        let index = match arg {
+            None => 0,
+            Some(arg) => stack_index("popd", arg, entries.len())?,
+        };
+        entries.remove(index);
+        self.set_stack_entries(entries)
+    }
+
+    pub fn dirs(&self, verbose: bool) -> String {
+        let entries = self.stack_entries();
+        if verbose {
+            entries
+                .iter()
+                .enumerate()
+                .map(|(index, entry)| format!("{:2}  {}", index, path_string(entry)))
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else {
+            entries
+                .iter()
+                .map(|entry| path_string(entry))
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+    }
+}
+
+/// Parses a `+N` / `-N` stack reference, counting from the left or right of the `dirs` list.
+fn stack_index(command: &str, arg: &str, len: usize) -> Result<usize, String> {
+    let invalid = || format!("{}: {}: invalid argument", command, arg);
+    let (from_right, digits) = match arg.split_at(1) {
+        ("+", digits) => (false, digits),
+        ("-", digits) => (true, digits),
+        _ => return Err(invalid()),
+    };
+    let offset: usize = digits.parse().map_err(|_| invalid())?;
+    if offset >= len {
+        return Err(format!("{}: {}: directory stack index out of range", command, arg));
+    }
+    Ok(if from_right { len - 1 - offset } else { offset })
+}
@@ -0,0 +1,446 @@
+//! Turns command line text into tokens for the parser: words, with their
+//! quoting and variable references kept apart, and operators. Aliases are
+//! expanded on the text before it is read.
+
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::parser::{ParseError, RedirectKind};
+
+/// One piece of a [`Word`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WordPart {
+    /// Unquoted text; `*`, `?` and `[` in it are glob patterns.
+    Literal(String),
+    /// Quoted text, always taken as-is.
+    Quoted(String),
+    /// A `$NAME`, `${NAME}` or `$?` reference, or a leading `~` for
+    /// `$HOME`, expanded when its command runs. An unquoted value may itself
+    /// hold glob patterns.
+    Variable { name: String, quoted: bool },
+}
+
+/// A word as written, before variable expansion.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+    /// Whether any of the word was quoted. A quoted word survives expanding
+    /// to nothing, so `""` is still an argument.
+    pub quoted: bool,
+}
+
+impl Word {
+    pub(crate) fn push(&mut self, ch: char, quoted: bool) {
+        match (self.parts.last_mut(), quoted) {
+            (Some(WordPart::Literal(text)), false) | (Some(WordPart::Quoted(text)), true) => {
+                text.push(ch)
+            }
+            (_, false) => self.parts.push(WordPart::Literal(ch.to_string())),
+            (_, true) => self.parts.push(WordPart::Quoted(ch.to_string())),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.parts.is_empty() && !self.quoted
+    }
+
+    /// The word's text with each variable replaced by its value in `env`;
+    /// unset variables expand to nothing.
+    pub fn expand(&self, env: &BTreeMap<String, String>) -> String {
+        let mut text = String::new();
+        for part in &self.parts {
+            match part {
+                WordPart::Literal(literal) | WordPart::Quoted(literal) => text.push_str(literal),
+                WordPart::Variable { name, .. } => text.extend(env.get(name).map(String::as_str)),
+            }
+        }
+        text
+    }
+
+    /// The expanded word as a glob pattern, if any unquoted part of it holds
+    /// `*`, `?` or `[`. Characters that came from quotes are escaped with `\`
+    /// so they only match themselves.
+    pub fn glob_pattern(&self, env: &BTreeMap<String, String>) -> Option<String> {
+        let mut pattern = String::new();
+        let mut wild = false;
+        for part in &self.parts {
+            let (text, quoted) = match part {
+                WordPart::Literal(text) => (text.as_str(), false),
+                WordPart::Quoted(text) => (text.as_str(), true),
+                WordPart::Variable { name, quoted } => {
+                    (env.get(name).map(String::as_str).unwrap_or(""), *quoted)
+                }
+            };
+            for ch in text.chars() {
+                let special = matches!(ch, '*' | '?' | '[' | ']' | '\\');
+                if special && quoted {
+                    pattern.push('\\');
+                }
+                wild |= !quoted && matches!(ch, '*' | '?' | '[');
+                pattern.push(ch);
+            }
+        }
+        wild.then_some(pattern)
+    }
+}
+
+/// Whether `name` can name a shell variable: a letter or `_`, then letters,
+/// digits and `_`.
+pub fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// Whether `name` can name an alias: anything a plain unquoted word can hold,
+/// except `/`, so a path is never taken for one.
+pub fn is_alias_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|ch| is_plain_char(ch) && ch != '/')
+}
+
+/// Characters that can make up a word with no quoting or expansion in it.
+fn is_plain_char(ch: char) -> bool {
+    !ch.is_whitespace() && !matches!(ch, '\'' | '"' | '\\' | '$' | '|' | ';' | '&' | '<' | '>' | '=')
+}
+
+/// Replaces each alias name in command position (at the start, after `|`,
+/// `;`, `&`, `&&`, `||` or a newline, or after a word such as `then` that a
+/// command follows) with its value. Only a plain unquoted word is looked up,
+/// so quoting the name, or escaping it as `\ls`, runs the command itself. An
+/// alias is not expanded again inside its own value, so `alias ls='ls -a'`
+/// works and aliases that refer to each other stop instead of looping.
+pub fn expand_aliases(input: &str, aliases: &BTreeMap<String, String>) -> String {
+    expand_aliases_within(input, aliases, &mut Vec::new())
+}
+
+fn expand_aliases_within(input: &str, aliases: &BTreeMap<String, String>, active: &mut Vec<String>) -> String {
+    let mut expanded = String::new();
+    let mut command_position = true;
+    let mut word_start = true;
+    let mut quote: Option<char> = None;
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if let Some(open) = quote {
+            if ch == open {
+                quote = None;
+            }
+            expanded.push(ch);
+            if ch == '\\' && open == '"' {
+                expanded.extend(chars.next());
+            }
+            continue;
+        }
+        if ch == '#' && word_start {
+            // A comment, copied as is; its quotes are not quotes.
+            expanded.push(ch);
+            while let Some(next) = chars.next_if(|&next| next != '\n') {
+                expanded.push(next);
+            }
+            continue;
+        }
+        if command_position && is_plain_char(ch) {
+            let mut word = ch.to_string();
+            while let Some(next) = chars.next_if(|&next| is_plain_char(next)) {
+                word.push(next);
+            }
+            // A word that carries on into quotes, an escape or `$` is not a plain name.
+            let plain = chars.peek().is_none_or(|&next| !matches!(next, '\'' | '"' | '\\' | '$' | '='));
+            // These are followed by another command, as in `then ls`.
+            command_position =
+                plain && matches!(word.as_str(), "if" | "then" | "elif" | "else" | "while" | "until" | "do");
+            word_start = false;
+            match aliases.get(&word) {
+                Some(value) if plain && !active.contains(&word) => {
+                    active.push(word);
+                    expanded.push_str(&expand_aliases_within(value, aliases, active));
+                    active.pop();
+                }
+                _ => expanded.push_str(&word),
+            }
+            continue;
+        }
+        match ch {
+            '\'' | '"' => {
+                quote = Some(ch);
+                command_position = false;
+            }
+            '\\' => {
+                // The escaped character is copied with it, whatever it is.
+                expanded.push(ch);
+                expanded.extend(chars.next());
+                command_position = false;
+                word_start = false;
+                continue;
+            }
+            '|' | ';' | '&' | '\n' => command_position = true,
+            ch if ch.is_whitespace() => {}
+            _ => command_position = false,
+        }
+        word_start = ch.is_whitespace() || matches!(ch, '|' | ';' | '&' | '<' | '>');
+        expanded.push(ch);
+    }
+    expanded
+}
+
+/// A piece of a command line, as the parser sees it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Token {
+    Word(Word),
+    /// `|`, `||`, `&&`, `&`, `;` or a newline.
+    Operator(&'static str),
+    Redirect(RedirectKind),
+}
+
+/// The tokens of `input`, and the offset in it just past each one.
+pub(crate) fn lex(input: &str) -> Result<(Vec<Token>, Vec<usize>), ParseError> {
+    let mut tokens = Vec::new();
+    let mut ends = Vec::new();
+    let mut current = Word::default();
+    let mut quote: Option<char> = None;
+    let mut chars = input.chars().peekable();
+
+    loop {
+        if ends.len() < tokens.len() {
+            let unread: usize = chars.clone().map(char::len_utf8).sum();
+            ends.resize(tokens.len(), input.len() - unread);
+        }
+        let Some(ch) = chars.next() else { break };
+        if let Some(active) = quote {
+            if ch == active {
+                quote = None;
+            } else if ch == '\\' && active == '"' {
+                // Only these are escaped in double quotes; elsewhere the `\` stays.
+                match chars.next_if(|&next| matches!(next, '$' | '`' | '"' | '\\' | '\n')) {
+                    Some('\n') => {}
+                    Some(escaped) => current.push(escaped, true),
+                    None => current.push(ch, true),
+                }
+            } else if ch == '$' && active == '"' {
+                push_variable(&mut current, &mut chars, true).map_err(ParseError::Syntax)?;
+            } else {
+                current.push(ch, true);
+            }
+            continue;
+        }
+
+        let token = match ch {
+            '\\' => {
+                match chars.next() {
+                    // A line continuation: the two lines are one.
+                    Some('\n') => {}
+                    // Taken as written, as if quoted, so `\*` is not a pattern.
+                    Some(escaped) => current.push(escaped, true),
+                    None => return Err(ParseError::Incomplete("Unfinished escape".to_string())),
+                }
+                continue;
+            }
+            '\'' | '"' => {
+                quote = Some(ch);
+                current.quoted = true;
+                continue;
+            }
+            '$' => {
+                push_variable(&mut current, &mut chars, false).map_err(ParseError::Syntax)?;
+                continue;
+            }
+            '#' if current.is_empty() => {
+                while chars.next_if(|&next| next != '\n').is_some() {}
+                continue;
+            }
+            // `~` alone or before a `/` at the start of a word is the home directory.
+            '~' if current.is_empty() && chars.peek().is_none_or(|&next| next == '/' || ends_word(next)) => {
+                current.parts.push(WordPart::Variable {
+                    name: "HOME".to_string(),
+                    quoted: true,
+                });
+                continue;
+            }
+            '\n' => Token::Operator("\n"),
+            c if c.is_whitespace() => {
+                end_word(&mut tokens, &mut current);
+                continue;
+            }
+            '<' | '>' => {
+                // `2>` only when the 2 stands alone, so `a2>f` is `a2 > f`.
+                let fd2 = ch == '>'
+                    && !current.quoted
+                    && matches!(current.parts.as_slice(), [WordPart::Literal(text)] if text == "2");
+                if fd2 {
+                    current = Word::default();
+                }
+                let append = ch == '>' && chars.next_if_eq(&'>').is_some();
+                Token::Redirect(match ch {
+                    '<' => RedirectKind::Input,
+                    _ if fd2 => RedirectKind::Error { append },
+                    _ => RedirectKind::Output { append },
+                })
+            }
+            '&' if chars.next_if_eq(&'>').is_some() => {
+                let append = chars.next_if_eq(&'>').is_some();
+                Token::Redirect(RedirectKind::Both { append })
+            }
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Operator("||"),
+            '|' => Token::Operator("|"),
+            '&' if chars.next_if_eq(&'&').is_some() => Token::Operator("&&"),
+            // `>&` is left to be read as a file name, as it always has been.
+            '&' if !current.is_empty() || !matches!(tokens.last(), Some(Token::Redirect(_))) => {
+                Token::Operator("&")
+            }
+            ';' => Token::Operator(";"),
+            _ => {
+                current.push(ch, false);
+                continue;
+            }
+        };
+        end_word(&mut tokens, &mut current);
+        tokens.push(token);
+    }
+
+    if quote.is_some() {
+        return Err(ParseError::Incomplete("Unclosed quote".to_string()));
+    }
+    end_word(&mut tokens, &mut current);
+    ends.resize(tokens.len(), input.len());
+    Ok((tokens, ends))
+}
+
+/// Whether `ch` ends an unquoted word.
+fn ends_word(ch: char) -> bool {
+    ch.is_whitespace() || matches!(ch, '|' | '&' | ';' | '<' | '>')
+}
+
+/// Finishes the word being read, if there is one.
+fn end_word(tokens: &mut Vec<Token>, current: &mut Word) {
+    if !current.is_empty() {
+        tokens.push(Token::Word(std::mem::take(current)));
+    }
+}
+
+/// Reads the variable reference after a `$`. A `$` not followed by a name
+/// or `?` is an ordinary character.
+fn push_variable(
+    word: &mut Word,
+    chars: &mut Peekable<Chars<'_>>,
+    quoted: bool,
+) -> Result<(), String> {
+    let mut name = String::new();
+    if chars.next_if_eq(&'{').is_some() {
+        loop {
+            match chars.next() {
+                Some('}') => break,
+                Some(ch) => name.push(ch),
+                None => return Err("${: bad substitution".to_string()),
+            }
+        }
+        if !is_variable_name(&name) && name != "?" {
+            return Err(format!("${{{}}}: bad substitution", name));
+        }
+    } else if chars.next_if_eq(&'?').is_some() {
+        name.push('?');
+    } else {
+        while let Some(ch) = chars.next_if(|&ch| ch.is_ascii_alphanumeric() || ch == '_') {
+            name.push(ch);
+        }
+    }
+    if is_variable_name(&name) || name == "?" {
+        word.parts.push(WordPart::Variable { name, quoted });
+    } else {
+        // A bare `$`, or `$1` and friends: positional parameters don't exist here.
+        word.push('$', quoted);
+        name.chars().for_each(|ch| word.push(ch, quoted));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{lex, Token, Word, WordPart};
+
+    /// `word` as a compact string: quoted parts in `[…]` and variables as
+    /// `$NAME`, in `"…"` when quoted.
+    pub(crate) fn render_word(word: &Word) -> String {
+        let mut text = String::new();
+        for part in &word.parts {
+            match part {
+                WordPart::Literal(literal) => text.push_str(literal),
+                WordPart::Quoted(quoted) => text.push_str(&format!("[{}]", quoted)),
+                WordPart::Variable { name, quoted: false } => text.push_str(&format!("${}", name)),
+                WordPart::Variable { name, quoted: true } => text.push_str(&format!("\"${}\"", name)),
+            }
+        }
+        if text.is_empty() && word.quoted {
+            text.push_str("[]");
+        }
+        text
+    }
+
+    fn render(input: &str) -> Vec<String> {
+        let (tokens, _) = lex(input).unwrap_or_else(|error| panic!("{:?}: {}", input, error));
+        tokens
+            .iter()
+            .map(|token| match token {
+                Token::Word(word) => render_word(word),
+                Token::Operator(operator) => operator.to_string(),
+                Token::Redirect(kind) => kind.operator().to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lexes_words_and_quotes() {
+        let cases: &[(&str, &[&str])] = &[
+            ("echo hello  world", &["echo", "hello", "world"]),
+            ("echo 'a b'", &["echo", "[a b]"]),
+            ("echo \"a b\"", &["echo", "[a b]"]),
+            ("echo ''", &["echo", "[]"]),
+            ("echo a'b'\"c\"", &["echo", "a[bc]"]),
+            ("echo '$HOME'", &["echo", "[$HOME]"]),
+            ("echo $HOME ${USER}x", &["echo", "$HOME", "$USERx"]),
+            ("echo \"at $HOME\"", &["echo", "[at ]\"$HOME\""]),
+            ("echo ~/notes", &["echo", "\"$HOME\"/notes"]),
+            ("echo 'a|b' \"c;d\"", &["echo", "[a|b]", "[c;d]"]),
+            ("echo hi # a comment", &["echo", "hi"]),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(render(input), *expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn lexes_escapes() {
+        let cases: &[(&str, &[&str])] = &[
+            ("echo a\\ b", &["echo", "a[ ]b"]),
+            ("echo \\$HOME", &["echo", "[$]HOME"]),
+            ("echo \\'quoted\\'", &["echo", "[']quoted[']"]),
+            ("echo a\\|b", &["echo", "a[|]b"]),
+            ("echo \\#not", &["echo", "[#]not"]),
+            ("echo \"a\\\"b\"", &["echo", "[a\"b]"]),
+            ("echo \"\\$HOME\"", &["echo", "[$HOME]"]),
+            ("echo \"a\\nb\"", &["echo", "[a\\nb]"]),
+            ("echo 'a\\b'", &["echo", "[a\\b]"]),
+            ("echo a\\\nb", &["echo", "ab"]),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(render(input), *expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn lexes_operators_and_redirections() {
+        let cases: &[(&str, &[&str])] = &[
+            ("a|b", &["a", "|", "b"]),
+            ("a && b || c", &["a", "&&", "b", "||", "c"]),
+            ("a; b\nc &", &["a", ";", "b", "\n", "c", "&"]),
+            ("cat <in >out", &["cat", "<", "in", ">", "out"]),
+            ("cat >>log 2>err 2>>errs", &["cat", ">>", "log", "2>", "err", "2>>", "errs"]),
+            ("cat &>all &>>more", &["cat", "&>", "all", "&>>", "more"]),
+            ("echo a2>b", &["echo", "a2", ">", "b"]),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(render(input), *expected, "{:?}", input);
+        }
+    }
+}
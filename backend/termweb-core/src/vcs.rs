@@ -0,0 +1,283 @@
+//! A tiny snapshot-based version control system over the virtual filesystem.
+//!
+//! Each commit stores a full copy of the repository's directory node; diffs
+//! and status are computed by walking two snapshots side by side. Copies
+//! share file content with the tree, but are charged against the
+//! filesystem's capacity as if they did not; see [`FileSystem::hold`].
+
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::fs::{Content, FileSystem, FsError, Node, READ};
+use crate::path::path_string;
+
+/// Name of the metadata directory `vcs` hides from snapshots.
+pub const META_DIR: &str = ".vcs";
+
+pub struct Commit {
+    pub id: String,
+    pub message: String,
+    pub parent: Option<usize>,
+    pub tree: Node,
+}
+
+#[derive(Default)]
+pub struct Repository {
+    pub commits: Vec<Commit>,
+    pub head: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl Change {
+    pub fn code(self) -> char {
+        match self {
+            Change::Added => 'A',
+            Change::Modified => 'M',
+            Change::Deleted => 'D',
+        }
+    }
+}
+
+impl Repository {
+    pub fn commit(&mut self, message: &str, tree: Node) -> &Commit {
+        let mut hasher = DefaultHasher::new();
+        message.hash(&mut hasher);
+        self.commits.len().hash(&mut hasher);
+        hash_node(&tree, &mut hasher);
+        let commit = Commit {
+            id: format!("{:016x}", hasher.finish())[..7].to_string(),
+            message: message.to_string(),
+            parent: self.head,
+            tree,
+        };
+        self.commits.push(commit);
+        self.head = Some(self.commits.len() - 1);
+        &self.commits[self.commits.len() - 1]
+    }
+
+    pub fn head_commit(&self) -> Option<&Commit> {
+        self.head.map(|index| &self.commits[index])
+    }
+
+    /// Finds a commit by id prefix, or `HEAD`.
+    pub fn find(&self, reference: &str) -> Result<usize, String> {
+        if reference == "HEAD" {
+            return self.head.ok_or_else(|| "vcs: no commits yet".to_string());
+        }
+        let matches: Vec<usize> = self
+            .commits
+            .iter()
+            .enumerate()
+            .filter(|(_, commit)| commit.id.starts_with(reference))
+            .map(|(index, _)| index)
+            .collect();
+        match matches.as_slice() {
+            [index] => Ok(*index),
+            [] => Err(format!("vcs: unknown revision '{}'", reference)),
+            _ => Err(format!("vcs: ambiguous revision '{}'", reference)),
+        }
+    }
+
+    /// Removes every file holding `content` from the stored commits, so what
+    /// `shred` destroyed cannot be checked out again. Returns the files
+    /// removed, for their charge to be given back.
+    pub fn purge(&mut self, content: &Content) -> Vec<Node> {
+        let mut removed = Vec::new();
+        for commit in &mut self.commits {
+            purge_node(&mut commit.tree, content, &mut removed);
+        }
+        removed
+    }
+
+    /// Walks from HEAD back through parents.
+    pub fn history(&self) -> Vec<&Commit> {
+        let mut history = Vec::new();
+        let mut current = self.head;
+        while let Some(index) = current {
+            let commit = &self.commits[index];
+            history.push(commit);
+            current = commit.parent;
+        }
+        history
+    }
+}
+
+fn purge_node(node: &mut Node, content: &Content, removed: &mut Vec<Node>) {
+    if let Node::Dir { children, .. } = node {
+        let names: Vec<String> = children
+            .iter()
+            .filter(|(_, child)| matches!(child, Node::File { content: held, .. } if held == content))
+            .map(|(name, _)| name.clone())
+            .collect();
+        removed.extend(names.iter().filter_map(|name| children.remove(name)));
+        for child in children.values_mut() {
+            purge_node(child, content, removed);
+        }
+    }
+}
+
+/// Copies the working tree at `root` for storage, leaving out the metadata
+/// directory. Like reading them, it needs every directory and file in it to
+/// be readable.
+pub fn snapshot(fs: &FileSystem, root: &[String]) -> Result<Node, String> {
+    let node = fs
+        .get_node(root)
+        .ok_or_else(|| "vcs: repository directory is missing".to_string())?;
+    copy_readable(fs, node, &mut root.to_vec(), true)
+}
+
+fn copy_readable(fs: &FileSystem, node: &Node, path: &mut Vec<String>, top: bool) -> Result<Node, String> {
+    if !matches!(node, Node::Symlink { .. }) && !fs.permitted(path, READ) {
+        return Err(format!("vcs: {}: {}", path_string(path), FsError::PermissionDenied));
+    }
+    let Node::Dir { children, meta } = node else {
+        return Ok(node.clone());
+    };
+    let mut copied = BTreeMap::new();
+    for (name, child) in children {
+        if top && name == META_DIR {
+            continue;
+        }
+        path.push(name.clone());
+        let child = copy_readable(fs, child, path, false);
+        path.pop();
+        copied.insert(name.clone(), child?);
+    }
+    Ok(Node::Dir {
+        children: copied,
+        meta: *meta,
+    })
+}
+
+/// Lists file-level changes needed to go from `old` to `new`, sorted by path.
+pub fn changes(old: &Node, new: &Node) -> Vec<(Change, String)> {
+    let mut changes = Vec::new();
+    collect_changes(Some(old), Some(new), String::new(), &mut changes);
+    changes
+}
+
+fn collect_changes(
+    old: Option<&Node>,
+    new: Option<&Node>,
+    prefix: String,
+    changes: &mut Vec<(Change, String)>,
+) {
+    let empty = BTreeMap::new();
+    match (old, new) {
+        (Some(Node::File { content: a, .. }), Some(Node::File { content: b, .. })) => {
+            if a != b {
+                changes.push((Change::Modified, prefix));
+            }
+        }
+        (Some(Node::Symlink { target: a, .. }), Some(Node::Symlink { target: b, .. })) => {
+            if a != b {
+                changes.push((Change::Modified, prefix));
+            }
+        }
+        (Some(Node::File { .. } | Node::Symlink { .. }), Some(Node::File { .. } | Node::Symlink { .. })) => {
+            changes.push((Change::Modified, prefix));
+        }
+        (Some(Node::File { .. } | Node::Symlink { .. }), None) => changes.push((Change::Deleted, prefix)),
+        (None, Some(Node::File { .. } | Node::Symlink { .. })) => changes.push((Change::Added, prefix)),
+        (Some(Node::File { .. } | Node::Symlink { .. }), Some(dir @ Node::Dir { .. })) => {
+            changes.push((Change::Deleted, prefix.clone()));
+            collect_changes(None, Some(dir), prefix, changes);
+        }
+        (Some(dir @ Node::Dir { .. }), Some(Node::File { .. } | Node::Symlink { .. })) => {
+            collect_changes(Some(dir), None, prefix.clone(), changes);
+            changes.push((Change::Added, prefix));
+        }
+        _ => {
+            let old_children = dir_children(old).unwrap_or(&empty);
+            let new_children = dir_children(new).unwrap_or(&empty);
+            let mut names: Vec<&String> = old_children.keys().chain(new_children.keys()).collect();
+            names.sort();
+            names.dedup();
+            for name in names {
+                if name == META_DIR {
+                    continue;
+                }
+                let path = if prefix.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}/{}", prefix, name)
+                };
+                collect_changes(old_children.get(name), new_children.get(name), path, changes);
+            }
+        }
+    }
+}
+
+/// The node at `path`, relative to `tree` and as [`changes`] gives it.
+pub fn lookup<'a>(tree: &'a Node, path: &str) -> Option<&'a Node> {
+    path.split('/').try_fold(tree, |node, name| dir_children(Some(node))?.get(name))
+}
+
+fn dir_children(node: Option<&Node>) -> Option<&BTreeMap<String, Node>> {
+    match node {
+        Some(Node::Dir { children, .. }) => Some(children),
+        _ => None,
+    }
+}
+
+fn hash_node(node: &Node, hasher: &mut DefaultHasher) {
+    match node {
+        Node::Dir { children, .. } => {
+            for (name, child) in children {
+                name.hash(hasher);
+                hash_node(child, hasher);
+            }
+        }
+        Node::File { content, .. } => content.hash(hasher),
+        Node::Symlink { target, .. } => {
+            "->".hash(hasher);
+            target.hash(hasher);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Shell;
+
+    #[test]
+    fn shred_removes_committed_copies() {
+        let mut shell = Shell::new();
+        shell.exec("echo topsecret > secret; echo x > notes; vcs init; vcs commit -m first");
+        shell.exec("echo y >> notes; vcs commit -m second");
+        let first = shell.exec("vcs log").output.lines().last().unwrap()[..7].to_string();
+        let response = shell.exec("shred -u secret");
+        assert_eq!(response.output, "shred: secret: removed 2 copies kept by vcs");
+        shell.exec(&format!("vcs checkout {}", first));
+        assert_eq!(shell.exec("cat notes").output, "x");
+        assert_eq!(shell.exec("test -e secret").exit_code, 1);
+    }
+
+    #[test]
+    fn commit_needs_readable_files() {
+        let mut shell = Shell::new();
+        shell.exec("echo x > notes; chmod 000 notes; vcs init");
+        let response = shell.exec("vcs commit -m first");
+        assert_eq!(response.exit_code, 1);
+        assert_eq!(response.output, "vcs: /home/user/notes: permission denied");
+    }
+
+    #[test]
+    fn commits_count_towards_capacity() {
+        let mut shell = Shell::new();
+        shell.exec("mkdir project; cd project; printf '%s' 0123456789 > notes; vcs init");
+        let used = shell.state.fs.usage();
+        shell.state.fs.set_capacity(Some(used + 12));
+        assert_eq!(shell.exec("vcs commit -m first").exit_code, 0);
+        assert_eq!(shell.state.fs.usage(), used + 10);
+        assert_eq!(shell.exec("echo more > other").exit_code, 1);
+        shell.exec("shred -u notes");
+        assert_eq!(shell.state.fs.usage(), used - 10);
+    }
+}
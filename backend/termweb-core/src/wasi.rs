@@ -0,0 +1,252 @@
+//! Real programs compiled to WASI, run against the virtual filesystem.
+//!
+//! wasmtime-wasi only knows how to expose host directories, so each run copies
+//! the virtual tree into a private temporary directory, preopens it as `/`
+//! (and the current directory as `.`), and reads the tree back once the
+//! program exits. Nothing outside that temporary directory is reachable.
+
+use std::path::{Path, PathBuf};
+
+use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+use wasmtime_wasi::pipe::{MemoryInputPipe, MemoryOutputPipe};
+use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+use wasmtime_wasi::{DirPerms, FilePerms, I32Exit, WasiCtxBuilder};
+
+use crate::command::{Command, CommandContext, CommandResult};
+use crate::fs::{Content, FileSystem, Metadata, Node, DEFAULT_DIR_MODE, DEFAULT_FILE_MODE};
+use crate::registry::CommandRegistry;
+
+const FUEL_PER_RUN: u64 = 5_000_000_000;
+const MAX_MEMORY_BYTES: usize = 256 << 20;
+const MAX_OUTPUT_BYTES: usize = 4 << 20;
+
+pub struct WasiCommand {
+    name: &'static str,
+    help: &'static str,
+    engine: Engine,
+    module: Module,
+}
+
+struct Host {
+    wasi: WasiP1Ctx,
+    limits: StoreLimits,
+}
+
+impl WasiCommand {
+    pub fn new(name: &str, bytes: &[u8]) -> Result<Self, String> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|err| err.to_string())?;
+        let module = Module::new(&engine, bytes).map_err(|err| format!("{}: {}", name, err))?;
+        // Commands live for the whole process, so leaking the names is bounded.
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let help = Box::leak(format!("{} [args...]", name).into_boxed_str());
+        Ok(WasiCommand {
+            name,
+            help,
+            engine,
+            module,
+        })
+    }
+
+    fn execute(
+        &self,
+        fs: &mut FileSystem,
+        cwd: &[String],
+        args: &[String],
+        stdin: &str,
+    ) -> Result<(i32, String, String), String> {
+        let sandbox = tempfile::tempdir().map_err(|err| err.to_string())?;
+        write_tree(fs.root(), sandbox.path()).map_err(|err| err.to_string())?;
+        let host_cwd: PathBuf = cwd
+            .iter()
+            .fold(sandbox.path().to_path_buf(), |path, part| path.join(part));
+
+        let stdout = MemoryOutputPipe::new(MAX_OUTPUT_BYTES);
+        let stderr = MemoryOutputPipe::new(MAX_OUTPUT_BYTES);
+        let mut argv = vec![self.name.to_string()];
+        argv.extend(args.iter().cloned());
+        let mut builder = WasiCtxBuilder::new();
+        builder
+            .args(&argv)
+            .env("PWD", crate::path::path_string(cwd))
+            .stdin(MemoryInputPipe::new(stdin.to_string()))
+            .stdout(stdout.clone())
+            .stderr(stderr.clone());
+        builder
+            .preopened_dir(sandbox.path(), "/", DirPerms::all(), FilePerms::all())
+            .and_then(|builder| {
+                builder.preopened_dir(&host_cwd, ".", DirPerms::all(), FilePerms::all())
+            })
+            .map_err(|err| err.to_string())?;
+
+        let host = Host {
+            wasi: builder.build_p1(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY_BYTES)
+                .build(),
+        };
+        let code = self.run_module(host)?;
+
+        let now = fs.clock().now_millis();
+        let root =
+            read_tree(sandbox.path(), Some(fs.root()), now).map_err(|err| err.to_string())?;
+        fs.replace_root(root);
+
+        let text = |pipe: &MemoryOutputPipe| {
+            String::from_utf8_lossy(&pipe.contents()).trim_end_matches('\n').to_string()
+        };
+        Ok((code, text(&stdout), text(&stderr)))
+    }
+
+    fn run_module(&self, host: Host) -> Result<i32, String> {
+        let mut store = Store::new(&self.engine, host);
+        store.limiter(|host| &mut host.limits);
+        store
+            .set_fuel(FUEL_PER_RUN)
+            .map_err(|err| err.to_string())?;
+        let mut linker: Linker<Host> = Linker::new(&self.engine);
+        preview1::add_to_linker_sync(&mut linker, |host| &mut host.wasi)
+            .map_err(|err| err.to_string())?;
+        linker
+            .module(&mut store, "", &self.module)
+            .map_err(|err| err.to_string())?;
+        let start = linker
+            .get_default(&mut store, "")
+            .and_then(|func| func.typed::<(), ()>(&store))
+            .map_err(|err| err.to_string())?;
+        match start.call(&mut store, ()) {
+            Ok(()) => Ok(0),
+            Err(err) => match err.downcast_ref::<I32Exit>() {
+                Some(exit) => Ok(exit.0),
+                None => match err.downcast_ref::<wasmtime::Trap>() {
+                    Some(wasmtime::Trap::OutOfFuel) => Err("CPU budget exceeded".to_string()),
+                    _ => Err(err.to_string()),
+                },
+            },
+        }
+    }
+}
+
+impl Command for WasiCommand {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn help(&self) -> &'static str {
+        self.help
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let state = &mut *ctx.state;
+        let stdin = ctx.stdin.as_deref().unwrap_or_default();
+        // wasmtime-wasi drives its sync API through a Tokio runtime of its own,
+        // which cannot be entered from a thread already inside one.
+        let result = std::thread::scope(|scope| {
+            scope
+                .spawn(|| self.execute(&mut state.fs, &state.cwd, args, stdin))
+                .join()
+                .unwrap_or_else(|_| Err("program panicked".to_string()))
+        });
+        match result {
+            Ok((code, output, errors)) if code != 0 && output.is_empty() && errors.is_empty() => {
+                CommandResult::error(format!("{}: exited with status {}", self.name, code)).with_exit_code(code)
+            }
+            Ok((code, output, errors)) => CommandResult::ok(output).with_errors([errors]).with_exit_code(code),
+            Err(message) => CommandResult::error(format!("{}: {}", self.name, message)),
+        }
+    }
+}
+
+/// Registers every `*.wasm` WASI program in `dir` as a command named after the file stem.
+pub fn load_dir(registry: &mut CommandRegistry, dir: &Path) -> Result<usize, String> {
+    let entries = std::fs::read_dir(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    let mut loaded = 0;
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("wasm") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let bytes = std::fs::read(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        registry.register(WasiCommand::new(name, &bytes)?);
+        loaded += 1;
+    }
+    Ok(loaded)
+}
+
+fn write_tree(node: &Node, path: &Path) -> std::io::Result<()> {
+    match node {
+        Node::Dir { children, .. } => {
+            std::fs::create_dir_all(path)?;
+            for (name, child) in children {
+                write_tree(child, &path.join(name))?;
+            }
+        }
+        Node::File { content, .. } => std::fs::write(path, content.bytes())?,
+        // Not materialised: a link could reach outside the sandbox.
+        Node::Symlink { .. } => {}
+    }
+    Ok(())
+}
+
+/// Reads the sandbox back into a tree. Nodes the program left alone keep
+/// their times from `previous`; anything it created or changed is dated `now`.
+fn read_tree(path: &Path, previous: Option<&Node>, now: u64) -> std::io::Result<Node> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        let previous_children = match previous {
+            Some(Node::Dir { children, .. }) => Some(children),
+            _ => None,
+        };
+        let mut children = std::collections::BTreeMap::new();
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let child = entry.path();
+            if std::fs::symlink_metadata(&child)?.file_type().is_symlink() {
+                continue;
+            }
+            let previous_child = previous_children.and_then(|previous| previous.get(&name));
+            children.insert(name.clone(), read_tree(&child, previous_child, now)?);
+        }
+        // Symlinks never reached the sandbox, so carry them over unless the
+        // program put something in their place.
+        for (name, node) in previous_children.into_iter().flatten() {
+            if let Node::Symlink { .. } = node {
+                children.entry(name.clone()).or_insert_with(|| node.clone());
+            }
+        }
+        let meta = match (previous, previous_children) {
+            (Some(previous), Some(previous_children))
+                if previous_children.keys().eq(children.keys()) =>
+            {
+                *previous.meta()
+            }
+            (Some(previous), Some(_)) => Metadata {
+                modified: now,
+                ..*previous.meta()
+            },
+            _ => Metadata::new(now, DEFAULT_DIR_MODE),
+        };
+        Ok(Node::Dir { children, meta })
+    } else {
+        let content = std::fs::read(path)?;
+        let meta = match previous {
+            Some(Node::File { content: old, meta }) if *old.bytes() == content => *meta,
+            Some(Node::File { meta, .. }) => Metadata {
+                modified: now,
+                ..*meta
+            },
+            _ => Metadata::new(now, DEFAULT_FILE_MODE),
+        };
+        Ok(Node::File {
+            content: Content::from(content),
+            meta,
+        })
+    }
+}
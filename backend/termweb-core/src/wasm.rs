@@ -0,0 +1,273 @@
+//! Commands compiled to WebAssembly and run under wasmtime.
+//!
+//! A plugin is a module exporting `memory` and `run() -> i32` (zero means
+//! success). It talks to the shell only through these imports from the
+//! `termweb` namespace, all of which take pointers into the plugin's memory:
+//!
+//! - `arg_count() -> i32`, `arg_len(i) -> i32`, `arg_read(i, ptr) -> i32`
+//! - `write(ptr, len)` appends to the command's output
+//! - `file_len(path, path_len) -> i32` is the file size, or -1 if unreadable
+//! - `file_read(path, path_len, ptr) -> i32` copies the file into memory
+//! - `file_write(path, path_len, ptr, len, append) -> i32` is 0 or -1
+//!
+//! Each run gets a fresh store with a fuel budget and a memory cap, so a
+//! runaway plugin fails with an error instead of stalling the server.
+
+use std::path::Path;
+
+use wasmtime::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+use crate::command::{Command, CommandContext, CommandResult};
+use crate::fs::FileSystem;
+use crate::path::resolve_path;
+use crate::registry::CommandRegistry;
+
+const FUEL_PER_RUN: u64 = 50_000_000;
+const MAX_MEMORY_BYTES: usize = 16 << 20;
+
+pub struct WasmCommand {
+    name: &'static str,
+    help: &'static str,
+    engine: Engine,
+    module: Module,
+}
+
+struct Host {
+    fs: FileSystem,
+    cwd: Vec<String>,
+    args: Vec<String>,
+    output: Vec<u8>,
+    limits: StoreLimits,
+}
+
+impl WasmCommand {
+    /// Compiles a plugin from binary `.wasm` or text `.wat` bytes.
+    pub fn new(name: &str, bytes: &[u8]) -> Result<Self, String> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|err| err.to_string())?;
+        let module = Module::new(&engine, bytes).map_err(|err| format!("{}: {}", name, err))?;
+        // Commands live for the whole process, so leaking the names is bounded.
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let help = Box::leak(format!("{} [args...]", name).into_boxed_str());
+        Ok(WasmCommand {
+            name,
+            help,
+            engine,
+            module,
+        })
+    }
+
+    fn execute(&self, host: Host) -> (Host, Result<i32, String>) {
+        let mut store = Store::new(&self.engine, host);
+        store.limiter(|host| &mut host.limits);
+        if let Err(err) = store.set_fuel(FUEL_PER_RUN) {
+            return (store.into_data(), Err(err.to_string()));
+        }
+        let result = self.instantiate_and_run(&mut store);
+        (store.into_data(), result)
+    }
+
+    fn instantiate_and_run(&self, store: &mut Store<Host>) -> Result<i32, String> {
+        let mut linker = Linker::new(&self.engine);
+        link_host_api(&mut linker).map_err(|err| err.to_string())?;
+        let instance = linker
+            .instantiate(&mut *store, &self.module)
+            .map_err(|err| err.to_string())?;
+        let run = instance
+            .get_typed_func::<(), i32>(&mut *store, "run")
+            .map_err(|err| err.to_string())?;
+        run.call(&mut *store, ())
+            .map_err(|err| match err.downcast_ref::<wasmtime::Trap>() {
+                Some(wasmtime::Trap::OutOfFuel) => "CPU budget exceeded".to_string(),
+                _ => err.to_string(),
+            })
+    }
+}
+
+impl Command for WasmCommand {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn help(&self) -> &'static str {
+        self.help
+    }
+
+    fn run(&self, ctx: &mut CommandContext<'_>, args: &[String]) -> CommandResult {
+        let host = Host {
+            fs: std::mem::take(&mut ctx.state.fs),
+            cwd: ctx.state.cwd.clone(),
+            args: args.to_vec(),
+            output: Vec::new(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY_BYTES)
+                .instances(1)
+                .build(),
+        };
+        let (host, result) = self.execute(host);
+        ctx.state.fs = host.fs;
+        let output = String::from_utf8_lossy(&host.output).into_owned();
+
+        match result {
+            Ok(0) => CommandResult::ok(output),
+            Ok(code) if output.is_empty() => {
+                CommandResult::error(format!("{}: exited with status {}", self.name, code)).with_exit_code(code)
+            }
+            Ok(code) => CommandResult::ok(output).with_exit_code(code),
+            Err(message) => CommandResult::error(format!("{}: {}", self.name, message)),
+        }
+    }
+}
+
+/// Registers every `*.wasm` / `*.wat` file in `dir` as a command named after the file stem.
+pub fn load_dir(registry: &mut CommandRegistry, dir: &Path) -> Result<usize, String> {
+    let entries = std::fs::read_dir(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    let mut loaded = 0;
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        if !matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("wasm" | "wat")
+        ) {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let bytes = std::fs::read(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        registry.register(WasmCommand::new(name, &bytes)?);
+        loaded += 1;
+    }
+    Ok(loaded)
+}
+
+fn memory(caller: &mut Caller<'_, Host>) -> Option<Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Some(memory),
+        _ => None,
+    }
+}
+
+fn read_bytes(caller: &mut Caller<'_, Host>, ptr: i32, len: i32) -> Option<Vec<u8>> {
+    let memory = memory(caller)?;
+    let start = usize::try_from(ptr).ok()?;
+    let end = start.checked_add(usize::try_from(len).ok()?)?;
+    memory.data(&*caller).get(start..end).map(<[u8]>::to_vec)
+}
+
+fn write_bytes(caller: &mut Caller<'_, Host>, ptr: i32, bytes: &[u8]) -> bool {
+    let Some(memory) = memory(caller) else {
+        return false;
+    };
+    let Ok(start) = usize::try_from(ptr) else {
+        return false;
+    };
+    memory.write(&mut *caller, start, bytes).is_ok()
+}
+
+fn read_path(caller: &mut Caller<'_, Host>, ptr: i32, len: i32) -> Option<Vec<String>> {
+    let bytes = read_bytes(caller, ptr, len)?;
+    let path = String::from_utf8(bytes).ok()?;
+    Some(resolve_path(&caller.data().cwd, &path))
+}
+
+fn link_host_api(linker: &mut Linker<Host>) -> wasmtime::Result<()> {
+    linker.func_wrap("termweb", "arg_count", |caller: Caller<'_, Host>| -> i32 {
+        caller.data().args.len() as i32
+    })?;
+    linker.func_wrap(
+        "termweb",
+        "arg_len",
+        |caller: Caller<'_, Host>, index: i32| -> i32 {
+            usize::try_from(index)
+                .ok()
+                .and_then(|index| caller.data().args.get(index))
+                .map_or(-1, |arg| arg.len() as i32)
+        },
+    )?;
+    linker.func_wrap(
+        "termweb",
+        "arg_read",
+        |mut caller: Caller<'_, Host>, index: i32, ptr: i32| -> i32 {
+            let Some(arg) = usize::try_from(index)
+                .ok()
+                .and_then(|index| caller.data().args.get(index).cloned())
+            else {
+                return -1;
+            };
+            if write_bytes(&mut caller, ptr, arg.as_bytes()) {
+                arg.len() as i32
+            } else {
+                -1
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "termweb",
+        "write",
+        |mut caller: Caller<'_, Host>, ptr: i32, len: i32| {
+            if let Some(bytes) = read_bytes(&mut caller, ptr, len) {
+                caller.data_mut().output.extend_from_slice(&bytes);
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "termweb",
+        "file_len",
+        |mut caller: Caller<'_, Host>, path: i32, path_len: i32| -> i32 {
+            let Some(path) = read_path(&mut caller, path, path_len) else {
+                return -1;
+            };
+            caller
+                .data()
+                .fs
+                .read_file(&path)
+                .map_or(-1, |content| content.len() as i32)
+        },
+    )?;
+    linker.func_wrap(
+        "termweb",
+        "file_read",
+        |mut caller: Caller<'_, Host>, path: i32, path_len: i32, ptr: i32| -> i32 {
+            let Some(path) = read_path(&mut caller, path, path_len) else {
+                return -1;
+            };
+            let Ok(content) = caller.data().fs.read_file(&path) else {
+                return -1;
+            };
+            if write_bytes(&mut caller, ptr, content.as_bytes()) {
+                content.len() as i32
+            } else {
+                -1
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "termweb",
+        "file_write",
+        |mut caller: Caller<'_, Host>,
+         path: i32,
+         path_len: i32,
+         ptr: i32,
+         len: i32,
+         append: i32|
+         -> i32 {
+            let Some(path) = read_path(&mut caller, path, path_len) else {
+                return -1;
+            };
+            let Some(content) =
+                read_bytes(&mut caller, ptr, len).and_then(|bytes| String::from_utf8(bytes).ok())
+            else {
+                return -1;
+            };
+            match caller.data_mut().fs.write_file(&path, content, append != 0) {
+                Ok(()) => 0,
+                Err(_) => -1,
+            }
+        },
+    )?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! The termweb shell compiled to WebAssembly, so the web UI can run a whole
+//! terminal in the browser with no server behind it.
+//!
+//! Build it for the frontend with
+//! `wasm-pack build termweb-wasm --target web --out-dir ../../frontend/src/wasm`.
+//! The filesystem lives only as long as the page; [`Terminal::export_tar`]
+//! and [`Terminal::import_tar`] move it to and from the server's
+//! `/api/export.tar` and `/api/import` when it should be kept.
+
+use serde::Serialize;
+use termweb_core::archive;
+use termweb_core::editor::{self, EditorOp};
+use termweb_core::path::resolve_path;
+use termweb_core::Shell;
+use wasm_bindgen::prelude::*;
+
+/// One shell session: its filesystem, directory, variables and history.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Terminal {
+    shell: Shell,
+}
+
+#[wasm_bindgen]
+impl Terminal {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Terminal {
+        Terminal::default()
+    }
+
+    /// Runs a command line and returns what `POST /api/command` would, as a
+    /// plain object.
+    pub fn exec(&mut self, command: &str) -> JsValue {
+        let response = self.shell.exec(command);
+        // Plain objects rather than `Map`s, so the result reads like the JSON.
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        response.serialize(&serializer).unwrap_or(JsValue::NULL)
+    }
+
+    /// Applies an editor operation, as sent in `editor` to
+    /// `POST /api/command`, to the file open in `edit`.
+    pub fn edit(&mut self, op: JsValue) -> Result<JsValue, JsError> {
+        let op: EditorOp =
+            serde_wasm_bindgen::from_value(op).map_err(|error| JsError::new(&error.to_string()))?;
+        let response = editor::apply(&mut self.shell.state, op);
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        Ok(response.serialize(&serializer).unwrap_or(JsValue::NULL))
+    }
+
+    /// The working directory, e.g. `/home/user`.
+    pub fn cwd(&self) -> String {
+        self.shell.state.cwd_string()
+    }
+
+    /// Sets how many characters wide the page's terminal is, for `ls`.
+    #[wasm_bindgen(js_name = setColumns)]
+    pub fn set_columns(&mut self, columns: usize) {
+        self.shell.state.columns = Some(columns);
+    }
+
+    /// The whole filesystem as a tar archive, in the format of `/api/export.tar`.
+    #[wasm_bindgen(js_name = exportTar)]
+    pub fn export_tar(&self) -> Result<Vec<u8>, JsError> {
+        archive::pack(&self.shell.state.fs, &[(String::new(), Vec::new())]).map_err(JsError::from)
+    }
+
+    /// Unpacks a tar archive beneath `path`, replacing files already there.
+    /// Returns the member names.
+    #[wasm_bindgen(js_name = importTar)]
+    pub fn import_tar(&mut self, archive: &[u8], path: Option<String>) -> Result<Vec<String>, JsError> {
+        let dest = resolve_path(&[], path.as_deref().unwrap_or("/"));
+        archive::unpack(&mut self.shell.state.fs, archive, &dest).map_err(JsError::from)
+    }
+}
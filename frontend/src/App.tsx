@@ -2,6 +2,7 @@ import type { KeyboardEvent } from "react";
 import { useEffect, useMemo, useRef, useState } from "react";
 import { Card } from "@/components/ui/card";
 import { Input } from "@/components/ui/input";
+import { OFFLINE, localTerminal, type LocalTerminal } from "@/lib/offline";
 
 type LineKind = "input" | "output" | "error";
 
@@ -12,26 +13,147 @@ type TerminalLine = {
   prompt?: string;
 };
 
+type ServerNotification = { kind: "bell" } | { kind: "message"; text: string };
+
+// The file open in `edit`, drawn in place of the terminal.
+type EditorView = {
+  path: string;
+  lines: string[];
+  modified: boolean;
+};
+
+type EditorOp =
+  | { op: "replace"; start: number; end: number; lines: string[] }
+  | { op: "save" }
+  | { op: "quit"; force?: boolean };
+
 type CommandResponse = {
   output: string;
   cwd: string;
-  status: "ok" | "error";
+  status: "ok" | "error" | "timeout";
+  exit_code: number;
+  error_code?: string;
   clear: boolean;
+  notifications?: ServerNotification[];
+  mode?: "editor";
+  editor?: EditorView;
+  // Set when `output` is only the first page of a large output.
+  truncated?: boolean;
+  continuation?: string;
+};
+
+type OutputPage = {
+  output: string;
+  truncated: boolean;
+  continuation?: string;
+};
+
+type Toast = {
+  id: string;
+  text: string;
 };
 
-const API_URL = import.meta.env.VITE_API_URL ?? "http://localhost:3000";
+// A production build is usually served by the backend itself.
+const API_URL = import.meta.env.VITE_API_URL ?? (import.meta.env.DEV ? "http://localhost:3000" : "");
+// An offline build still saves its files to a server, if it is given one.
+const SYNC_SERVER = OFFLINE && import.meta.env.VITE_API_URL !== undefined;
+const BASE_TITLE = document.title;
+const TOAST_MS = 4000;
+const SESSION_KEY = "termweb-session";
+// Where the server starts new sessions; shown as `~` in the prompt.
+const HOME_DIR = "/home/user";
+// Sent when the server runs with token authentication.
+const API_TOKEN: string | undefined = import.meta.env.VITE_API_TOKEN;
+const AUTH_HEADERS: Record<string, string> = API_TOKEN ? { Authorization: `Bearer ${API_TOKEN}` } : {};
+
+// How many characters fit across the output, so `ls` can fill the width.
+function terminalColumns(element: HTMLElement | null): number | undefined {
+  const context = document.createElement("canvas").getContext("2d");
+  if (!element || !context) return undefined;
+  const style = getComputedStyle(element);
+  context.font = style.font;
+  const width = element.clientWidth - parseFloat(style.paddingLeft) - parseFloat(style.paddingRight);
+  return Math.floor(width / context.measureText("0").width) || undefined;
+}
+
+// One server session per tab: sessionStorage survives reloads, not new tabs.
+// A new session's startup files may print a greeting, passed to `greet`, as
+// is what a session named with `attach` and reopened by `?name=` showed.
+async function ensureSession(renew = false, greet?: (text: string) => void): Promise<string> {
+  const existing = sessionStorage.getItem(SESSION_KEY);
+  if (existing && !renew) return existing;
+  const name = new URLSearchParams(window.location.search).get("name");
+  const response = await fetch(`${API_URL}/api/session`, {
+    method: "POST",
+    headers: name ? { ...AUTH_HEADERS, "Content-Type": "application/json" } : AUTH_HEADERS,
+    body: name ? JSON.stringify({ name }) : undefined,
+  });
+  const { id, greeting, scrollback } = (await response.json()) as {
+    id: string;
+    greeting?: string;
+    scrollback?: string;
+  };
+  sessionStorage.setItem(SESSION_KEY, id);
+  if (scrollback) greet?.(scrollback);
+  if (greeting) greet?.(greeting);
+  return id;
+}
+
+// The server's history is authoritative: it has `!!` references expanded
+// and honours `history -c`.
+async function fetchHistory(session: string): Promise<string[] | null> {
+  const response = await fetch(`${API_URL}/api/history`, {
+    headers: { ...AUTH_HEADERS, "X-Session-Id": session },
+  });
+  if (!response.ok) return null;
+  const { entries } = (await response.json()) as { entries: string[] };
+  return entries;
+}
+
+// The next page of a large output, named by the previous page's token.
+async function fetchPage(continuation: string): Promise<OutputPage | null> {
+  const response = await fetch(`${API_URL}/api/output/${continuation}`, { headers: AUTH_HEADERS });
+  if (!response.ok) return null;
+  return (await response.json()) as OutputPage;
+}
+
+// Files removed locally stay on the server: importing only adds and replaces.
+async function saveToServer(local: LocalTerminal) {
+  const session = await ensureSession();
+  await fetch(`${API_URL}/api/import?path=/`, {
+    method: "POST",
+    headers: { ...AUTH_HEADERS, "Content-Type": "application/x-tar", "X-Session-Id": session },
+    body: local.exportTar(),
+  });
+}
+
+async function loadFromServer(local: LocalTerminal) {
+  const session = await ensureSession();
+  const response = await fetch(`${API_URL}/api/export.tar`, {
+    headers: { ...AUTH_HEADERS, "X-Session-Id": session },
+  });
+  if (response.ok) local.importTar(new Uint8Array(await response.arrayBuffer()));
+}
 
 function App() {
   const [lines, setLines] = useState<TerminalLine[]>([]);
-  const [cwd, setCwd] = useState("/");
+  const [cwd, setCwd] = useState(HOME_DIR);
   const [input, setInput] = useState("");
   const [history, setHistory] = useState<string[]>([]);
   const [historyIndex, setHistoryIndex] = useState<number | null>(null);
   const [isRunning, setIsRunning] = useState(false);
+  const [toasts, setToasts] = useState<Toast[]>([]);
+  const [unseen, setUnseen] = useState(0);
+  const [editor, setEditor] = useState<EditorView | null>(null);
+  const [draft, setDraft] = useState("");
+  const [editorStatus, setEditorStatus] = useState("");
   const outputRef = useRef<HTMLDivElement>(null);
   const inputRef = useRef<HTMLInputElement>(null);
 
-  const prompt = useMemo(() => `user@termweb:${cwd}$`, [cwd]);
+  const prompt = useMemo(() => {
+    const shown = cwd === HOME_DIR || cwd.startsWith(`${HOME_DIR}/`) ? `~${cwd.slice(HOME_DIR.length)}` : cwd;
+    return `user@termweb:${shown}$`;
+  }, [cwd]);
 
   useEffect(() => {
     if (outputRef.current) {
@@ -42,10 +164,158 @@ function App() {
     }
   }, [lines, isRunning]);
 
+  // Badge the tab while notifications arrive in the background.
+  useEffect(() => {
+    document.title = unseen > 0 ? `(${unseen}) ${BASE_TITLE}` : BASE_TITLE;
+  }, [unseen]);
+
+  useEffect(() => {
+    const onVisible = () => {
+      if (!document.hidden) setUnseen(0);
+    };
+    document.addEventListener("visibilitychange", onVisible);
+    return () => document.removeEventListener("visibilitychange", onVisible);
+  }, []);
+
+  useEffect(() => {
+    if (!SYNC_SERVER) return;
+    localTerminal()
+      .then(loadFromServer)
+      .catch(() => {});
+  }, []);
+
+  useEffect(() => {
+    if (OFFLINE) return;
+    const session = sessionStorage.getItem(SESSION_KEY);
+    if (!session) {
+      // Start the session now, so its greeting shows before the first command.
+      ensureSession(false, greet).catch(() => {});
+      return;
+    }
+    fetchHistory(session)
+      .then((entries) => entries && setHistory(entries))
+      .catch(() => {});
+  }, []);
+
   const appendLine = (line: TerminalLine) => {
     setLines((prev) => [...prev, line]);
   };
 
+  const greet = (text: string) => {
+    appendLine({ id: crypto.randomUUID(), kind: "output", text });
+  };
+
+  const notify = (notifications: ServerNotification[]) => {
+    for (const notification of notifications) {
+      const toast = {
+        id: crypto.randomUUID(),
+        text: notification.kind === "bell" ? "Bell" : notification.text,
+      };
+      setToasts((prev) => [...prev, toast]);
+      setTimeout(() => {
+        setToasts((prev) => prev.filter((item) => item.id !== toast.id));
+      }, TOAST_MS);
+    }
+    if (document.hidden && notifications.length > 0) {
+      setUnseen((count) => count + notifications.length);
+    }
+  };
+
+  // Null when the server turned the command away.
+  const runOnServer = async (command: string, op?: EditorOp): Promise<CommandResponse | null> => {
+    // Retries reuse the key so the server never runs the command twice.
+    const idempotencyKey = crypto.randomUUID();
+    const send = (session: string) =>
+      fetch(`${API_URL}/api/command`, {
+        method: "POST",
+        headers: {
+          ...AUTH_HEADERS,
+          "Content-Type": "application/json",
+          "Idempotency-Key": idempotencyKey,
+          "X-Session-Id": session,
+        },
+        body: JSON.stringify(op ? { editor: op } : { command, columns: terminalColumns(outputRef.current) }),
+      });
+    let session = await ensureSession(false, greet);
+    let response = await send(session).catch(() => send(session));
+    if (response.status === 404) {
+      // The server no longer knows this session (e.g. it restarted).
+      session = await ensureSession(true, greet);
+      response = await send(session);
+    }
+    if (response.status === 429) {
+      appendLine({
+        id: crypto.randomUUID(),
+        kind: "error",
+        text: "Too many requests; try again shortly.",
+      });
+      return null;
+    }
+    const data = (await response.json()) as CommandResponse;
+    if (op) return data;
+    const entries = await fetchHistory(session).catch(() => null);
+    if (entries) setHistory(entries);
+    return data;
+  };
+
+  // Opens, updates or closes the editor to match a response.
+  const showMode = (data: CommandResponse) => {
+    if (data.mode === "editor" && data.editor) {
+      if (data.editor.path !== editor?.path) setDraft(data.editor.lines.join("\n"));
+      setEditor(data.editor);
+    } else {
+      setEditor(null);
+      setEditorStatus("");
+      inputRef.current?.focus();
+    }
+  };
+
+  const sendEditorOps = async (ops: EditorOp[]): Promise<CommandResponse | null> => {
+    let data: CommandResponse | null = null;
+    for (const op of ops) {
+      data = OFFLINE ? ((await localTerminal()).edit(op) as CommandResponse) : await runOnServer("", op);
+      if (!data || data.status !== "ok") break;
+    }
+    return data;
+  };
+
+  const saveDraft = async () => {
+    if (!editor) return;
+    const lines = draft === "" ? [] : draft.split("\n");
+    const data = await sendEditorOps([
+      { op: "replace", start: 1, end: editor.lines.length, lines },
+      { op: "save" },
+    ]).catch(() => null);
+    if (!data) {
+      setEditorStatus("Failed to save.");
+      return;
+    }
+    setEditorStatus(data.output);
+    showMode(data);
+  };
+
+  const quitEditor = async () => {
+    if (!editor) return;
+    const unsaved = editor.modified || draft !== editor.lines.join("\n");
+    if (unsaved && !window.confirm("Discard unsaved changes?")) return;
+    const data = await sendEditorOps([{ op: "quit", force: unsaved }]).catch(() => null);
+    if (!data) {
+      setEditorStatus("Failed to reach the server.");
+      return;
+    }
+    showMode(data);
+  };
+
+  const handleEditorKeyDown = (event: KeyboardEvent<HTMLTextAreaElement>) => {
+    if (event.ctrlKey && event.key.toLowerCase() === "s") {
+      event.preventDefault();
+      void saveDraft();
+    } else if (event.key === "Escape") {
+      event.preventDefault();
+      void quitEditor();
+    }
+  };
+
   const runCommand = async (command: string) => {
     const trimmed = command.trim();
     if (!trimmed) {
@@ -66,31 +336,44 @@ function App() {
     setIsRunning(true);
 
     try {
-      const response = await fetch(`${API_URL}/api/command`, {
-        method: "POST",
-        headers: { "Content-Type": "application/json" },
-        body: JSON.stringify({ command: trimmed }),
-      });
-      const data = (await response.json()) as CommandResponse;
-      setCwd(data.cwd);
+      let data: CommandResponse | null;
+      if (OFFLINE) {
+        const local = await localTerminal();
+        const columns = terminalColumns(outputRef.current);
+        if (columns) local.setColumns(columns);
+        data = local.exec(trimmed) as CommandResponse;
+        if (SYNC_SERVER) void saveToServer(local).catch(() => {});
+      } else {
+        data = await runOnServer(trimmed);
+      }
+      if (!data) return;
+      showMode(data);
+      // A timed-out command may not have settled on a directory yet.
+      if (data.cwd) setCwd(data.cwd);
+      notify(data.notifications ?? []);
 
+      // Output after a `clear` in a command list still belongs on screen.
       if (data.clear) {
         setLines([]);
-        return;
       }
 
+      const kind = data.status === "ok" ? "output" : "error";
       if (data.output) {
-        appendLine({
-          id: crypto.randomUUID(),
-          kind: data.status === "error" ? "error" : "output",
-          text: data.output,
-        });
+        appendLine({ id: crypto.randomUUID(), kind, text: data.output });
+      }
+      // A large output arrives a page at a time.
+      let continuation = data.continuation;
+      while (continuation) {
+        const page = await fetchPage(continuation);
+        if (!page) break;
+        appendLine({ id: crypto.randomUUID(), kind, text: page.output });
+        continuation = page.continuation;
       }
     } catch (error) {
       appendLine({
         id: crypto.randomUUID(),
         kind: "error",
-        text: "Failed to reach the server.",
+        text: OFFLINE ? "Failed to start the shell." : "Failed to reach the server.",
       });
     } finally {
       setIsRunning(false);
@@ -197,9 +480,43 @@ function App() {
           </div>
         </Card>
 
+        {editor && (
+          <div className="fixed inset-0 z-10 flex flex-col bg-background font-mono text-sm">
+            <div className="flex items-center justify-between border-b border-border/60 bg-black/90 px-4 py-2">
+              <span className="text-emerald-400">
+                {editor.path}
+                {editor.modified || draft !== editor.lines.join("\n") ? " [modified]" : ""}
+              </span>
+              <span className="text-xs text-muted-foreground">Ctrl + S to save, Esc to quit</span>
+            </div>
+            <textarea
+              value={draft}
+              onChange={(event) => setDraft(event.target.value)}
+              onKeyDown={handleEditorKeyDown}
+              spellCheck={false}
+              autoFocus
+              className="flex-1 resize-none bg-transparent p-4 text-foreground outline-none"
+            />
+            {editorStatus && (
+              <div className="border-t border-border/60 px-4 py-1 text-xs text-muted-foreground">{editorStatus}</div>
+            )}
+          </div>
+        )}
+
         <footer className="mt-4 text-xs text-muted-foreground">
           Tip: Use ↑ / ↓ for history, Ctrl + L to clear.
         </footer>
+
+        <div className="fixed bottom-6 right-6 flex flex-col gap-2">
+          {toasts.map((toast) => (
+            <div
+              key={toast.id}
+              className="rounded-md border border-border bg-black/90 px-4 py-2 font-mono text-sm text-foreground shadow-sm"
+            >
+              {toast.text}
+            </div>
+          ))}
+        </div>
       </div>
     </div>
   );
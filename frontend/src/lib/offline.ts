@@ -0,0 +1,35 @@
+// The shell compiled to WebAssembly, for builds made with VITE_OFFLINE=true.
+// Generate it first with:
+//   wasm-pack build ../backend/termweb-wasm --target web --out-dir ../../frontend/src/wasm
+
+export type LocalTerminal = {
+  exec(command: string): unknown;
+  edit(op: unknown): unknown;
+  cwd(): string;
+  setColumns(columns: number): void;
+  exportTar(): Uint8Array<ArrayBuffer>;
+  importTar(archive: Uint8Array, path?: string): string[];
+};
+
+type WasmModule = {
+  default: () => Promise<unknown>;
+  Terminal: new () => LocalTerminal;
+};
+
+export const OFFLINE = import.meta.env.VITE_OFFLINE === "true";
+
+// A glob, so builds without the generated module still compile.
+const modules = import.meta.glob<WasmModule>("../wasm/termweb_wasm.js");
+
+let terminal: Promise<LocalTerminal> | null = null;
+
+export function localTerminal(): Promise<LocalTerminal> {
+  terminal ??= (async () => {
+    const load = modules["../wasm/termweb_wasm.js"];
+    if (!load) throw new Error("termweb-wasm has not been built; run wasm-pack first");
+    const wasm = await load();
+    await wasm.default();
+    return new wasm.Terminal();
+  })();
+  return terminal;
+}